@@ -0,0 +1,300 @@
+use crate::{
+    ex_command::SetAction,
+    input::Key,
+    keymap::{find_command, parse_keys, Command, Mode, MODES},
+    settings::Settings,
+};
+use std::{
+    env, fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+use toml::{Table, Value};
+
+/// A key binding from the config file; a command of `None` removes a default binding
+#[derive(Debug, PartialEq)]
+pub struct ConfigBinding {
+    pub mode: Mode,
+    pub keys: Vec<Key>,
+    pub command: Option<Command>,
+}
+
+/// Settings read from `config.toml`, e.g.
+/// ```toml
+/// tabstop = 2
+/// expandtab = true
+///
+/// [colors]
+/// keyword = "bright_blue"
+///
+/// [keymap.normal]
+/// ",w" = "save"
+/// "<C-s>" = ""  # Unbound
+///
+/// [keymap.insert]
+/// "jk" = "normal_mode"
+/// ```
+#[derive(Debug, Default, PartialEq)]
+pub struct Config {
+    pub settings: Settings,
+    pub key_bindings: Vec<ConfigBinding>,
+}
+
+fn get_config_directory() -> PathBuf {
+    match env::var_os("XDG_CONFIG_HOME") {
+        Some(config_home) if !config_home.is_empty() => PathBuf::from(config_home),
+        _ => PathBuf::from(env::var_os("HOME").unwrap_or_default()).join(".config"),
+    }
+    .join("text-editor")
+}
+
+pub fn get_config_path() -> PathBuf {
+    get_config_directory().join("config.toml")
+}
+
+/// Holds the user's syntax definitions, one `.toml` file per language
+pub fn get_syntax_directory() -> PathBuf {
+    get_config_directory().join("syntax")
+}
+
+fn parse_keymap(keymap: &Value) -> Result<Vec<ConfigBinding>, String> {
+    let mut key_bindings = vec![];
+
+    let modes = keymap
+        .as_table()
+        .ok_or("keymap should be a table of modes")?;
+
+    for (mode_name, bindings) in modes {
+        let mode = MODES
+            .iter()
+            .find(|(name, _)| name == mode_name)
+            .map(|(_, mode)| *mode)
+            .ok_or_else(|| format!("Unknown mode: keymap.{}", mode_name))?;
+
+        let bindings = bindings
+            .as_table()
+            .ok_or_else(|| format!("keymap.{} should be a table of keys", mode_name))?;
+
+        for (notation, command_name) in bindings {
+            let command_name = command_name.as_str().ok_or_else(|| {
+                format!(
+                    "keymap.{}.\"{}\" should be a command name",
+                    mode_name, notation
+                )
+            })?;
+
+            key_bindings.push(ConfigBinding {
+                mode,
+                keys: parse_keys(notation)?,
+                command: match command_name {
+                    "" => None,
+                    command_name => Some(find_command(command_name)?),
+                },
+            });
+        }
+    }
+
+    Ok(key_bindings)
+}
+
+/// Sets an option as `:set` would, e.g. `number = false` as `:set nonumber`
+fn parse_setting(settings: &mut Settings, name: &str, value: &Value) -> Result<(), String> {
+    let action = match value {
+        Value::Boolean(true) => SetAction::Enable,
+        Value::Boolean(false) => SetAction::Disable,
+        Value::Integer(number) => SetAction::Assign(number.to_string()),
+        Value::String(text) => SetAction::Assign(text.clone()),
+        _ => return Err(format!("Invalid argument: {}", name)),
+    };
+
+    settings.set(name, &action)
+}
+
+/// Reads TOML, reporting where in `text` any error is, e.g. `line 2, column 11: ...`
+pub fn parse_toml(text: &str) -> Result<Table, String> {
+    text.parse::<Table>().map_err(|e| match e.span() {
+        Some(span) => {
+            let text_before = text.get(..span.start).unwrap_or(text);
+            let line = text_before.matches('\n').count() + 1;
+            let column = text_before
+                .rsplit('\n')
+                .next()
+                .unwrap_or_default()
+                .chars()
+                .count()
+                + 1;
+
+            format!("line {}, column {}: {}", line, column, e.message())
+        }
+        None => e.message().to_string(),
+    })
+}
+
+pub fn parse(text: &str) -> Result<Config, String> {
+    let table = parse_toml(text)?;
+
+    let mut config = Config::default();
+
+    for (name, value) in &table {
+        match (name.as_str(), value) {
+            ("keymap", _) => config.key_bindings = parse_keymap(value)?,
+            ("colors", Value::Table(colors)) => {
+                for (color_name, value) in colors {
+                    parse_setting(
+                        &mut config.settings,
+                        &format!("colors.{}", color_name),
+                        value,
+                    )?;
+                }
+            }
+            (name, value) => parse_setting(&mut config.settings, name, value)?,
+        }
+    }
+
+    Ok(config)
+}
+
+/// Reads the config file, if there is one
+pub fn read_config_file(path: &Path) -> Result<Config, String> {
+    match fs::read_to_string(path) {
+        Ok(text) => parse(&text).map_err(|e| format!("Error in {}: {}", path.display(), e)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Config::default()),
+        Err(e) => Err(format!("Failed to read {}: {}", path.display(), e)),
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::{theme::Color, utils::ctrl_key};
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse_keymap() {
+        let config = parse(
+            r#"
+            [keymap.normal]
+            ",w" = "save"
+            "<C-s>" = ""
+
+            [keymap.insert]
+            jk = "normal_mode"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.key_bindings,
+            vec![
+                ConfigBinding {
+                    mode: Mode::Insert,
+                    keys: vec![Key::U8(b'j'), Key::U8(b'k')],
+                    command: Some(Command::NormalMode),
+                },
+                ConfigBinding {
+                    mode: Mode::Normal,
+                    keys: vec![Key::U8(b','), Key::U8(b'w')],
+                    command: Some(Command::Save),
+                },
+                ConfigBinding {
+                    mode: Mode::Normal,
+                    keys: vec![Key::U8(ctrl_key('s'))],
+                    command: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_settings() {
+        let config = parse(
+            r#"
+            tabstop = 2
+            expandtab = true
+            number = false
+            defaultmode = "insert"
+            scrolloff = 5
+            clipboardcopy = "wl-copy"
+
+            [colors]
+            keyword = "bright_blue"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.settings.tab_size, 2);
+        assert!(config.settings.expand_tabs);
+        assert!(!config.settings.line_numbers);
+        assert_eq!(config.settings.default_mode, Mode::Insert);
+        assert_eq!(config.settings.scroll_margin, 5);
+        assert_eq!(config.settings.clipboard_copy_command, vec!["wl-copy"]);
+        assert_eq!(config.settings.colors.keyword, Some(Color::Ansi(12)));
+        assert!(config.key_bindings.is_empty());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse("[keymap.command]\nx = \"save\""),
+            Err(String::from("Unknown mode: keymap.command"))
+        );
+        assert_eq!(
+            parse("[keymap.normal]\nx = \"sav\""),
+            Err(String::from("Unknown command: sav"))
+        );
+        assert_eq!(
+            parse("[keymap.normal]\n\"<Nope>\" = \"save\""),
+            Err(String::from("Unknown key: <Nope>"))
+        );
+        assert_eq!(
+            parse("[keymap.normal]\nx = 1"),
+            Err(String::from("keymap.normal.\"x\" should be a command name"))
+        );
+        assert_eq!(
+            parse("colour = \"red\""),
+            Err(String::from("Unknown option: colour"))
+        );
+        assert_eq!(
+            parse("tabstop = 0"),
+            Err(String::from("Invalid argument: tabstop=0"))
+        );
+        assert_eq!(
+            parse("expandtab = [true]"),
+            Err(String::from("Invalid argument: expandtab"))
+        );
+        assert_eq!(
+            parse("[colors]\nkeyword = \"mauve\""),
+            Err(String::from("Invalid argument: colors.keyword=mauve"))
+        );
+        assert!(parse("[keymap").is_err());
+    }
+
+    #[test]
+    fn test_parse_error_location() {
+        let error = parse("number = true\ntabstop = \n").unwrap_err();
+        assert!(error.starts_with("line 2, column 11: "), "{}", error);
+
+        let error = parse("[keymap").unwrap_err();
+        assert!(error.starts_with("line 1, column 8: "), "{}", error);
+    }
+
+    #[test]
+    fn test_read_config_file() {
+        let directory = tempdir().unwrap();
+        let path = directory.path().join("config.toml");
+
+        assert_eq!(read_config_file(&path), Ok(Config::default()));
+
+        fs::write(&path, "[keymap.visual]\nq = \"quit\"\n").unwrap();
+        assert_eq!(read_config_file(&path).unwrap().key_bindings.len(), 1);
+
+        fs::write(&path, "keymap = 1\n").unwrap();
+        assert_eq!(
+            read_config_file(&path),
+            Err(format!(
+                "Error in {}: keymap should be a table of modes",
+                path.display()
+            ))
+        );
+    }
+}
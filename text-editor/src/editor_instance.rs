@@ -1,22 +1,54 @@
 use crate::{
+    config::Config,
+    ex_command::{self, CommandLine, ExCommand, LineAddress, LineRange, SetAction, SetArgument},
+    file_io::write_file_atomically,
     globals::{
-        Syntax, DEFAULT_STATUS_BAR_MESSAGE, HIGHLIGHT_NUMBERS, HIGHLIGHT_STRINGS, LINE_NUMBER_GAP,
-        QUIT_CONFIRMATION_COUNT, SYNTAX_CONFIGURATIONS, TAB_SIZE, WELCOME_MESSAGE,
+        KEY_SEQUENCE_TIMEOUT, KITTY_KEYBOARD_PROTOCOL, LINE_NUMBER_GAP, MAX_PUT_SIZE,
+        MAX_SEARCH_MATCH_COUNT, MODELINE_LINES, MOUSE_SCROLL_LINES, SWAP_FILE_WRITE_INTERVAL,
+        WELCOME_MESSAGE,
     },
-    input::{EditorKey, Key},
-    output::{clear_display, move_cursor_to_top_left, prompt_user, AnsiEscapeCode},
+    history::{Edit, History, TextPosition},
+    input::read_key_input,
+    input::{EditorKey, Key, MouseButton, MouseEvent, MouseEventKind},
+    keymap::{format_keys, get_command_name, Command, Keymap, KeymapMatch, Mode},
+    normal_mode::{
+        self, CharacterFind, Motion, MotionKind, NormalCommand, Operator, OperatorTarget,
+        ParseResult, SelectionKind,
+    },
+    output::{
+        clear_display, disable_bracketed_paste, disable_kitty_keyboard_protocol,
+        disable_mouse_reporting, move_cursor_to_top_left, prompt_user, prompt_user_allowing_empty,
+        prompt_user_with_completion, refresh_screen, AnsiEscapeCode,
+    },
+    registers::{is_register_name, Clipboard, Register, Registers},
+    search::{SearchMatch, SearchQuery, Searcher},
+    settings::Settings,
+    swap::{
+        describe_differences, get_swap_paths, read_swap_file, remove_swap_file,
+        set_emergency_snapshot, write_swap_file,
+    },
+    syntax::{get_modeline_file_type, LineState, Syntax, SyntaxRegistry},
     terminal::disable_raw_mode,
-    utils::{ctrl_key, flush_stdout, get_file_name_from_path, get_window_size, lines_to_string},
+    text_buffer::TextBuffer,
+    theme::{detect_color_support, ColorSupport, Style},
+    utils::{
+        byte_index_to_column, column_to_byte_index, flush_stdout, get_file_name_from_path,
+        get_window_size, grapheme_count, grapheme_width, truncate_at_char_boundary,
+    },
     WindowSize,
 };
 use std::{
+    borrow::Cow,
     cmp::{max, min},
-    fs::{self, OpenOptions},
-    io::{self, BufRead, BufReader, Write},
-    os::unix::fs::OpenOptionsExt,
+    fs,
+    io::{self, BufReader, ErrorKind, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::Arc,
     time::Instant,
 };
 use termios::Termios;
+use unicode_segmentation::UnicodeSegmentation;
 
 #[derive(Clone, Copy)]
 pub struct CursorPosition {
@@ -45,13 +77,13 @@ pub enum HighlightType {
     SearchMatch,
 }
 
-#[derive(Clone)]
-pub struct Line {
-    pub text: String,
+/// Display state for a line of the buffer
+#[derive(Clone, Default)]
+pub struct Row {
     pub render: String,
     pub highlight: Vec<HighlightType>,
-    pub index: usize,
-    pub has_open_multiline_comment: bool,
+    pub start_state: LineState, // As it was when last highlighted
+    pub end_state: LineState,
 }
 
 struct StatusMessage {
@@ -71,16 +103,227 @@ struct File {
     name: String,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ReplaceChoice {
+    Yes,
+    No,
+    All,
+    Quit,
+}
+
 #[derive(Debug, PartialEq)]
 struct SavedHighlight {
     line_index: usize,
     highlight: Vec<HighlightType>,
 }
 
+/// Word motions stop where the class of character changes
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum CharacterClass {
+    Whitespace, // Including the end of a line
+    Punctuation,
+    Word,
+    EmptyLine, // A word of its own
+}
+
+/// Steps through the buffer for word motions, a grapheme at a time
+/// Positions range up to the length of each line, which stands for its newline.
+struct WordScanner<'a> {
+    buffer: &'a TextBuffer,
+    big_words: bool, // `W`, `B` and `E` only separate words at whitespace
+    line_index: usize,
+    classes: Vec<CharacterClass>, // Of `line_index`
+}
+
+impl<'a> WordScanner<'a> {
+    fn new(buffer: &'a TextBuffer, big_words: bool) -> Self {
+        WordScanner {
+            buffer,
+            big_words,
+            line_index: usize::MAX,
+            classes: vec![],
+        }
+    }
+
+    fn load_line(&mut self, line_index: usize) {
+        if line_index == self.line_index {
+            return;
+        }
+
+        self.line_index = line_index;
+        self.classes = self
+            .buffer
+            .line(line_index)
+            .graphemes(true)
+            .map(|grapheme| {
+                let char = grapheme.chars().next().unwrap_or(' ');
+
+                if char.is_whitespace() {
+                    CharacterClass::Whitespace
+                } else if !self.big_words && EditorInstance::is_separator(char) {
+                    CharacterClass::Punctuation
+                } else {
+                    CharacterClass::Word
+                }
+            })
+            .collect();
+    }
+
+    fn get_line_length(&mut self, line_index: usize) -> usize {
+        self.load_line(line_index);
+        self.classes.len()
+    }
+
+    fn get_class(&mut self, position: TextPosition) -> CharacterClass {
+        self.load_line(position.line);
+
+        match self.classes.get(position.column) {
+            Some(class) => *class,
+            None if self.classes.is_empty() => CharacterClass::EmptyLine,
+            None => CharacterClass::Whitespace,
+        }
+    }
+
+    fn next(&mut self, position: TextPosition) -> Option<TextPosition> {
+        if position.column < self.get_line_length(position.line) {
+            Some(TextPosition {
+                column: position.column + 1,
+                ..position
+            })
+        } else if position.line + 1 < self.buffer.line_count() {
+            Some(TextPosition {
+                line: position.line + 1,
+                column: 0,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn previous(&mut self, position: TextPosition) -> Option<TextPosition> {
+        if position.column > 0 {
+            Some(TextPosition {
+                column: position.column - 1,
+                ..position
+            })
+        } else if position.line > 0 {
+            Some(TextPosition {
+                line: position.line - 1,
+                column: self.get_line_length(position.line - 1),
+            })
+        } else {
+            None
+        }
+    }
+
+    fn get_end_of_buffer(&mut self) -> TextPosition {
+        let line = self.buffer.line_count() - 1;
+
+        TextPosition {
+            line,
+            column: self.get_line_length(line),
+        }
+    }
+
+    /// `w`: past the rest of the current word and any whitespace after it
+    fn next_word_start(&mut self, mut position: TextPosition) -> TextPosition {
+        let class = self.get_class(position);
+
+        if class != CharacterClass::Whitespace {
+            loop {
+                position = match self.next(position) {
+                    Some(next) => next,
+                    None => return self.get_end_of_buffer(),
+                };
+
+                if class == CharacterClass::EmptyLine || self.get_class(position) != class {
+                    break;
+                }
+            }
+        }
+
+        while self.get_class(position) == CharacterClass::Whitespace {
+            position = match self.next(position) {
+                Some(next) => next,
+                None => return self.get_end_of_buffer(),
+            };
+        }
+
+        position
+    }
+
+    /// `e`: the last character of the current word, or of the next if already there
+    fn next_word_end(&mut self, mut position: TextPosition) -> TextPosition {
+        loop {
+            position = match self.next(position) {
+                Some(next) => next,
+                None => return position,
+            };
+
+            if !matches!(
+                self.get_class(position),
+                CharacterClass::Whitespace | CharacterClass::EmptyLine
+            ) {
+                break;
+            }
+        }
+
+        let class = self.get_class(position);
+
+        while let Some(next) = self.next(position) {
+            if self.get_class(next) != class {
+                break;
+            }
+
+            position = next;
+        }
+
+        position
+    }
+
+    /// `b`: the first character of the current word, or of the previous if already there
+    fn previous_word_start(&mut self, mut position: TextPosition) -> TextPosition {
+        loop {
+            position = match self.previous(position) {
+                Some(previous) => previous,
+                None => return position,
+            };
+
+            if self.get_class(position) != CharacterClass::Whitespace {
+                break;
+            }
+        }
+
+        let class = self.get_class(position);
+
+        if class == CharacterClass::EmptyLine {
+            return position;
+        }
+
+        while let Some(previous) = self.previous(position) {
+            if self.get_class(previous) != class {
+                break;
+            }
+
+            position = previous;
+        }
+
+        position
+    }
+}
+
+/// A change to a block selection, whose text is repeated on each line once typed
+#[derive(Debug, PartialEq)]
+struct BlockInsert {
+    position: TextPosition, // The top-left of the block
+    lines: Range<usize>,
+}
+
 #[derive(Debug, PartialEq)]
 enum EditorMode {
     Normal,
     Insert,
+    Visual(SelectionKind),
 }
 
 pub struct EditorInstance {
@@ -88,18 +331,40 @@ pub struct EditorInstance {
     pub window_size: WindowSize,
     pub cursor_position: CursorPosition,
     editor_mode: EditorMode,
-    lines: Vec<Line>,
+    normal_mode_keys: Vec<char>, // Typed so far towards a Normal-mode command
+    keymap: Keymap,
+    color_support: ColorSupport, // Of the terminal, which themes are drawn with
+    pending_keys: Vec<Key>,      // Typed so far towards a multi-key binding
+    pending_keys_typed_at: Instant,
+    registers: Registers,
+    active_register: Option<char>, // Named with `"x` for the command being run
+    is_awaiting_register_name: bool, // After Ctrl-R in Insert mode
+    last_character_find: Option<CharacterFind>, // Repeated by `;` and `,`
+    selection_anchor: TextPosition, // The end of the selection opposite the cursor
+    mouse_press_position: Option<TextPosition>, // Where dragging the mouse selects from
+    pending_block_insert: Option<BlockInsert>,
+    buffer: TextBuffer,
+    rows: Vec<Row>, // One per line of `buffer`
     line_scrolled_to: u32,
     column_scrolled_to: u16,
     file: Option<File>,
     status_message: Option<StatusMessage>,
     edited: bool,
     quit_confirmations: u8,
-    previous_search_match_line_index: Option<usize>,
+    previous_search_match: Option<TextPosition>,
     search_direction: SearchDirection,
-    saved_highlight: Option<SavedHighlight>,
-    syntax: Option<&'static Syntax>,
+    saved_highlights: Vec<SavedHighlight>, // Restored newest first
+    search_highlight: Option<Searcher>,    // Matches stay highlighted until cleared
+    search_match_counts: Option<(String, Vec<usize>)>, // Matches per line of the query typed
+    prompt_hint: Option<String>,
+    syntax: Option<Arc<Syntax>>,
+    syntaxes: SyntaxRegistry, // Searched by file name for the syntax
     num_columns_for_line_number: usize,
+    settings: Settings,
+    history: History,
+    swap_file_path: Option<PathBuf>, // Once written or recovered; the only swap file removed
+    swap_file_written_at: Instant,
+    is_swap_file_stale: bool,
 }
 
 impl EditorInstance {
@@ -113,332 +378,175 @@ impl EditorInstance {
                 render_x: 0,
             },
             editor_mode: EditorMode::Insert,
+            normal_mode_keys: vec![],
+            keymap: Keymap::new(),
+            color_support: detect_color_support(),
+            pending_keys: vec![],
+            pending_keys_typed_at: Instant::now(),
+            registers: Registers::new(Clipboard::new(&[], &[])),
+            active_register: None,
+            is_awaiting_register_name: false,
+            last_character_find: None,
+            selection_anchor: TextPosition::default(),
+            mouse_press_position: None,
+            pending_block_insert: None,
             syntax: None,
-            lines: vec![],
+            syntaxes: SyntaxRegistry::bundled(),
+            buffer: TextBuffer::new(),
+            rows: vec![],
             line_scrolled_to: 0,
             column_scrolled_to: 0,
             file: None,
             status_message: None,
             edited: false,
             quit_confirmations: 0,
-            previous_search_match_line_index: None,
+            previous_search_match: None,
             search_direction: SearchDirection::Forward,
-            saved_highlight: None,
+            saved_highlights: vec![],
+            search_highlight: None,
+            search_match_counts: None,
+            prompt_hint: None,
             num_columns_for_line_number: 0,
+            settings: Settings::default(),
+            history: History::new(),
+            swap_file_path: None,
+            swap_file_written_at: Instant::now(),
+            is_swap_file_stale: false,
         }
     }
 
-    fn get_current_line(&self) -> Option<&Line> {
+    fn get_current_line(&self) -> Option<Cow<'_, str>> {
         let index = self.cursor_position.y as usize;
 
         match index {
-            i if i < self.lines.len() => Some(&self.lines[i]),
+            i if i < self.buffer.line_count() => Some(self.buffer.line(i)),
             _ => None,
         }
     }
 
-    fn get_render_text_from_text(text: &str) -> String {
+    fn get_render_text_from_text(text: &str, tab_size: u8) -> String {
         let mut render = String::new();
-        let mut render_index = 0;
+        let mut render_width = 0;
 
-        for char in text.chars() {
-            if char == '\t' {
+        for grapheme in text.graphemes(true) {
+            if grapheme == "\t" {
                 render.push(' ');
-                render_index += 1;
+                render_width += 1;
 
-                while render_index % TAB_SIZE != 0 {
+                while render_width % tab_size as usize != 0 {
                     render.push(' ');
-                    render_index += 1;
+                    render_width += 1;
                 }
             } else {
-                render.push(char);
-                render_index += 1;
+                render.push_str(grapheme);
+                render_width += grapheme_width(grapheme);
             }
         }
 
         render
     }
 
-    fn is_separator(char: char) -> bool {
-        (char.is_ascii_punctuation() || char.is_ascii_whitespace() || char == '\n') && char != '_'
-    }
-
-    fn set_line_highlight(&mut self, line_index: usize) {
-        let chars = &mut self.lines[line_index].render.chars();
-        let num_chars = chars.clone().count();
-        let mut highlight = vec![HighlightType::Normal; num_chars];
-
-        if let Some(syntax) = self.syntax {
-            let mut is_previous_char_separator = true;
-            let mut current_string_quote = None;
-
-            let current_line = &self.lines[line_index];
-            let mut is_part_of_multiline_comment =
-                current_line.index > 0 && self.lines[line_index - 1].has_open_multiline_comment;
-
-            let mut i = 0;
-            'outer: while i < num_chars {
-                let char = match chars.next() {
-                    Some(char) => char,
-                    None => break,
-                };
-
-                let previous_highlight = if i > 0 {
-                    &highlight[i - 1]
-                } else {
-                    &HighlightType::Normal
-                };
-
-                if current_string_quote.is_none() {
-                    let mut single_line_comment_iterator = syntax.single_line_comment_start.chars();
-
-                    if !is_part_of_multiline_comment
-                        && chars.clone().count() >= single_line_comment_iterator.clone().count() - 1
-                    {
-                        if let Some(first_single_line_comment_char) =
-                            single_line_comment_iterator.next()
-                        {
-                            if first_single_line_comment_char == char
-                                && chars
-                                    .clone()
-                                    .zip(single_line_comment_iterator)
-                                    .all(|(char, comment_char)| char == comment_char)
-                            {
-                                highlight[i..].fill(HighlightType::Comment);
-                                break;
-                            }
-                        }
-                    }
-
-                    let mut multi_line_comment_start_iterator =
-                        syntax.multi_line_comment_start.chars();
-
-                    let multi_line_comment_start_length =
-                        multi_line_comment_start_iterator.clone().count();
-
-                    let mut multi_line_comment_end_iterator = syntax.multi_line_comment_end.chars();
-
-                    let multi_line_comment_end_length =
-                        multi_line_comment_end_iterator.clone().count();
-
-                    if is_part_of_multiline_comment {
-                        highlight[i] = HighlightType::MultiLineComment;
-
-                        if let Some(first_multi_line_comment_end_char) =
-                            multi_line_comment_end_iterator.next()
-                        {
-                            if chars.clone().count() >= multi_line_comment_end_length - 1
-                                && first_multi_line_comment_end_char == char
-                                && chars
-                                    .clone()
-                                    .zip(multi_line_comment_end_iterator.clone())
-                                    .all(|(char, comment_char)| char == comment_char)
-                            {
-                                highlight[i + 1..i + multi_line_comment_end_length]
-                                    .fill(HighlightType::MultiLineComment);
-
-                                i += multi_line_comment_end_length;
-                                is_part_of_multiline_comment = false;
-                                is_previous_char_separator = true;
-
-                                for _ in 0..multi_line_comment_end_length - 1 {
-                                    chars.next();
-                                }
-
-                                continue;
-                            }
-                        }
-
-                        i += 1;
-                        continue;
-                    } else if !is_part_of_multiline_comment
-                        && chars.clone().count() >= multi_line_comment_start_length - 1
-                    {
-                        if let Some(first_multi_line_comment_start_char) =
-                            multi_line_comment_start_iterator.next()
-                        {
-                            if first_multi_line_comment_start_char == char
-                                && chars
-                                    .clone()
-                                    .zip(multi_line_comment_start_iterator.clone())
-                                    .all(|(char, comment_char)| char == comment_char)
-                            {
-                                highlight[i..i + multi_line_comment_start_length]
-                                    .fill(HighlightType::MultiLineComment);
-
-                                i += multi_line_comment_start_length;
-                                is_part_of_multiline_comment = true;
-
-                                for _ in 0..multi_line_comment_start_length - 1 {
-                                    chars.next();
-                                }
-
-                                continue;
-                            }
-                        }
-                    }
-                }
-
-                if (syntax.flags & HIGHLIGHT_STRINGS) != 0 {
-                    match current_string_quote {
-                        Some(quote) => {
-                            highlight[i] = HighlightType::String;
-
-                            if char == '\\' {
-                                highlight[i + 1] = HighlightType::String;
-                                i += 2;
-                                chars.next();
-                                continue;
-                            }
-
-                            if char == quote {
-                                current_string_quote = None;
-                            }
-
-                            i += 1;
-                            is_previous_char_separator = true;
-                            continue;
-                        }
-                        None => {
-                            if char == '"'
-                                || (char == '\''
-                                    && (i == 0
-                                        || self.lines[line_index].render.chars().nth(i - 1)
-                                            != Some('&')))
-                            {
-                                current_string_quote = Some(char);
-                                highlight[i] = HighlightType::String;
-                                i += 1;
-                                continue;
-                            }
-                        }
-                    }
-                }
-
-                if (syntax.flags & HIGHLIGHT_NUMBERS) != 0
-                    && char.is_ascii_digit()
-                    && (is_previous_char_separator || previous_highlight == &HighlightType::Number)
-                    || (char == '.'
-                            && previous_highlight == &HighlightType::Number
-                            // Rust: 3..
-                            && chars.clone().next() != Some('.'))
-                    || (['o', 'x'].contains(&char.to_ascii_lowercase())
-                        && previous_highlight == &HighlightType::Number)
-                {
-                    highlight[i] = HighlightType::Number;
-                    i += 1;
-                    is_previous_char_separator = false;
-                    continue;
-                }
+    /// Highlights are stored per `char` of the render text, in which tabs expand to several
+    fn get_render_char_index_from_column(text: &str, column: usize, tab_size: u8) -> usize {
+        let mut render_char_index = 0;
+        let mut render_width = 0;
 
-                if is_previous_char_separator {
-                    for (k, keyword) in syntax.keywords.iter().chain(syntax.types).enumerate() {
-                        let mut keyword_iterator = keyword.chars();
-                        let keyword_length = keyword.chars().count();
-
-                        if (i == 0 || {
-                            let previous_char = self.lines[line_index].render.chars().nth(i - 1);
-                            previous_char != Some('_') && previous_char != Some('.')
-                        }) && chars.clone().count() >= keyword_length - 1
-                        {
-                            if let Some(keyword_first_char) = keyword_iterator.next() {
-                                if keyword_first_char == char
-                                    && chars
-                                        .clone()
-                                        .zip(keyword_iterator)
-                                        .all(|(char, keyword_char)| char == keyword_char)
-                                    && match chars.clone().nth(keyword_length - 1) {
-                                        Some(char) => EditorInstance::is_separator(char),
-                                        None => true,
-                                    }
-                                {
-                                    for j in i..i + keyword_length {
-                                        highlight[j] = if k >= syntax.keywords.len() {
-                                            HighlightType::Type
-                                        } else {
-                                            HighlightType::Keyword
-                                        };
-
-                                        if j < i + keyword_length - 1 {
-                                            chars.next();
-                                        }
-                                    }
-
-                                    i += keyword_length;
-                                    is_previous_char_separator = false;
-                                    continue 'outer;
-                                }
-                            }
-                        }
-                    }
-                }
+        for grapheme in text.graphemes(true).take(column) {
+            if grapheme == "\t" {
+                let tab_width = tab_size as usize - render_width % tab_size as usize;
 
-                is_previous_char_separator = EditorInstance::is_separator(char);
-                i += 1;
+                render_char_index += tab_width;
+                render_width += tab_width;
+            } else {
+                render_char_index += grapheme.chars().count();
+                render_width += grapheme_width(grapheme);
             }
+        }
 
-            let did_is_part_of_multiline_comment_change =
-                current_line.has_open_multiline_comment != is_part_of_multiline_comment;
+        render_char_index
+    }
 
-            self.lines[line_index].has_open_multiline_comment = is_part_of_multiline_comment;
+    pub fn is_separator(char: char) -> bool {
+        (char.is_ascii_punctuation() || char.is_ascii_whitespace() || char == '\n') && char != '_'
+    }
 
-            if did_is_part_of_multiline_comment_change && line_index < self.lines.len() - 1 {
-                self.set_line_highlight(line_index + 1);
-            }
+    /// Returns what the line before ends inside of
+    fn get_line_start_state(&self, line_index: usize) -> LineState {
+        match line_index {
+            0 => LineState::new(),
+            _ => self.rows[line_index - 1].end_state.clone(),
         }
+    }
+
+    /// Highlights a line, given what the line before ends inside of
+    fn set_line_highlight(&mut self, line_index: usize) {
+        let start_state = self.get_line_start_state(line_index);
+        let row = &self.rows[line_index];
+
+        let (highlight, end_state) = match &self.syntax {
+            Some(syntax) => syntax.highlight_line(&row.render, &start_state),
+            None => (
+                vec![HighlightType::Normal; row.render.chars().count()],
+                LineState::new(),
+            ),
+        };
 
-        self.lines[line_index].highlight = highlight;
+        let row = &mut self.rows[line_index];
+        row.highlight = highlight;
+        row.start_state = start_state;
+        row.end_state = end_state;
     }
 
-    fn get_color_from_highlight_type(highlight_type: &HighlightType) -> i8 {
-        match highlight_type {
-            HighlightType::Normal => 37,
-            HighlightType::Number => 93,
-            HighlightType::String => 33,
-            HighlightType::Comment | HighlightType::MultiLineComment => 36,
-            HighlightType::Keyword => 95,
-            HighlightType::Type => 92,
-            HighlightType::SearchMatch => 34,
+    /// Highlights the lines in `line_range`, then those after it only until one starts inside of
+    /// what it did when last highlighted, as have the rest
+    fn update_line_highlights_in(&mut self, line_range: Range<usize>) {
+        let mut line_index = line_range.start;
+
+        while line_index < self.rows.len()
+            && (line_index < line_range.end
+                || self.rows[line_index].start_state != self.get_line_start_state(line_index))
+        {
+            self.set_line_highlight(line_index);
+            line_index += 1;
         }
     }
 
     fn update_line_highlights(&mut self) {
-        for line_index in 0..self.lines.len() {
-            self.set_line_highlight(line_index);
-        }
+        self.restore_saved_highlights();
+        self.update_line_highlights_in(0..self.rows.len());
     }
 
-    fn set_syntax_from_file_name(&mut self) {
-        match &self.file {
-            None => self.syntax = None,
-            Some(file) => {
-                let file_name = file.name.clone();
-                let file_extension = file_name.rfind('.').map(|index| &file_name[index..]);
+    /// Picks the syntax named by a modeline, or else the one for the file's name, or else the
+    /// one for the interpreter in its shebang
+    fn detect_syntax(&mut self) {
+        let num_lines = self.buffer.line_count();
+        let modeline_lines = (0..min(MODELINE_LINES, num_lines))
+            .chain(max(MODELINE_LINES, num_lines.saturating_sub(MODELINE_LINES))..num_lines);
+
+        self.syntax = modeline_lines
+            .filter_map(|line_index| {
+                get_modeline_file_type(&self.buffer.line(line_index))
+                    .and_then(|file_type| self.syntaxes.find_by_name(file_type))
+            })
+            .next()
+            .or_else(|| {
+                self.file
+                    .as_ref()
+                    .and_then(|file| self.syntaxes.find_by_file_name(&file.name))
+            })
+            .or_else(|| match num_lines {
+                0 => None,
+                _ => self.syntaxes.find_by_shebang(&self.buffer.line(0)),
+            });
 
-                for configuration in SYNTAX_CONFIGURATIONS {
-                    for file_match in configuration.file_match {
-                        match file_extension {
-                            Some(file_extension) => {
-                                if file_extension == *file_match {
-                                    self.syntax = Some(configuration);
-                                    self.update_line_highlights();
-                                    return;
-                                }
-                            }
-                            None => {
-                                if file_name.contains(file_match) {
-                                    self.syntax = Some(configuration);
-                                    self.update_line_highlights();
-                                    return;
-                                }
-                            }
-                        }
-                    }
-                }
+        self.update_line_highlights();
+    }
 
-                self.syntax = None;
-            }
-        }
+    /// Adds the user's syntax definitions, which take priority over the bundled ones
+    pub fn load_syntax_directory(&mut self, directory: &Path) -> Result<(), String> {
+        let result = self.syntaxes.load_directory(directory);
+        self.detect_syntax();
+        result
     }
 
     pub fn open(&mut self, file_path: &str) {
@@ -446,187 +554,405 @@ impl EditorInstance {
             fs::File::open(file_path).expect("Failed to open file at specified path"),
         );
 
-        for line in reader.lines() {
-            let index = self.lines.len();
-            let text =
-                line.unwrap_or_else(|_| panic!("Failed to read line from file: {}", file_path));
-            let render = EditorInstance::get_render_text_from_text(&text);
+        self.saved_highlights.clear();
+        self.search_highlight = None;
 
-            self.lines.push(Line {
-                text,
-                render,
-                highlight: vec![],
-                index,
-                has_open_multiline_comment: false,
-            });
+        self.buffer = TextBuffer::from_reader(reader)
+            .unwrap_or_else(|_| panic!("Failed to read line from file: {}", file_path));
+        self.rows = vec![Row::default(); self.buffer.line_count()];
 
-            self.set_num_columns_for_line_number(LINE_NUMBER_GAP);
-            self.set_line_highlight(index);
+        for line_index in 0..self.rows.len() {
+            self.update_render(line_index);
         }
 
+        self.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+
         self.file = Some(File {
             path: file_path.to_string(),
             name: get_file_name_from_path(file_path),
         });
 
-        self.set_syntax_from_file_name();
+        self.detect_syntax();
 
-        self.editor_mode = EditorMode::Normal;
+        self.history = History::new();
+        self.editor_mode = self.get_default_mode();
+
+        self.check_for_swap_file();
     }
 
-    fn save(&mut self) {
-        if self.file.is_none() {
-            match prompt_user::<fn(&mut EditorInstance, &str, Key)>(self, "Save as: ", None) {
-                Some(file_path) => {
-                    self.file = Some(File {
-                        name: get_file_name_from_path(&file_path),
-                        path: file_path,
-                    });
+    fn get_swap_paths(&self) -> Vec<PathBuf> {
+        get_swap_paths(self.file.as_ref().map(|file| file.path.as_str()))
+    }
 
-                    self.set_syntax_from_file_name();
-                }
-                None => {
-                    self.set_status_message("Save aborted", false);
-                    return;
-                }
-            }
+    /// Returns this instance's swap file if it belongs to the current file name, or else the
+    /// first name not taken by another editor's swap file or one the user kept
+    fn choose_swap_path(&self) -> Option<PathBuf> {
+        let swap_paths = self.get_swap_paths();
+
+        match &self.swap_file_path {
+            Some(swap_path) if swap_paths.contains(swap_path) => Some(swap_path.clone()),
+            _ => swap_paths
+                .into_iter()
+                .find(|swap_path| fs::symlink_metadata(swap_path).is_err()),
         }
+    }
 
-        if let Some(file) = &self.file {
-            let mut fs_file = match OpenOptions::new()
-                .read(true)
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .mode(0o644) // Owner R/W; others R
-                .open(&file.path)
-            {
-                Ok(fs_file) => fs_file,
-                Err(e) => {
-                    self.set_status_message(
-                        &format!("Failed to open {} for saving: {:?}", file.path, e),
-                        true,
-                    );
-                    return;
-                }
+    /// Offers to recover unsaved changes left behind by an editor that did not exit cleanly, or
+    /// that is still editing the file
+    fn check_for_swap_file(&mut self) {
+        let (swap_path, swap_buffer) =
+            match self.get_swap_paths().into_iter().find_map(|swap_path| {
+                read_swap_file(&swap_path)
+                    .ok()
+                    .map(|swap_buffer| (swap_path, swap_buffer))
+            }) {
+                Some(found) => found,
+                None => return,
             };
 
-            let content = lines_to_string(&self.lines);
+        let mut summary = format!("Found swap file {}", swap_path.display());
+
+        loop {
+            let choice = prompt_user::<fn(&mut EditorInstance, &str, Key)>(
+                self,
+                &format!(
+                    "{}. (r)ecover, (d)iff, (x) discard, ESC to keep it: ",
+                    summary
+                ),
+                None,
+            );
+
+            match choice.as_deref() {
+                Some("r") => {
+                    self.recover_from_swap_file(swap_path, swap_buffer);
+                    return;
+                }
+                Some("d") => summary = describe_differences(&self.buffer, &swap_buffer),
+                Some("x") => {
+                    match remove_swap_file(&swap_path) {
+                        Ok(_) => self.set_status_message("Swap file discarded", false),
+                        Err(e) => self.set_status_message(
+                            &format!("Failed to remove {}: {}", swap_path.display(), e),
+                            true,
+                        ),
+                    }
 
-            match fs_file.write_all(content.as_bytes()) {
-                Ok(_) => {
+                    return;
+                }
+                Some(_) => {}
+                None => {
                     self.set_status_message(
-                        &format!("{} bytes written to disk", content.len()),
+                        &format!("Swap file kept at {}", swap_path.display()),
                         false,
                     );
-                    self.edited = false;
+                    return;
                 }
-                Err(e) => self.set_status_message(
-                    &format!("Failed to write to {}: {:?}", file.path, e),
-                    true,
-                ),
             }
         }
     }
 
-    pub fn process_key(&mut self, key: Key) {
-        match key {
-            Key::U8(b'\r') => self.insert_line(), // Enter
+    /// Replaces the buffer with the swap file contents as a single undoable edit, so that
+    /// undo returns to the file on disk
+    fn recover_from_swap_file(&mut self, swap_path: PathBuf, swap_buffer: TextBuffer) {
+        let cursor_before = self.get_text_position();
 
-            Key::Custom(EditorKey::ArrowLeft) => self.move_cursor(CursorMovement::Left),
-            Key::Custom(EditorKey::ArrowDown) => self.move_cursor(CursorMovement::Down),
-            Key::Custom(EditorKey::ArrowUp) => self.move_cursor(CursorMovement::Up),
-            Key::Custom(EditorKey::ArrowRight) => self.move_cursor(CursorMovement::Right),
+        let original_text = self.buffer.to_string();
+        let recovered_text = swap_buffer.to_string();
 
-            Key::U8(key) if key == b'h' && self.editor_mode == EditorMode::Normal => {
-                self.move_cursor(CursorMovement::Left)
-            }
-            Key::U8(key) if key == b'j' && self.editor_mode == EditorMode::Normal => {
-                self.move_cursor(CursorMovement::Down)
-            }
-            Key::U8(key) if key == b'k' && self.editor_mode == EditorMode::Normal => {
-                self.move_cursor(CursorMovement::Up)
-            }
-            Key::U8(key) if key == b'l' && self.editor_mode == EditorMode::Normal => {
-                self.move_cursor(CursorMovement::Right)
-            }
+        self.delete_text_at(0, &original_text);
+        self.insert_text_at(0, &recovered_text);
+        self.set_cursor_from_text_position(TextPosition::default());
 
-            Key::Custom(EditorKey::Home) => {
-                self.cursor_position.x = self
-                    .num_columns_for_line_number
-                    .try_into()
-                    .expect("Failed to convert new cursor x-position usize to u16");
-            }
-            Key::Custom(EditorKey::End) => {
-                if let Some(current_line) = self.get_current_line() {
-                    let num_characters_in_line: u16 = current_line
-                        .text
-                        .chars()
-                        .count()
-                        .try_into()
-                        .expect("Failed to convert line length usize to u16");
+        self.history.record(
+            Edit::Delete {
+                char_index: 0,
+                text: original_text,
+            },
+            cursor_before,
+            cursor_before,
+        );
+        self.history.record(
+            Edit::Insert {
+                char_index: 0,
+                text: recovered_text,
+            },
+            cursor_before,
+            self.get_text_position(),
+        );
+        self.history.commit();
 
-                    let line_number_columns_offset: u16 = self
-                        .num_columns_for_line_number
-                        .try_into()
-                        .expect("Failed to convert usize to u16");
+        self.edited = !self.history.is_at_saved_state();
+        // The recovered swap file is now this instance's, and is removed once saved
+        self.swap_file_path = Some(swap_path);
+        self.set_status_message("Recovered unsaved changes from swap file", false);
+    }
 
-                    self.cursor_position.x = num_characters_in_line + line_number_columns_offset;
-                }
+    /// Called from the main loop: keeps the emergency snapshot for the panic hook current, and
+    /// writes the swap file periodically while there are unsaved changes
+    pub fn update_swap_file(&mut self) {
+        if !self.edited {
+            if self.is_swap_file_stale || self.swap_file_path.is_some() {
+                self.remove_swap_file();
             }
 
-            // Backspace: historically sent `8`; now sends `127`
-            // Delete: historically sent `127`; now sends `<esc>[3~`
-            Key::Custom(EditorKey::Backspace) | Key::Custom(EditorKey::Delete) => {
-                if key == Key::Custom(EditorKey::Delete) {
-                    self.move_cursor(CursorMovement::Right);
-                }
+            return;
+        }
 
-                self.delete_character();
+        if !self.is_swap_file_stale {
+            return;
+        }
+
+        let swap_path = match self.choose_swap_path() {
+            Some(swap_path) => swap_path,
+            None => {
+                // Tried again after the next edit
+                self.is_swap_file_stale = false;
+                self.set_status_message("Failed to write swap file: every name is taken", true);
+                return;
             }
+        };
+        set_emergency_snapshot(Some((swap_path.clone(), self.buffer.clone())));
 
-            Key::Custom(EditorKey::PageUp) => {
-                self.cursor_position.y = self.line_scrolled_to;
+        if self.swap_file_written_at.elapsed() >= SWAP_FILE_WRITE_INTERVAL {
+            self.write_swap_file(swap_path);
+        }
+    }
 
-                for _ in 0..self.window_size.rows {
-                    self.move_cursor(CursorMovement::Up);
-                }
-            }
-            Key::Custom(EditorKey::PageDown) => {
-                self.cursor_position.y = min(
-                    self.lines
-                        .len()
-                        .try_into()
-                        .expect("Failed to convert usize to u16"),
-                    self.line_scrolled_to + self.window_size.rows - 1,
-                );
+    fn write_swap_file(&mut self, swap_path: PathBuf) {
+        self.swap_file_written_at = Instant::now();
 
-                for _ in 0..self.window_size.rows {
-                    self.move_cursor(CursorMovement::Down);
-                }
+        // A new file that is given a name on save moves its swap file
+        if let Some(previous_swap_path) = self.swap_file_path.take() {
+            if previous_swap_path != swap_path {
+                let _ = remove_swap_file(&previous_swap_path);
             }
+        }
 
-            Key::U8(key) if key == ctrl_key('s') => self.save(),
-            Key::U8(key) if key == ctrl_key('f') => self.prompt_and_find_text(),
-            Key::U8(key) if key == ctrl_key('g') => self.prompt_and_go_to_line(),
-            Key::U8(key) if key == ctrl_key('h') => {
-                self.set_status_message(DEFAULT_STATUS_BAR_MESSAGE, false);
+        match write_swap_file(&swap_path, &self.buffer) {
+            Ok(_) => {
+                self.swap_file_path = Some(swap_path);
+                self.is_swap_file_stale = false;
             }
+            Err(e) => self.set_status_message(
+                &format!("Failed to write swap file {}: {}", swap_path.display(), e),
+                true,
+            ),
+        }
+    }
+
+    fn remove_swap_file(&mut self) {
+        set_emergency_snapshot(None);
+        self.is_swap_file_stale = false;
+
+        if let Some(swap_path) = self.swap_file_path.take() {
+            if let Err(e) = remove_swap_file(&swap_path) {
+                self.set_status_message(
+                    &format!("Failed to remove swap file {}: {}", swap_path.display(), e),
+                    true,
+                );
+            }
+        }
+    }
+
+    /// Returns whether the buffer was written
+    fn save(&mut self) -> bool {
+        let file_path = match &self.file {
+            Some(file) => file.path.clone(),
+            None => {
+                match prompt_user::<fn(&mut EditorInstance, &str, Key)>(self, "Save as: ", None) {
+                    Some(file_path) => file_path,
+                    None => {
+                        self.set_status_message("Save aborted", false);
+                        return false;
+                    }
+                }
+            }
+        };
+
+        self.write_buffer(file_path)
+    }
+
+    /// Writes the buffer to `file_path`, which becomes the buffer's file if it had none
+    /// Writing to any other file leaves the buffer's file and unsaved state as they were.
+    /// Returns whether the buffer was written.
+    fn write_buffer(&mut self, file_path: String) -> bool {
+        let is_buffer_file = self.file.as_ref().is_none_or(|file| file.path == file_path);
+
+        match write_file_atomically(
+            Path::new(&file_path),
+            &self.buffer,
+            self.settings.create_backup_file,
+        ) {
+            Ok(bytes_written) => {
+                self.set_status_message(&format!("{} bytes written to disk", bytes_written), false);
+
+                if is_buffer_file {
+                    if self.file.is_none() {
+                        self.file = Some(File {
+                            name: get_file_name_from_path(&file_path),
+                            path: file_path,
+                        });
+
+                        self.detect_syntax();
+                    }
+
+                    self.edited = false;
+                    self.history.mark_saved();
+                    self.remove_swap_file();
+                }
+
+                true
+            }
+            Err(e) => {
+                self.set_status_message(&format!("Failed to save {}: {}", file_path, e), true);
+                false
+            }
+        }
+    }
+
+    fn quit(&mut self) -> ! {
+        disable_bracketed_paste();
+        disable_mouse_reporting();
+
+        if KITTY_KEYBOARD_PROTOCOL {
+            disable_kitty_keyboard_protocol();
+        }
+
+        clear_display();
+        move_cursor_to_top_left();
+        disable_raw_mode(self.original_termios);
+
+        self.remove_swap_file();
+        std::process::exit(0);
+    }
+
+    /// Runs the command bound to the keys typed, waiting for more keys while they could still
+    /// complete a longer binding
+    pub fn process_key(&mut self, key: Key) {
+        // Pastes and mouse events cannot be bound
+        if matches!(key, Key::Paste(_) | Key::Mouse(_)) {
+            self.process_pending_keys();
+            self.process_unbound_key(key);
+            return;
+        }
+
+        if !self.is_keymap_active() {
+            self.process_unbound_key(key);
+            return;
+        }
+
+        self.pending_keys.push(key);
+
+        match self
+            .keymap
+            .lookup(self.get_keymap_mode(), &self.pending_keys)
+        {
+            KeymapMatch::Prefix => self.pending_keys_typed_at = Instant::now(),
+            KeymapMatch::Command(command) => {
+                self.pending_keys.clear();
+                self.run_keymap_command(command);
+            }
+            KeymapMatch::None => self.replay_pending_keys(),
+        }
+    }
+
+    /// Takes keys that could have started a longer binding on their own once no more have
+    /// been typed for `KEY_SEQUENCE_TIMEOUT`
+    pub fn check_key_sequence_timeout(&mut self) {
+        if !self.pending_keys.is_empty()
+            && self.pending_keys_typed_at.elapsed() >= KEY_SEQUENCE_TIMEOUT
+        {
+            self.process_pending_keys();
+        }
+    }
+
+    /// Runs the command bound to the pending keys, or processes them as unbound keys
+    fn process_pending_keys(&mut self) {
+        match self.keymap.get(self.get_keymap_mode(), &self.pending_keys) {
+            Some(command) => {
+                self.pending_keys.clear();
+                self.run_keymap_command(command);
+            }
+            None => self.replay_pending_keys(),
+        }
+    }
+
+    /// Processes the first pending key as unbound, then looks up bindings for the rest anew
+    fn replay_pending_keys(&mut self) {
+        let mut keys = std::mem::take(&mut self.pending_keys).into_iter();
+
+        if let Some(key) = keys.next() {
+            self.process_unbound_key(key);
+        }
+
+        for key in keys {
+            self.process_key(key);
+        }
+    }
+
+    /// The mode that files open in
+    fn get_default_mode(&self) -> EditorMode {
+        match self.settings.default_mode {
+            Mode::Insert => EditorMode::Insert,
+            _ => EditorMode::Normal,
+        }
+    }
+
+    fn get_keymap_mode(&self) -> Mode {
+        match self.editor_mode {
+            EditorMode::Normal => Mode::Normal,
+            EditorMode::Insert => Mode::Insert,
+            EditorMode::Visual(_) => Mode::Visual,
+        }
+    }
 
-            Key::U8(key) if key == ctrl_key('q') => {
-                if self.edited && self.quit_confirmations < QUIT_CONFIRMATION_COUNT {
-                    let confirmations_remaining = QUIT_CONFIRMATION_COUNT - self.quit_confirmations;
+    /// Bindings apply to keys that start a command, not to those that finish a Normal-mode
+    /// command (e.g. the `x` of `rx`) or name a register
+    fn is_keymap_active(&self) -> bool {
+        !self.is_awaiting_register_name
+            && (self.editor_mode == EditorMode::Insert
+                || normal_mode::is_command_start(&self.normal_mode_keys))
+    }
+
+    fn run_keymap_command(&mut self, command: Command) {
+        // Leaving Insert mode finishes a block change as part of the same edit
+        if command == Command::NormalMode {
+            self.process_unbound_key(Key::U8(b'\x1b'));
+            return;
+        }
+
+        self.history.commit();
+        self.normal_mode_keys.clear(); // A count typed first is ignored
+
+        match command {
+            Command::Find => self.prompt_and_find_text(),
+            Command::Replace => self.prompt_and_replace_text(),
+            Command::GoToLine => self.prompt_and_go_to_line(),
+            Command::Undo => {
+                self.undo();
+            }
+            Command::Redo => self.redo(),
+            Command::Save => {
+                self.save();
+            }
+            Command::Quit => {
+                if self.edited && self.quit_confirmations < self.settings.quit_confirmations {
+                    let confirmations_remaining =
+                        self.settings.quit_confirmations - self.quit_confirmations;
 
                     self.set_status_message(
                         &format!(
-                        "WARNING: File has unsaved changes! Press Ctrl-Q {} more time{} to quit.",
-                        confirmations_remaining,
-                        if confirmations_remaining == 1 {
-                            ""
-                        } else {
-                            "s"
-                        }
-                    ),
+                            "WARNING: File has unsaved changes! Press {} {} more time{} to quit.",
+                            self.keymap
+                                .describe_binding(self.get_keymap_mode(), Command::Quit)
+                                .unwrap_or_default(),
+                            confirmations_remaining,
+                            if confirmations_remaining == 1 {
+                                ""
+                            } else {
+                                "s"
+                            }
+                        ),
                         true,
                     );
 
@@ -634,26 +960,167 @@ impl EditorInstance {
                     return;
                 }
 
-                clear_display();
-                move_cursor_to_top_left();
-                disable_raw_mode(self.original_termios);
+                self.quit();
+            }
+            Command::Help => self.show_help(),
+            Command::ExCommandLine => self.prompt_and_run_command(),
+            Command::InsertRegister => {
+                self.is_awaiting_register_name = true;
+                self.set_status_message("Insert the contents of register: ", false);
+            }
+            Command::InsertMode => {
+                if self.editor_mode == EditorMode::Normal {
+                    self.editor_mode = EditorMode::Insert;
+                }
+            }
+            Command::NormalMode => unreachable!("Handled above"),
+            Command::MoveLeft => self.move_cursor(CursorMovement::Left),
+            Command::MoveRight => self.move_cursor(CursorMovement::Right),
+            Command::MoveUp => self.move_cursor(CursorMovement::Up),
+            Command::MoveDown => self.move_cursor(CursorMovement::Down),
+            Command::WordForward => self.move_cursor_by_motion(Motion::WordForward, None),
+            Command::WordBackward => self.move_cursor_by_motion(Motion::WordBackward, None),
+            Command::LineStart => {
+                self.cursor_position.x = self
+                    .num_columns_for_line_number
+                    .try_into()
+                    .expect("Failed to convert new cursor x-position usize to u16");
+            }
+            Command::LineEnd => {
+                if let Some(current_line) = self.get_current_line() {
+                    let num_characters_in_line: u16 =
+                        grapheme_count(&current_line).try_into().unwrap_or(u16::MAX);
+
+                    let line_number_columns_offset: u16 = self
+                        .num_columns_for_line_number
+                        .try_into()
+                        .expect("Failed to convert usize to u16");
+
+                    self.cursor_position.x =
+                        num_characters_in_line.saturating_add(line_number_columns_offset);
+                }
+            }
+            Command::PageUp => {
+                self.cursor_position.y = self.line_scrolled_to;
+
+                for _ in 0..self.window_size.rows {
+                    self.move_cursor(CursorMovement::Up);
+                }
+            }
+            Command::PageDown => {
+                self.cursor_position.y = min(
+                    self.buffer.line_count().try_into().unwrap_or(u32::MAX),
+                    self.line_scrolled_to + self.window_size.rows - 1,
+                );
+
+                for _ in 0..self.window_size.rows {
+                    self.move_cursor(CursorMovement::Down);
+                }
+            }
+            Command::FileStart => self.move_cursor_by_motion(Motion::FileStart, None),
+            Command::FileEnd => self.move_cursor_by_motion(Motion::FileEnd, None),
+        }
+
+        self.quit_confirmations = 0;
+    }
+
+    /// Shows the keys bound to the main commands in the current mode
+    pub fn show_help(&mut self) {
+        let help = self.keymap.get_help(self.get_keymap_mode());
+        self.set_status_message(&help, false);
+    }
+
+    pub fn apply_config(&mut self, config: Config) {
+        let previous_settings = std::mem::replace(&mut self.settings, config.settings);
+        self.apply_settings(&previous_settings);
+        self.editor_mode = self.get_default_mode();
+
+        for binding in config.key_bindings {
+            match binding.command {
+                Some(command) => self.keymap.bind(binding.mode, binding.keys, command),
+                None => {
+                    self.keymap.unbind(binding.mode, &binding.keys);
+                }
+            }
+        }
+    }
+
+    fn process_unbound_key(&mut self, key: Key) {
+        if !self.is_text_edit_key(&key) {
+            self.history.commit(); // Typing is undone in runs, ending at any other key
+        }
+
+        if self.is_awaiting_register_name {
+            self.is_awaiting_register_name = false;
+            self.set_status_message("", false);
+
+            // Any other key cancels
+            if let Key::U8(name) = key {
+                if is_register_name(name as char) {
+                    self.insert_register(name as char);
+                }
+            }
+
+            return;
+        }
+
+        match key {
+            Key::U8(b'\r') => self.insert_line(), // Enter
+
+            // Backspace: historically sent `8`; now sends `127`
+            // Delete: historically sent `127`; now sends `<esc>[3~`
+            Key::Custom(EditorKey::Backspace) | Key::Custom(EditorKey::Delete) => {
+                if key == Key::Custom(EditorKey::Delete) {
+                    self.move_cursor(CursorMovement::Right);
+                }
+
+                self.delete_character();
+            }
 
-                std::process::exit(0);
+            // Keys held with modifiers that are not bound act as the key on its own
+            Key::Modified(key, _) => self.process_key(Key::Custom(key)),
+
+            Key::Paste(text) => self.paste_text(text),
+            Key::Mouse(event) => self.process_mouse_event(event),
+            // Terminals send a key held with Alt as Escape followed by the key, which is read
+            // the same way when the two are typed quickly
+            Key::Alt(character) => {
+                self.process_key(Key::U8(b'\x1b'));
+                self.process_key(match character.is_ascii() {
+                    true => Key::U8(character as u8),
+                    false => Key::Char(character),
+                });
+            }
+            Key::Char(character) if self.editor_mode == EditorMode::Insert => {
+                self.insert_character(character)
             }
+            Key::Char(character) => self.process_normal_mode_key(character),
             _ => {
                 if let Key::U8(key) = key {
                     match self.editor_mode {
-                        EditorMode::Normal => {
-                            if key == b'i' {
-                                self.editor_mode = EditorMode::Insert;
+                        EditorMode::Normal | EditorMode::Visual(_) => {
+                            if key != b'\x1b' {
+                                self.process_normal_mode_key(key as char);
+                            } else if !self.normal_mode_keys.is_empty() {
+                                self.normal_mode_keys.clear();
+                            } else if self.editor_mode != EditorMode::Normal {
+                                self.editor_mode = EditorMode::Normal;
+                            } else {
+                                self.clear_search_highlight();
                             }
                         }
                         EditorMode::Insert => {
                             if key == b'\x1b' {
+                                self.finish_block_insert();
                                 self.editor_mode = EditorMode::Normal;
                                 return;
                             }
 
+                            if key == b'\t' && self.settings.expand_tabs {
+                                self.insert_spaces_to_tab_stop();
+                                return;
+                            }
+
                             self.insert_character(key as char);
                         }
                     }
@@ -664,6 +1131,170 @@ impl EditorInstance {
         self.quit_confirmations = 0;
     }
 
+    fn is_text_edit_key(&self, key: &Key) -> bool {
+        match key {
+            Key::U8(b'\r') | Key::Custom(EditorKey::Backspace) | Key::Custom(EditorKey::Delete) => {
+                true
+            }
+            // Finishing a block change copies the typed text, as part of the same edit
+            Key::U8(b'\x1b') => self.pending_block_insert.is_some(),
+            Key::U8(key) => {
+                self.editor_mode == EditorMode::Insert && (!key.is_ascii_control() || *key == b'\t')
+            }
+            Key::Char(_) => self.editor_mode == EditorMode::Insert,
+            _ => false,
+        }
+    }
+
+    fn get_text_position(&self) -> TextPosition {
+        TextPosition {
+            line: self.cursor_position.y as usize,
+            column: (self.cursor_position.x as usize)
+                .saturating_sub(self.num_columns_for_line_number),
+        }
+    }
+
+    fn set_cursor_from_text_position(&mut self, position: TextPosition) {
+        self.cursor_position.y = position
+            .line
+            .try_into()
+            .expect("Failed to convert line index usize to cursor y-position u32");
+
+        // The cursor stops at the furthest column it can hold, on lines longer than that
+        self.cursor_position.x = (position.column + self.num_columns_for_line_number)
+            .try_into()
+            .unwrap_or(u16::MAX);
+    }
+
+    /// Converts a position into an index into `buffer`; the line past the last maps to its end
+    fn get_char_index(&self, position: TextPosition) -> usize {
+        if position.line >= self.buffer.line_count() {
+            return self.buffer.len_chars();
+        }
+
+        let line = self.buffer.line(position.line);
+        let byte_index = column_to_byte_index(&line, position.column);
+
+        self.buffer.line_to_char(position.line) + line[..byte_index].chars().count()
+    }
+
+    fn get_text_position_from_char_index(&self, char_index: usize) -> TextPosition {
+        let line_index = self.buffer.char_to_line(char_index);
+
+        if line_index == self.buffer.line_count() {
+            return TextPosition {
+                line: line_index,
+                column: 0,
+            };
+        }
+
+        let byte_index = self.buffer.char_to_byte(char_index)
+            - self
+                .buffer
+                .char_to_byte(self.buffer.line_to_char(line_index));
+
+        TextPosition {
+            line: line_index,
+            column: byte_index_to_column(&self.buffer.line(line_index), byte_index),
+        }
+    }
+
+    fn update_render(&mut self, line_index: usize) {
+        self.rows[line_index].render = EditorInstance::get_render_text_from_text(
+            &self.buffer.line(line_index),
+            self.settings.tab_size,
+        );
+    }
+
+    /// Adds or removes rows following `line_index` so that there is one per line of the buffer,
+    /// then re-renders the `num_changed_lines` lines starting at `line_index`
+    /// Lines after them are re-highlighted only as far as a multi-line comment's opening or
+    /// closing has changed them
+    fn update_rows_from(&mut self, line_index: usize, num_changed_lines: usize) {
+        // Search highlights are reapplied when drawing
+        self.restore_saved_highlights();
+
+        let line_count = self.buffer.line_count();
+
+        if line_count > self.rows.len() {
+            let new_rows = vec![Row::default(); line_count - self.rows.len()];
+            self.rows.splice(line_index..line_index, new_rows);
+        } else {
+            let start = min(line_index + 1, line_count);
+            let num_removed_rows = self.rows.len() - line_count;
+            self.rows.drain(start..start + num_removed_rows);
+        }
+
+        let changed_lines = line_index..min(line_index + num_changed_lines, line_count);
+
+        for index in changed_lines.clone() {
+            self.update_render(index);
+        }
+
+        self.update_line_highlights_in(changed_lines);
+
+        self.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+    }
+
+    /// Inserts `text`, which may contain newlines, at `char_index`
+    fn insert_text_at(&mut self, char_index: usize, text: &str) {
+        let line_index = self.buffer.char_to_line(char_index);
+
+        self.buffer.insert(char_index, text);
+        self.is_swap_file_stale = true;
+        self.update_rows_from(line_index, text.matches('\n').count() + 1);
+    }
+
+    /// Removes `text`, which must match the buffer contents starting at `char_index`
+    fn delete_text_at(&mut self, char_index: usize, text: &str) {
+        let line_index = self.buffer.char_to_line(char_index);
+
+        self.buffer
+            .remove(char_index..char_index + text.chars().count());
+        self.is_swap_file_stale = true;
+        self.update_rows_from(line_index, 1);
+    }
+
+    fn apply_edit(&mut self, edit: &Edit) {
+        match edit {
+            Edit::Insert { char_index, text } => self.insert_text_at(*char_index, text),
+            Edit::Delete { char_index, text } => self.delete_text_at(*char_index, text),
+        }
+    }
+
+    /// Returns whether there was a change to undo
+    fn undo(&mut self) -> bool {
+        match self.history.undo() {
+            Some(transaction) => {
+                for edit in transaction.edits.iter().rev() {
+                    self.apply_edit(&edit.inverse());
+                }
+
+                self.set_cursor_from_text_position(transaction.cursor_before);
+                self.edited = !self.history.is_at_saved_state();
+                true
+            }
+            None => {
+                self.set_status_message("Already at oldest change", false);
+                false
+            }
+        }
+    }
+
+    fn redo(&mut self) {
+        match self.history.redo() {
+            Some(transaction) => {
+                for edit in &transaction.edits {
+                    self.apply_edit(edit);
+                }
+
+                self.set_cursor_from_text_position(transaction.cursor_after);
+                self.edited = !self.history.is_at_saved_state();
+            }
+            None => self.set_status_message("Already at newest change", false),
+        }
+    }
+
     pub fn move_cursor(&mut self, direction: CursorMovement) {
         match direction {
             CursorMovement::Left => {
@@ -672,14 +1303,13 @@ impl EditorInstance {
                 } else if self.cursor_position.y > 0 {
                     self.cursor_position.y -= 1;
 
-                    let num_characters_in_previous_line: u16 = self
-                        .get_current_line()
-                        .expect("Index error while retrieving previous line")
-                        .text
-                        .chars()
-                        .count()
-                        .try_into()
-                        .expect("Failed to convert line length usize to u16");
+                    let num_characters_in_previous_line: u16 = grapheme_count(
+                        &self
+                            .get_current_line()
+                            .expect("Index error while retrieving previous line"),
+                    )
+                    .try_into()
+                    .unwrap_or(u16::MAX);
 
                     let line_number_columns_offset: u16 = self
                         .num_columns_for_line_number
@@ -687,11 +1317,11 @@ impl EditorInstance {
                         .expect("Failed to convert usize to u16");
 
                     self.cursor_position.x =
-                        num_characters_in_previous_line + line_number_columns_offset
+                        num_characters_in_previous_line.saturating_add(line_number_columns_offset)
                 }
             }
             CursorMovement::Down => {
-                if (self.cursor_position.y as usize) < self.lines.len() {
+                if (self.cursor_position.y as usize) < self.buffer.line_count() {
                     self.cursor_position.y += 1;
                 }
             }
@@ -703,9 +1333,9 @@ impl EditorInstance {
             CursorMovement::Right => {
                 if let Some(current_line) = self.get_current_line() {
                     if (self.cursor_position.x as usize)
-                        < current_line.text.chars().count() + self.num_columns_for_line_number
+                        < grapheme_count(&current_line) + self.num_columns_for_line_number
                     {
-                        self.cursor_position.x += 1;
+                        self.cursor_position.x = self.cursor_position.x.saturating_add(1);
                     } else {
                         self.cursor_position.y += 1;
                         self.cursor_position.x = self
@@ -717,17 +1347,18 @@ impl EditorInstance {
             }
         }
 
-        let current_line_after_cursor_move = self.get_current_line();
+        self.clamp_cursor_to_line();
+    }
 
-        let line_length = current_line_after_cursor_move.map_or(0, |line| {
-            line.text.chars().count() + self.num_columns_for_line_number
+    /// Moves the cursor back to the end of its line if it is past it
+    fn clamp_cursor_to_line(&mut self) {
+        let line_length = self.get_current_line().map_or(0, |line| {
+            grapheme_count(&line) + self.num_columns_for_line_number
         });
 
         self.cursor_position.x = min(
             self.cursor_position.x,
-            line_length
-                .try_into()
-                .expect("Failed to convert line length usize to u16"),
+            line_length.try_into().unwrap_or(u16::MAX),
         );
     }
 
@@ -749,11 +1380,14 @@ impl EditorInstance {
             .get_current_line()
             .expect("Index error while retrieving current line");
 
-        (0..cursor_x_position).fold(0, |acc, x| {
-            match current_line.text.chars().nth(x as usize) {
-                Some('\t') => acc + TAB_SIZE as u16 - (acc % TAB_SIZE as u16),
-                _ => acc + 1,
+        let mut graphemes = current_line.graphemes(true);
+
+        (0..cursor_x_position).fold(0, |acc, _| match graphemes.next() {
+            Some("\t") => {
+                acc + self.settings.tab_size as u16 - (acc % self.settings.tab_size as u16)
             }
+            Some(grapheme) => acc + grapheme_width(grapheme) as u16,
+            None => acc + 1,
         })
     }
 
@@ -765,18 +1399,13 @@ impl EditorInstance {
             .get_current_line()
             .expect("Index error while retrieving current line");
 
-        let num_characters_in_line = current_line.text.chars().count();
-        let mut text_iterator = current_line.text.chars();
-
-        while (calculated_x_position as usize) < num_characters_in_line {
-            let char = text_iterator.next();
-
-            match char {
-                Some('\t') => {
-                    calculated_render_x_position +=
-                        TAB_SIZE as u16 - (calculated_render_x_position % TAB_SIZE as u16)
+        for grapheme in current_line.graphemes(true) {
+            match grapheme {
+                "\t" => {
+                    calculated_render_x_position += self.settings.tab_size as u16
+                        - (calculated_render_x_position % self.settings.tab_size as u16)
                 }
-                _ => calculated_render_x_position += 1,
+                _ => calculated_render_x_position += grapheme_width(grapheme) as u16,
             }
 
             if calculated_render_x_position > cursor_render_x_position {
@@ -795,18 +1424,26 @@ impl EditorInstance {
             .try_into()
             .expect("Failed to convert usize to u16");
 
-        self.cursor_position.render_x = if (self.cursor_position.y as usize) < self.lines.len() {
-            self.cursor_x_to_render_x(self.cursor_position.x - num_columns_for_line_number)
-        } else {
-            0
-        } + num_columns_for_line_number;
-
-        if self.cursor_position.y < self.line_scrolled_to {
-            self.line_scrolled_to = self.cursor_position.y;
+        self.cursor_position.render_x =
+            if (self.cursor_position.y as usize) < self.buffer.line_count() {
+                self.cursor_x_to_render_x(self.cursor_position.x - num_columns_for_line_number)
+            } else {
+                0
+            } + num_columns_for_line_number;
+
+        // Lines kept visible around the cursor, as far as the buffer goes
+        let scroll_margin = self.get_scroll_margin();
+        let num_lines_below =
+            (self.buffer.line_count() as u32).saturating_sub(self.cursor_position.y + 1);
+        let first_line = self.cursor_position.y.saturating_sub(scroll_margin);
+        let last_line = self.cursor_position.y + min(scroll_margin, num_lines_below);
+
+        if first_line < self.line_scrolled_to {
+            self.line_scrolled_to = first_line;
         }
 
-        if self.cursor_position.y >= self.line_scrolled_to + self.window_size.rows {
-            self.line_scrolled_to = self.cursor_position.y - self.window_size.rows + 1;
+        if last_line >= self.line_scrolled_to + self.window_size.rows {
+            self.line_scrolled_to = last_line - self.window_size.rows + 1;
         }
 
         if self.cursor_position.render_x - num_columns_for_line_number < self.column_scrolled_to {
@@ -818,155 +1455,148 @@ impl EditorInstance {
         }
     }
 
-    fn insert_character_into_line(&mut self, character: char) {
-        let line_index = self.cursor_position.y as usize;
-        let line = &mut self.lines[line_index];
-
-        line.text.insert(
-            (self.cursor_position.x as usize).saturating_sub(self.num_columns_for_line_number),
-            character,
-        );
-        line.render = EditorInstance::get_render_text_from_text(&line.text);
-        self.set_line_highlight(line_index);
+    /// The `scrolloff` setting, limited so that the cursor can be kept in view
+    fn get_scroll_margin(&self) -> u32 {
+        min(
+            self.settings.scroll_margin as u32,
+            self.window_size.rows.saturating_sub(1) / 2,
+        )
     }
 
-    fn insert_character(&mut self, character: char) {
-        if self.cursor_position.y as usize == self.lines.len() {
-            self.lines.push(Line {
-                text: String::new(),
-                render: String::new(),
-                highlight: vec![],
-                index: self.lines.len(),
-                has_open_multiline_comment: false,
-            });
+    /// Inserts a tab's worth of spaces with `expandtab`, reaching the next tab stop
+    fn insert_spaces_to_tab_stop(&mut self) {
+        let render_x = match (self.cursor_position.y as usize) < self.buffer.line_count() {
+            true => self.cursor_x_to_render_x(
+                self.get_text_position()
+                    .column
+                    .try_into()
+                    .unwrap_or(u16::MAX),
+            ),
+            false => 0,
+        };
+        let tab_size = self.settings.tab_size as u16;
 
-            self.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+        for _ in 0..tab_size - render_x % tab_size {
+            self.insert_character(' ');
         }
-
-        self.insert_character_into_line(character);
-        self.cursor_position.x += 1;
-        self.edited = true;
     }
 
-    fn append_string_to_previous_line(&mut self, string: &str) {
-        let previous_line_index = (self.cursor_position.y - 1) as usize;
-        let previous_line = &mut self.lines[previous_line_index];
-        previous_line.text.push_str(string);
-        previous_line.render = EditorInstance::get_render_text_from_text(&previous_line.text);
-        self.set_line_highlight(previous_line_index);
-    }
+    /// Returns the grapheme column following the inserted character, which is unchanged if
+    /// the character combines with the preceding grapheme (e.g. an accent)
+    fn insert_character_into_line(&mut self, character: char) -> usize {
+        let char_index = self.get_char_index(self.get_text_position());
 
-    fn delete_character_from_line(&mut self) {
-        let line_index = self.cursor_position.y as usize;
-        let line = &mut self.lines[line_index];
-        line.text
-            .remove(self.cursor_position.x as usize - self.num_columns_for_line_number - 1);
-        line.render = EditorInstance::get_render_text_from_text(&line.text);
-        self.set_line_highlight(line_index);
+        self.insert_text_at(char_index, &character.to_string());
+
+        self.get_text_position_from_char_index(char_index + 1)
+            .column
     }
 
-    fn delete_character(&mut self) {
-        let line_index = self.cursor_position.y as usize;
+    fn insert_character(&mut self, character: char) {
+        let cursor_before = self.get_text_position();
 
-        if line_index == self.lines.len()
-            || (self.cursor_position.x as usize == self.num_columns_for_line_number
-                && line_index == 0)
-        {
-            return;
-        }
+        // Typing past the last line starts a new line at the end of the buffer
+        if self.cursor_position.y as usize == self.buffer.line_count() {
+            let end_of_buffer = self.buffer.len_chars();
+            self.insert_text_at(end_of_buffer, "\n");
 
-        if self.cursor_position.x as usize > self.num_columns_for_line_number {
-            self.delete_character_from_line();
-            self.cursor_position.x -= 1;
-        } else {
-            let line_number_columns_offset: u16 = self
-                .num_columns_for_line_number
-                .try_into()
-                .expect("Failed to convert usize to u16");
+            self.history.record(
+                Edit::Insert {
+                    char_index: end_of_buffer,
+                    text: String::from("\n"),
+                },
+                cursor_before,
+                cursor_before,
+            );
+        }
 
-            let previous_line_length: u16 = self.lines[line_index - 1]
-                .text
-                .chars()
-                .count()
-                .try_into()
-                .expect("Failed to convert line index usize to cursor x-position u16");
+        let char_index = self.get_char_index(self.get_text_position());
 
-            self.cursor_position.x = previous_line_length + line_number_columns_offset;
+        let column = self.insert_character_into_line(character);
+        self.cursor_position.x = (column + self.num_columns_for_line_number)
+            .try_into()
+            .unwrap_or(u16::MAX);
+        self.edited = true;
 
-            let string_to_append = self
-                .get_current_line()
-                .expect("Index error while retrieving current line")
-                .text
-                .clone();
+        self.history.record(
+            Edit::Insert {
+                char_index,
+                text: character.to_string(),
+            },
+            cursor_before,
+            self.get_text_position(),
+        );
+    }
 
-            self.append_string_to_previous_line(&string_to_append);
-            self.lines.remove(line_index);
+    /// Removes the grapheme before the cursor and returns it
+    fn delete_character_from_line(&mut self) -> String {
+        let column = self.cursor_position.x as usize - self.num_columns_for_line_number - 1;
+        let position = TextPosition {
+            line: self.cursor_position.y as usize,
+            column,
+        };
 
-            for line in self.lines.iter_mut().skip(line_index) {
-                line.index -= 1;
-            }
+        let start = self.get_char_index(position);
+        let end = self.get_char_index(TextPosition {
+            column: column + 1,
+            ..position
+        });
 
-            self.set_num_columns_for_line_number(LINE_NUMBER_GAP);
-            self.cursor_position.y -= 1;
-        }
+        let grapheme = self.buffer.slice(start..end);
+        self.delete_text_at(start, &grapheme);
 
-        self.edited = true;
+        grapheme
     }
 
-    fn insert_line(&mut self) {
+    fn delete_character(&mut self) {
         let line_index = self.cursor_position.y as usize;
 
-        if self.cursor_position.x as usize == self.num_columns_for_line_number {
-            self.lines.insert(
-                line_index,
-                Line {
-                    text: String::new(),
-                    render: String::new(),
-                    highlight: vec![],
-                    index: line_index,
-                    has_open_multiline_comment: false,
-                },
-            );
-
-            for line in self.lines.iter_mut().skip(line_index + 1) {
-                line.index += 1;
-            }
-        } else {
-            let new_next_line_text = self.lines[line_index].text
-                [self.cursor_position.x as usize - self.num_columns_for_line_number..]
-                .to_string();
+        if line_index == self.buffer.line_count()
+            || (self.cursor_position.x as usize == self.num_columns_for_line_number
+                && line_index == 0)
+        {
+            return;
+        }
 
-            let new_next_line_render_text =
-                EditorInstance::get_render_text_from_text(&new_next_line_text);
+        let cursor_before = self.get_text_position();
 
-            self.lines[line_index]
-                .text
-                .truncate(self.cursor_position.x as usize - self.num_columns_for_line_number);
+        if self.cursor_position.x as usize > self.num_columns_for_line_number {
+            let grapheme = self.delete_character_from_line();
+            self.cursor_position.x -= 1;
 
-            self.lines[line_index].render =
-                EditorInstance::get_render_text_from_text(&self.lines[line_index].text);
+            self.history.record(
+                Edit::Delete {
+                    char_index: self.get_char_index(self.get_text_position()),
+                    text: grapheme,
+                },
+                cursor_before,
+                self.get_text_position(),
+            );
+        } else {
+            // Joins the line onto the previous one by removing the newline between them
+            let char_index = self.buffer.line_to_char(line_index) - 1;
 
-            self.set_line_highlight(line_index);
+            self.delete_text_at(char_index, "\n");
+            self.set_cursor_from_text_position(self.get_text_position_from_char_index(char_index));
 
-            self.lines.insert(
-                line_index + 1,
-                Line {
-                    text: new_next_line_text,
-                    render: new_next_line_render_text,
-                    highlight: vec![],
-                    index: line_index + 1,
-                    has_open_multiline_comment: false,
+            self.history.record(
+                Edit::Delete {
+                    char_index,
+                    text: String::from("\n"),
                 },
+                cursor_before,
+                self.get_text_position(),
             );
+        }
 
-            self.set_line_highlight(line_index + 1);
+        self.edited = true;
+    }
 
-            for line in self.lines.iter_mut().skip(line_index + 2) {
-                line.index += 1;
-            }
-        }
+    fn insert_line(&mut self) {
+        let cursor_before = self.get_text_position();
+        let char_index = self.get_char_index(cursor_before);
 
-        self.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+        self.insert_text_at(char_index, "\n");
 
         self.cursor_position.y += 1;
         self.cursor_position.x = self
@@ -975,2091 +1605,6105 @@ impl EditorInstance {
             .expect("Failed to convert new cursor x-position usize to u16");
 
         self.edited = true;
-    }
-
-    fn find_text_callback(&mut self, query: &str, key: Key) {
-        if let Some(saved_highlight) = self.saved_highlight.take() {
-            self.lines[saved_highlight.line_index].highlight = saved_highlight.highlight;
-        }
-
-        match key {
-            Key::U8(key) if key == b'\x1b' || key == b'\r' => {
-                self.previous_search_match_line_index = None;
-                self.search_direction = SearchDirection::Forward;
-                return;
-            }
-            Key::Custom(EditorKey::ArrowRight) | Key::Custom(EditorKey::ArrowDown) => {
-                self.search_direction = SearchDirection::Forward;
-            }
-            Key::Custom(EditorKey::ArrowLeft) | Key::Custom(EditorKey::ArrowUp) => {
-                self.search_direction = SearchDirection::Backward;
-            }
-            _ => {
-                self.previous_search_match_line_index = None;
-                self.search_direction = SearchDirection::Forward;
-            }
-        }
 
-        if self.previous_search_match_line_index.is_none() {
-            self.search_direction = SearchDirection::Forward;
-        }
+        self.history.record(
+            Edit::Insert {
+                char_index,
+                text: String::from("\n"),
+            },
+            cursor_before,
+            self.get_text_position(),
+        );
+    }
 
-        let mut current_line_index: isize = match self.previous_search_match_line_index {
-            Some(i) => i
-                .try_into()
-                .expect("Failed to convert previous search match line index usize to isize"),
-            None => {
-                let previous_search_match_line_index: isize = self
-                    .cursor_position
-                    .y
-                    .try_into()
-                    .expect("Failed to convert u32 to isize");
+    /// Buffers `key` until the keys typed form a complete command, then runs it
+    fn process_normal_mode_key(&mut self, key: char) {
+        self.normal_mode_keys.push(key);
 
-                previous_search_match_line_index - 1
-            }
+        let parse_result = match self.editor_mode {
+            EditorMode::Visual(_) => normal_mode::parse_visual(&self.normal_mode_keys),
+            _ => normal_mode::parse(&self.normal_mode_keys),
         };
 
-        for _ in 0..self.lines.len() {
-            current_line_index += match self.search_direction {
-                SearchDirection::Forward => 1,
-                SearchDirection::Backward => -1,
-            };
-
-            match current_line_index {
-                -1 => {
-                    let num_lines: isize = self
-                        .lines
-                        .len()
-                        .try_into()
-                        .expect("Failed to convert lines index usize to isize for search");
+        match parse_result {
+            ParseResult::Incomplete => {}
+            ParseResult::Invalid => self.normal_mode_keys.clear(),
+            ParseResult::Complete(parsed_command) => {
+                self.normal_mode_keys.clear();
 
-                    current_line_index = num_lines - 1;
-                }
-                x if x
-                    == self
-                        .lines
-                        .len()
-                        .try_into()
-                        .expect("Failed to convert number of lines from usize to isize") =>
-                {
-                    current_line_index = 0
-                }
-                _ => {}
-            }
+                let count = parsed_command.count_or_one();
+                self.active_register = parsed_command.register;
 
-            if self.lines[current_line_index as usize]
-                .render
-                .contains(query)
-            {
-                self.previous_search_match_line_index = Some(current_line_index as usize);
+                match parsed_command.command {
+                    NormalCommand::Move(motion) => {
+                        self.move_cursor_by_motion(motion, parsed_command.count)
+                    }
+                    NormalCommand::Operate(operator, target) => {
+                        self.apply_operator(operator, target, parsed_command.count)
+                    }
+                    NormalCommand::DeleteCharacters => self.delete_characters_under_cursor(count),
+                    NormalCommand::PutAfter => self.put_register(false, count),
+                    NormalCommand::PutBefore => self.put_register(true, count),
+                    NormalCommand::OpenLineBelow => self.open_line(false),
+                    NormalCommand::OpenLineAbove => self.open_line(true),
+                    NormalCommand::Insert => self.editor_mode = EditorMode::Insert,
+                    NormalCommand::Append => {
+                        if let Some(current_line) = self.get_current_line() {
+                            if self.get_text_position().column < grapheme_count(&current_line) {
+                                self.cursor_position.x = self.cursor_position.x.saturating_add(1);
+                            }
+                        }
 
-                self.cursor_position.y = current_line_index
-                    .try_into()
-                    .expect("Failed to convert matched line index usize to cursor y-position u32");
+                        self.editor_mode = EditorMode::Insert;
+                    }
+                    NormalCommand::AppendToLine => {
+                        if let Some(current_line) = self.get_current_line() {
+                            let column = grapheme_count(&current_line);
 
-                self.cursor_position.x = self.render_x_to_cursor_x(
-                    self.lines[current_line_index as usize]
-                        .render
-                        .find(query)
-                        .unwrap()
-                        .try_into()
-                        .expect(
-                            "Failed to convert matched line index usize to cursor x-position u16",
-                        ),
-                ) + self.num_columns_for_line_number as u16;
+                            self.set_cursor_from_text_position(TextPosition {
+                                column,
+                                ..self.get_text_position()
+                            });
+                        }
 
-                self.line_scrolled_to = self.cursor_position.y.saturating_sub(5);
+                        self.editor_mode = EditorMode::Insert;
+                    }
+                    NormalCommand::InsertAtLineStart => {
+                        self.move_cursor_to_first_non_blank(self.cursor_position.y as usize);
+                        self.editor_mode = EditorMode::Insert;
+                    }
+                    NormalCommand::JoinLines => self.join_lines(count),
+                    NormalCommand::ReplaceCharacters(character) => {
+                        self.replace_characters_under_cursor(character, count)
+                    }
+                    NormalCommand::Undo => {
+                        for _ in 0..count {
+                            if !self.undo() {
+                                break;
+                            }
+                        }
+                    }
+                    NormalCommand::Select(kind) => self.select(kind),
+                    NormalCommand::CommandLine => self.prompt_and_run_command(),
+                    NormalCommand::SwapSelectionEnds => {
+                        let cursor = self.get_text_position();
 
-                if (self.line_scrolled_to + self.window_size.rows) as usize >= self.lines.len() {
-                    self.line_scrolled_to = self
-                        .lines
-                        .len()
-                        .saturating_sub(self.window_size.rows as usize)
-                        .try_into()
-                        .expect("Failed to convert usize to u32");
+                        self.set_cursor_from_text_position(self.selection_anchor);
+                        self.selection_anchor = cursor;
+                    }
                 }
 
-                self.saved_highlight = Some(SavedHighlight {
-                    line_index: current_line_index as usize,
-                    highlight: self.lines[current_line_index as usize].highlight.clone(),
-                });
-
-                let start = self.cursor_position.x as usize - self.num_columns_for_line_number;
-                self.lines[current_line_index as usize].highlight[start..start + query.len()]
-                    .fill(HighlightType::SearchMatch);
-
-                return;
+                self.active_register = None;
             }
         }
     }
 
-    fn prompt_and_go_to_line(&mut self) {
-        if let Some(line) = prompt_user::<fn(&mut EditorInstance, &str, Key)>(
-            self,
-            &format!(
-                "Enter a line number between 1 and {} (ESC to abort): ",
-                self.lines.len() + 1
-            ),
-            None,
-        ) {
-            match line.parse::<u32>() {
-                Ok(line) if line > 0 => {
-                    let num_lines = self
-                        .lines
-                        .len()
-                        .try_into()
-                        .expect("Failed to convert usize to u32");
-
-                    self.cursor_position.y = min(line - 1, num_lines);
-                    self.cursor_position.x = self
-                        .num_columns_for_line_number
-                        .try_into()
-                        .expect("Failed to convert line number column index to u16");
-
-                    self.line_scrolled_to =
-                        min(line - 1, num_lines).saturating_sub(self.window_size.rows / 2)
-                }
-                _ => self.set_status_message("Invalid line number", true),
-            };
+    /// Returns where `motion`, repeated `count` times, leads from `position`, or `None` if it
+    /// cannot be made (e.g. `f` without a match)
+    /// Horizontal motions may reach the end of the line itself, as operators treat the target
+    /// of an exclusive motion as just past the text they act on.
+    fn get_motion_target(
+        &self,
+        motion: Motion,
+        count: Option<usize>,
+        position: TextPosition,
+    ) -> Option<TextPosition> {
+        let line_count = self.buffer.line_count();
+
+        if line_count == 0 {
+            return None;
         }
-    }
 
-    fn prompt_and_find_text(&mut self) {
-        let saved_cursor_position = self.cursor_position;
-        let saved_column_scrolled_to = self.column_scrolled_to;
-        let saved_line_scrolled_to = self.line_scrolled_to;
+        let last_line_index = line_count - 1;
+        let times = count.unwrap_or(1);
 
-        if prompt_user(
-            self,
-            "Search (ESC to abort, arrows to jump): ",
-            Some(EditorInstance::find_text_callback),
-        )
-        .is_none()
-        {
-            self.cursor_position = saved_cursor_position;
-            self.column_scrolled_to = saved_column_scrolled_to;
-            self.line_scrolled_to = saved_line_scrolled_to;
-        }
-    }
+        let line_length = |line_index: usize| grapheme_count(&self.buffer.line(line_index));
+        let first_non_blank = |line_index: usize| TextPosition {
+            line: line_index,
+            column: EditorInstance::get_first_non_blank_column(&self.buffer.line(line_index)),
+        };
 
-    fn add_welcome_message_to_buffer(&self, buffer: &mut String, message: &mut String) {
-        message.truncate(self.window_size.columns as usize);
+        let target = match motion {
+            Motion::Left => TextPosition {
+                column: position.column.saturating_sub(times),
+                ..position
+            },
+            Motion::Right => TextPosition {
+                column: min(
+                    position.column.saturating_add(times),
+                    line_length(position.line),
+                ),
+                ..position
+            },
+            Motion::Down | Motion::Up => {
+                let line = match motion {
+                    Motion::Down => min(position.line.saturating_add(times), last_line_index),
+                    _ => position.line.saturating_sub(times),
+                };
 
-        let message_length: u16 = message
-            .chars()
-            .count()
-            .try_into()
-            .expect("Failed to convert welcome message length to u16 during screen refresh");
+                TextPosition {
+                    line,
+                    column: min(position.column, line_length(line)),
+                }
+            }
+            Motion::WordForward
+            | Motion::WordBackward
+            | Motion::WordEnd
+            | Motion::BigWordForward
+            | Motion::BigWordBackward
+            | Motion::BigWordEnd => {
+                let mut scanner = WordScanner::new(
+                    &self.buffer,
+                    matches!(
+                        motion,
+                        Motion::BigWordForward | Motion::BigWordBackward | Motion::BigWordEnd
+                    ),
+                );
 
-        let mut padding = (self.window_size.columns - message_length) / 2;
+                (0..times).fold(position, |position, _| match motion {
+                    Motion::WordForward | Motion::BigWordForward => {
+                        scanner.next_word_start(position)
+                    }
+                    Motion::WordBackward | Motion::BigWordBackward => {
+                        scanner.previous_word_start(position)
+                    }
+                    _ => scanner.next_word_end(position),
+                })
+            }
+            Motion::LineStart => TextPosition {
+                column: 0,
+                ..position
+            },
+            Motion::FirstNonBlank => first_non_blank(position.line),
+            Motion::LineEnd => {
+                let line = min(position.line + times - 1, last_line_index);
 
-        if padding > 0 {
-            buffer.push('~');
-            padding -= 1;
-        }
+                TextPosition {
+                    line,
+                    column: line_length(line).saturating_sub(1),
+                }
+            }
+            Motion::FileStart => first_non_blank(min(count.unwrap_or(1), line_count) - 1),
+            Motion::FileEnd => first_non_blank(min(count.unwrap_or(line_count), line_count) - 1),
+            Motion::ParagraphForward | Motion::ParagraphBackward => {
+                let is_empty = |line_index: usize| self.buffer.line(line_index).is_empty();
+                let forward = motion == Motion::ParagraphForward;
+                let mut line = position.line;
+
+                for _ in 0..times {
+                    let at_end = |line: usize| match forward {
+                        true => line >= last_line_index,
+                        false => line == 0,
+                    };
+                    let step = |line: usize| match forward {
+                        true => line + 1,
+                        false => line - 1,
+                    };
+
+                    while !at_end(line) && is_empty(line) {
+                        line = step(line);
+                    }
 
-        for _ in 0..padding {
-            buffer.push(' ');
-        }
+                    while !at_end(line) && !is_empty(line) {
+                        line = step(line);
+                    }
+                }
 
-        buffer.push_str(message);
-    }
+                // Without an empty line to stop at, the paragraph ends with the buffer
+                match (forward, is_empty(line)) {
+                    (true, false) => TextPosition {
+                        line,
+                        column: line_length(line),
+                    },
+                    _ => TextPosition { line, column: 0 },
+                }
+            }
+            Motion::ScreenTop | Motion::ScreenMiddle | Motion::ScreenBottom => {
+                let first_visible_line = min(self.line_scrolled_to as usize, last_line_index);
+                let last_visible_line = min(
+                    first_visible_line + (self.window_size.rows as usize).max(1) - 1,
+                    last_line_index,
+                );
 
-    fn set_num_columns_for_line_number(&mut self, line_number_gap: u8) {
-        let num_lines = self.lines.len();
+                // Lines within `scrolloff` of the edges are skipped, as they would scroll into
+                // the middle, unless they are at the ends of the buffer
+                let scroll_margin = self.get_scroll_margin() as usize;
+                let top_margin = match first_visible_line {
+                    0 => 0,
+                    _ => scroll_margin,
+                };
+                let bottom_margin = match last_visible_line == last_line_index {
+                    true => 0,
+                    false => scroll_margin,
+                };
 
-        self.num_columns_for_line_number = if num_lines > 0 {
-            num_lines.to_string().len() + line_number_gap as usize
-        } else {
-            0
+                first_non_blank(match motion {
+                    Motion::ScreenTop => min(
+                        first_visible_line + max(times - 1, top_margin),
+                        last_visible_line,
+                    ),
+                    Motion::ScreenBottom => max(
+                        last_visible_line.saturating_sub(max(times - 1, bottom_margin)),
+                        first_visible_line,
+                    ),
+                    _ => first_visible_line + (last_visible_line - first_visible_line) / 2,
+                })
+            }
+            Motion::FindCharacter(find) => {
+                return self.find_character_on_line(find, times, position, false)
+            }
+            Motion::RepeatCharacterFind | Motion::RepeatCharacterFindReversed => {
+                let find = self.last_character_find?;
+                let find = match motion {
+                    Motion::RepeatCharacterFind => find,
+                    _ => find.reversed(),
+                };
+
+                return self.find_character_on_line(find, times, position, true);
+            }
+            Motion::MatchingBracket => return self.find_matching_bracket(position),
         };
 
-        if (self.cursor_position.x as usize) < self.num_columns_for_line_number {
-            self.cursor_position.x = self
-                .num_columns_for_line_number
-                .try_into()
-                .expect("Failed to convert line number column index to u16");
+        Some(target)
+    }
+
+    /// Resolves `;` and `,` to the find they repeat
+    fn get_motion_kind(&self, motion: Motion) -> MotionKind {
+        match (motion, self.last_character_find) {
+            (Motion::RepeatCharacterFind, Some(find)) => Motion::FindCharacter(find).kind(),
+            (Motion::RepeatCharacterFindReversed, Some(find)) => {
+                Motion::FindCharacter(find.reversed()).kind()
+            }
+            _ => motion.kind(),
         }
     }
 
-    /// Uses a String as a buffer to store all lines, before calling `write` once
-    /// Prints a welcome message in the middle of the screen using its row/column count
-    pub fn draw_rows(&mut self) {
-        let mut buffer = String::new();
+    /// `f`/`t`/`F`/`T`: the `count`th occurrence of the character on the line
+    /// A repeated `t` or `T` skips an occurrence right next to the cursor, which it would
+    /// otherwise stop in front of again.
+    fn find_character_on_line(
+        &self,
+        find: CharacterFind,
+        count: usize,
+        position: TextPosition,
+        is_repeat: bool,
+    ) -> Option<TextPosition> {
+        let line = self.buffer.line(position.line);
+        let graphemes: Vec<&str> = line.graphemes(true).collect();
+        let character = find.character.to_string();
+
+        let skip = match find.till && is_repeat {
+            true => 2,
+            false => 1,
+        };
 
-        if (self.cursor_position.x as usize) < self.num_columns_for_line_number {
-            self.cursor_position.x = self
-                .num_columns_for_line_number
-                .try_into()
-                .expect("Failed to convert line number column index to u16");
-        }
+        let columns: Vec<usize> = match find.backward {
+            true => (0..position.column.saturating_sub(skip - 1))
+                .rev()
+                .collect(),
+            false => (position.column + skip..graphemes.len()).collect(),
+        };
 
-        for row in 0..self.window_size.rows {
-            let scrolled_to_row = row + self.line_scrolled_to;
+        let column = columns
+            .into_iter()
+            .filter(|column| graphemes[*column] == character)
+            .nth(count - 1)?;
 
-            if scrolled_to_row as usize >= self.lines.len() {
-                if self.lines.is_empty() && row == self.window_size.rows / 3 {
-                    self.add_welcome_message_to_buffer(&mut buffer, &mut WELCOME_MESSAGE.clone());
-                } else {
-                    buffer.push('~');
-                }
-            } else {
-                let line = &self.lines[scrolled_to_row as usize];
-                let line_content = &line.render;
+        let column = match (find.till, find.backward) {
+            (true, false) => column - 1,
+            (true, true) => column + 1,
+            _ => column,
+        };
 
-                let start = self.column_scrolled_to as usize;
-                let end = max(
-                    start + self.window_size.columns as usize - self.num_columns_for_line_number,
-                    start,
-                );
+        Some(TextPosition { column, ..position })
+    }
 
-                let num_characters = line_content.chars().count();
+    /// `%`: the bracket matching the first one at or after the cursor on its line
+    fn find_matching_bracket(&self, position: TextPosition) -> Option<TextPosition> {
+        const BRACKET_PAIRS: [(&str, &str); 3] = [("(", ")"), ("[", "]"), ("{", "}")];
 
-                let to_iter = if num_characters > end {
-                    Some(&line_content[start..end])
-                } else if num_characters > start {
-                    Some(&line_content[start..])
-                } else {
-                    None
-                };
+        let line = self.buffer.line(position.line);
 
-                let mut line_prefix = (line.index + 1).to_string();
+        let (column, bracket) = line
+            .graphemes(true)
+            .enumerate()
+            .skip(position.column)
+            .find(|(_, grapheme)| {
+                BRACKET_PAIRS
+                    .iter()
+                    .any(|(open, close)| grapheme == open || grapheme == close)
+            })?;
 
-                line_prefix.push_str(
-                    &" ".repeat(self.num_columns_for_line_number - line_prefix.chars().count()),
-                );
+        let (&(open, close), forward) = BRACKET_PAIRS.iter().find_map(|pair| match bracket {
+            bracket if bracket == pair.0 => Some((pair, true)),
+            bracket if bracket == pair.1 => Some((pair, false)),
+            _ => None,
+        })?;
 
-                buffer.push_str(&line_prefix);
+        let mut depth = 0;
+        let mut line_index = position.line;
 
-                if let Some(to_iter) = to_iter {
-                    let mut current_highlight_type = &HighlightType::Normal;
+        loop {
+            let text = self.buffer.line(line_index);
+            let graphemes: Vec<&str> = text.graphemes(true).collect();
 
-                    to_iter.chars().enumerate().for_each(|(i, char)| {
-                        if char.is_ascii_control() {
-                            buffer.push_str(AnsiEscapeCode::ReverseMode.as_str());
-                            buffer.push(if char as u8 <= 26 {
-                                (b'@' + char as u8) as char
-                            } else {
-                                '?'
-                            });
-                            buffer.push_str(AnsiEscapeCode::Reset.as_str());
-
-                            if current_highlight_type != &HighlightType::Normal {
-                                buffer.push_str("\x1b[");
-                                buffer.push_str(
-                                    &EditorInstance::get_color_from_highlight_type(
-                                        current_highlight_type,
-                                    )
-                                    .to_string(),
-                                );
-                                buffer.push('m');
-                            }
-                        } else {
-                            let highlight_type = &line.highlight[start + i];
-
-                            match highlight_type {
-                                HighlightType::Normal => {
-                                    if current_highlight_type != &HighlightType::Normal {
-                                        buffer.push_str(AnsiEscapeCode::DefaultColor.as_str());
-                                        current_highlight_type = &HighlightType::Normal;
-                                    }
-                                }
-                                _ => {
-                                    if current_highlight_type != highlight_type {
-                                        current_highlight_type = highlight_type;
-                                        buffer.push_str("\x1b[");
-                                        buffer.push_str(
-                                            &EditorInstance::get_color_from_highlight_type(
-                                                highlight_type,
-                                            )
-                                            .to_string(),
-                                        );
-                                        buffer.push('m');
-                                    }
-                                }
-                            };
+            let columns: Vec<usize> = match (forward, line_index == position.line) {
+                (true, true) => (column..graphemes.len()).collect(),
+                (true, false) => (0..graphemes.len()).collect(),
+                (false, true) => (0..=column).rev().collect(),
+                (false, false) => (0..graphemes.len()).rev().collect(),
+            };
 
-                            buffer.push(char);
-                        }
-                    });
+            for column in columns {
+                if graphemes[column] == open {
+                    depth += if forward { 1 } else { -1 };
+                } else if graphemes[column] == close {
+                    depth += if forward { -1 } else { 1 };
+                }
 
-                    buffer.push_str(AnsiEscapeCode::DefaultColor.as_str());
+                if depth == 0 {
+                    return Some(TextPosition {
+                        line: line_index,
+                        column,
+                    });
                 }
             }
 
-            buffer.push_str(AnsiEscapeCode::EraseLineToRight.as_str());
-            buffer.push_str("\r\n");
+            match forward {
+                true if line_index + 1 < self.buffer.line_count() => line_index += 1,
+                false if line_index > 0 => line_index -= 1,
+                _ => return None,
+            }
         }
-
-        write!(io::stdout(), "{}", buffer).expect("Failed to write to stdout while drawing rows");
-        flush_stdout();
     }
 
-    fn get_editor_mode_display(&self) -> String {
-        let mut buffer = String::new();
+    fn move_cursor_by_motion(&mut self, motion: Motion, count: Option<usize>) {
+        let position = self.get_text_position();
+
+        if position.line >= self.buffer.line_count() {
+            if motion == Motion::Up {
+                self.move_cursor(CursorMovement::Up);
+            }
 
-        if self.editor_mode == EditorMode::Insert {
-            buffer.push_str(" NORMAL (<esc>) ");
+            return;
         }
 
-        buffer.push_str(AnsiEscapeCode::Reset.as_str());
-        buffer.push_str(AnsiEscapeCode::BackgroundGreen.as_str());
-        buffer.push_str(AnsiEscapeCode::ForegroundBlack.as_str());
+        let mut target = match self.get_motion_target(motion, count, position) {
+            Some(target) => target,
+            None => return,
+        };
 
-        match self.editor_mode {
-            EditorMode::Normal => buffer.push_str(" NORMAL "),
-            EditorMode::Insert => buffer.push_str(" INSERT "),
+        if let Motion::FindCharacter(find) = motion {
+            self.last_character_find = Some(find);
         }
 
-        buffer.push_str(AnsiEscapeCode::Reset.as_str());
-        buffer.push_str(AnsiEscapeCode::ReverseMode.as_str());
+        // The cursor rests on the last character rather than past it, unless it already was
+        if !matches!(motion, Motion::Down | Motion::Up) {
+            let last_column = grapheme_count(&self.buffer.line(target.line)).saturating_sub(1);
+            let current_column = match target.line == position.line {
+                true => position.column,
+                false => 0,
+            };
 
-        if self.editor_mode == EditorMode::Normal {
-            buffer.push_str(" INSERT (i)");
+            target.column = min(target.column, max(last_column, current_column));
         }
 
-        buffer
+        self.set_cursor_from_text_position(target);
     }
 
-    pub fn draw_status_bar(&self) {
-        let mut buffer = AnsiEscapeCode::Reset.as_string();
+    fn get_first_non_blank_column(line: &str) -> usize {
+        line.graphemes(true)
+            .take_while(|grapheme| grapheme.chars().all(char::is_whitespace))
+            .count()
+    }
 
-        buffer.push_str(AnsiEscapeCode::ReverseMode.as_str());
+    fn move_cursor_to_first_non_blank(&mut self, line_index: usize) {
+        let column = match line_index < self.buffer.line_count() {
+            true => EditorInstance::get_first_non_blank_column(&self.buffer.line(line_index)),
+            false => 0,
+        };
 
-        let num_lines = self.lines.len();
+        self.set_cursor_from_text_position(TextPosition {
+            line: line_index,
+            column,
+        });
+    }
 
-        let mut status_bar_content = format!(
-            " {:.20}{} {} {} {} line{} {} {} MODE: {} {} {} Ctrl-H: help {} {}",
-            self.file.as_ref().map_or("[New File]", |file| &file.name),
-            if self.edited {
-                format!(
-                    " {}(modified){}{}",
-                    AnsiEscapeCode::BackgroundRed.as_str(),
-                    AnsiEscapeCode::Reset.as_str(),
-                    AnsiEscapeCode::ReverseMode.as_str(),
-                )
-            } else {
-                String::new()
+    /// The `char` range of whole lines, including the newline of the last one
+    fn get_line_char_range(&self, lines: Range<usize>) -> Range<usize> {
+        self.buffer.line_to_char(lines.start)..self.buffer.line_to_char(lines.end)
+    }
+
+    fn apply_operator(&mut self, operator: Operator, target: OperatorTarget, count: Option<usize>) {
+        let cursor = self.get_text_position();
+        let line_count = self.buffer.line_count();
+
+        if cursor.line >= line_count {
+            return;
+        }
+
+        let (start, end, linewise) = match target {
+            OperatorTarget::Selection => {
+                return self.apply_operator_to_selection(operator, count.unwrap_or(1))
+            }
+            OperatorTarget::Lines => (
+                cursor,
+                TextPosition {
+                    line: min(cursor.line + count.unwrap_or(1) - 1, line_count - 1),
+                    ..cursor
+                },
+                true,
+            ),
+            OperatorTarget::Motion(motion) => {
+                let mut scanner = WordScanner::new(&self.buffer, false);
+
+                // Like vim, `cw` on a word changes to its end, leaving the following space
+                let motion = match (operator, motion) {
+                    (Operator::Change, Motion::WordForward)
+                        if scanner.get_class(cursor) != CharacterClass::Whitespace =>
+                    {
+                        Motion::WordEnd
+                    }
+                    (Operator::Change, Motion::BigWordForward)
+                        if scanner.get_class(cursor) != CharacterClass::Whitespace =>
+                    {
+                        Motion::BigWordEnd
+                    }
+                    _ => motion,
+                };
+
+                let mut target = match self.get_motion_target(motion, count, cursor) {
+                    Some(target) => target,
+                    None => return,
+                };
+
+                if let Motion::FindCharacter(find) = motion {
+                    self.last_character_find = Some(find);
+                }
+
+                // A word motion that leaves the line stops at its end rather than taking the
+                // indentation of the next line too
+                if matches!(motion, Motion::WordForward | Motion::BigWordForward)
+                    && target.line > cursor.line
+                    && target.column
+                        <= EditorInstance::get_first_non_blank_column(
+                            &self.buffer.line(target.line),
+                        )
+                {
+                    target = TextPosition {
+                        line: target.line - 1,
+                        column: grapheme_count(&self.buffer.line(target.line - 1)),
+                    };
+                }
+
+                let (start, mut end) =
+                    match (target.line, target.column) < (cursor.line, cursor.column) {
+                        true => (target, cursor),
+                        false => (cursor, target),
+                    };
+
+                match self.get_motion_kind(motion) {
+                    MotionKind::Linewise => (start, end, true),
+                    MotionKind::Inclusive => {
+                        end.column += 1;
+                        (start, end, false)
+                    }
+                    MotionKind::Exclusive => {
+                        // An exclusive motion to the start of a line ends with the line before
+                        if end.line > start.line && end.column == 0 {
+                            end = TextPosition {
+                                line: end.line - 1,
+                                column: grapheme_count(&self.buffer.line(end.line - 1)),
+                            };
+                        }
+
+                        (start, end, false)
+                    }
+                }
+            }
+        };
+
+        match linewise {
+            true => self.operate_on_lines(operator, start.line..end.line + 1, cursor.column),
+            false => {
+                let char_range = self.get_char_index(start)..self.get_char_index(end);
+                self.operate_on_characters(operator, char_range);
+            }
+        }
+    }
+
+    fn change_case(operator: Operator, text: &str) -> String {
+        match operator {
+            Operator::Lowercase => text.to_lowercase(),
+            Operator::Uppercase => text.to_uppercase(),
+            _ => text
+                .chars()
+                .map(|char| match char.is_lowercase() {
+                    true => char.to_uppercase().collect::<String>(),
+                    false => char.to_lowercase().collect::<String>(),
+                })
+                .collect(),
+        }
+    }
+
+    /// Replaces the text in `char_range` with its case changed by `operator`
+    fn change_case_at(&mut self, operator: Operator, char_range: Range<usize>) {
+        let text = self.buffer.slice(char_range.clone());
+        let changed_text = EditorInstance::change_case(operator, &text);
+
+        if changed_text != text {
+            self.replace_text_at(char_range.start, &text, &changed_text);
+        }
+    }
+
+    /// Applies an operator to whole lines; `column` is where the cursor was
+    fn operate_on_lines(&mut self, operator: Operator, lines: Range<usize>, column: usize) {
+        let char_range = self.get_line_char_range(lines.clone());
+
+        match operator {
+            Operator::Indent | Operator::Outdent => {
+                self.shift_lines(lines.clone(), operator == Operator::Indent);
+                self.move_cursor_to_first_non_blank(lines.start);
+            }
+            Operator::ToggleCase | Operator::Lowercase | Operator::Uppercase => {
+                self.change_case_at(operator, char_range);
+                self.set_cursor_from_text_position(TextPosition {
+                    line: lines.start,
+                    column: min(column, grapheme_count(&self.buffer.line(lines.start))),
+                });
+            }
+            Operator::Delete => {
+                let text = self.buffer.slice(char_range.clone());
+                self.store_in_register(
+                    Register {
+                        text: text.clone(),
+                        linewise: true,
+                    },
+                    true,
+                );
+                self.replace_text_at(char_range.start, &text, "");
+
+                let line_index = min(lines.start, self.buffer.line_count().saturating_sub(1));
+                self.move_cursor_to_first_non_blank(line_index);
+            }
+            Operator::Change => {
+                self.store_in_register(
+                    Register {
+                        text: self.buffer.slice(char_range.clone()),
+                        linewise: true,
+                    },
+                    true,
+                );
+
+                // The lines are emptied, leaving one to type into
+                let char_range = char_range.start..char_range.end - 1;
+                let text = self.buffer.slice(char_range.clone());
+                self.replace_text_at(char_range.start, &text, "");
+
+                self.set_cursor_from_text_position(TextPosition {
+                    line: lines.start,
+                    column: 0,
+                });
+                self.editor_mode = EditorMode::Insert;
+            }
+            Operator::Yank => {
+                self.store_in_register(
+                    Register {
+                        text: self.buffer.slice(char_range),
+                        linewise: true,
+                    },
+                    false,
+                );
+                self.set_cursor_from_text_position(TextPosition {
+                    line: lines.start,
+                    column: min(column, grapheme_count(&self.buffer.line(lines.start))),
+                });
+            }
+        }
+    }
+
+    /// Applies an operator to the text in `char_range`, which may span lines
+    fn operate_on_characters(&mut self, operator: Operator, char_range: Range<usize>) {
+        if char_range.is_empty() {
+            return;
+        }
+
+        let start = self.get_text_position_from_char_index(char_range.start);
+
+        match operator {
+            Operator::Indent | Operator::Outdent => {
+                let last_line_index = self.buffer.char_to_line(char_range.end - 1);
+
+                self.shift_lines(
+                    start.line..last_line_index + 1,
+                    operator == Operator::Indent,
+                );
+                self.move_cursor_to_first_non_blank(start.line);
+                return;
+            }
+            Operator::ToggleCase | Operator::Lowercase | Operator::Uppercase => {
+                self.change_case_at(operator, char_range)
+            }
+            Operator::Delete | Operator::Change | Operator::Yank => {
+                let text = self.buffer.slice(char_range.clone());
+
+                self.store_in_register(
+                    Register {
+                        text: text.clone(),
+                        linewise: false,
+                    },
+                    operator != Operator::Yank,
+                );
+
+                if operator != Operator::Yank {
+                    self.replace_text_at(char_range.start, &text, "");
+                }
+
+                if operator == Operator::Change {
+                    self.editor_mode = EditorMode::Insert;
+                }
+            }
+        }
+
+        self.set_cursor_from_text_position(start);
+    }
+
+    /// Applies an operator to the columns `columns` of each line in `lines`
+    /// Yanked blocks are put as characters, one block line per line.
+    fn operate_on_block(&mut self, operator: Operator, lines: Range<usize>, columns: Range<usize>) {
+        let top_left = TextPosition {
+            line: lines.start,
+            column: columns.start,
+        };
+
+        if let Operator::Indent | Operator::Outdent = operator {
+            self.shift_lines(lines.clone(), operator == Operator::Indent);
+            self.move_cursor_to_first_non_blank(lines.start);
+            return;
+        }
+
+        let mut block_lines = vec![];
+
+        for line_index in lines.clone() {
+            let start = TextPosition {
+                line: line_index,
+                column: columns.start,
+            };
+            let end = TextPosition {
+                column: columns.end,
+                ..start
+            };
+
+            let char_range = self.get_char_index(start)..self.get_char_index(end);
+            block_lines.push(self.buffer.slice(char_range.clone()));
+
+            match operator {
+                Operator::Delete | Operator::Change => {
+                    let text = self.buffer.slice(char_range.clone());
+
+                    if !text.is_empty() {
+                        self.replace_text_at(char_range.start, &text, "");
+                    }
+                }
+                Operator::ToggleCase | Operator::Lowercase | Operator::Uppercase => {
+                    self.change_case_at(operator, char_range)
+                }
+                _ => {}
+            }
+        }
+
+        let text = block_lines.join("\n");
+
+        // A block of nothing, on lines too short to reach it, leaves the registers as they were
+        if !text.is_empty()
+            && matches!(
+                operator,
+                Operator::Delete | Operator::Change | Operator::Yank
+            )
+        {
+            self.store_in_register(
+                Register {
+                    text,
+                    linewise: false,
+                },
+                operator != Operator::Yank,
+            );
+        }
+
+        if operator == Operator::Change {
+            // What is typed on the first line is copied to the rest on leaving Insert mode
+            self.pending_block_insert = Some(BlockInsert {
+                position: top_left,
+                lines,
+            });
+            self.editor_mode = EditorMode::Insert;
+        }
+
+        self.set_cursor_from_text_position(top_left);
+    }
+
+    /// Copies the text typed since a block change to the other lines of the block, at the same
+    /// column; lines that are too short are skipped
+    fn finish_block_insert(&mut self) {
+        let block_insert = match self.pending_block_insert.take() {
+            Some(block_insert) => block_insert,
+            None => return,
+        };
+
+        let position = block_insert.position;
+        let cursor = self.get_text_position();
+
+        if cursor.line != position.line || cursor.column <= position.column {
+            return;
+        }
+
+        let char_range = self.get_char_index(position)..self.get_char_index(cursor);
+        let text = self.buffer.slice(char_range);
+
+        for line_index in block_insert.lines.skip(1) {
+            // Lines that end before the block are left alone
+            if grapheme_count(&self.buffer.line(line_index)) > position.column {
+                let char_index = self.get_char_index(TextPosition {
+                    line: line_index,
+                    column: position.column,
+                });
+
+                self.replace_text_at(char_index, "", &text);
+            }
+        }
+
+        self.set_cursor_from_text_position(position);
+    }
+
+    /// The selected lines and, for a block, columns; `None` outside Visual mode
+    fn get_selection(&self) -> Option<(SelectionKind, TextPosition, TextPosition)> {
+        let kind = match self.editor_mode {
+            EditorMode::Visual(kind) => kind,
+            _ => return None,
+        };
+
+        let cursor = self.get_text_position();
+        let anchor = self.selection_anchor;
+
+        let (start, end) = match (anchor.line, anchor.column) <= (cursor.line, cursor.column) {
+            true => (anchor, cursor),
+            false => (cursor, anchor),
+        };
+
+        Some((kind, start, end))
+    }
+
+    /// The columns of a line that are selected, extending past its end if the newline is
+    fn get_selected_columns(&self, line_index: usize) -> Option<Range<usize>> {
+        let (kind, start, end) = self.get_selection()?;
+
+        if line_index < start.line || line_index > end.line {
+            return None;
+        }
+
+        let line_length = grapheme_count(&self.buffer.line(line_index));
+
+        match kind {
+            SelectionKind::Characters => Some(
+                match line_index == start.line {
+                    true => start.column,
+                    false => 0,
+                }..match line_index == end.line {
+                    true => end.column + 1,
+                    false => line_length + 1,
+                },
+            ),
+            SelectionKind::Lines => Some(0..line_length + 1),
+            SelectionKind::Block => {
+                let cursor_column = self.get_text_position().column;
+                let anchor_column = self.selection_anchor.column;
+
+                Some(min(cursor_column, anchor_column)..max(cursor_column, anchor_column) + 1)
+            }
+        }
+    }
+
+    fn apply_operator_to_selection(&mut self, operator: Operator, count: usize) {
+        let (kind, start, end) = match self.get_selection() {
+            Some(selection) => selection,
+            None => return,
+        };
+
+        self.editor_mode = EditorMode::Normal;
+
+        if end.line >= self.buffer.line_count() {
+            return;
+        }
+
+        // Visual mode shifts by the count, rather than over that many lines
+        let repeat = match operator {
+            Operator::Indent | Operator::Outdent => count,
+            _ => 1,
+        };
+
+        for _ in 0..repeat {
+            match kind {
+                SelectionKind::Characters => {
+                    // Selecting past the end of a line takes its newline
+                    let end_index = match end.column < grapheme_count(&self.buffer.line(end.line)) {
+                        true => self.get_char_index(TextPosition {
+                            column: end.column + 1,
+                            ..end
+                        }),
+                        false => self.buffer.line_to_char(end.line + 1),
+                    };
+
+                    self.operate_on_characters(operator, self.get_char_index(start)..end_index);
+                }
+                SelectionKind::Lines => {
+                    self.operate_on_lines(operator, start.line..end.line + 1, 0)
+                }
+                SelectionKind::Block => {
+                    let left = min(start.column, end.column);
+                    let right = max(start.column, end.column) + 1;
+
+                    self.operate_on_block(operator, start.line..end.line + 1, left..right);
+                }
+            }
+        }
+    }
+
+    /// `v`, `V` and Ctrl-V start selecting, switch to another kind of selection, or stop
+    fn select(&mut self, kind: SelectionKind) {
+        match self.editor_mode {
+            EditorMode::Visual(current_kind) if current_kind == kind => {
+                self.editor_mode = EditorMode::Normal
+            }
+            EditorMode::Visual(_) => self.editor_mode = EditorMode::Visual(kind),
+            _ => {
+                self.selection_anchor = self.get_text_position();
+                self.editor_mode = EditorMode::Visual(kind);
+            }
+        }
+    }
+
+    /// Indents lines by a tab (or a tab's width of spaces with `expandtab`), or outdents them
+    /// by a tab or up to a tab's width of spaces
+    fn shift_lines(&mut self, lines: Range<usize>, indent: bool) {
+        let indentation = match self.settings.expand_tabs {
+            true => " ".repeat(self.settings.tab_size as usize),
+            false => String::from("\t"),
+        };
+
+        for line_index in lines {
+            let line = self.buffer.line(line_index);
+            let line_start = self.buffer.line_to_char(line_index);
+
+            if indent {
+                if !line.is_empty() {
+                    self.replace_text_at(line_start, "", &indentation);
+                }
+
+                continue;
+            }
+
+            let indentation: String = match line.starts_with('\t') {
+                true => String::from("\t"),
+                false => line
+                    .chars()
+                    .take_while(|char| *char == ' ')
+                    .take(self.settings.tab_size as usize)
+                    .collect(),
+            };
+
+            if !indentation.is_empty() {
+                self.replace_text_at(line_start, &indentation, "");
+            }
+        }
+    }
+
+    fn delete_characters_under_cursor(&mut self, count: usize) {
+        let cursor = self.get_text_position();
+
+        if cursor.line >= self.buffer.line_count() {
+            return;
+        }
+
+        let end = TextPosition {
+            column: cursor.column.saturating_add(count),
+            ..cursor
+        };
+
+        let char_range = self.get_char_index(cursor)..self.get_char_index(end);
+        let text = self.buffer.slice(char_range.clone());
+
+        if text.is_empty() {
+            return;
+        }
+
+        self.replace_text_at(char_range.start, &text, "");
+        self.store_in_register(
+            Register {
+                text,
+                linewise: false,
             },
-            AnsiEscapeCode::Reset.as_str(),
-            AnsiEscapeCode::ReverseMode.as_str(),
-            num_lines,
-            if num_lines == 1 { "" } else { "s" },
-            AnsiEscapeCode::Reset.as_str(),
-            AnsiEscapeCode::ReverseMode.as_str(),
-            self.get_editor_mode_display(),
-            AnsiEscapeCode::Reset.as_str(),
-            AnsiEscapeCode::ReverseMode.as_str(),
-            AnsiEscapeCode::Reset.as_str(),
-            AnsiEscapeCode::ReverseMode.as_str(),
+            true,
         );
 
-        let num_characters_in_terminal_commands = if self.edited { 60 } else { 48 };
+        // Stay on the last character when deleting at the end of the line
+        let last_column = grapheme_count(&self.buffer.line(cursor.line)).saturating_sub(1);
+        self.set_cursor_from_text_position(TextPosition {
+            column: min(cursor.column, last_column),
+            ..cursor
+        });
+    }
+
+    /// Stores yanked or `deleted` text in the register named for the command, if any
+    fn store_in_register(&mut self, register: Register, deleted: bool) {
+        let result = match deleted {
+            true => self.registers.delete(self.active_register, register),
+            false => self.registers.yank(self.active_register, register),
+        };
+
+        if let Err(message) = result {
+            self.set_status_message(&message, true);
+        }
+    }
+
+    /// Returns the register named for the command, if any, or reports why there is nothing
+    /// to put
+    fn get_register_to_put(&mut self, name: Option<char>) -> Option<Register> {
+        match self.registers.get(name) {
+            Ok(Some(register)) => Some(register),
+            Ok(None) => {
+                self.set_status_message("Nothing to put", true);
+                None
+            }
+            Err(message) => {
+                self.set_status_message(&message, true);
+                None
+            }
+        }
+    }
+
+    /// Puts the register `count` times, after the cursor (or below the line) or before it
+    fn put_register(&mut self, before: bool, count: usize) {
+        let register = match self.get_register_to_put(self.active_register) {
+            Some(register) if !register.text.is_empty() => register,
+            _ => return,
+        };
+
+        // Putting a register once is never refused, only a count that would hang the editor
+        if count > 1 && register.text.len().saturating_mul(count) > MAX_PUT_SIZE {
+            self.set_status_message("Count too large to put", true);
+            return;
+        }
+
+        let cursor = self.get_text_position();
+        let line_count = self.buffer.line_count();
+        let text = register.text.repeat(count);
+
+        if register.linewise {
+            let line_index = match before {
+                true => min(cursor.line, line_count),
+                false => min(cursor.line + 1, line_count),
+            };
+
+            self.replace_text_at(self.buffer.line_to_char(line_index), "", &text);
+            self.move_cursor_to_first_non_blank(line_index);
+            return;
+        }
+
+        if cursor.line >= line_count {
+            // Lines end with a newline, including one put past the last
+            self.replace_text_at(self.buffer.len_chars(), "", &format!("{}\n", text));
+            self.move_cursor_to_first_non_blank(cursor.line);
+            return;
+        }
+
+        let line_length = grapheme_count(&self.buffer.line(cursor.line));
+
+        let column = match before {
+            true => cursor.column,
+            false => min(cursor.column + 1, line_length),
+        };
+
+        let char_index = self.get_char_index(TextPosition { column, ..cursor });
+        self.replace_text_at(char_index, "", &text);
+
+        // The cursor ends on the last character put, unless that is on another line
+        let position = match text.contains('\n') {
+            true => self.get_text_position_from_char_index(char_index),
+            false => self.get_text_position_from_char_index(char_index + text.chars().count() - 1),
+        };
+
+        self.set_cursor_from_text_position(position);
+    }
+
+    /// Inserts the text of a register before the cursor, for Ctrl-R in Insert mode
+    fn insert_register(&mut self, name: char) {
+        if let Some(register) = self.get_register_to_put(Some(name)) {
+            self.insert_text_at_cursor(register.text);
+        }
+    }
+
+    /// Inserts text before the cursor, leaving the cursor just after it
+    fn insert_text_at_cursor(&mut self, mut text: String) {
+        let cursor = self.get_text_position();
+        let char_index = self.get_char_index(cursor);
+
+        // Lines end with a newline, including one inserted past the last
+        let is_past_last_line = cursor.line >= self.buffer.line_count() && !text.ends_with('\n');
+
+        if is_past_last_line {
+            text.push('\n');
+        }
+
+        self.replace_text_at(char_index, "", &text);
+        self.set_cursor_from_text_position(self.get_text_position_from_char_index(
+            char_index + text.chars().count() - usize::from(is_past_last_line),
+        ));
+    }
+
+    /// Inserts a bracketed paste as a single undoable edit, without treating it as keys
+    /// Outside Insert mode, the text is inserted at the cursor and the mode is kept.
+    fn paste_text(&mut self, text: String) {
+        if text.is_empty() {
+            return;
+        }
+
+        self.normal_mode_keys.clear();
+
+        if let EditorMode::Visual(_) = self.editor_mode {
+            self.editor_mode = EditorMode::Normal;
+        }
+
+        self.insert_text_at_cursor(text);
+        self.history.commit();
+    }
+
+    /// Clicking moves the cursor, dragging selects and the wheel scrolls
+    fn process_mouse_event(&mut self, event: MouseEvent) {
+        match event.kind {
+            MouseEventKind::Press(MouseButton::Left) => {
+                // Clicks on the status bar or message are ignored
+                let position = match self.get_text_position_on_screen(event.column, event.row) {
+                    Some(position) => position,
+                    None => return,
+                };
+
+                self.normal_mode_keys.clear();
+
+                if let EditorMode::Visual(_) = self.editor_mode {
+                    self.editor_mode = EditorMode::Normal;
+                }
+
+                self.mouse_press_position = Some(position);
+                self.set_cursor_from_text_position(position);
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                let anchor = match self.mouse_press_position {
+                    Some(anchor) => anchor,
+                    None => return,
+                };
+
+                // Dragging below the text keeps selecting along its last visible row
+                let row = min(event.row, self.window_size.rows.saturating_sub(1));
+
+                let position = match self.get_text_position_on_screen(event.column, row) {
+                    Some(position) => position,
+                    None => return,
+                };
+
+                if !matches!(self.editor_mode, EditorMode::Visual(_)) {
+                    self.pending_block_insert = None;
+                    self.selection_anchor = anchor;
+                    self.editor_mode = EditorMode::Visual(SelectionKind::Characters);
+                }
+
+                self.set_cursor_from_text_position(position);
+            }
+            MouseEventKind::Release => self.mouse_press_position = None,
+            MouseEventKind::ScrollUp => self.scroll_by_lines(-(MOUSE_SCROLL_LINES as i64)),
+            MouseEventKind::ScrollDown => self.scroll_by_lines(MOUSE_SCROLL_LINES as i64),
+            _ => {}
+        }
+    }
+
+    /// The position of the text shown at a screen cell, or `None` outside the text rows
+    /// Clicks past the end of a line or below the last line land at the nearest position.
+    fn get_text_position_on_screen(&mut self, column: u16, row: u32) -> Option<TextPosition> {
+        if row >= self.window_size.rows {
+            return None;
+        }
+
+        let line = min(
+            (self.line_scrolled_to + row) as usize,
+            self.buffer.line_count().saturating_sub(1),
+        );
+
+        if line >= self.buffer.line_count() {
+            return Some(TextPosition::default()); // Empty buffer
+        }
+
+        let num_columns_for_line_number: u16 = self
+            .num_columns_for_line_number
+            .try_into()
+            .expect("Failed to convert usize to u16");
+
+        let render_x = column.saturating_sub(num_columns_for_line_number) + self.column_scrolled_to;
+
+        // `render_x_to_cursor_x` reads the cursor's line
+        let saved_cursor_y = self.cursor_position.y;
+        self.cursor_position.y = line
+            .try_into()
+            .expect("Failed to convert line index usize to cursor y-position u32");
+        let text_column = self.render_x_to_cursor_x(render_x);
+        self.cursor_position.y = saved_cursor_y;
+
+        Some(TextPosition {
+            line,
+            column: text_column as usize,
+        })
+    }
+
+    /// Scrolls the view without moving the cursor, unless it would go off screen
+    fn scroll_by_lines(&mut self, num_lines: i64) {
+        let last_line = self.buffer.line_count().saturating_sub(1) as i64;
+
+        self.line_scrolled_to = (self.line_scrolled_to as i64 + num_lines)
+            .clamp(0, last_line)
+            .try_into()
+            .expect("Failed to convert line index i64 to u32");
+
+        let last_visible_line = self.line_scrolled_to + self.window_size.rows.saturating_sub(1);
+
+        // The cursor keeps its distance from the edges, except at the ends of the buffer
+        let scroll_margin = self.get_scroll_margin();
+        let first_cursor_line = match self.line_scrolled_to {
+            0 => 0,
+            line => line + scroll_margin,
+        };
+        let last_cursor_line = match last_visible_line as i64 >= last_line {
+            true => last_visible_line,
+            false => last_visible_line - scroll_margin,
+        };
+
+        if !(first_cursor_line..=last_cursor_line).contains(&self.cursor_position.y) {
+            self.cursor_position.y = self
+                .cursor_position
+                .y
+                .clamp(first_cursor_line, last_cursor_line);
+            self.clamp_cursor_to_line();
+        }
+    }
+
+    fn open_line(&mut self, above: bool) {
+        let line_count = self.buffer.line_count();
+        let cursor_line = self.cursor_position.y as usize;
+
+        let line_index = match above {
+            true => min(cursor_line, line_count),
+            false => min(cursor_line + 1, line_count),
+        };
+
+        self.replace_text_at(self.buffer.line_to_char(line_index), "", "\n");
+        self.set_cursor_from_text_position(TextPosition {
+            line: min(line_index, self.buffer.line_count() - 1),
+            column: 0,
+        });
+        self.editor_mode = EditorMode::Insert;
+    }
+
+    /// Joins `count` lines (at least two) into one, replacing the indentation of each joined
+    /// line with a space
+    fn join_lines(&mut self, count: usize) {
+        let line_index = self.cursor_position.y as usize;
+
+        for _ in 0..max(count, 2) - 1 {
+            if line_index + 1 >= self.buffer.line_count() {
+                break;
+            }
+
+            let line = self.buffer.line(line_index).into_owned();
+            let next_line = self.buffer.line(line_index + 1);
+            let next_line_content = next_line.trim_start();
+
+            let num_removed_chars =
+                1 + next_line.chars().count() - next_line_content.chars().count();
+            let separator = match line.is_empty()
+                || line.ends_with(char::is_whitespace)
+                || next_line_content.is_empty()
+            {
+                true => "",
+                false => " ",
+            };
+
+            let char_index = self.buffer.line_to_char(line_index + 1) - 1;
+            let removed_text = self
+                .buffer
+                .slice(char_index..char_index + num_removed_chars);
+
+            self.replace_text_at(char_index, &removed_text, separator);
+            self.set_cursor_from_text_position(TextPosition {
+                line: line_index,
+                column: grapheme_count(&line),
+            });
+        }
+    }
+
+    /// Replaces `count` characters under the cursor, if the line has that many
+    fn replace_characters_under_cursor(&mut self, character: char, count: usize) {
+        let cursor = self.get_text_position();
+
+        if cursor.line >= self.buffer.line_count()
+            || cursor.column.saturating_add(count) > grapheme_count(&self.buffer.line(cursor.line))
+        {
+            return;
+        }
+
+        let char_range = self.get_char_index(cursor)..self.get_char_index(TextPosition {
+            column: cursor.column + count,
+            ..cursor
+        });
+        let text = self.buffer.slice(char_range.clone());
+
+        self.replace_text_at(
+            char_range.start,
+            &text,
+            &character.to_string().repeat(count),
+        );
+        self.set_cursor_from_text_position(TextPosition {
+            column: cursor.column + count - 1,
+            ..cursor
+        });
+    }
+
+    fn restore_saved_highlights(&mut self) {
+        while let Some(saved_highlight) = self.saved_highlights.pop() {
+            if let Some(row) = self.rows.get_mut(saved_highlight.line_index) {
+                row.highlight = saved_highlight.highlight;
+            }
+        }
+    }
+
+    /// Highlights a match until `restore_saved_highlights` is called
+    fn highlight_search_match(&mut self, line_index: usize, search_match: &SearchMatch) {
+        let line = self.buffer.line(line_index);
+
+        let highlight_start = EditorInstance::get_render_char_index_from_column(
+            &line,
+            search_match.column_range.start,
+            self.settings.tab_size,
+        );
+        let highlight_end = EditorInstance::get_render_char_index_from_column(
+            &line,
+            search_match.column_range.end,
+            self.settings.tab_size,
+        );
+
+        self.saved_highlights.push(SavedHighlight {
+            line_index,
+            highlight: self.rows[line_index].highlight.clone(),
+        });
+
+        self.rows[line_index].highlight[highlight_start..highlight_end]
+            .fill(HighlightType::SearchMatch);
+    }
+
+    /// Moves the cursor to a match, scrolling so that it is near the top of the screen
+    fn move_cursor_to_search_match(&mut self, line_index: usize, search_match: &SearchMatch) {
+        self.set_cursor_from_text_position(TextPosition {
+            line: line_index,
+            column: search_match.column_range.start,
+        });
+
+        self.line_scrolled_to = self.cursor_position.y.saturating_sub(5);
+
+        if (self.line_scrolled_to + self.window_size.rows) as usize >= self.buffer.line_count() {
+            self.line_scrolled_to = self
+                .buffer
+                .line_count()
+                .saturating_sub(self.window_size.rows as usize)
+                .try_into()
+                .expect("Failed to convert usize to u32");
+        }
+    }
+
+    /// Highlights every match on the lines on screen, replacing previous search highlights
+    fn highlight_visible_search_matches(&mut self) {
+        let searcher = match self.search_highlight.take() {
+            Some(searcher) => searcher,
+            None => return,
+        };
+
+        self.restore_saved_highlights();
+
+        let first_line_index = self.line_scrolled_to as usize;
+        let last_line_index = min(
+            first_line_index + self.window_size.rows as usize,
+            self.buffer.line_count(),
+        );
+
+        for line_index in first_line_index..last_line_index {
+            let search_matches = searcher.find_all(&self.buffer.line(line_index));
+
+            for search_match in search_matches {
+                self.highlight_search_match(line_index, &search_match);
+            }
+        }
+
+        self.search_highlight = Some(searcher);
+    }
+
+    fn clear_search_highlight(&mut self) {
+        self.search_highlight = None;
+        self.restore_saved_highlights();
+    }
+
+    /// Returns the 1-based number of the match starting at `byte_index` on line `line_index`
+    /// and the number of matches, either `None` when past `MAX_SEARCH_MATCH_COUNT`
+    /// The matches per line are counted once for each query, and reused while moving between
+    /// its matches.
+    fn count_search_matches(
+        &mut self,
+        query: &str,
+        searcher: &Searcher,
+        line_index: usize,
+        byte_index: usize,
+    ) -> (Option<usize>, Option<usize>) {
+        let line_counts = match self.search_match_counts.take() {
+            Some((counted_query, line_counts)) if counted_query == query => line_counts,
+            _ => {
+                let mut line_counts = vec![];
+                let mut num_matches = 0;
+
+                // Lines past the one that exceeds the limit are not counted
+                for line_index in 0..self.buffer.line_count() {
+                    if num_matches > MAX_SEARCH_MATCH_COUNT {
+                        break;
+                    }
+
+                    line_counts.push(searcher.count(&self.buffer.line(line_index)));
+                    num_matches += line_counts[line_index];
+                }
+
+                line_counts
+            }
+        };
+
+        let match_number = (line_index < line_counts.len()).then(|| {
+            line_counts[..line_index].iter().sum::<usize>()
+                + searcher.count_until(&self.buffer.line(line_index), byte_index)
+        });
+        let num_matches = line_counts.iter().sum::<usize>();
+
+        self.search_match_counts = Some((query.to_string(), line_counts));
+
+        let within_limit =
+            |count: usize| Some(count).filter(|&count| count <= MAX_SEARCH_MATCH_COUNT);
+
+        (
+            match_number.and_then(within_limit),
+            within_limit(num_matches),
+        )
+    }
+
+    pub fn get_prompt_hint(&self) -> &str {
+        self.prompt_hint.as_deref().unwrap_or_default()
+    }
+
+    pub fn clear_prompt_hint(&mut self) {
+        self.prompt_hint = None;
+    }
+
+    /// Finds the match after (or before, searching backward) `from`, wrapping around the ends
+    /// of the buffer and finally back to the line of `from`
+    /// Returns the line of the match and whether the search wrapped.
+    fn find_next_search_match(
+        &self,
+        searcher: &Searcher,
+        from: TextPosition,
+        include_from: bool,
+    ) -> Option<(usize, SearchMatch, bool)> {
+        let line_count = self.buffer.line_count();
+
+        if line_count == 0 {
+            return None;
+        }
+
+        // The cursor may be on the empty line past the end of the buffer
+        let from = match from.line < line_count {
+            true => from,
+            false => TextPosition {
+                line: line_count - 1,
+                column: usize::MAX,
+            },
+        };
+
+        for offset in 0..=line_count {
+            let (line_index, wrapped) = match self.search_direction {
+                SearchDirection::Forward => (
+                    (from.line + offset) % line_count,
+                    from.line + offset >= line_count,
+                ),
+                SearchDirection::Backward => (
+                    (from.line + line_count - offset) % line_count,
+                    offset > from.line,
+                ),
+            };
+
+            let search_matches = searcher.find_all(&self.buffer.line(line_index));
+
+            // The line of `from` is searched on both sides of it: first past it, then again
+            // after wrapping all the way around
+            let is_candidate = |search_match: &SearchMatch| {
+                let column = search_match.column_range.start;
+
+                match (offset, &self.search_direction) {
+                    (0, SearchDirection::Forward) => {
+                        column > from.column || (include_from && column == from.column)
+                    }
+                    (0, SearchDirection::Backward) => {
+                        column < from.column || (include_from && column == from.column)
+                    }
+                    (offset, SearchDirection::Forward) if offset == line_count => {
+                        column <= from.column
+                    }
+                    (offset, SearchDirection::Backward) if offset == line_count => {
+                        column >= from.column
+                    }
+                    _ => true,
+                }
+            };
+
+            let search_match = match self.search_direction {
+                SearchDirection::Forward => search_matches.into_iter().find(is_candidate),
+                SearchDirection::Backward => search_matches.into_iter().rev().find(is_candidate),
+            };
+
+            if let Some(search_match) = search_match {
+                return Some((line_index, search_match, wrapped));
+            }
+        }
+
+        None
+    }
+
+    fn find_text_callback(&mut self, query: &str, key: Key) {
+        match key {
+            Key::U8(b'\x1b') => {
+                self.clear_search_highlight();
+                self.previous_search_match = None;
+                self.search_match_counts = None;
+                self.search_direction = SearchDirection::Forward;
+                return;
+            }
+            Key::U8(b'\r') => {
+                // The matches stay highlighted
+                self.previous_search_match = None;
+                self.search_match_counts = None;
+                self.search_direction = SearchDirection::Forward;
+                return;
+            }
+            Key::Custom(EditorKey::ArrowRight) | Key::Custom(EditorKey::ArrowDown) => {
+                self.search_direction = SearchDirection::Forward;
+            }
+            Key::Custom(EditorKey::ArrowLeft) | Key::Custom(EditorKey::ArrowUp) => {
+                self.search_direction = SearchDirection::Backward;
+            }
+            _ => {
+                self.previous_search_match = None;
+                self.search_direction = SearchDirection::Forward;
+            }
+        }
+
+        if self.previous_search_match.is_none() {
+            self.search_direction = SearchDirection::Forward;
+        }
+
+        if query.is_empty() {
+            self.clear_search_highlight();
+            self.prompt_hint = None;
+            return;
+        }
+
+        let searcher = match SearchQuery::parse(query).compile() {
+            Ok(searcher) => searcher,
+            Err(error) => {
+                self.prompt_hint = Some(format!(" ({})", error));
+                self.clear_search_highlight();
+                return;
+            }
+        };
+
+        // A new query may match at the cursor itself
+        let (from, include_from) = match self.previous_search_match {
+            Some(previous_search_match) => (previous_search_match, false),
+            None => (self.get_text_position(), true),
+        };
+
+        if let Some((line_index, search_match, wrapped)) =
+            self.find_next_search_match(&searcher, from, include_from)
+        {
+            let position = TextPosition {
+                line: line_index,
+                column: search_match.column_range.start,
+            };
+
+            self.previous_search_match = Some(position);
+            self.move_cursor_to_search_match(line_index, &search_match);
+
+            let (match_number, num_matches) = self.count_search_matches(
+                query,
+                &searcher,
+                line_index,
+                search_match.byte_range.start,
+            );
+            let format_count = |count: Option<usize>| match count {
+                Some(count) => count.to_string(),
+                None => format!("{}+", MAX_SEARCH_MATCH_COUNT),
+            };
+
+            self.prompt_hint = Some(format!(
+                " (match {} of {}{})",
+                format_count(match_number),
+                format_count(num_matches),
+                if wrapped { ", search wrapped" } else { "" }
+            ));
+            self.search_highlight = Some(searcher);
+            self.highlight_visible_search_matches();
+
+            return;
+        }
+
+        self.prompt_hint = Some(String::from(" (no matches)"));
+        self.clear_search_highlight();
+    }
+
+    fn prompt_and_go_to_line(&mut self) {
+        if let Some(line) = prompt_user::<fn(&mut EditorInstance, &str, Key)>(
+            self,
+            &format!(
+                "Enter a line number between 1 and {} (ESC to abort): ",
+                self.buffer.line_count() + 1
+            ),
+            None,
+        ) {
+            match line.parse::<u32>() {
+                Ok(line) if line > 0 => self.go_to_line(line - 1),
+                _ => self.set_status_message("Invalid line number", true),
+            };
+        }
+    }
+
+    /// Moves to the start of a line, scrolled to the middle of the screen
+    fn go_to_line(&mut self, line_index: u32) {
+        let num_lines = self.buffer.line_count().try_into().unwrap_or(u32::MAX);
+
+        self.cursor_position.y = min(line_index, num_lines);
+        self.cursor_position.x = self
+            .num_columns_for_line_number
+            .try_into()
+            .unwrap_or(u16::MAX);
+
+        self.line_scrolled_to = min(line_index, num_lines).saturating_sub(self.window_size.rows / 2)
+    }
+
+    fn prompt_and_find_text(&mut self) {
+        let saved_cursor_position = self.cursor_position;
+        let saved_column_scrolled_to = self.column_scrolled_to;
+        let saved_line_scrolled_to = self.line_scrolled_to;
+
+        if prompt_user(
+            self,
+            "Search (ESC to abort, arrows to jump): ",
+            Some(EditorInstance::find_text_callback),
+        )
+        .is_none()
+        {
+            self.cursor_position = saved_cursor_position;
+            self.column_scrolled_to = saved_column_scrolled_to;
+            self.line_scrolled_to = saved_line_scrolled_to;
+        }
+    }
+
+    fn prompt_and_replace_text(&mut self) {
+        let saved_cursor_position = self.cursor_position;
+        let saved_column_scrolled_to = self.column_scrolled_to;
+        let saved_line_scrolled_to = self.line_scrolled_to;
+
+        let query = prompt_user(
+            self,
+            "Replace (ESC to abort, arrows to jump): ",
+            Some(EditorInstance::find_text_callback),
+        );
+
+        // Undo the search preview's jumps; replacing starts from the top of the line range
+        self.cursor_position = saved_cursor_position;
+        self.column_scrolled_to = saved_column_scrolled_to;
+        self.line_scrolled_to = saved_line_scrolled_to;
+        self.clear_search_highlight();
+
+        let query = match query {
+            Some(query) => query,
+            None => return,
+        };
+
+        let replacement = match prompt_user_allowing_empty(
+            self,
+            &format!("Replace {} with (ESC to abort): ", query),
+        ) {
+            Some(replacement) => replacement,
+            None => return,
+        };
+
+        let line_range = match prompt_user_allowing_empty(
+            self,
+            "Lines to search (Enter for all, . for current, or e.g. 10,20): ",
+        ) {
+            Some(line_range) => line_range,
+            None => return,
+        };
+
+        let line_range = match self.parse_line_range(&line_range) {
+            Some(line_range) => line_range,
+            None => {
+                self.set_status_message("Invalid line range", true);
+                return;
+            }
+        };
+
+        let searcher = match SearchQuery::parse(&query).compile() {
+            Ok(searcher) => searcher,
+            Err(error) => {
+                self.set_status_message(&error, true);
+                return;
+            }
+        };
+
+        let num_replacements = self.replace_matches(
+            &searcher,
+            &replacement,
+            line_range,
+            true,
+            EditorInstance::confirm_replacement,
+        );
+
+        self.set_status_message(
+            &format!(
+                "{} replacement{} made",
+                num_replacements,
+                if num_replacements == 1 { "" } else { "s" }
+            ),
+            false,
+        );
+    }
+
+    /// Parses `%` or nothing (the whole buffer), `.` (the current line), `N` or `N,M` (1-based)
+    fn parse_line_range(&self, input: &str) -> Option<Range<usize>> {
+        match input.trim() {
+            "" | "%" => Some(0..self.buffer.line_count()),
+            "." => {
+                let line_index = self.cursor_position.y as usize;
+                Some(line_index..line_index + 1)
+            }
+            line_range => {
+                let (start, end) = line_range
+                    .split_once(',')
+                    .unwrap_or((line_range, line_range));
+                let start: usize = start.trim().parse().ok()?;
+                let end: usize = end.trim().parse().ok()?;
+
+                match start {
+                    0 => None,
+                    _ if end < start => None,
+                    _ => Some(start - 1..end),
+                }
+            }
+        }
+    }
+
+    fn confirm_replacement(&mut self) -> ReplaceChoice {
+        self.set_status_message("Replace this match? (y)es, (n)o, (a)ll, (q)uit", false);
+
+        loop {
+            refresh_screen(self);
+
+            match read_key_input() {
+                Some(Key::U8(b'y')) => return ReplaceChoice::Yes,
+                Some(Key::U8(b'n')) => return ReplaceChoice::No,
+                Some(Key::U8(b'a')) => return ReplaceChoice::All,
+                Some(Key::U8(b'q')) | Some(Key::U8(b'\x1b')) => return ReplaceChoice::Quit,
+                _ => {}
+            }
+        }
+    }
+
+    /// Replaces `old_text`, which must match the buffer contents at `char_index`
+    fn replace_text_at(&mut self, char_index: usize, old_text: &str, new_text: &str) {
+        let cursor_before = self.get_text_position();
+
+        if !old_text.is_empty() {
+            self.delete_text_at(char_index, old_text);
+
+            self.history.record(
+                Edit::Delete {
+                    char_index,
+                    text: old_text.to_string(),
+                },
+                cursor_before,
+                cursor_before,
+            );
+        }
+
+        if !new_text.is_empty() {
+            self.insert_text_at(char_index, new_text);
+
+            self.history.record(
+                Edit::Insert {
+                    char_index,
+                    text: new_text.to_string(),
+                },
+                cursor_before,
+                cursor_before,
+            );
+        }
+
+        self.edited = true;
+    }
+
+    /// Replaces matches on the lines in `line_range`, asking `confirm` about each one until it
+    /// answers `All` or `Quit`. Unless `global` is set, only the first match on each line is
+    /// considered. The replacements are undone together.
+    /// Returns the number of replacements made.
+    fn replace_matches(
+        &mut self,
+        searcher: &Searcher,
+        replacement: &str,
+        line_range: Range<usize>,
+        global: bool,
+        mut confirm: impl FnMut(&mut EditorInstance) -> ReplaceChoice,
+    ) -> usize {
+        self.history.commit();
+
+        let mut num_replacements = 0;
+        let mut replace_all = false;
+        let mut line_index = line_range.start;
+        let mut byte_index = 0;
+        let mut previous_match_end = None; // Of a non-empty match on the line
+
+        while line_index < min(line_range.end, self.buffer.line_count()) {
+            let line = self.buffer.line(line_index).into_owned();
+
+            let search_match = match searcher.find_at(&line, byte_index) {
+                Some(search_match) => search_match,
+                None => {
+                    line_index += 1;
+                    byte_index = 0;
+                    previous_match_end = None;
+                    continue;
+                }
+            };
+
+            // Step over the character after an empty match, so that it is not matched again
+            let num_skipped_bytes = match search_match.byte_range.is_empty() {
+                true => line[search_match.byte_range.end..]
+                    .chars()
+                    .next()
+                    .map_or(1, char::len_utf8),
+                false => 0,
+            };
+
+            // An empty match right after another match is not one, e.g. `b*` matches `abc`
+            // only before the `a`, at the `b` and after the `c`
+            if num_skipped_bytes > 0 && previous_match_end == Some(search_match.byte_range.start) {
+                byte_index = search_match.byte_range.end + num_skipped_bytes;
+                previous_match_end = None;
+
+                if byte_index > line.len() {
+                    line_index += 1;
+                    byte_index = 0;
+                }
+
+                continue;
+            }
+
+            let choice = match replace_all {
+                true => ReplaceChoice::All,
+                false => {
+                    self.move_cursor_to_search_match(line_index, &search_match);
+                    self.highlight_search_match(line_index, &search_match);
+
+                    let choice = confirm(self);
+
+                    self.restore_saved_highlights();
+                    choice
+                }
+            };
+
+            match choice {
+                ReplaceChoice::Quit => break,
+                ReplaceChoice::No => byte_index = search_match.byte_range.end + num_skipped_bytes,
+                ReplaceChoice::Yes | ReplaceChoice::All => {
+                    replace_all = choice == ReplaceChoice::All;
+
+                    let new_text = searcher.expand_replacement(&line, &search_match, replacement);
+                    let char_index = self.buffer.line_to_char(line_index)
+                        + line[..search_match.byte_range.start].chars().count();
+
+                    self.replace_text_at(
+                        char_index,
+                        &line[search_match.byte_range.clone()],
+                        &new_text,
+                    );
+
+                    num_replacements += 1;
+                    byte_index = search_match.byte_range.start + new_text.len() + num_skipped_bytes;
+                }
+            }
+
+            previous_match_end = (num_skipped_bytes == 0).then_some(byte_index);
+
+            if !global || byte_index > self.buffer.line(line_index).len() {
+                line_index += 1;
+                byte_index = 0;
+                previous_match_end = None;
+            }
+        }
+
+        self.history.commit();
+        num_replacements
+    }
+
+    fn prompt_and_run_command(&mut self) {
+        if let Some(input) = prompt_user_with_completion(self, ":", ex_command::complete) {
+            self.run_command(&input);
+        }
+    }
+
+    /// Runs a command typed after `:`, reporting any error in the status bar
+    fn run_command(&mut self, input: &str) {
+        let result =
+            ex_command::parse(input).and_then(|command_line| self.execute_command(command_line));
+
+        if let Err(message) = result {
+            self.set_status_message(&message, true);
+        }
+    }
+
+    /// Resolves the lines a command acts on, the current line by default
+    fn resolve_line_range(&self, range: Option<LineRange>) -> Result<Range<usize>, String> {
+        let current_line_number = self.cursor_position.y as usize + 1;
+        let num_lines = self.buffer.line_count();
+
+        let range = range.unwrap_or(LineRange {
+            start: LineAddress::current_line(),
+            end: LineAddress::current_line(),
+        });
+
+        let start = range.start.resolve(current_line_number, num_lines);
+        let end = range.end.resolve(current_line_number, num_lines);
+
+        match (start, end) {
+            // A backwards range is swapped
+            (Some(start), Some(end)) if min(start, end) > 0 && max(start, end) <= num_lines => {
+                Ok(min(start, end) - 1..max(start, end))
+            }
+            _ => Err(String::from("Invalid range")),
+        }
+    }
+
+    fn execute_command(&mut self, command_line: CommandLine) -> Result<(), String> {
+        match command_line.command {
+            ExCommand::GoToLine => {
+                let num_lines = self.buffer.line_count();
+
+                let line_number = command_line
+                    .range
+                    .and_then(|range| {
+                        range
+                            .end
+                            .resolve(self.cursor_position.y as usize + 1, num_lines)
+                    })
+                    .unwrap_or(1)
+                    .clamp(1, max(num_lines, 1));
+
+                self.go_to_line(
+                    (line_number - 1)
+                        .try_into()
+                        .expect("Failed to convert line index usize to u32"),
+                );
+                self.move_cursor_to_first_non_blank(line_number - 1);
+            }
+            ExCommand::Delete => {
+                let lines = self.resolve_line_range(command_line.range)?;
+                self.operate_on_lines(Operator::Delete, lines, 0);
+            }
+            ExCommand::Substitute {
+                pattern,
+                replacement,
+                global,
+                confirm,
+            } => {
+                let lines = self.resolve_line_range(command_line.range)?;
+
+                let num_replacements = self.replace_matches(
+                    &SearchQuery::parse(&pattern).compile()?,
+                    &replacement,
+                    lines,
+                    global,
+                    |editor| match confirm {
+                        true => editor.confirm_replacement(),
+                        false => ReplaceChoice::All,
+                    },
+                );
+
+                if num_replacements == 0 {
+                    return Err(format!("Pattern not found: {}", pattern));
+                }
+
+                self.set_status_message(
+                    &format!(
+                        "{} replacement{} made",
+                        num_replacements,
+                        if num_replacements == 1 { "" } else { "s" }
+                    ),
+                    false,
+                );
+            }
+            ExCommand::Write { file_path, quit } => {
+                let written = match file_path {
+                    Some(file_path) => self.write_buffer(file_path),
+                    None if self.file.is_none() => return Err(String::from("No file name")),
+                    None => self.save(),
+                };
+
+                if written && quit {
+                    self.quit();
+                }
+            }
+            ExCommand::Quit { force } => {
+                if self.edited && !force {
+                    return Err(String::from(
+                        "No write since last change (add ! to override)",
+                    ));
+                }
+
+                self.quit();
+            }
+            ExCommand::Edit { file_path, force } => self.edit_file(file_path, force)?,
+            ExCommand::Set(arguments) => {
+                if arguments.is_empty() {
+                    self.set_status_message(&self.settings.describe(), false);
+                }
+
+                for argument in arguments {
+                    self.set_option(argument)?;
+                }
+            }
+            ExCommand::Map {
+                modes, keys: None, ..
+            } => match self.keymap.list(modes[0]) {
+                bindings if bindings.is_empty() => return Err(String::from("No mapping found")),
+                bindings => self.set_status_message(&bindings, false),
+            },
+            ExCommand::Map {
+                modes,
+                keys: Some(keys),
+                command: None,
+            } => {
+                let command = self
+                    .keymap
+                    .get(modes[0], &keys)
+                    .ok_or_else(|| String::from("No mapping found"))?;
+
+                self.set_status_message(
+                    &format!("{} {}", format_keys(&keys), get_command_name(command)),
+                    false,
+                );
+            }
+            ExCommand::Map {
+                modes,
+                keys: Some(keys),
+                command: Some(command),
+            } => {
+                for mode in modes {
+                    self.keymap.bind(*mode, keys.clone(), command);
+                }
+            }
+            ExCommand::SetFileType(file_type) => {
+                self.syntax = Some(
+                    self.syntaxes
+                        .find_by_name(&file_type)
+                        .ok_or_else(|| format!("Unknown file type: {}", file_type))?,
+                );
+                self.update_line_highlights();
+            }
+            ExCommand::Unmap { modes, keys } => {
+                let mut is_unbound = false;
+
+                for mode in modes {
+                    is_unbound |= self.keymap.unbind(*mode, &keys);
+                }
+
+                if !is_unbound {
+                    return Err(String::from("No such mapping"));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Switches to another file, or reloads the current one, starting an empty buffer for a
+    /// file that does not exist yet
+    fn edit_file(&mut self, file_path: Option<String>, force: bool) -> Result<(), String> {
+        if self.edited && !force {
+            return Err(String::from(
+                "No write since last change (add ! to override)",
+            ));
+        }
+
+        let file_path = match file_path.or_else(|| self.file.as_ref().map(|file| file.path.clone()))
+        {
+            Some(file_path) => file_path,
+            None => return Err(String::from("No file name")),
+        };
+
+        let exists = match fs::File::open(&file_path).and_then(|file| file.metadata()) {
+            Ok(metadata) if metadata.is_dir() => {
+                return Err(format!("{} is a directory", file_path));
+            }
+            Ok(_) => true,
+            Err(e) if e.kind() == ErrorKind::NotFound => false,
+            Err(e) => return Err(format!("Failed to open {}: {}", file_path, e)),
+        };
+
+        // Any unsaved changes are being discarded along with their swap file
+        self.remove_swap_file();
+        self.edited = false;
+        self.previous_search_match = None;
+        self.line_scrolled_to = 0;
+        self.column_scrolled_to = 0;
+
+        match exists {
+            true => self.open(&file_path),
+            false => {
+                self.saved_highlights.clear();
+                self.search_highlight = None;
+
+                self.buffer = TextBuffer::new();
+                self.rows = vec![];
+                self.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+
+                self.file = Some(File {
+                    name: get_file_name_from_path(&file_path),
+                    path: file_path.clone(),
+                });
+
+                self.detect_syntax();
+
+                self.history = History::new();
+                self.editor_mode = self.get_default_mode();
+                self.set_status_message(&format!("\"{}\" [New file]", file_path), false);
+            }
+        }
+
+        self.set_cursor_from_text_position(TextPosition::default());
+        Ok(())
+    }
+
+    fn set_option(&mut self, argument: SetArgument) -> Result<(), String> {
+        if argument.action == SetAction::Show {
+            let value = self.settings.get(&argument.option)?;
+            self.set_status_message(&value, false);
+            return Ok(());
+        }
+
+        let previous_settings = self.settings.clone();
+        self.settings.set(&argument.option, &argument.action)?;
+        self.apply_settings(&previous_settings);
+
+        Ok(())
+    }
+
+    /// Updates what depends on the settings once they have changed from `previous_settings`
+    fn apply_settings(&mut self, previous_settings: &Settings) {
+        if self.settings.tab_size != previous_settings.tab_size {
+            for line_index in 0..self.rows.len() {
+                self.update_render(line_index);
+            }
+
+            self.update_line_highlights();
+        }
+
+        // The cursor stays on the same text as the line numbers are shown or hidden
+        let position = self.get_text_position();
+        self.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+        self.set_cursor_from_text_position(position);
+
+        self.registers.clipboard.set_commands(
+            &self.settings.clipboard_copy_command,
+            &self.settings.clipboard_paste_command,
+        );
+    }
+
+    fn add_welcome_message_to_buffer(&self, buffer: &mut String, message: &mut String) {
+        truncate_at_char_boundary(message, self.window_size.columns as usize);
+
+        let message_length: u16 = message
+            .chars()
+            .count()
+            .try_into()
+            .expect("Failed to convert welcome message length to u16 during screen refresh");
+
+        let mut padding = (self.window_size.columns - message_length) / 2;
+
+        if padding > 0 {
+            let theme = self.settings.theme;
+
+            buffer.push_str(&self.get_style_code(theme.normal.patch(theme.non_text)));
+            buffer.push('~');
+            buffer.push_str(&self.get_style_code(theme.normal));
+            padding -= 1;
+        }
+
+        for _ in 0..padding {
+            buffer.push(' ');
+        }
+
+        buffer.push_str(message);
+    }
+
+    fn set_num_columns_for_line_number(&mut self, line_number_gap: u8) {
+        let num_lines = self.buffer.line_count();
+
+        self.num_columns_for_line_number = if num_lines > 0 && self.settings.line_numbers {
+            num_lines.to_string().len() + line_number_gap as usize
+        } else {
+            0
+        };
+
+        if (self.cursor_position.x as usize) < self.num_columns_for_line_number {
+            self.cursor_position.x = self
+                .num_columns_for_line_number
+                .try_into()
+                .expect("Failed to convert line number column index to u16");
+        }
+    }
+
+    /// Uses a String as a buffer to store all lines, before calling `write` once
+    /// Prints a welcome message in the middle of the screen using its row/column count
+    pub fn draw_rows(&mut self) {
+        let mut buffer = String::new();
+
+        self.write_rows(&mut buffer);
+
+        write!(io::stdout(), "{}", buffer).expect("Failed to write to stdout while drawing rows");
+        flush_stdout();
+    }
+
+    fn get_style_code(&self, style: Style) -> String {
+        style.to_escape_code(self.color_support)
+    }
+
+    /// The theme's style for a kind of text, with the colour given in the settings if any
+    fn get_highlight_style(&self, highlight_type: &HighlightType) -> Style {
+        let theme = self.settings.theme;
+        let colors = &self.settings.colors;
+
+        let (style, color) = match highlight_type {
+            HighlightType::Normal => return theme.normal,
+            HighlightType::Number => (theme.number, colors.number),
+            HighlightType::String => (theme.string, colors.string),
+            HighlightType::Comment | HighlightType::MultiLineComment => {
+                (theme.comment, colors.comment)
+            }
+            HighlightType::Keyword => (theme.keyword, colors.keyword),
+            HighlightType::Type => (theme.type_name, colors.type_name),
+            HighlightType::SearchMatch => (theme.search_match, colors.search_match),
+        };
+
+        theme.normal.patch(match color {
+            Some(color) => style.fg(color),
+            None => style,
+        })
+    }
+
+    fn write_rows(&mut self, buffer: &mut String) {
+        self.highlight_visible_search_matches();
+
+        if (self.cursor_position.x as usize) < self.num_columns_for_line_number {
+            self.cursor_position.x = self
+                .num_columns_for_line_number
+                .try_into()
+                .expect("Failed to convert line number column index to u16");
+        }
+
+        let theme = self.settings.theme;
+        let normal_style_code = self.get_style_code(theme.normal);
+
+        for row in 0..self.window_size.rows {
+            let scrolled_to_row = row + self.line_scrolled_to;
+
+            // Rows start in the normal style, whose background also fills them to the right
+            buffer.push_str(&normal_style_code);
+
+            if scrolled_to_row as usize >= self.buffer.line_count() {
+                if self.buffer.line_count() == 0 && row == self.window_size.rows / 3 {
+                    self.add_welcome_message_to_buffer(buffer, &mut WELCOME_MESSAGE.clone());
+                } else {
+                    buffer.push_str(&self.get_style_code(theme.normal.patch(theme.non_text)));
+                    buffer.push('~');
+                }
+            } else {
+                let line = &self.rows[scrolled_to_row as usize];
+
+                // Screen columns of the render text that are visible
+                let start = self.column_scrolled_to as usize;
+                let end = start
+                    + (self.window_size.columns as usize)
+                        .saturating_sub(self.num_columns_for_line_number);
+
+                if self.num_columns_for_line_number > 0 {
+                    let mut line_prefix = (scrolled_to_row + 1).to_string();
+
+                    line_prefix.push_str(
+                        &" ".repeat(self.num_columns_for_line_number - line_prefix.chars().count()),
+                    );
+
+                    buffer.push_str(&self.get_style_code(theme.normal.patch(theme.gutter)));
+                    buffer.push_str(&line_prefix);
+                    buffer.push_str(&normal_style_code);
+                }
+
+                // The selection is drawn over the syntax highlighting
+                let text = self.buffer.line(scrolled_to_row as usize);
+                let selected_columns = self.get_selected_columns(scrolled_to_row as usize);
+                let is_newline_selected = selected_columns
+                    .as_ref()
+                    .is_some_and(|columns| columns.end > grapheme_count(&text));
+                let selected_render_range = selected_columns.map(|columns| {
+                    EditorInstance::get_render_char_index_from_column(
+                        &text,
+                        columns.start,
+                        self.settings.tab_size,
+                    )
+                        ..EditorInstance::get_render_char_index_from_column(
+                            &text,
+                            columns.end,
+                            self.settings.tab_size,
+                        )
+                });
+
+                let mut current_style = theme.normal;
+                let mut render_x = 0;
+                let mut char_index = 0; // Highlights are stored per `char` of the render text
+
+                for grapheme in line.render.graphemes(true) {
+                    let grapheme_render_x = render_x;
+                    let highlight_index = char_index;
+
+                    render_x += grapheme_width(grapheme);
+                    char_index += grapheme.chars().count();
+
+                    if render_x > end {
+                        break;
+                    }
+
+                    if grapheme_render_x < start {
+                        // Pad the visible half of a wide character cut off by scrolling
+                        buffer.push_str(&" ".repeat(render_x.saturating_sub(start)));
+                        continue;
+                    }
+
+                    let control_character = grapheme
+                        .chars()
+                        .next()
+                        .filter(|char| char.is_ascii_control());
+
+                    let mut style = self.get_highlight_style(&line.highlight[highlight_index]);
+
+                    if selected_render_range
+                        .as_ref()
+                        .is_some_and(|range| range.contains(&highlight_index))
+                    {
+                        style = style.patch(theme.selection);
+                    }
+
+                    // Control characters are shown as the letter that types them, reversed
+                    if control_character.is_some() {
+                        style = style.patch(Style::new().reverse());
+                    }
+
+                    if style != current_style {
+                        buffer.push_str(&self.get_style_code(style));
+                        current_style = style;
+                    }
+
+                    match control_character {
+                        Some(char) => buffer.push(if char as u8 <= 26 {
+                            (b'@' + char as u8) as char
+                        } else {
+                            '?'
+                        }),
+                        None => buffer.push_str(grapheme),
+                    }
+                }
+
+                // A selected newline shows as a cell past the end of the line
+                if is_newline_selected && render_x >= start && render_x < end {
+                    buffer.push_str(&self.get_style_code(theme.normal.patch(theme.selection)));
+                    buffer.push(' ');
+                }
+            }
+
+            buffer.push_str(&normal_style_code);
+            buffer.push_str(AnsiEscapeCode::EraseLineToRight.as_str());
+            buffer.push_str("\r\n");
+        }
+    }
+
+    /// Describes the mode for the status bar, along with the mode it switches to
+    fn get_editor_mode_display(&self) -> Vec<(Style, String)> {
+        let theme = self.settings.theme;
+        let mut segments = vec![];
+
+        if self.editor_mode != EditorMode::Normal {
+            segments.push((theme.status_bar, String::from(" NORMAL (<esc>) ")));
+        }
+
+        let mode = match self.editor_mode {
+            EditorMode::Normal => " NORMAL ",
+            EditorMode::Insert => " INSERT ",
+            EditorMode::Visual(SelectionKind::Characters) => " VISUAL ",
+            EditorMode::Visual(SelectionKind::Lines) => " VISUAL LINE ",
+            EditorMode::Visual(SelectionKind::Block) => " VISUAL BLOCK ",
+        };
+
+        segments.push((theme.status_bar_mode, String::from(mode)));
+
+        if self.editor_mode == EditorMode::Normal {
+            segments.push((theme.status_bar, String::from(" INSERT (i)")));
+        }
+
+        segments
+    }
+
+    /// Writes styled text up to `max_width` columns, returning the number of columns written
+    fn write_segments(
+        &self,
+        buffer: &mut String,
+        segments: &[(Style, String)],
+        max_width: usize,
+    ) -> usize {
+        let mut width = 0;
+
+        for (style, text) in segments {
+            buffer.push_str(&self.get_style_code(*style));
+
+            for grapheme in text.graphemes(true) {
+                if width + grapheme_width(grapheme) > max_width {
+                    return width;
+                }
+
+                width += grapheme_width(grapheme);
+                buffer.push_str(grapheme);
+            }
+        }
+
+        width
+    }
+
+    pub fn draw_status_bar(&self) {
+        let mut buffer = String::new();
+
+        self.write_status_bar(&mut buffer);
+
+        write!(io::stdout(), "{}", buffer)
+            .expect("Failed to write to stdout while drawing status bar");
+        flush_stdout();
+    }
+
+    fn write_status_bar(&self, buffer: &mut String) {
+        let theme = self.settings.theme;
+        let status_bar = theme.status_bar;
+        let gap = (theme.normal, String::from(" ")); // Between blocks of the status bar
+
+        let num_lines = self.buffer.line_count();
+
+        let mut left_segments = vec![(
+            status_bar,
+            format!(
+                " {:.20}",
+                self.file.as_ref().map_or("[New File]", |file| &file.name)
+            ),
+        )];
+
+        if self.edited {
+            left_segments.push((status_bar, String::from(" ")));
+            left_segments.push((theme.status_bar_modified, String::from("(modified)")));
+        }
+
+        left_segments.extend([
+            (status_bar, String::from(" ")),
+            gap.clone(),
+            (
+                status_bar,
+                format!(
+                    " {} line{} ",
+                    num_lines,
+                    if num_lines == 1 { "" } else { "s" }
+                ),
+            ),
+            gap.clone(),
+            (status_bar, String::from(" MODE: ")),
+        ]);
+        left_segments.extend(self.get_editor_mode_display());
+        left_segments.extend([(status_bar, String::from(" ")), gap.clone()]);
+
+        if let Some(keys) = self
+            .keymap
+            .describe_binding(self.get_keymap_mode(), Command::Help)
+        {
+            left_segments.extend([(status_bar, format!(" {}: help ", keys)), gap.clone()]);
+        }
+
+        let right_segments = [
+            gap,
+            (
+                status_bar,
+                format!(
+                    " {}{}/{} ",
+                    self.syntax
+                        .as_ref()
+                        .map_or(String::new(), |syntax| format!("{} ", syntax.file_type)),
+                    self.cursor_position.y + 1,
+                    num_lines
+                ),
+            ),
+        ];
+
+        let columns = self.window_size.columns as usize;
+        let space_left = columns - self.write_segments(buffer, &left_segments, columns);
+
+        // The cursor position goes at the right, with as much of it as fits
+        let right_width = min(
+            right_segments
+                .iter()
+                .flat_map(|(_, text)| text.graphemes(true))
+                .map(grapheme_width)
+                .sum(),
+            space_left,
+        );
+
+        buffer.push_str(&self.get_style_code(status_bar));
+        buffer.push_str(&" ".repeat(space_left - right_width));
+        self.write_segments(buffer, &right_segments, right_width);
+
+        buffer.push_str(AnsiEscapeCode::Reset.as_str());
+        buffer.push_str("\r\n"); // Add newline for status message
+    }
+
+    pub fn set_status_message(&mut self, message: &str, error: bool) {
+        self.status_message = Some(StatusMessage {
+            message: message.to_string(),
+            time_set: Instant::now(),
+            error,
+        });
+    }
+
+    pub fn draw_status_message_bar(&mut self) {
+        let theme = self.settings.theme;
+        let mut buffer = self.get_style_code(theme.normal);
+
+        buffer.push_str(AnsiEscapeCode::EraseLineToRight.as_str());
+
+        if let Some(status_message) = &self.status_message {
+            if status_message.time_set.elapsed().as_secs() < 5 {
+                let style = match status_message.error {
+                    false => theme.normal,
+                    true => theme.normal.patch(theme.error_message),
+                };
+
+                self.write_segments(
+                    &mut buffer,
+                    &[(style, format!(" {} ", status_message.message))],
+                    self.window_size.columns as usize,
+                );
+            }
+        }
+
+        buffer.push_str(AnsiEscapeCode::Reset.as_str());
+
+        write!(io::stdout(), "{buffer}")
+            .expect("Failed to write to stdout while drawing status message bar");
+        flush_stdout();
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::{globals::TAB_SIZE, terminal::get_populated_termios, utils::ctrl_key};
+
+    /// Appends a line to the buffer as if it had been read from a file
+    fn push_line(editor: &mut EditorInstance, text: &str) {
+        editor
+            .buffer
+            .insert(editor.buffer.len_chars(), &format!("{}\n", text));
+        editor.rows.push(Row::default());
+        editor.update_render(editor.rows.len() - 1);
+        editor.set_line_highlight(editor.rows.len() - 1);
+    }
+
+    fn set_lines(editor: &mut EditorInstance, texts: &[&str]) {
+        editor.buffer = TextBuffer::new();
+        editor.rows.clear();
+
+        for text in texts {
+            push_line(editor, text);
+        }
+    }
+
+    fn editor_with_lines(texts: &[&str]) -> EditorInstance {
+        let mut editor = EditorInstance::new(get_populated_termios());
+
+        set_lines(&mut editor, texts);
+        editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+        editor
+    }
+
+    fn editor_with_file(texts: &[&str], path: &Path) -> EditorInstance {
+        let mut editor = editor_with_lines(texts);
+
+        editor.file = Some(File {
+            path: path.to_str().unwrap().to_string(),
+            name: get_file_name_from_path(path.to_str().unwrap()),
+        });
+
+        editor
+    }
+
+    fn texts(editor: &EditorInstance) -> Vec<String> {
+        (0..editor.buffer.line_count())
+            .map(|line_index| editor.buffer.line(line_index).into_owned())
+            .collect()
+    }
+
+    fn normal_mode_editor(texts: &[&str]) -> EditorInstance {
+        let mut editor = editor_with_lines(texts);
+        editor.editor_mode = EditorMode::Normal;
+        editor
+    }
+
+    fn type_keys(editor: &mut EditorInstance, keys: &str) {
+        for key in keys.chars() {
+            match key.is_ascii() {
+                true => editor.process_key(Key::U8(key as u8)),
+                false => editor.process_key(Key::Char(key)),
+            }
+        }
+    }
+
+    /// Returns the status message and whether it is an error
+    fn status(editor: &EditorInstance) -> (String, bool) {
+        let status_message = editor.status_message.as_ref().unwrap();
+        (status_message.message.clone(), status_message.error)
+    }
+
+    #[test]
+    fn test_new_editor_instance() {
+        let editor = EditorInstance::new(get_populated_termios());
+
+        // Check initial properties
+        assert_eq!(editor.cursor_position.x, 0);
+        assert_eq!(editor.cursor_position.y, 0);
+        assert_eq!(editor.cursor_position.render_x, 0);
+        assert_eq!(editor.editor_mode, EditorMode::Insert);
+        assert_eq!(editor.buffer.line_count(), 0);
+        assert_eq!(editor.line_scrolled_to, 0);
+        assert_eq!(editor.column_scrolled_to, 0);
+        assert!(editor.file.is_none());
+        assert!(editor.status_message.is_none());
+        assert!(!editor.edited);
+        assert_eq!(editor.quit_confirmations, 0);
+        assert_eq!(editor.previous_search_match, None);
+        assert_eq!(editor.search_direction, SearchDirection::Forward);
+        assert!(editor.saved_highlights.is_empty());
+        assert_eq!(editor.num_columns_for_line_number, 0);
+    }
+
+    #[test]
+    fn test_get_current_line() {
+        let mut editor = EditorInstance::new(get_populated_termios());
+
+        // No lines
+        assert!(editor.get_current_line().is_none());
+
+        // Happy path
+        push_line(&mut editor, "First line");
+        push_line(&mut editor, "Second line");
+
+        editor.cursor_position.y = 0;
+        let current_line = editor.get_current_line();
+        assert!(current_line.is_some());
+        assert_eq!(current_line.unwrap(), "First line");
+
+        editor.cursor_position.y = 1;
+        let current_line = editor.get_current_line();
+        assert!(current_line.is_some());
+        assert_eq!(current_line.unwrap(), "Second line");
+
+        // Out of bounds
+        editor.cursor_position.y = 2;
+        assert!(editor.get_current_line().is_none());
+    }
+
+    #[test]
+    fn test_get_render_text_from_text() {
+        let input = "Hello\tWorld!";
+        let expected_output = "Hello   World!"; // Three spaces to next tab
+        let output = EditorInstance::get_render_text_from_text(input, TAB_SIZE);
+        assert_eq!(output, expected_output);
+
+        let input = "\tTabbed";
+        let expected_output = "    Tabbed"; // Four spaces to first tab
+        let output = EditorInstance::get_render_text_from_text(input, TAB_SIZE);
+        assert_eq!(output, expected_output);
+
+        let input = "No tabs here!";
+        let expected_output = "No tabs here!";
+        let output = EditorInstance::get_render_text_from_text(input, TAB_SIZE);
+        assert_eq!(output, expected_output);
+    }
+
+    mod test_is_separator {
+        use super::*;
+
+        #[test]
+        fn test_with_punctuation() {
+            assert!(EditorInstance::is_separator('.'));
+            assert!(EditorInstance::is_separator(','));
+            assert!(EditorInstance::is_separator('!'));
+            assert!(EditorInstance::is_separator('?'));
+            assert!(EditorInstance::is_separator(';'));
+            assert!(EditorInstance::is_separator(':'));
+        }
+
+        #[test]
+        fn test_with_whitespace() {
+            assert!(EditorInstance::is_separator(' '));
+            assert!(EditorInstance::is_separator('\t'));
+        }
+
+        #[test]
+        fn test_with_newline() {
+            assert!(EditorInstance::is_separator('\n'));
+        }
+
+        #[test]
+        fn test_with_non_separators() {
+            assert!(!EditorInstance::is_separator('a'));
+            assert!(!EditorInstance::is_separator('Z'));
+            assert!(!EditorInstance::is_separator('0'));
+            assert!(!EditorInstance::is_separator('9'));
+        }
+
+        #[test]
+        fn test_with_underscore() {
+            assert!(!EditorInstance::is_separator('_')); // Underscore is NOT a separator
+        }
+
+        #[test]
+        fn test_with_extended_ascii() {
+            assert!(!EditorInstance::is_separator('é')); // Non-ASCII character
+            assert!(!EditorInstance::is_separator('中')); // Unicode character
+        }
+
+        #[test]
+        fn test_with_edge_cases() {
+            assert!(EditorInstance::is_separator('['));
+            assert!(EditorInstance::is_separator(']'));
+            assert!(EditorInstance::is_separator('{'));
+            assert!(EditorInstance::is_separator('}'));
+        }
+    }
+
+    mod test_highlight {
+        use super::*;
+
+        fn c_editor_with_lines(texts: &[&str]) -> EditorInstance {
+            let mut editor = editor_with_lines(texts);
+            editor.syntax = editor.syntaxes.find_by_file_name("test.c");
+            editor.update_line_highlights();
+            editor
+        }
+
+        fn is_comment(editor: &EditorInstance, line_index: usize) -> bool {
+            editor.rows[line_index]
+                .highlight
+                .iter()
+                .all(|highlight| *highlight == HighlightType::MultiLineComment)
+        }
+
+        #[test]
+        fn test_opening_comment_highlights_following_lines() {
+            let mut editor = c_editor_with_lines(&["int a;", "int b;", "*/ int c;", "int d;"]);
+            assert!(!is_comment(&editor, 1));
+
+            editor.insert_text_at(0, "/*");
+            assert!(is_comment(&editor, 0));
+            assert!(is_comment(&editor, 1));
+            assert_eq!(
+                editor.rows[2].highlight[0..2],
+                vec![HighlightType::MultiLineComment; 2]
+            );
+            assert_eq!(editor.rows[2].highlight[3..6], vec![HighlightType::Type; 3]);
+            assert_eq!(editor.rows[3].highlight[0..3], vec![HighlightType::Type; 3]);
+
+            editor.delete_text_at(0, "/*");
+            assert_eq!(editor.rows[1].highlight[0..3], vec![HighlightType::Type; 3]);
+            assert_eq!(
+                editor.rows[2].highlight[0..2],
+                vec![HighlightType::Normal; 2]
+            );
+        }
+
+        #[test]
+        fn test_removing_lines_rehighlights_following_lines() {
+            let mut editor = c_editor_with_lines(&["int a;", "/* b", "int c;", "*/ int d;"]);
+            assert!(is_comment(&editor, 2));
+
+            editor.editor_mode = EditorMode::Normal;
+            editor.set_cursor_from_text_position(TextPosition { line: 1, column: 0 });
+            for key in "dd".bytes() {
+                editor.process_key(Key::U8(key));
+            }
+
+            assert_eq!(texts(&editor), vec!["int a;", "int c;", "*/ int d;"]);
+            assert_eq!(editor.rows[1].highlight[0..3], vec![HighlightType::Type; 3]);
+            assert!(editor.rows[1].start_state.is_empty());
+            assert_eq!(
+                editor.rows[2].highlight[0..2],
+                vec![HighlightType::Normal; 2]
+            );
+        }
+
+        #[test]
+        fn test_rehighlighting_stops_once_state_is_unchanged() {
+            let mut editor = c_editor_with_lines(&["int a;", "int b;", "int c;"]);
+
+            // Only re-highlighting would clear this
+            editor.rows[2].highlight.fill(HighlightType::Keyword);
+
+            editor.insert_text_at(0, "\n/* x */");
+            assert_eq!(
+                texts(&editor),
+                vec!["", "/* x */int a;", "int b;", "int c;"]
+            );
+            assert!(editor.rows[1].highlight[..7]
+                .iter()
+                .all(|highlight| *highlight == HighlightType::MultiLineComment));
+            assert_eq!(editor.rows[3].highlight, vec![HighlightType::Keyword; 6]);
+        }
+
+        #[test]
+        fn test_opening_string_highlights_following_lines() {
+            let mut editor = editor_with_lines(&["let a = 1;", "let b = 2;", "\"#; let c;"]);
+            editor.syntax = editor.syntaxes.find_by_file_name("main.rs");
+            editor.update_line_highlights();
+
+            editor.insert_text_at(8, "r#\"");
+            assert_eq!(editor.rows[1].highlight, vec![HighlightType::String; 10]);
+            assert_eq!(
+                editor.rows[2].highlight[0..2],
+                vec![HighlightType::String; 2]
+            );
+            assert_eq!(
+                editor.rows[2].highlight[4..7],
+                vec![HighlightType::Keyword; 3]
+            );
+
+            editor.delete_text_at(8, "r#\"");
+            assert_eq!(
+                editor.rows[1].highlight[0..3],
+                vec![HighlightType::Keyword; 3]
+            );
+            assert!(editor.rows[1].start_state.is_empty());
+        }
+
+        #[test]
+        fn test_opening_comment_in_large_file() {
+            let mut editor = c_editor_with_lines(&vec!["int a;"; 50_000]);
+
+            editor.insert_text_at(0, "/*");
+            assert!(is_comment(&editor, 49_999));
+
+            let last_line_end = editor.get_char_index(TextPosition {
+                line: 49_999,
+                column: 6,
+            });
+            editor.insert_text_at(last_line_end, "*/");
+            assert!(editor.rows[49_999].end_state.is_empty());
+
+            editor.delete_text_at(0, "/*");
+            assert_eq!(
+                editor.rows[49_999].highlight[0..3],
+                vec![HighlightType::Type; 3]
+            );
+        }
+    }
+
+    mod test_detect_syntax {
+        use super::*;
+
+        fn detected_file_type(file_name: &str, texts: &[&str]) -> Option<String> {
+            let mut editor = editor_with_lines(texts);
+            editor.file = Some(File {
+                name: file_name.to_string(),
+                path: format!("some-path/{file_name}"),
+            });
+
+            editor.detect_syntax();
+            editor
+                .syntax
+                .as_ref()
+                .map(|syntax| syntax.file_type.clone())
+        }
+
+        #[test]
+        fn test_with_exact_file_names() {
+            assert_eq!(
+                detected_file_type("Makefile", &["all:"]),
+                Some(String::from("Make"))
+            );
+            assert_eq!(
+                detected_file_type("Dockerfile", &["FROM rust"]),
+                Some(String::from("Dockerfile"))
+            );
+        }
+
+        #[test]
+        fn test_with_shebang() {
+            assert_eq!(
+                detected_file_type("deploy", &["#!/usr/bin/env python3", "import os"]),
+                Some(String::from("Python"))
+            );
+            assert_eq!(
+                detected_file_type("build.rs", &["#!/bin/sh"]),
+                Some(String::from("Rust"))
+            );
+            assert_eq!(detected_file_type("deploy", &["import os"]), None);
+        }
+
+        #[test]
+        fn test_with_modeline() {
+            assert_eq!(
+                detected_file_type("notes.txt", &["// vim: ft=rust", "fn main() {}"]),
+                Some(String::from("Rust"))
+            );
+
+            // Modelines are looked for in the first and last lines, over the file name
+            let mut texts = vec!["x = 1"; 20];
+            texts.push("# -*- mode: python -*-");
+            assert_eq!(
+                detected_file_type("script.sh", &texts),
+                Some(String::from("Python"))
+            );
+
+            texts.insert(10, "# vim: ft=c");
+            assert_eq!(
+                detected_file_type("script.sh", &texts[..20]),
+                Some(String::from("Shell"))
+            );
+        }
+
+        #[test]
+        fn test_setfiletype() {
+            let mut editor = editor_with_lines(&["fn main() {}"]);
+
+            editor.run_command("setf rust");
+            assert_eq!(editor.syntax.as_ref().unwrap().file_type, "Rust");
+            assert_eq!(
+                editor.rows[0].highlight[0..2],
+                vec![HighlightType::Keyword; 2]
+            );
+
+            editor.run_command("setfiletype py");
+            assert_eq!(editor.syntax.as_ref().unwrap().file_type, "Python");
+
+            editor.run_command("setfiletype cobol");
+            assert_eq!(editor.syntax.as_ref().unwrap().file_type, "Python");
+            let status_message = editor.status_message.as_ref().unwrap();
+            assert_eq!(status_message.message, "Unknown file type: cobol");
+            assert!(status_message.error);
+        }
+
+        #[test]
+        fn test_with_supported_file_types() {
+            let mut editor = EditorInstance::new(get_populated_termios());
+
+            [
+                ("test.rs", "Rust"),
+                ("test.c", "C"),
+                ("test.py", "Python"),
+                ("test.js", "JavaScript"),
+            ]
+            .iter()
+            .for_each(|(file_name, expected_file_type)| {
+                editor.file = Some(File {
+                    name: file_name.to_string(),
+                    path: format!("some-path/{file_name}"),
+                });
+
+                editor.detect_syntax();
+                assert_eq!(
+                    editor.syntax.as_ref().unwrap().file_type,
+                    *expected_file_type
+                );
+            });
+        }
+
+        #[test]
+        fn test_with_unsupported_file_types() {
+            let mut editor = EditorInstance::new(get_populated_termios());
+
+            editor.file = Some(File {
+                name: "test.foo".to_string(),
+                path: "some-path/test.foo".to_string(),
+            });
+
+            editor.syntax = editor.syntaxes.find_by_file_name("test.c");
+
+            editor.detect_syntax();
+            assert!(editor.syntax.is_none());
+        }
+
+        #[test]
+        fn test_with_user_syntax_definitions() {
+            let directory = tempfile::tempdir().unwrap();
+            fs::write(
+                directory.path().join("go.toml"),
+                "name = \"Go\"\nextensions = [\"go\"]\nkeywords = [\"func\"]\n\
+                 [comments]\nline = \"//\"",
+            )
+            .unwrap();
+
+            let mut editor = editor_with_lines(&["func f // x"]);
+            editor.file = Some(File {
+                name: "main.go".to_string(),
+                path: "some-path/main.go".to_string(),
+            });
+
+            editor.detect_syntax();
+            assert!(editor.syntax.is_none());
+
+            assert_eq!(editor.load_syntax_directory(directory.path()), Ok(()));
+            assert_eq!(editor.syntax.as_ref().unwrap().file_type, "Go");
+
+            let highlight = &editor.rows[0].highlight;
+            assert_eq!(highlight[0..4], vec![HighlightType::Keyword; 4]);
+            assert_eq!(highlight[5], HighlightType::Normal);
+            assert_eq!(highlight[7..], vec![HighlightType::Comment; 4]);
+        }
+    }
+
+    mod test_open {
+        use super::*;
+        use std::fs::File;
+        use std::io::Write;
+        use std::sync::{Arc, Mutex};
+        use tempfile::tempdir;
+
+        #[test]
+        fn test_open_valid_file() {
+            let mut editor = EditorInstance::new(get_populated_termios());
+            editor.editor_mode = EditorMode::Insert;
+
+            let dir = tempdir().unwrap();
+            let file_path = dir.path().join("test.txt");
+            let mut file = File::create(&file_path).unwrap();
+
+            // No lines
+            editor.open(file_path.to_str().unwrap());
+            assert_eq!(editor.buffer.line_count(), 0);
+
+            // 2 lines
+            writeln!(file, "Line 1").unwrap();
+            writeln!(file, "Line 2").unwrap();
+
+            editor.open(file_path.to_str().unwrap());
+
+            assert_eq!(editor.buffer.line_count(), 2);
+            assert_eq!(editor.buffer.line(0), "Line 1");
+            assert_eq!(editor.buffer.line(1), "Line 2");
+
+            assert_eq!(
+                editor.file.as_ref().unwrap().path,
+                file_path.to_str().unwrap()
+            );
+            assert_eq!(editor.file.as_ref().unwrap().name, "test.txt");
+
+            assert!(editor.syntax.is_none());
+
+            assert_eq!(editor.editor_mode, EditorMode::Normal);
+        }
+
+        #[test]
+        fn test_open_file_with_syntax_detection() {
+            let mut editor = EditorInstance::new(get_populated_termios());
+
+            let dir = tempdir().unwrap();
+            let file_path = dir.path().join("test.rs");
+            let mut file = File::create(&file_path).unwrap();
+            writeln!(file, "fn main() {{}}").unwrap();
+
+            editor.open(file_path.to_str().unwrap());
+
+            assert_eq!(editor.buffer.line_count(), 1);
+            assert_eq!(editor.buffer.line(0), "fn main() {}");
+
+            assert!(editor.syntax.is_some());
+            assert_eq!(editor.syntax.as_ref().unwrap().file_type, "Rust");
+        }
+
+        #[test]
+        fn test_open_non_existent_file() {
+            let editor = EditorInstance::new(get_populated_termios());
+
+            let non_existent_file = "/non/existent/file.txt";
+
+            let editor_arc = Arc::new(Mutex::new(editor));
+
+            std::panic::set_hook(Box::new(|_| {})); // Prevent panic from printing with --nocapture flag
+
+            assert!(std::panic::catch_unwind(|| {
+                let mut editor_ref = editor_arc.lock().unwrap();
+                editor_ref.open(non_existent_file);
+            })
+            .is_err());
+        }
+
+        #[test]
+        fn test_open_large_file() {
+            let mut editor = EditorInstance::new(get_populated_termios());
+
+            let dir = tempdir().unwrap();
+            let file_path = dir.path().join("large.txt");
+            let mut file = File::create(&file_path).unwrap();
+
+            for i in 0..100_000 {
+                writeln!(file, "Line {}", i).unwrap();
+            }
+
+            editor.open(file_path.to_str().unwrap());
+
+            assert_eq!(editor.buffer.line_count(), 100_000);
+            assert_eq!(editor.rows.len(), 100_000);
+            assert_eq!(editor.buffer.line(99_999), "Line 99999");
+            assert_eq!(editor.rows[99_999].render, "Line 99999");
+        }
+    }
+
+    mod test_save {
+        use super::*;
+        use tempfile::tempdir;
+
+        #[test]
+        fn test_save_writes_file_and_backup() {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("test.txt");
+            fs::write(&path, "Old\n").unwrap();
+
+            let mut editor = editor_with_file(&["New", "Lines"], &path);
+            editor.settings.create_backup_file = true;
+            editor.edited = true;
+
+            editor.save();
+
+            assert_eq!(fs::read_to_string(&path).unwrap(), "New\nLines\n");
+            assert_eq!(
+                fs::read_to_string(dir.path().join("test.txt~")).unwrap(),
+                "Old\n"
+            );
+            assert!(!editor.edited);
+
+            let status_message = editor.status_message.unwrap();
+            assert_eq!(status_message.message, "10 bytes written to disk");
+            assert!(!status_message.error);
+        }
+
+        #[test]
+        fn test_save_failure_sets_error_status() {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("missing").join("test.txt");
+
+            let mut editor = editor_with_file(&["Text"], &path);
+            editor.edited = true;
+
+            editor.save();
+
+            assert!(editor.edited);
+
+            let status_message = editor.status_message.unwrap();
+            assert!(status_message.message.starts_with("Failed to save"));
+            assert!(status_message.error);
+        }
+    }
+
+    mod test_swap_file {
+        use super::*;
+        use tempfile::tempdir;
+
+        #[test]
+        fn test_update_swap_file_writes_and_save_removes() {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("test.txt");
+            let swap_path = dir.path().join(".test.txt.swp");
+
+            let mut editor = editor_with_file(&["Hello"], &path);
+            editor.process_key(Key::U8(b'!'));
+
+            // Not written until the interval has passed
+            editor.update_swap_file();
+            assert!(!swap_path.exists());
+
+            editor.swap_file_written_at -= SWAP_FILE_WRITE_INTERVAL;
+            editor.update_swap_file();
+            assert_eq!(fs::read_to_string(&swap_path).unwrap(), "!Hello\n");
+            assert!(!editor.is_swap_file_stale);
+
+            editor.save();
+            assert!(!swap_path.exists());
+            assert!(editor.swap_file_path.is_none());
+        }
+
+        #[test]
+        fn test_swap_file_of_another_editor_is_left_alone() {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("test.txt");
+            let kept_swap_path = dir.path().join(".test.txt.swp");
+            let swap_path = dir.path().join(".test.txt.swo");
+            fs::write(&kept_swap_path, "Kept\n").unwrap();
+
+            let mut editor = editor_with_file(&["Hello"], &path);
+            editor.process_key(Key::U8(b'!'));
+            editor.swap_file_written_at -= SWAP_FILE_WRITE_INTERVAL;
+            editor.update_swap_file();
+            assert_eq!(fs::read_to_string(&swap_path).unwrap(), "!Hello\n");
+
+            editor.save();
+            assert!(!swap_path.exists());
+            assert_eq!(fs::read_to_string(&kept_swap_path).unwrap(), "Kept\n");
+        }
+
+        #[test]
+        fn test_update_swap_file_removes_after_undo_to_saved_state() {
+            let dir = tempdir().unwrap();
+            let swap_path = dir.path().join(".test.txt.swp");
+
+            let mut editor = editor_with_file(&["Hello"], &dir.path().join("test.txt"));
+            editor.process_key(Key::U8(b'!'));
+            editor.swap_file_written_at -= SWAP_FILE_WRITE_INTERVAL;
+            editor.update_swap_file();
+            assert!(swap_path.exists());
+
+            editor.undo();
+            editor.update_swap_file();
+            assert!(!swap_path.exists());
+        }
+
+        #[test]
+        fn test_recover_from_swap_file() {
+            let dir = tempdir().unwrap();
+            let swap_path = dir.path().join(".test.txt.swp");
+            fs::write(&swap_path, "Recovered\ntext\n").unwrap();
+
+            let mut editor = editor_with_file(&["Original"], &dir.path().join("test.txt"));
+            let swap_buffer = read_swap_file(&swap_path).unwrap();
+
+            editor.recover_from_swap_file(swap_path, swap_buffer);
+            assert_eq!(texts(&editor), vec!["Recovered", "text"]);
+            assert_eq!(editor.rows.len(), 2);
+            assert!(editor.edited);
+
+            editor.undo();
+            assert_eq!(texts(&editor), vec!["Original"]);
+            assert!(!editor.edited);
+        }
+    }
+
+    mod test_process_keypress {
+        use super::*;
+
+        #[test]
+        fn test_cursor_movement() {
+            let mut editor = EditorInstance::new(get_populated_termios());
+
+            push_line(&mut editor, "Hello");
+
+            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+
+            // Up on first line
+            editor.process_key(Key::Custom(EditorKey::ArrowUp));
+            assert_eq!(editor.cursor_position.y, 0);
+            assert_eq!(
+                editor.cursor_position.x as usize,
+                editor.num_columns_for_line_number
+            );
+
+            // Left
+            editor.cursor_position.x = 5;
+            editor.process_key(Key::Custom(EditorKey::ArrowLeft));
+            assert_eq!(editor.cursor_position.x, 4);
+
+            // Right
+            editor.cursor_position.x = 4;
+            editor.process_key(Key::Custom(EditorKey::ArrowRight));
+            assert_eq!(editor.cursor_position.x, 5);
+
+            // Down
+            push_line(&mut editor, "World");
+
+            assert_eq!(editor.cursor_position.y, 0);
+            editor.process_key(Key::Custom(EditorKey::ArrowDown));
+            assert_eq!(editor.cursor_position.y, 1);
+            assert_eq!(editor.cursor_position.x, 5);
+
+            // Down on last line
+            editor.process_key(Key::Custom(EditorKey::ArrowDown));
+            assert_eq!(editor.cursor_position.y, 2);
+
+            // Up
+            editor.cursor_position.y = 1;
+            editor.process_key(Key::Custom(EditorKey::ArrowUp));
+            assert_eq!(editor.cursor_position.y, 0);
+            assert_eq!(editor.cursor_position.x, 0);
+
+            // Right at end of line
+            editor.cursor_position.x = 5 + editor.num_columns_for_line_number as u16;
+            editor.process_key(Key::Custom(EditorKey::ArrowRight));
+            assert_eq!(
+                editor.cursor_position.x as usize,
+                editor.num_columns_for_line_number
+            );
+            assert_eq!(editor.cursor_position.y, 1);
+
+            // Left at start of line
+            editor.cursor_position.x = editor.num_columns_for_line_number as u16;
+            editor.process_key(Key::Custom(EditorKey::ArrowLeft));
+            assert_eq!(
+                editor.cursor_position.x as usize,
+                5 + editor.num_columns_for_line_number
+            );
+            assert_eq!(editor.cursor_position.y, 0);
+
+            // Home
+            editor.process_key(Key::Custom(EditorKey::Home));
+            assert_eq!(
+                editor.cursor_position.x as usize,
+                editor.num_columns_for_line_number
+            );
+
+            // End
+            editor.process_key(Key::Custom(EditorKey::End));
+            assert_eq!(
+                editor.cursor_position.x as usize,
+                5 + editor.num_columns_for_line_number
+            );
+        }
+
+        #[test]
+        fn test_delete() {
+            let mut editor = EditorInstance::new(get_populated_termios());
+
+            push_line(&mut editor, "Hello");
+
+            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+
+            // Delete
+            editor.cursor_position.x = 2 + editor.num_columns_for_line_number as u16;
+            editor.process_key(Key::Custom(EditorKey::Delete));
+            assert_eq!(editor.buffer.line(0), "Helo");
+
+            // Backspace
+            editor.process_key(Key::Custom(EditorKey::Backspace));
+            assert_eq!(editor.buffer.line(0), "Hlo");
+
+            // Backspace at start of line
+            push_line(&mut editor, "World");
+
+            editor.cursor_position.x = editor.num_columns_for_line_number as u16;
+            editor.cursor_position.y = 1;
+
+            editor.process_key(Key::Custom(EditorKey::Backspace));
+
+            assert_eq!(editor.buffer.line_count(), 1);
+            assert_eq!(editor.buffer.line(0), "HloWorld");
+
+            // Delete at end of line
+            push_line(&mut editor, "World");
+
+            assert_eq!(editor.cursor_position.y, 0);
+            editor.cursor_position.x =
+                (editor.num_columns_for_line_number + editor.buffer.line(0).chars().count()) as u16;
+            editor.process_key(Key::Custom(EditorKey::Delete));
+            assert_eq!(editor.buffer.line(0), "HloWorldWorld");
+        }
+
+        #[test]
+        fn test_insert_character() {
+            let mut editor = EditorInstance::new(get_populated_termios());
+
+            assert_eq!(editor.editor_mode, EditorMode::Insert);
+            editor.process_key(Key::U8(b'a'));
+            assert_eq!(editor.buffer.line(0), "a");
+        }
+
+        #[test]
+        fn test_use_vim_movement_character_in_normal_mode() {
+            let mut editor = EditorInstance::new(get_populated_termios());
+            editor.editor_mode = EditorMode::Normal;
+
+            push_line(&mut editor, "Hello");
+
+            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+
+            // Right
+            editor.process_key(Key::U8(b'l'));
+            assert_eq!(editor.buffer.line(0), "Hello");
+            assert_eq!(
+                editor.cursor_position.x as usize,
+                editor.num_columns_for_line_number + 1
+            );
+
+            // Left
+            editor.process_key(Key::U8(b'h'));
+            assert_eq!(editor.buffer.line(0), "Hello");
+            assert_eq!(
+                editor.cursor_position.x as usize,
+                editor.num_columns_for_line_number
+            );
+
+            // Down
+            push_line(&mut editor, "World");
+
+            editor.process_key(Key::U8(b'j'));
+            assert_eq!(editor.buffer.line(0), "Hello");
+            assert_eq!(editor.cursor_position.y, 1);
+
+            // Up
+            editor.process_key(Key::U8(b'k'));
+            assert_eq!(editor.buffer.line(1), "World");
+            assert_eq!(editor.cursor_position.y, 0);
+        }
+
+        #[test]
+        fn test_switch_editor_mode() {
+            let mut editor = EditorInstance::new(get_populated_termios());
+
+            assert_eq!(editor.editor_mode, EditorMode::Insert);
+            editor.process_key(Key::U8(b'\x1b'));
+            assert_eq!(editor.editor_mode, EditorMode::Normal);
+            editor.process_key(Key::U8(b'i'));
+            assert_eq!(editor.editor_mode, EditorMode::Insert);
+        }
+    }
+
+    mod test_normal_mode {
+        use super::*;
+
+        fn move_to(editor: &mut EditorInstance, line: usize, column: usize) {
+            editor.set_cursor_from_text_position(TextPosition { line, column });
+        }
+
+        #[test]
+        fn test_motions_with_counts() {
+            let mut editor = normal_mode_editor(&["abcdef", "ab", "abcdef", "abcdef"]);
+
+            type_keys(&mut editor, "3l");
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 0, column: 3 }
+            );
+
+            type_keys(&mut editor, "10l");
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 0, column: 5 }
+            );
+
+            type_keys(&mut editor, "j");
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 1, column: 2 }
+            );
+
+            type_keys(&mut editor, "5j2h");
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 3, column: 0 }
+            );
+
+            type_keys(&mut editor, "2k");
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 1, column: 0 }
+            );
+        }
+
+        #[test]
+        fn test_delete_lines() {
+            let mut editor = normal_mode_editor(&["one", "  two", "three", "four"]);
+
+            type_keys(&mut editor, "dd");
+            assert_eq!(texts(&editor), vec!["  two", "three", "four"]);
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 0, column: 2 }
+            );
+
+            type_keys(&mut editor, "j2dd");
+            assert_eq!(texts(&editor), vec!["  two"]);
+            assert_eq!(editor.get_text_position().line, 0);
+
+            type_keys(&mut editor, "p");
+            assert_eq!(texts(&editor), vec!["  two", "three", "four"]);
+            assert_eq!(editor.get_text_position().line, 1);
+        }
+
+        #[test]
+        fn test_delete_with_motion() {
+            let mut editor = normal_mode_editor(&["abcdef", "x", "y"]);
+
+            move_to(&mut editor, 0, 4);
+            type_keys(&mut editor, "d2h");
+            assert_eq!(texts(&editor), vec!["abef", "x", "y"]);
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 0, column: 2 }
+            );
+
+            type_keys(&mut editor, "dj");
+            assert_eq!(texts(&editor), vec!["y"]);
+        }
+
+        #[test]
+        fn test_change_is_undone_with_typed_text() {
+            let mut editor = normal_mode_editor(&["abc def"]);
+
+            type_keys(&mut editor, "c3lxyz\x1b");
+            assert_eq!(texts(&editor), vec!["xyz def"]);
+            assert_eq!(editor.editor_mode, EditorMode::Normal);
+
+            type_keys(&mut editor, "u");
+            assert_eq!(texts(&editor), vec!["abc def"]);
+        }
+
+        #[test]
+        fn test_change_lines() {
+            let mut editor = normal_mode_editor(&["  abc", "def", "ghi"]);
+
+            type_keys(&mut editor, "2ccnew");
+            assert_eq!(texts(&editor), vec!["new", "ghi"]);
+            assert_eq!(editor.editor_mode, EditorMode::Insert);
+        }
+
+        #[test]
+        fn test_yank_and_put() {
+            let mut editor = normal_mode_editor(&["abc", "def"]);
+
+            type_keys(&mut editor, "yy");
+            assert_eq!(texts(&editor), vec!["abc", "def"]);
+
+            type_keys(&mut editor, "jP");
+            assert_eq!(texts(&editor), vec!["abc", "abc", "def"]);
+            assert_eq!(editor.get_text_position().line, 1);
+
+            type_keys(&mut editor, "y2l");
+            type_keys(&mut editor, "2p");
+            assert_eq!(texts(&editor), vec!["abc", "aababbc", "def"]);
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 1, column: 4 }
+            );
+        }
+
+        #[test]
+        fn test_put_without_register() {
+            let mut editor = normal_mode_editor(&["abc"]);
+
+            type_keys(&mut editor, "p");
+
+            assert_eq!(texts(&editor), vec!["abc"]);
+            assert!(editor.status_message.unwrap().error);
+        }
+
+        #[test]
+        fn test_put_empty_register() {
+            let mut editor = normal_mode_editor(&["abc"]);
+            editor
+                .registers
+                .yank(
+                    Some('a'),
+                    Register {
+                        text: String::new(),
+                        linewise: false,
+                    },
+                )
+                .unwrap();
+
+            type_keys(&mut editor, "\"ap\"aP");
+
+            assert_eq!(texts(&editor), vec!["abc"]);
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 0, column: 0 }
+            );
+        }
+
+        #[test]
+        fn test_put_with_large_count() {
+            let mut editor = normal_mode_editor(&["abc"]);
+
+            type_keys(&mut editor, "yy99999999p");
+            assert_eq!(texts(&editor), vec!["abc"]);
+            assert_eq!(
+                status(&editor),
+                (String::from("Count too large to put"), true)
+            );
+
+            type_keys(&mut editor, "99p");
+            assert_eq!(editor.buffer.line_count(), 100);
+        }
+
+        #[test]
+        fn test_line_longer_than_cursor_can_reach() {
+            let line = "a".repeat(70_000);
+            let mut editor = normal_mode_editor(&[&line]);
+
+            type_keys(&mut editor, "$ax\x1b0ix");
+            assert_eq!(texts(&editor)[0].len(), 70_002);
+        }
+
+        #[test]
+        fn test_undo_with_large_count() {
+            let mut editor = normal_mode_editor(&["ab"]);
+
+            type_keys(&mut editor, "ylpp99999999999999999999u");
+            assert_eq!(texts(&editor), vec!["ab"]);
+        }
+
+        #[test]
+        fn test_delete_characters() {
+            let mut editor = normal_mode_editor(&["abcdef"]);
+
+            move_to(&mut editor, 0, 1);
+            type_keys(&mut editor, "2x");
+            assert_eq!(texts(&editor), vec!["adef"]);
+
+            type_keys(&mut editor, "10x");
+            assert_eq!(texts(&editor), vec!["a"]);
+            assert_eq!(editor.get_text_position().column, 0);
+
+            type_keys(&mut editor, "P");
+            assert_eq!(texts(&editor), vec!["defa"]);
+        }
+
+        #[test]
+        fn test_indent_and_outdent() {
+            let mut editor = normal_mode_editor(&["a", "", "b"]);
+
+            type_keys(&mut editor, "3>>");
+            assert_eq!(texts(&editor), vec!["\ta", "", "\tb"]);
+
+            type_keys(&mut editor, "<j");
+            assert_eq!(texts(&editor), vec!["a", "", "\tb"]);
+
+            set_lines(&mut editor, &["      a"]);
+            type_keys(&mut editor, "<<");
+            assert_eq!(texts(&editor), vec!["  a"]);
+        }
+
+        #[test]
+        fn test_open_lines() {
+            let mut editor = normal_mode_editor(&["a", "b"]);
+
+            type_keys(&mut editor, "ox\x1b");
+            assert_eq!(texts(&editor), vec!["a", "x", "b"]);
+
+            type_keys(&mut editor, "Oy\x1b");
+            assert_eq!(texts(&editor), vec!["a", "y", "x", "b"]);
+        }
+
+        #[test]
+        fn test_insert_commands() {
+            let mut editor = normal_mode_editor(&["  abc"]);
+
+            move_to(&mut editor, 0, 3);
+            type_keys(&mut editor, "a1\x1b");
+            assert_eq!(texts(&editor), vec!["  ab1c"]);
+
+            type_keys(&mut editor, "A2\x1b");
+            assert_eq!(texts(&editor), vec!["  ab1c2"]);
+
+            type_keys(&mut editor, "I3\x1b");
+            assert_eq!(texts(&editor), vec!["  3ab1c2"]);
+        }
+
+        #[test]
+        fn test_join_lines() {
+            let mut editor = normal_mode_editor(&["a", "   b", "", "c"]);
+
+            type_keys(&mut editor, "J");
+            assert_eq!(texts(&editor), vec!["a b", "", "c"]);
+            assert_eq!(editor.get_text_position().column, 1);
+
+            type_keys(&mut editor, "3J");
+            assert_eq!(texts(&editor), vec!["a b c"]);
+        }
+
+        #[test]
+        fn test_replace_characters() {
+            let mut editor = normal_mode_editor(&["abcd"]);
+
+            type_keys(&mut editor, "2rx");
+            assert_eq!(texts(&editor), vec!["xxcd"]);
+            assert_eq!(editor.get_text_position().column, 1);
+
+            // Not enough characters
+            type_keys(&mut editor, "5ry");
+            assert_eq!(texts(&editor), vec!["xxcd"]);
+
+            type_keys(&mut editor, "ré");
+            assert_eq!(texts(&editor), vec!["xécd"]);
+        }
+
+        fn position(line: usize, column: usize) -> TextPosition {
+            TextPosition { line, column }
+        }
+
+        #[test]
+        fn test_word_motions() {
+            let mut editor = normal_mode_editor(&["foo.bar(baz)  qux", "", "  end"]);
+
+            type_keys(&mut editor, "w");
+            assert_eq!(editor.get_text_position(), position(0, 3));
+
+            type_keys(&mut editor, "3w");
+            assert_eq!(editor.get_text_position(), position(0, 8));
+
+            type_keys(&mut editor, "2w");
+            assert_eq!(editor.get_text_position(), position(0, 14));
+
+            // An empty line is a word of its own
+            type_keys(&mut editor, "w");
+            assert_eq!(editor.get_text_position(), position(1, 0));
+
+            type_keys(&mut editor, "w");
+            assert_eq!(editor.get_text_position(), position(2, 2));
+
+            type_keys(&mut editor, "2b");
+            assert_eq!(editor.get_text_position(), position(0, 14));
+
+            type_keys(&mut editor, "b");
+            assert_eq!(editor.get_text_position(), position(0, 11));
+
+            type_keys(&mut editor, "0e");
+            assert_eq!(editor.get_text_position(), position(0, 2));
+
+            type_keys(&mut editor, "2e");
+            assert_eq!(editor.get_text_position(), position(0, 6));
+        }
+
+        #[test]
+        fn test_big_word_motions() {
+            let mut editor = normal_mode_editor(&["foo.bar(baz)  qux"]);
+
+            type_keys(&mut editor, "W");
+            assert_eq!(editor.get_text_position(), position(0, 14));
+
+            type_keys(&mut editor, "B");
+            assert_eq!(editor.get_text_position(), position(0, 0));
+
+            type_keys(&mut editor, "E");
+            assert_eq!(editor.get_text_position(), position(0, 11));
+
+            // At the end of the buffer
+            type_keys(&mut editor, "3W");
+            assert_eq!(editor.get_text_position(), position(0, 16));
+        }
+
+        #[test]
+        fn test_line_motions() {
+            let mut editor = normal_mode_editor(&["  abc", "de"]);
+
+            type_keys(&mut editor, "$");
+            assert_eq!(editor.get_text_position(), position(0, 4));
+
+            type_keys(&mut editor, "0");
+            assert_eq!(editor.get_text_position(), position(0, 0));
+
+            type_keys(&mut editor, "^");
+            assert_eq!(editor.get_text_position(), position(0, 2));
+
+            type_keys(&mut editor, "2$");
+            assert_eq!(editor.get_text_position(), position(1, 1));
+        }
+
+        #[test]
+        fn test_file_motions() {
+            let mut editor = normal_mode_editor(&["a", " b", "  c"]);
+
+            type_keys(&mut editor, "G");
+            assert_eq!(editor.get_text_position(), position(2, 2));
+
+            type_keys(&mut editor, "gg");
+            assert_eq!(editor.get_text_position(), position(0, 0));
+
+            type_keys(&mut editor, "2G");
+            assert_eq!(editor.get_text_position(), position(1, 1));
+
+            type_keys(&mut editor, "3gg");
+            assert_eq!(editor.get_text_position(), position(2, 2));
+
+            type_keys(&mut editor, "dgg");
+            assert_eq!(texts(&editor), Vec::<String>::new());
+        }
+
+        #[test]
+        fn test_paragraph_motions() {
+            let mut editor = normal_mode_editor(&["a", "b", "", "", "c", "d"]);
+
+            type_keys(&mut editor, "}");
+            assert_eq!(editor.get_text_position(), position(2, 0));
+
+            type_keys(&mut editor, "}");
+            assert_eq!(editor.get_text_position(), position(5, 0));
+
+            type_keys(&mut editor, "{");
+            assert_eq!(editor.get_text_position(), position(3, 0));
+
+            type_keys(&mut editor, "2{");
+            assert_eq!(editor.get_text_position(), position(0, 0));
+
+            type_keys(&mut editor, "d}");
+            assert_eq!(texts(&editor), vec!["", "", "", "c", "d"]);
+        }
+
+        #[test]
+        fn test_screen_motions() {
+            let mut editor = normal_mode_editor(&["a", "b", "c", "d", "e"]);
+
+            editor.window_size.rows = 3;
+            editor.line_scrolled_to = 1;
+
+            type_keys(&mut editor, "H");
+            assert_eq!(editor.get_text_position().line, 1);
+
+            type_keys(&mut editor, "L");
+            assert_eq!(editor.get_text_position().line, 3);
+
+            type_keys(&mut editor, "M");
+            assert_eq!(editor.get_text_position().line, 2);
+
+            type_keys(&mut editor, "2H");
+            assert_eq!(editor.get_text_position().line, 2);
+        }
+
+        #[test]
+        fn test_character_find_motions() {
+            let mut editor = normal_mode_editor(&["a,b,c,d"]);
+
+            type_keys(&mut editor, "f,");
+            assert_eq!(editor.get_text_position().column, 1);
+
+            type_keys(&mut editor, ";");
+            assert_eq!(editor.get_text_position().column, 3);
+
+            type_keys(&mut editor, ",");
+            assert_eq!(editor.get_text_position().column, 1);
+
+            type_keys(&mut editor, "2t,");
+            assert_eq!(editor.get_text_position().column, 4);
+
+            // A repeated `t` does not get stuck in front of the same character
+            type_keys(&mut editor, "0t,;");
+            assert_eq!(editor.get_text_position().column, 2);
+
+            type_keys(&mut editor, "$F,");
+            assert_eq!(editor.get_text_position().column, 5);
+
+            type_keys(&mut editor, "Tb");
+            assert_eq!(editor.get_text_position().column, 3);
+
+            // No match
+            type_keys(&mut editor, "fz");
+            assert_eq!(editor.get_text_position().column, 3);
+        }
+
+        #[test]
+        fn test_matching_bracket() {
+            let mut editor = normal_mode_editor(&["if (a[0]) {", "  (b)", "}"]);
+
+            type_keys(&mut editor, "%");
+            assert_eq!(editor.get_text_position(), position(0, 8));
+
+            type_keys(&mut editor, "%");
+            assert_eq!(editor.get_text_position(), position(0, 3));
+
+            type_keys(&mut editor, "$%");
+            assert_eq!(editor.get_text_position(), position(2, 0));
+
+            type_keys(&mut editor, "%");
+            assert_eq!(editor.get_text_position(), position(0, 10));
+        }
+
+        #[test]
+        fn test_operators_with_motions() {
+            let mut editor = normal_mode_editor(&["foo bar baz", "qux"]);
+
+            type_keys(&mut editor, "dw");
+            assert_eq!(texts(&editor), vec!["bar baz", "qux"]);
+
+            type_keys(&mut editor, "de");
+            assert_eq!(texts(&editor), vec![" baz", "qux"]);
+
+            type_keys(&mut editor, "ld$");
+            assert_eq!(texts(&editor), vec![" ", "qux"]);
+
+            set_lines(&mut editor, &["call(a, b)"]);
+            move_to(&mut editor, 0, 4);
+            type_keys(&mut editor, "d%");
+            assert_eq!(texts(&editor), vec!["call"]);
+
+            set_lines(&mut editor, &["a,b,c"]);
+            move_to(&mut editor, 0, 0);
+            type_keys(&mut editor, "dt,");
+            assert_eq!(texts(&editor), vec![",b,c"]);
+
+            type_keys(&mut editor, "d2f,");
+            assert_eq!(texts(&editor), vec![",b,c"]);
+
+            type_keys(&mut editor, "df,");
+            assert_eq!(texts(&editor), vec!["c"]);
+        }
+
+        #[test]
+        fn test_word_operators_stay_on_the_line() {
+            let mut editor = normal_mode_editor(&["foo bar", "  baz"]);
+
+            move_to(&mut editor, 0, 4);
+            type_keys(&mut editor, "dw");
+            assert_eq!(texts(&editor), vec!["foo ", "  baz"]);
+
+            set_lines(&mut editor, &["foo bar", "baz"]);
+            move_to(&mut editor, 0, 4);
+            type_keys(&mut editor, "yw");
+            assert_eq!(editor.registers.get(None).unwrap().unwrap().text, "bar");
+        }
+
+        #[test]
+        fn test_change_word_keeps_following_space() {
+            let mut editor = normal_mode_editor(&["foo bar"]);
+
+            type_keys(&mut editor, "cwx\x1b");
+            assert_eq!(texts(&editor), vec!["x bar"]);
+        }
+
+        #[test]
+        fn test_case_operators() {
+            let mut editor = normal_mode_editor(&["Hello World", "abc"]);
+
+            type_keys(&mut editor, "g~w");
+            assert_eq!(texts(&editor), vec!["hELLO World", "abc"]);
+
+            type_keys(&mut editor, "gUU");
+            assert_eq!(texts(&editor), vec!["HELLO WORLD", "abc"]);
+
+            type_keys(&mut editor, "gu2j");
+            assert_eq!(texts(&editor), vec!["hello world", "abc"]);
+        }
+
+        #[test]
+        fn test_escape_cancels_pending_command() {
+            let mut editor = normal_mode_editor(&["a", "b"]);
+
+            type_keys(&mut editor, "d\x1bj");
+
+            assert_eq!(texts(&editor), vec!["a", "b"]);
+            assert_eq!(editor.get_text_position().line, 1);
+        }
+    }
+
+    mod test_registers {
+        use super::*;
+
+        #[test]
+        fn test_named_registers() {
+            let mut editor = normal_mode_editor(&["a", "b", "c"]);
+
+            type_keys(&mut editor, "\"ayyj\"byyj\"Ayy");
+            type_keys(&mut editor, "\"bp\"aP");
+
+            assert_eq!(texts(&editor), vec!["a", "b", "c", "a", "c", "b"]);
+        }
+
+        #[test]
+        fn test_numbered_and_yank_registers() {
+            let mut editor = normal_mode_editor(&["one", "two", "three"]);
+
+            type_keys(&mut editor, "yyjddx");
+            assert_eq!(texts(&editor), vec!["one", "hree"]);
+
+            type_keys(&mut editor, "\"1p\"0p\"-P");
+            assert_eq!(texts(&editor), vec!["one", "hree", "two", "tone"]);
+        }
+
+        #[test]
+        fn test_black_hole_register() {
+            let mut editor = normal_mode_editor(&["keep", "drop"]);
+
+            type_keys(&mut editor, "yyj\"_ddp");
+            assert_eq!(texts(&editor), vec!["keep", "keep"]);
+        }
+
+        #[test]
+        fn test_clipboard_register() {
+            let mut editor = normal_mode_editor(&["copied", ""]);
+
+            type_keys(&mut editor, "\"+yyj\"+p");
+            assert_eq!(texts(&editor), vec!["copied", "", "copied"]);
+        }
+
+        #[test]
+        fn test_visual_mode_yank_to_register() {
+            let mut editor = normal_mode_editor(&["abc def"]);
+
+            type_keys(&mut editor, "ve\"zy");
+            assert_eq!(
+                editor.registers.get(Some('z')).unwrap().unwrap().text,
+                "abc"
+            );
+        }
+
+        #[test]
+        fn test_insert_register_in_insert_mode() {
+            let mut editor = normal_mode_editor(&["foo bar", ""]);
+
+            type_keys(&mut editor, "\"qyeji");
+            editor.process_key(Key::U8(ctrl_key('r')));
+            type_keys(&mut editor, "q!");
+
+            assert_eq!(texts(&editor), vec!["foo bar", "foo!"]);
+
+            // Keys that do not name a register cancel
+            editor.process_key(Key::U8(ctrl_key('r')));
+            editor.process_key(Key::Custom(EditorKey::ArrowLeft));
+            type_keys(&mut editor, "?");
+
+            assert_eq!(texts(&editor), vec!["foo bar", "foo!?"]);
+        }
+    }
+
+    mod test_paste {
+        use super::*;
+
+        #[test]
+        fn test_paste_is_one_undoable_edit() {
+            let mut editor = editor_with_lines(&["ab"]);
+            editor.set_cursor_from_text_position(TextPosition { line: 0, column: 1 });
+
+            editor.process_key(Key::U8(b'x'));
+            editor.process_key(Key::Paste(String::from("one\n\ttwo\n")));
+            editor.process_key(Key::U8(b'y'));
+
+            assert_eq!(texts(&editor), vec!["axone", "\ttwo", "yb"]);
+
+            editor.undo();
+            assert_eq!(texts(&editor), vec!["axone", "\ttwo", "b"]);
+
+            editor.undo();
+            assert_eq!(texts(&editor), vec!["axb"]);
+        }
+
+        #[test]
+        fn test_paste_in_normal_mode_is_not_run_as_commands() {
+            let mut editor = editor_with_lines(&["abc"]);
+            editor.editor_mode = EditorMode::Normal;
+
+            editor.process_key(Key::U8(b'd'));
+            editor.process_key(Key::Paste(String::from("dd:q")));
+
+            assert_eq!(texts(&editor), vec!["dd:qabc"]);
+            assert_eq!(editor.editor_mode, EditorMode::Normal);
+            assert!(editor.normal_mode_keys.is_empty());
+        }
+
+        #[test]
+        fn test_paste_into_empty_buffer() {
+            let mut editor = editor_with_lines(&[]);
+
+            editor.process_key(Key::Paste(String::from("a\nb")));
+
+            assert_eq!(texts(&editor), vec!["a", "b"]);
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 1, column: 1 }
+            );
+        }
+    }
+
+    mod test_modified_keys {
+        use super::*;
+        use crate::input::Modifiers;
+
+        #[test]
+        fn test_ctrl_arrows_move_by_words() {
+            let mut editor = editor_with_lines(&["one two three"]);
+            editor.set_cursor_from_text_position(TextPosition { line: 0, column: 4 });
+
+            editor.process_key(Key::Modified(EditorKey::ArrowRight, Modifiers::CTRL));
+            assert_eq!(editor.get_text_position().column, 8);
+            assert_eq!(editor.editor_mode, EditorMode::Insert);
+
+            editor.process_key(Key::Modified(EditorKey::ArrowLeft, Modifiers::SHIFT));
+            editor.process_key(Key::Modified(EditorKey::ArrowLeft, Modifiers::CTRL));
+            assert_eq!(editor.get_text_position().column, 0);
+
+            // Other modifiers act as the plain key
+            editor.process_key(Key::Modified(EditorKey::ArrowRight, Modifiers::ALT));
+            assert_eq!(editor.get_text_position().column, 1);
+        }
+
+        #[test]
+        fn test_ctrl_home_and_end() {
+            let mut editor = editor_with_lines(&["abc", "def", "  ghi"]);
+            editor.set_cursor_from_text_position(TextPosition { line: 1, column: 1 });
+
+            editor.process_key(Key::Modified(EditorKey::End, Modifiers::CTRL));
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 2, column: 2 }
+            );
+
+            editor.process_key(Key::Modified(EditorKey::Home, Modifiers::CTRL));
+            assert_eq!(editor.get_text_position(), TextPosition::default());
+        }
+
+        #[test]
+        fn test_alt_key_is_escape_then_key() {
+            let mut editor = editor_with_lines(&["abc", "def"]);
+
+            editor.process_key(Key::U8(b'x'));
+            editor.process_key(Key::Alt('j'));
+
+            assert_eq!(texts(&editor), vec!["xabc", "def"]);
+            assert_eq!(editor.editor_mode, EditorMode::Normal);
+            assert_eq!(editor.get_text_position().line, 1);
+        }
+
+        #[test]
+        fn test_insert_key_enters_insert_mode() {
+            let mut editor = editor_with_lines(&["abc"]);
+            editor.editor_mode = EditorMode::Normal;
+
+            editor.process_key(Key::Custom(EditorKey::Insert));
+            assert_eq!(editor.editor_mode, EditorMode::Insert);
+
+            // And is otherwise ignored, as are keys without a use
+            editor.process_key(Key::Custom(EditorKey::Insert));
+            editor.process_key(Key::Custom(EditorKey::Function(5)));
+            editor.process_key(Key::Custom(EditorKey::BackTab));
+            assert_eq!(editor.editor_mode, EditorMode::Insert);
+            assert_eq!(texts(&editor), vec!["abc"]);
+        }
+    }
+
+    mod test_keymap {
+        use super::*;
+
+        #[test]
+        fn test_multi_key_binding() {
+            let mut editor = editor_with_lines(&[""]);
+            editor.run_command("imap jk normal_mode");
+
+            type_keys(&mut editor, "ajk");
+            assert_eq!(texts(&editor), vec!["a"]);
+            assert_eq!(editor.editor_mode, EditorMode::Normal);
+
+            // Keys that turn out not to complete a binding are typed as usual
+            type_keys(&mut editor, "ajxj");
+            assert_eq!(texts(&editor), vec!["ajx"]);
+            assert_eq!(editor.pending_keys, vec![Key::U8(b'j')]);
+
+            // ...including once no more keys have been typed for a while
+            editor.check_key_sequence_timeout();
+            assert_eq!(texts(&editor), vec!["ajx"]);
+
+            editor.pending_keys_typed_at = Instant::now() - KEY_SEQUENCE_TIMEOUT;
+            editor.check_key_sequence_timeout();
+            assert_eq!(texts(&editor), vec!["ajxj"]);
+            assert!(editor.pending_keys.is_empty());
+        }
+
+        #[test]
+        fn test_normal_mode_mappings() {
+            let mut editor = editor_with_lines(&["one two"]);
+            editor.editor_mode = EditorMode::Normal;
+
+            editor.run_command("nmap ,e line_end");
+            type_keys(&mut editor, ",e");
+            assert_eq!(editor.get_text_position().column, 7);
+
+            // A count may come first, but keys finishing a command are not mapped
+            editor.run_command("nmap q line_start");
+            type_keys(&mut editor, "2q");
+            assert_eq!(editor.get_text_position().column, 0);
+
+            type_keys(&mut editor, "rq");
+            assert_eq!(texts(&editor), vec!["qne two"]);
+        }
+
+        #[test]
+        fn test_list_and_unmap() {
+            let mut editor = editor_with_lines(&[""]);
+
+            editor.run_command("imap jk normal_mode");
+            editor.run_command("imap jk");
+            assert_eq!(status(&editor), (String::from("jk normal_mode"), false));
+
+            editor.run_command("iunmap jk");
+            editor.run_command("iunmap jk");
+            assert_eq!(status(&editor), (String::from("No such mapping"), true));
+
+            editor.run_command("vmap ,q");
+            assert_eq!(status(&editor), (String::from("No mapping found"), true));
+        }
+
+        #[test]
+        fn test_help_follows_bindings() {
+            let mut editor = editor_with_lines(&[""]);
+            editor.editor_mode = EditorMode::Normal;
+
+            editor.show_help();
+            assert!(status(&editor).0.starts_with("Ctrl-F: find | "));
+
+            editor.run_command("map <C-p> find");
+            editor.run_command("unmap <C-f>");
+            editor.show_help();
+            assert!(status(&editor).0.starts_with("Ctrl-P: find | "));
+        }
+
+        #[test]
+        fn test_apply_config() {
+            let mut editor = editor_with_lines(&[""]);
+
+            editor.apply_config(
+                crate::config::parse("[keymap.insert]\n\"<C-s>\" = \"\"\n\"<C-l>\" = \"save\"")
+                    .unwrap(),
+            );
+
+            assert_eq!(
+                editor.keymap.get(Mode::Insert, &[Key::U8(ctrl_key('s'))]),
+                None
+            );
+            assert_eq!(
+                editor.keymap.get(Mode::Insert, &[Key::U8(ctrl_key('l'))]),
+                Some(Command::Save)
+            );
+        }
+    }
+
+    mod test_mouse {
+        use super::*;
+
+        fn mouse_editor(texts: &[&str]) -> EditorInstance {
+            let mut editor = normal_mode_editor(texts);
+            editor.window_size = WindowSize {
+                rows: 3,
+                columns: 20,
+            };
+            editor
+        }
+
+        /// Sends a mouse event at a column of the text, right of the line numbers
+        fn mouse(editor: &mut EditorInstance, kind: MouseEventKind, column: u16, row: u32) {
+            let num_columns_for_line_number = editor.num_columns_for_line_number as u16;
+
+            editor.process_key(Key::Mouse(MouseEvent {
+                kind,
+                column: column + num_columns_for_line_number,
+                row,
+            }));
+        }
+
+        const CLICK: MouseEventKind = MouseEventKind::Press(MouseButton::Left);
+        const DRAG: MouseEventKind = MouseEventKind::Drag(MouseButton::Left);
+
+        #[test]
+        fn test_click_moves_cursor() {
+            let mut editor = mouse_editor(&["abc", "\tdef", "gh"]);
+
+            mouse(&mut editor, CLICK, 1, 0);
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 0, column: 1 }
+            );
+
+            // Within and after a tab
+            mouse(&mut editor, CLICK, 2, 1);
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 1, column: 0 }
+            );
+
+            mouse(&mut editor, CLICK, 5, 1);
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 1, column: 2 }
+            );
+
+            // Past the end of the line
+            mouse(&mut editor, CLICK, 15, 2);
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 2, column: 2 }
+            );
+        }
+
+        #[test]
+        fn test_click_on_line_numbers_and_below_text() {
+            let mut editor = mouse_editor(&["abc", "de"]);
+            editor.set_cursor_from_text_position(TextPosition { line: 0, column: 2 });
+
+            editor.process_key(Key::Mouse(MouseEvent {
+                kind: CLICK,
+                column: 0,
+                row: 1,
+            }));
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 1, column: 0 }
+            );
+
+            mouse(&mut editor, CLICK, 1, 2);
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 1, column: 1 }
+            );
+        }
+
+        #[test]
+        fn test_click_accounts_for_scrolling() {
+            let mut editor = mouse_editor(&["0", "1", "2", "abcdefghijklmnopqrstuvwxyz"]);
+            editor.line_scrolled_to = 2;
+            editor.column_scrolled_to = 10;
+
+            mouse(&mut editor, CLICK, 3, 1);
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition {
+                    line: 3,
+                    column: 13
+                }
+            );
+        }
+
+        #[test]
+        fn test_click_on_status_bar_is_ignored() {
+            let mut editor = mouse_editor(&["abc", "def", "ghi", "jkl"]);
+            editor.set_cursor_from_text_position(TextPosition { line: 1, column: 1 });
+
+            mouse(&mut editor, CLICK, 0, 3);
+            mouse(&mut editor, CLICK, 0, 4);
+            mouse(&mut editor, DRAG, 2, 2);
+
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 1, column: 1 }
+            );
+            assert_eq!(editor.editor_mode, EditorMode::Normal);
+        }
+
+        #[test]
+        fn test_click_in_empty_buffer() {
+            let mut editor = mouse_editor(&[]);
+
+            mouse(&mut editor, CLICK, 4, 1);
+            assert_eq!(editor.get_text_position(), TextPosition::default());
+        }
+
+        #[test]
+        fn test_drag_selects() {
+            let mut editor = mouse_editor(&["abc", "def"]);
+
+            mouse(&mut editor, CLICK, 1, 0);
+            mouse(&mut editor, DRAG, 2, 0);
+            mouse(&mut editor, DRAG, 1, 1);
+            mouse(&mut editor, MouseEventKind::Release, 1, 1);
+
+            assert_eq!(
+                editor.editor_mode,
+                EditorMode::Visual(SelectionKind::Characters)
+            );
+            assert_eq!(editor.selection_anchor, TextPosition { line: 0, column: 1 });
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 1, column: 1 }
+            );
+
+            editor.process_key(Key::U8(b'd'));
+            assert_eq!(texts(&editor), vec!["af"]);
 
-        status_bar_content
-            .truncate(self.window_size.columns as usize + num_characters_in_terminal_commands);
+            // Clicking ends a selection
+            mouse(&mut editor, CLICK, 0, 0);
+            mouse(&mut editor, DRAG, 1, 0);
+            mouse(&mut editor, CLICK, 0, 0);
+            assert_eq!(editor.editor_mode, EditorMode::Normal);
+        }
 
-        buffer.push_str(&status_bar_content);
+        #[test]
+        fn test_drag_from_insert_mode() {
+            let mut editor = mouse_editor(&["abc"]);
+            editor.editor_mode = EditorMode::Insert;
 
-        let space_left = self.window_size.columns as usize + num_characters_in_terminal_commands
-            - status_bar_content.chars().count();
+            mouse(&mut editor, CLICK, 0, 0);
+            assert_eq!(editor.editor_mode, EditorMode::Insert);
 
-        let mut cursor_position_information = format!(
-            "{} {} {}{}/{} ",
-            AnsiEscapeCode::Reset.as_str(),
-            AnsiEscapeCode::ReverseMode.as_str(),
-            self.syntax
-                .as_ref()
-                .map_or(String::new(), |syntax| format!("{} ", syntax.file_type)),
-            self.cursor_position.y + 1,
-            self.lines.len()
-        );
+            mouse(&mut editor, DRAG, 2, 0);
+            assert_eq!(
+                editor.editor_mode,
+                EditorMode::Visual(SelectionKind::Characters)
+            );
+        }
 
-        let num_characters_in_terminal_commands = 7;
+        #[test]
+        fn test_wheel_scrolls() {
+            let lines: Vec<String> = (0..10).map(|line| line.to_string()).collect();
+            let mut editor = mouse_editor(&lines.iter().map(String::as_str).collect::<Vec<_>>());
 
-        cursor_position_information.truncate(space_left + num_characters_in_terminal_commands);
+            mouse(&mut editor, MouseEventKind::ScrollDown, 0, 0);
+            editor.scroll();
+            assert_eq!(editor.line_scrolled_to, 3);
+            assert_eq!(editor.cursor_position.y, 3); // Kept on screen
 
-        let gap = space_left + num_characters_in_terminal_commands
-            - cursor_position_information.chars().count();
+            mouse(&mut editor, MouseEventKind::ScrollDown, 0, 0);
+            mouse(&mut editor, MouseEventKind::ScrollDown, 0, 0);
+            mouse(&mut editor, MouseEventKind::ScrollDown, 0, 0);
+            editor.scroll();
+            assert_eq!(editor.line_scrolled_to, 9);
 
-        buffer.push_str(&" ".repeat(gap));
-        buffer.push_str(&cursor_position_information);
-        buffer.push_str(AnsiEscapeCode::Reset.as_str());
-        buffer.push_str("\r\n"); // Add newline for status message
+            mouse(&mut editor, MouseEventKind::ScrollUp, 0, 0);
+            editor.scroll();
+            assert_eq!(editor.line_scrolled_to, 6);
+            assert_eq!(editor.cursor_position.y, 8);
 
-        write!(io::stdout(), "{}", buffer)
-            .expect("Failed to write to stdout while drawing status bar");
-        flush_stdout();
+            mouse(&mut editor, MouseEventKind::ScrollUp, 0, 0);
+            mouse(&mut editor, MouseEventKind::ScrollUp, 0, 0);
+            mouse(&mut editor, MouseEventKind::ScrollUp, 0, 0);
+            editor.scroll();
+            assert_eq!(editor.line_scrolled_to, 0);
+            assert_eq!(editor.cursor_position.y, 2);
+        }
     }
 
-    pub fn set_status_message(&mut self, message: &str, error: bool) {
-        self.status_message = Some(StatusMessage {
-            message: message.to_string(),
-            time_set: Instant::now(),
-            error,
-        });
-    }
+    mod test_visual_mode {
+        use super::*;
 
-    pub fn draw_status_message_bar(&mut self) {
-        let mut buffer = AnsiEscapeCode::Reset.as_string();
+        #[test]
+        fn test_enter_switch_and_leave_visual_modes() {
+            let mut editor = normal_mode_editor(&["abc"]);
 
-        buffer.push_str(AnsiEscapeCode::EraseLineToRight.as_str());
+            type_keys(&mut editor, "v");
+            assert_eq!(
+                editor.editor_mode,
+                EditorMode::Visual(SelectionKind::Characters)
+            );
 
-        if let Some(status_message) = &self.status_message {
-            if status_message.time_set.elapsed().as_secs() < 5 {
-                let mut message = match status_message.error {
-                    false => format!(" {} ", status_message.message.clone()),
-                    true => format!(
-                        "{} {} {}",
-                        AnsiEscapeCode::BackgroundRed.as_str(),
-                        status_message.message.clone(),
-                        AnsiEscapeCode::Reset.as_str()
-                    ),
-                };
-                message.truncate(
-                    self.window_size.columns as usize + if status_message.error { 8 } else { 0 },
-                );
-                buffer.push_str(&message);
-            }
-        }
+            type_keys(&mut editor, "V");
+            assert_eq!(editor.editor_mode, EditorMode::Visual(SelectionKind::Lines));
 
-        write!(io::stdout(), "{buffer}")
-            .expect("Failed to write to stdout while drawing status message bar");
-        flush_stdout();
-    }
-}
+            type_keys(&mut editor, "\x16");
+            assert_eq!(editor.editor_mode, EditorMode::Visual(SelectionKind::Block));
 
-#[cfg(test)]
-mod unit_tests {
-    use super::*;
-    use crate::terminal::get_populated_termios;
+            type_keys(&mut editor, "\x16");
+            assert_eq!(editor.editor_mode, EditorMode::Normal);
 
-    #[test]
-    fn test_new_editor_instance() {
-        let editor = EditorInstance::new(get_populated_termios());
+            type_keys(&mut editor, "v\x1b");
+            assert_eq!(editor.editor_mode, EditorMode::Normal);
+        }
 
-        // Check initial properties
-        assert_eq!(editor.cursor_position.x, 0);
-        assert_eq!(editor.cursor_position.y, 0);
-        assert_eq!(editor.cursor_position.render_x, 0);
-        assert_eq!(editor.editor_mode, EditorMode::Insert);
-        assert!(editor.lines.is_empty());
-        assert_eq!(editor.line_scrolled_to, 0);
-        assert_eq!(editor.column_scrolled_to, 0);
-        assert!(editor.file.is_none());
-        assert!(editor.status_message.is_none());
-        assert!(!editor.edited);
-        assert_eq!(editor.quit_confirmations, 0);
-        assert_eq!(editor.previous_search_match_line_index, None);
-        assert_eq!(editor.search_direction, SearchDirection::Forward);
-        assert_eq!(editor.saved_highlight, None);
-        assert_eq!(editor.num_columns_for_line_number, 0);
-    }
+        #[test]
+        fn test_selected_columns() {
+            let mut editor = normal_mode_editor(&["abcdef", "gh", "ijklm"]);
 
-    #[test]
-    fn test_get_current_line() {
-        let mut editor = EditorInstance::new(get_populated_termios());
+            editor.set_cursor_from_text_position(TextPosition { line: 0, column: 4 });
+            type_keys(&mut editor, "vjj");
 
-        // No lines
-        assert!(editor.get_current_line().is_none());
+            assert_eq!(editor.get_selected_columns(0), Some(4..7));
+            assert_eq!(editor.get_selected_columns(1), Some(0..3));
+            assert_eq!(editor.get_selected_columns(2), Some(0..3));
 
-        // Happy path
-        editor.lines.push(Line {
-            text: String::from("First line"),
-            render: String::from("First line"),
-            highlight: vec![],
-            index: 0,
-            has_open_multiline_comment: false,
-        });
-        editor.lines.push(Line {
-            text: String::from("Second line"),
-            render: String::from("Second line"),
-            highlight: vec![],
-            index: 1,
-            has_open_multiline_comment: false,
-        });
+            type_keys(&mut editor, "\x16");
+            assert_eq!(editor.get_selected_columns(1), Some(2..5));
 
-        editor.cursor_position.y = 0;
-        let current_line = editor.get_current_line();
-        assert!(current_line.is_some());
-        assert_eq!(current_line.unwrap().text, "First line");
+            type_keys(&mut editor, "\x1b");
+            assert_eq!(editor.get_selected_columns(0), None);
+        }
 
-        editor.cursor_position.y = 1;
-        let current_line = editor.get_current_line();
-        assert!(current_line.is_some());
-        assert_eq!(current_line.unwrap().text, "Second line");
+        #[test]
+        fn test_delete_characters() {
+            let mut editor = normal_mode_editor(&["abcdef", "ghi"]);
 
-        // Out of bounds
-        editor.cursor_position.y = 2;
-        assert!(editor.get_current_line().is_none());
-    }
+            type_keys(&mut editor, "lvjd");
+            assert_eq!(texts(&editor), vec!["ai"]);
+            assert_eq!(editor.editor_mode, EditorMode::Normal);
 
-    #[test]
-    fn test_get_render_text_from_text() {
-        let input = "Hello\tWorld!";
-        let expected_output = "Hello   World!"; // Three spaces to next tab
-        let output = EditorInstance::get_render_text_from_text(input);
-        assert_eq!(output, expected_output);
+            type_keys(&mut editor, "P");
+            assert_eq!(texts(&editor), vec!["abcdef", "ghi"]);
+        }
 
-        let input = "\tTabbed";
-        let expected_output = "    Tabbed"; // Four spaces to first tab
-        let output = EditorInstance::get_render_text_from_text(input);
-        assert_eq!(output, expected_output);
+        #[test]
+        fn test_selection_past_the_end_of_a_line_takes_the_newline() {
+            let mut editor = normal_mode_editor(&["", "abc"]);
 
-        let input = "No tabs here!";
-        let expected_output = "No tabs here!";
-        let output = EditorInstance::get_render_text_from_text(input);
-        assert_eq!(output, expected_output);
-    }
+            type_keys(&mut editor, "vx");
+            assert_eq!(texts(&editor), vec!["abc"]);
+        }
 
-    mod test_is_separator {
-        use super::*;
+        #[test]
+        fn test_swap_selection_ends() {
+            let mut editor = normal_mode_editor(&["abcdef"]);
+
+            type_keys(&mut editor, "lvllohy");
+            assert_eq!(editor.registers.get(None).unwrap().unwrap().text, "abcd");
+            assert_eq!(editor.get_text_position().column, 0);
+        }
 
         #[test]
-        fn test_with_punctuation() {
-            assert!(EditorInstance::is_separator('.'));
-            assert!(EditorInstance::is_separator(','));
-            assert!(EditorInstance::is_separator('!'));
-            assert!(EditorInstance::is_separator('?'));
-            assert!(EditorInstance::is_separator(';'));
-            assert!(EditorInstance::is_separator(':'));
+        fn test_yank_and_change_lines() {
+            let mut editor = normal_mode_editor(&["a", "b", "c"]);
+
+            type_keys(&mut editor, "Vjy");
+            assert_eq!(
+                editor.registers.get(None).unwrap(),
+                Some(Register {
+                    text: String::from("a\nb\n"),
+                    linewise: true
+                })
+            );
+
+            type_keys(&mut editor, "jVcx\x1b");
+            assert_eq!(texts(&editor), vec!["a", "x", "c"]);
         }
 
         #[test]
-        fn test_with_whitespace() {
-            assert!(EditorInstance::is_separator(' '));
-            assert!(EditorInstance::is_separator('\t'));
+        fn test_indent_and_case_change() {
+            let mut editor = normal_mode_editor(&["ab", "cd"]);
+
+            type_keys(&mut editor, "Vj2>");
+            assert_eq!(texts(&editor), vec!["\t\tab", "\t\tcd"]);
+
+            type_keys(&mut editor, "v$U");
+            assert_eq!(texts(&editor), vec!["\t\tAB", "\t\tcd"]);
+
+            type_keys(&mut editor, "Vj~");
+            assert_eq!(texts(&editor), vec!["\t\tab", "\t\tCD"]);
         }
 
         #[test]
-        fn test_with_newline() {
-            assert!(EditorInstance::is_separator('\n'));
+        fn test_delete_block() {
+            let mut editor = normal_mode_editor(&["abcd", "e", "fghi"]);
+
+            type_keys(&mut editor, "l\x16jjld");
+            assert_eq!(texts(&editor), vec!["ad", "e", "fi"]);
+            assert_eq!(
+                editor.registers.get(None).unwrap().unwrap().text,
+                "bc\n\ngh"
+            );
         }
 
         #[test]
-        fn test_with_non_separators() {
-            assert!(!EditorInstance::is_separator('a'));
-            assert!(!EditorInstance::is_separator('Z'));
-            assert!(!EditorInstance::is_separator('0'));
-            assert!(!EditorInstance::is_separator('9'));
+        fn test_yank_empty_block_keeps_registers() {
+            let mut editor = normal_mode_editor(&["", "abc"]);
+
+            type_keys(&mut editor, "jyyk\x16yp");
+            assert_eq!(editor.registers.get(None).unwrap().unwrap().text, "abc\n");
+            assert_eq!(texts(&editor), vec!["", "abc", "abc"]);
         }
 
         #[test]
-        fn test_with_underscore() {
-            assert!(!EditorInstance::is_separator('_')); // Underscore is NOT a separator
+        fn test_change_block_repeats_typed_text() {
+            let mut editor = normal_mode_editor(&["abcd", "e", "fghi"]);
+
+            type_keys(&mut editor, "l\x16jjlcXY\x1b");
+            assert_eq!(texts(&editor), vec!["aXYd", "e", "fXYi"]);
+
+            // The change and the text typed are undone together
+            type_keys(&mut editor, "u");
+            assert_eq!(texts(&editor), vec!["abcd", "e", "fghi"]);
         }
+    }
+
+    mod test_move_cursor {
+        use super::*;
 
         #[test]
-        fn test_with_extended_ascii() {
-            assert!(!EditorInstance::is_separator('é')); // Non-ASCII character
-            assert!(!EditorInstance::is_separator('中')); // Unicode character
+        fn test_move_cursor_left() {
+            let mut editor = EditorInstance::new(get_populated_termios());
+
+            push_line(&mut editor, "Hello, World!");
+
+            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+
+            editor.cursor_position.x = 5;
+            editor.move_cursor(CursorMovement::Left);
+            assert_eq!(editor.cursor_position.x, 4);
+
+            // Move left at start of line
+            editor.cursor_position.x = editor.num_columns_for_line_number as u16;
+
+            editor.insert_text_at(0, "Prev Line\n");
+
+            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            editor.cursor_position.y = 1;
+            editor.move_cursor(CursorMovement::Left);
+            assert_eq!(editor.cursor_position.y, 0);
+            assert_eq!(
+                editor.cursor_position.x,
+                9 + editor.num_columns_for_line_number as u16
+            );
         }
 
         #[test]
-        fn test_with_edge_cases() {
-            assert!(EditorInstance::is_separator('['));
-            assert!(EditorInstance::is_separator(']'));
-            assert!(EditorInstance::is_separator('{'));
-            assert!(EditorInstance::is_separator('}'));
+        fn test_move_cursor_right() {
+            let mut editor = EditorInstance::new(get_populated_termios());
+
+            push_line(&mut editor, "Hello");
+
+            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+
+            editor.cursor_position.x = editor.num_columns_for_line_number as u16;
+            editor.move_cursor(CursorMovement::Right);
+            assert_eq!(
+                editor.cursor_position.x,
+                editor.num_columns_for_line_number as u16 + 1
+            );
+
+            // Move right at end of line
+            editor.cursor_position.x = 5 + editor.num_columns_for_line_number as u16;
+            editor.move_cursor(CursorMovement::Right);
+            assert_eq!(editor.cursor_position.y, 1);
+            assert_eq!(editor.cursor_position.x, 0);
         }
-    }
 
-    mod test_set_syntax_from_file_name {
-        use super::*;
+        #[test]
+        fn test_move_cursor_up() {
+            let mut editor = EditorInstance::new(get_populated_termios());
+
+            push_line(&mut editor, "Line 1");
+
+            push_line(&mut editor, "Line 2");
+
+            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+
+            editor.cursor_position.y = 1;
+            editor.move_cursor(CursorMovement::Up);
+            assert_eq!(editor.cursor_position.y, 0);
+        }
 
         #[test]
-        fn test_with_supported_file_types() {
+        fn test_move_cursor_down() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            [
-                ("test.rs", "Rust"),
-                ("test.c", "C"),
-                ("test.py", "Python"),
-                ("test.js", "JavaScript"),
-            ]
-            .iter()
-            .for_each(|(file_name, expected_file_type)| {
-                editor.file = Some(File {
-                    name: file_name.to_string(),
-                    path: format!("some-path/{file_name}"),
-                });
+            push_line(&mut editor, "Line 1");
 
-                editor.set_syntax_from_file_name();
-                assert_eq!(editor.syntax.unwrap().file_type, *expected_file_type);
-            });
+            push_line(&mut editor, "Line 2");
+
+            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+
+            editor.cursor_position.y = 0;
+            editor.move_cursor(CursorMovement::Down);
+            assert_eq!(editor.cursor_position.y, 1);
         }
 
         #[test]
-        fn test_with_unsupported_file_types() {
+        fn test_cursor_stays_within_bounds() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.file = Some(File {
-                name: "test.foo".to_string(),
-                path: format!("some-path/test.foo"),
-            });
+            push_line(&mut editor, "");
 
-            editor.syntax = Some(&SYNTAX_CONFIGURATIONS[0]);
+            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
 
-            editor.set_syntax_from_file_name();
-            assert!(editor.syntax.is_none());
+            editor.cursor_position.x = editor.num_columns_for_line_number as u16;
+            editor.move_cursor(CursorMovement::Left);
+            assert_eq!(
+                editor.cursor_position.x,
+                editor.num_columns_for_line_number as u16
+            );
+
+            editor.cursor_position.y = 0;
+            editor.move_cursor(CursorMovement::Up);
+            assert_eq!(editor.cursor_position.y, 0);
+
+            editor.cursor_position.y = 1;
+            editor.move_cursor(CursorMovement::Down);
+            assert_eq!(editor.cursor_position.y, 1);
         }
     }
 
-    mod test_open {
+    mod test_cursor_x_to_render_x {
         use super::*;
-        use std::fs::File;
-        use std::io::Write;
-        use std::sync::{Arc, Mutex};
-        use tempfile::tempdir;
 
         #[test]
-        fn test_open_valid_file() {
+        fn test_cursor_x_to_render_x_no_tabs() {
             let mut editor = EditorInstance::new(get_populated_termios());
-            editor.editor_mode = EditorMode::Insert;
-
-            let dir = tempdir().unwrap();
-            let file_path = dir.path().join("test.txt");
-            let mut file = File::create(&file_path).unwrap();
-
-            // No lines
-            editor.open(file_path.to_str().unwrap());
-            assert_eq!(editor.lines.len(), 0);
 
-            // 2 lines
-            writeln!(file, "Line 1").unwrap();
-            writeln!(file, "Line 2").unwrap();
-
-            editor.open(file_path.to_str().unwrap());
-
-            assert_eq!(editor.lines.len(), 2);
-            assert_eq!(editor.lines[0].text, "Line 1");
-            assert_eq!(editor.lines[1].text, "Line 2");
-
-            assert_eq!(
-                editor.file.as_ref().unwrap().path,
-                file_path.to_str().unwrap()
-            );
-            assert_eq!(editor.file.as_ref().unwrap().name, "test.txt");
+            push_line(&mut editor, "abcdef");
 
-            assert!(editor.syntax.is_none());
+            editor.cursor_position.y = 0;
 
-            assert_eq!(editor.editor_mode, EditorMode::Normal);
+            assert_eq!(editor.cursor_x_to_render_x(0), 0);
+            assert_eq!(editor.cursor_x_to_render_x(3), 3);
+            assert_eq!(editor.cursor_x_to_render_x(6), 6);
         }
 
         #[test]
-        fn test_open_file_with_syntax_detection() {
+        fn test_cursor_x_to_render_x_with_tabs() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            let dir = tempdir().unwrap();
-            let file_path = dir.path().join("test.rs");
-            let mut file = File::create(&file_path).unwrap();
-            writeln!(file, "fn main() {{}}").unwrap();
-
-            editor.open(file_path.to_str().unwrap());
+            push_line(&mut editor, "ab\tcd");
 
-            assert_eq!(editor.lines.len(), 1);
-            assert_eq!(editor.lines[0].text, "fn main() {}");
+            editor.cursor_position.y = 0;
 
-            assert!(editor.syntax.is_some());
-            assert_eq!(editor.syntax.unwrap().file_type, "Rust");
+            assert_eq!(editor.cursor_x_to_render_x(0), 0);
+            assert_eq!(editor.cursor_x_to_render_x(2), 2);
+            assert_eq!(editor.cursor_x_to_render_x(3), 4); // Tab expands to render width 4
+            assert_eq!(editor.cursor_x_to_render_x(5), 6); // After tab, next positions are linear
         }
 
         #[test]
-        fn test_open_non_existent_file() {
-            let editor = EditorInstance::new(get_populated_termios());
-
-            let non_existent_file = "/non/existent/file.txt";
-
-            let editor_arc = Arc::new(Mutex::new(editor));
+        fn test_cursor_x_to_render_x_empty_line() {
+            let mut editor = EditorInstance::new(get_populated_termios());
 
-            std::panic::set_hook(Box::new(|_| {})); // Prevent panic from printing with --nocapture flag
+            push_line(&mut editor, "");
 
-            assert!(std::panic::catch_unwind(|| {
-                let mut editor_ref = editor_arc.lock().unwrap();
-                editor_ref.open(non_existent_file);
-            })
-            .is_err());
+            assert_eq!(editor.cursor_x_to_render_x(0), 0);
+            assert_eq!(editor.cursor_x_to_render_x(1), 1);
         }
     }
 
-    mod test_process_keypress {
+    mod test_render_x_to_cursor_x {
         use super::*;
 
         #[test]
-        fn test_cursor_movement() {
+        fn test_render_x_to_cursor_x_no_tabs() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.lines.push(Line {
-                text: String::from("Hello"),
-                render: String::from("Hello"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+            push_line(&mut editor, "abcdef");
 
             editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
 
-            // Up on first line
-            editor.process_key(Key::Custom(EditorKey::ArrowUp));
-            assert_eq!(editor.cursor_position.y, 0);
-            assert_eq!(
-                editor.cursor_position.x as usize,
-                editor.num_columns_for_line_number
-            );
-
-            // Left
-            editor.cursor_position.x = 5;
-            editor.process_key(Key::Custom(EditorKey::ArrowLeft));
-            assert_eq!(editor.cursor_position.x, 4);
-
-            // Right
-            editor.cursor_position.x = 4;
-            editor.process_key(Key::Custom(EditorKey::ArrowRight));
-            assert_eq!(editor.cursor_position.x, 5);
+            assert_eq!(editor.render_x_to_cursor_x(0), 0);
+            assert_eq!(editor.render_x_to_cursor_x(3), 3);
+            assert_eq!(editor.render_x_to_cursor_x(6), 6);
+        }
 
-            // Down
-            editor.lines.push(Line {
-                text: String::from("World"),
-                render: String::from("World"),
-                highlight: vec![],
-                index: 1,
-                has_open_multiline_comment: false,
-            });
+        #[test]
+        fn test_render_x_to_cursor_x_with_tabs() {
+            let mut editor = EditorInstance::new(get_populated_termios());
 
-            assert_eq!(editor.cursor_position.y, 0);
-            editor.process_key(Key::Custom(EditorKey::ArrowDown));
-            assert_eq!(editor.cursor_position.y, 1);
-            assert_eq!(editor.cursor_position.x, 5);
+            push_line(&mut editor, "ab\tcd");
 
-            // Down on last line
-            editor.process_key(Key::Custom(EditorKey::ArrowDown));
-            assert_eq!(editor.cursor_position.y, 2);
+            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
 
-            // Up
-            editor.cursor_position.y = 1;
-            editor.process_key(Key::Custom(EditorKey::ArrowUp));
-            assert_eq!(editor.cursor_position.y, 0);
-            assert_eq!(editor.cursor_position.x, 0);
+            assert_eq!(editor.render_x_to_cursor_x(0), 0);
+            assert_eq!(editor.render_x_to_cursor_x(2), 2);
+            assert_eq!(editor.render_x_to_cursor_x(4), 3);
+            assert_eq!(editor.render_x_to_cursor_x(6), 5);
+        }
 
-            // Right at end of line
-            editor.cursor_position.x = 5 + editor.num_columns_for_line_number as u16;
-            editor.process_key(Key::Custom(EditorKey::ArrowRight));
-            assert_eq!(
-                editor.cursor_position.x as usize,
-                editor.num_columns_for_line_number
-            );
-            assert_eq!(editor.cursor_position.y, 1);
+        #[test]
+        fn test_render_x_to_cursor_x_empty_line() {
+            let mut editor = EditorInstance::new(get_populated_termios());
 
-            // Left at start of line
-            editor.cursor_position.x = editor.num_columns_for_line_number as u16;
-            editor.process_key(Key::Custom(EditorKey::ArrowLeft));
-            assert_eq!(
-                editor.cursor_position.x as usize,
-                5 + editor.num_columns_for_line_number
-            );
-            assert_eq!(editor.cursor_position.y, 0);
+            push_line(&mut editor, "");
 
-            // Home
-            editor.process_key(Key::Custom(EditorKey::Home));
-            assert_eq!(
-                editor.cursor_position.x as usize,
-                editor.num_columns_for_line_number
-            );
+            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
 
-            // End
-            editor.process_key(Key::Custom(EditorKey::End));
-            assert_eq!(
-                editor.cursor_position.x as usize,
-                5 + editor.num_columns_for_line_number
-            );
+            assert_eq!(editor.render_x_to_cursor_x(0), 0);
+            // Cursor can not move to index 1 as there are no characters
         }
 
         #[test]
-        fn test_delete() {
+        fn test_render_x_to_cursor_x_out_of_bounds() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.lines.push(Line {
-                text: String::from("Hello"),
-                render: String::from("Hello"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+            push_line(&mut editor, "abc");
 
             editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
 
-            // Delete
-            editor.cursor_position.x = 2 + editor.num_columns_for_line_number as u16;
-            editor.process_key(Key::Custom(EditorKey::Delete));
-            assert_eq!(editor.lines[0].text, "Helo");
-
-            // Backspace
-            editor.process_key(Key::Custom(EditorKey::Backspace));
-            assert_eq!(editor.lines[0].text, "Hlo");
-
-            // Backspace at start of line
-            editor.lines.push(Line {
-                text: String::from("World"),
-                render: String::from("World"),
-                highlight: vec![],
-                index: 1,
-                has_open_multiline_comment: false,
-            });
+            assert_eq!(editor.render_x_to_cursor_x(10), 3);
+        }
+    }
 
-            editor.cursor_position.x = editor.num_columns_for_line_number as u16;
-            editor.cursor_position.y = 1;
+    mod test_scroll {
+        use super::*;
 
-            editor.process_key(Key::Custom(EditorKey::Backspace));
+        #[test]
+        fn test_scroll_no_scroll_needed() {
+            let mut editor = EditorInstance::new(get_populated_termios());
 
-            assert_eq!(editor.lines.len(), 1);
-            assert_eq!(editor.lines[0].text, "HloWorld");
+            editor.window_size = WindowSize {
+                rows: 10,
+                columns: 20,
+            };
+            set_lines(&mut editor, &["This is a test."]);
+            editor.cursor_position = CursorPosition {
+                x: 4,
+                y: 2,
+                render_x: 4,
+            };
 
-            // Delete at end of line
-            editor.lines.push(Line {
-                text: String::from("World"),
-                render: String::from("World"),
-                highlight: vec![],
-                index: 1,
-                has_open_multiline_comment: false,
-            });
+            editor.scroll();
 
-            assert_eq!(editor.cursor_position.y, 0);
-            editor.cursor_position.x =
-                (editor.num_columns_for_line_number + editor.lines[0].text.chars().count()) as u16;
-            editor.process_key(Key::Custom(EditorKey::Delete));
-            assert_eq!(editor.lines[0].text, "HloWorldWorld");
+            assert_eq!(editor.line_scrolled_to, 0);
+            assert_eq!(editor.column_scrolled_to, 0);
         }
 
         #[test]
-        fn test_insert_character() {
+        fn test_scroll_vertical_scrolling() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            assert_eq!(editor.editor_mode, EditorMode::Insert);
-            editor.process_key(Key::U8(b'a'));
-            assert_eq!(editor.lines[0].text, "a");
+            editor.window_size = WindowSize {
+                rows: 3,
+                columns: 20,
+            };
+            set_lines(&mut editor, &["Line of text"]);
+            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            editor.cursor_position = CursorPosition {
+                x: editor.num_columns_for_line_number as u16,
+                y: 5,
+                render_x: editor.num_columns_for_line_number as u16,
+            };
+
+            editor.scroll();
+
+            assert_eq!(editor.line_scrolled_to, 3);
+            assert_eq!(editor.column_scrolled_to, 0);
         }
 
         #[test]
-        fn test_use_vim_movement_character_in_normal_mode() {
+        fn test_scroll_horizontal_scrolling() {
             let mut editor = EditorInstance::new(get_populated_termios());
-            editor.editor_mode = EditorMode::Normal;
-
-            editor.lines.push(Line {
-                text: String::from("Hello"),
-                render: String::from("Hello"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
 
+            editor.window_size = WindowSize {
+                rows: 10,
+                columns: 10,
+            };
+            push_line(&mut editor, "This is a very long line of text.");
             editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            editor.cursor_position = CursorPosition {
+                x: 10,
+                y: 0,
+                render_x: 10,
+            };
 
-            // Right
-            editor.process_key(Key::U8(b'l'));
-            assert_eq!(editor.lines[0].text, "Hello");
-            assert_eq!(
-                editor.cursor_position.x as usize,
-                editor.num_columns_for_line_number + 1
-            );
+            editor.scroll();
 
-            // Left
-            editor.process_key(Key::U8(b'h'));
-            assert_eq!(editor.lines[0].text, "Hello");
-            assert_eq!(
-                editor.cursor_position.x as usize,
-                editor.num_columns_for_line_number
-            );
+            assert_eq!(editor.line_scrolled_to, 0);
+            assert_eq!(editor.column_scrolled_to, 1);
+        }
 
-            // Down
-            editor.lines.push(Line {
-                text: String::from("World"),
-                render: String::from("World"),
-                highlight: vec![],
-                index: 1,
-                has_open_multiline_comment: false,
-            });
+        #[test]
+        fn test_scroll_keeps_scroll_margin() {
+            let lines: Vec<String> = (0..20).map(|line| line.to_string()).collect();
+            let mut editor =
+                editor_with_lines(&lines.iter().map(String::as_str).collect::<Vec<_>>());
+            editor.window_size = WindowSize {
+                rows: 10,
+                columns: 20,
+            };
+            editor.run_command("set scrolloff=3");
 
-            editor.process_key(Key::U8(b'j'));
-            assert_eq!(editor.lines[0].text, "Hello");
-            assert_eq!(editor.cursor_position.y, 1);
+            editor.set_cursor_from_text_position(TextPosition { line: 7, column: 0 });
+            editor.scroll();
+            assert_eq!(editor.line_scrolled_to, 1);
 
-            // Up
-            editor.process_key(Key::U8(b'k'));
-            assert_eq!(editor.lines[1].text, "World");
-            assert_eq!(editor.cursor_position.y, 0);
-        }
+            editor.set_cursor_from_text_position(TextPosition { line: 4, column: 0 });
+            editor.scroll();
+            assert_eq!(editor.line_scrolled_to, 1);
 
-        #[test]
-        fn test_switch_editor_mode() {
-            let mut editor = EditorInstance::new(get_populated_termios());
+            editor.set_cursor_from_text_position(TextPosition { line: 3, column: 0 });
+            editor.scroll();
+            assert_eq!(editor.line_scrolled_to, 0);
 
-            assert_eq!(editor.editor_mode, EditorMode::Insert);
-            editor.process_key(Key::U8(b'\x1b'));
-            assert_eq!(editor.editor_mode, EditorMode::Normal);
-            editor.process_key(Key::U8(b'i'));
-            assert_eq!(editor.editor_mode, EditorMode::Insert);
+            // There is nothing to keep in view past the last line
+            editor.set_cursor_from_text_position(TextPosition {
+                line: 19,
+                column: 0,
+            });
+            editor.scroll();
+            assert_eq!(editor.line_scrolled_to, 10);
+
+            // Scrolling with the wheel moves the cursor away from the edge
+            editor.scroll_by_lines(-5);
+            assert_eq!(editor.line_scrolled_to, 5);
+            assert_eq!(editor.cursor_position.y, 11);
         }
     }
 
-    mod test_move_cursor {
+    mod test_insert_character_into_line {
         use super::*;
 
         #[test]
-        fn test_move_cursor_left() {
+        fn test_insert_character_into_empty_line() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.lines.push(Line {
-                text: "Hello, World!".to_string(),
-                render: "Hello, World!".to_string(),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
-
+            push_line(&mut editor, "");
             editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            editor.cursor_position = CursorPosition {
+                x: editor.num_columns_for_line_number as u16,
+                y: 0,
+                render_x: editor.num_columns_for_line_number as u16,
+            };
 
-            editor.cursor_position.x = 5;
-            editor.move_cursor(CursorMovement::Left);
-            assert_eq!(editor.cursor_position.x, 4);
-
-            // Move left at start of line
-            editor.cursor_position.x = editor.num_columns_for_line_number as u16;
-
-            editor.lines.insert(
-                0,
-                Line {
-                    text: "Prev Line".to_string(),
-                    render: "Prev Line".to_string(),
-                    highlight: vec![],
-                    index: 0,
-                    has_open_multiline_comment: false,
-                },
-            );
+            editor.insert_character_into_line('a');
 
-            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
-            editor.cursor_position.y = 1;
-            editor.move_cursor(CursorMovement::Left);
-            assert_eq!(editor.cursor_position.y, 0);
-            assert_eq!(
-                editor.cursor_position.x,
-                9 + editor.num_columns_for_line_number as u16
-            );
+            assert_eq!(editor.buffer.line(0), "a");
+            assert_eq!(editor.rows[0].render, "a");
         }
 
         #[test]
-        fn test_move_cursor_right() {
+        fn test_insert_character_into_non_empty_line() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.lines.push(Line {
-                text: "Hello".to_string(),
-                render: "Hello".to_string(),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
-
+            push_line(&mut editor, "Hello");
             editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
 
-            editor.cursor_position.x = editor.num_columns_for_line_number as u16;
-            editor.move_cursor(CursorMovement::Right);
-            assert_eq!(
-                editor.cursor_position.x,
-                editor.num_columns_for_line_number as u16 + 1
-            );
+            editor.cursor_position = CursorPosition {
+                x: 2 + editor.num_columns_for_line_number as u16,
+                y: 0,
+                render_x: 2 + editor.num_columns_for_line_number as u16,
+            };
 
-            // Move right at end of line
-            editor.cursor_position.x = 5 + editor.num_columns_for_line_number as u16;
-            editor.move_cursor(CursorMovement::Right);
-            assert_eq!(editor.cursor_position.y, 1);
-            assert_eq!(editor.cursor_position.x, 0);
+            editor.insert_character_into_line('a');
+
+            assert_eq!(editor.buffer.line(0), "Heallo");
+            assert_eq!(editor.rows[0].render, "Heallo");
         }
 
         #[test]
-        fn test_move_cursor_up() {
+        fn test_insert_character_at_end_of_line() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.lines.push(Line {
-                text: "Line 1".to_string(),
-                render: "Line 1".to_string(),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
-
-            editor.lines.push(Line {
-                text: "Line 2".to_string(),
-                render: "Line 2".to_string(),
-                highlight: vec![],
-                index: 1,
-                has_open_multiline_comment: false,
-            });
-
+            push_line(&mut editor, "Hello");
             editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            editor.cursor_position = CursorPosition {
+                x: 5 + editor.num_columns_for_line_number as u16,
+                y: 0,
+                render_x: 5 + editor.num_columns_for_line_number as u16,
+            };
 
-            editor.cursor_position.y = 1;
-            editor.move_cursor(CursorMovement::Up);
-            assert_eq!(editor.cursor_position.y, 0);
+            editor.insert_character_into_line('!');
+
+            assert_eq!(editor.buffer.line(0), "Hello!");
+            assert_eq!(editor.rows[0].render, "Hello!");
         }
 
         #[test]
-        fn test_move_cursor_down() {
+        fn test_insert_character_updates_render_text() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.lines.push(Line {
-                text: "Line 1".to_string(),
-                render: "Line 1".to_string(),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
-
-            editor.lines.push(Line {
-                text: "Line 2".to_string(),
-                render: "Line 2".to_string(),
-                highlight: vec![],
-                index: 1,
-                has_open_multiline_comment: false,
-            });
-
+            push_line(&mut editor, "Tab\t");
             editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            editor.cursor_position = CursorPosition {
+                x: 3 + editor.num_columns_for_line_number as u16,
+                y: 0,
+                render_x: 6 + editor.num_columns_for_line_number as u16,
+            };
 
-            editor.cursor_position.y = 0;
-            editor.move_cursor(CursorMovement::Down);
-            assert_eq!(editor.cursor_position.y, 1);
+            editor.insert_character('s');
+
+            assert_eq!(editor.buffer.line(0), "Tabs\t");
+            assert_eq!(editor.rows[0].render, "Tabs    ");
+            assert!(editor.edited);
         }
 
         #[test]
-        fn test_cursor_stays_within_bounds() {
+        fn test_insert_character_updates_highlight() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.lines.push(Line {
-                text: "".to_string(),
-                render: "".to_string(),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
-
+            push_line(&mut editor, "Hello");
             editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            editor.cursor_position = CursorPosition {
+                x: 5 + editor.num_columns_for_line_number as u16,
+                y: 0,
+                render_x: 5 + editor.num_columns_for_line_number as u16,
+            };
 
-            editor.cursor_position.x = editor.num_columns_for_line_number as u16;
-            editor.move_cursor(CursorMovement::Left);
-            assert_eq!(
-                editor.cursor_position.x,
-                editor.num_columns_for_line_number as u16
-            );
-
-            editor.cursor_position.y = 0;
-            editor.move_cursor(CursorMovement::Up);
-            assert_eq!(editor.cursor_position.y, 0);
+            editor.insert_character_into_line('!');
 
-            editor.cursor_position.y = 1;
-            editor.move_cursor(CursorMovement::Down);
-            assert_eq!(editor.cursor_position.y, 1);
+            assert_eq!(editor.buffer.line(0), "Hello!");
+            assert_eq!(editor.rows[0].render, "Hello!");
+            assert_eq!(editor.rows[0].highlight.len(), 6);
         }
     }
 
-    mod test_cursor_x_to_render_x {
+    mod test_insert_character {
         use super::*;
 
         #[test]
-        fn test_cursor_x_to_render_x_no_tabs() {
+        fn test_insert_character_into_new_line() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.lines.push(Line {
-                text: String::from("abcdef"),
-                render: String::from("abcdef"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
-
-            editor.cursor_position.y = 0;
+            editor.cursor_position = CursorPosition {
+                x: 0,
+                y: 0,
+                render_x: 0,
+            };
+            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            set_lines(&mut editor, &[]);
+            editor.insert_character('a');
 
-            assert_eq!(editor.cursor_x_to_render_x(0), 0);
-            assert_eq!(editor.cursor_x_to_render_x(3), 3);
-            assert_eq!(editor.cursor_x_to_render_x(6), 6);
+            assert_eq!(editor.buffer.line_count(), 1);
+            assert_eq!(editor.buffer.line(0), "a");
+            assert_eq!(
+                editor.cursor_position.x,
+                1 + editor.num_columns_for_line_number as u16
+            );
+            assert!(editor.edited);
         }
 
         #[test]
-        fn test_cursor_x_to_render_x_with_tabs() {
+        fn test_insert_character_into_existing_line() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.lines.push(Line {
-                text: String::from("ab\tcd"),
-                render: String::from("ab    cd"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+            push_line(&mut editor, "Hello");
+            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            editor.cursor_position = CursorPosition {
+                x: 5 + editor.num_columns_for_line_number as u16,
+                y: 0,
+                render_x: 5 + editor.num_columns_for_line_number as u16,
+            };
 
-            editor.cursor_position.y = 0;
+            editor.insert_character('!');
 
-            assert_eq!(editor.cursor_x_to_render_x(0), 0);
-            assert_eq!(editor.cursor_x_to_render_x(2), 2);
-            assert_eq!(editor.cursor_x_to_render_x(3), 4); // Tab expands to render width 4
-            assert_eq!(editor.cursor_x_to_render_x(5), 6); // After tab, next positions are linear
+            assert_eq!(editor.buffer.line_count(), 1);
+            assert_eq!(editor.buffer.line(0), "Hello!");
+            assert_eq!(
+                editor.cursor_position.x,
+                6 + editor.num_columns_for_line_number as u16
+            );
+            assert!(editor.edited);
         }
 
         #[test]
-        fn test_cursor_x_to_render_x_empty_line() {
+        fn test_insert_character_with_new_line_creation() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.lines.push(Line {
-                text: String::from(""),
-                render: String::from(""),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+            push_line(&mut editor, "Line 1");
+            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            editor.cursor_position = CursorPosition {
+                x: editor.num_columns_for_line_number as u16,
+                y: 1,
+                render_x: editor.num_columns_for_line_number as u16,
+            }; // Below the existing line
 
-            assert_eq!(editor.cursor_x_to_render_x(0), 0);
-            assert_eq!(editor.cursor_x_to_render_x(1), 1);
+            editor.insert_character('A');
+
+            assert_eq!(editor.buffer.line_count(), 2); // A new line is created
+            assert_eq!(editor.buffer.line(1), "A");
+            assert_eq!(
+                editor.cursor_position.x,
+                1 + editor.num_columns_for_line_number as u16
+            );
+            assert!(editor.edited);
         }
     }
 
-    mod test_render_x_to_cursor_x {
+    mod test_delete_character_from_line {
         use super::*;
 
         #[test]
-        fn test_render_x_to_cursor_x_no_tabs() {
+        fn test_delete_character_from_line_end() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.lines.push(Line {
-                text: String::from("abcdef"),
-                render: String::from("abcdef"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+            push_line(&mut editor, "Test");
+            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+
+            editor.cursor_position = CursorPosition {
+                x: 4 + editor.num_columns_for_line_number as u16,
+                y: 0,
+                render_x: 4 + editor.num_columns_for_line_number as u16,
+            };
+
+            editor.delete_character_from_line();
+
+            assert_eq!(editor.buffer.line(0), "Tes");
+            assert!(editor.rows[0].render.contains("Tes"));
+        }
+
+        #[test]
+        fn test_delete_character_from_line_middle() {
+            let mut editor = EditorInstance::new(get_populated_termios());
 
+            push_line(&mut editor, "Hello World");
             editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
 
-            assert_eq!(editor.render_x_to_cursor_x(0), 0);
-            assert_eq!(editor.render_x_to_cursor_x(3), 3);
-            assert_eq!(editor.render_x_to_cursor_x(6), 6);
+            editor.cursor_position = CursorPosition {
+                x: 7 + editor.num_columns_for_line_number as u16,
+                y: 0,
+                render_x: 7 + editor.num_columns_for_line_number as u16,
+            };
+
+            editor.delete_character_from_line();
+
+            assert_eq!(editor.buffer.line(0), "Hello orld");
+            assert!(editor.rows[0].render.contains("Hello orld"));
         }
 
         #[test]
-        fn test_render_x_to_cursor_x_with_tabs() {
+        fn test_delete_character_from_line_start() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.lines.push(Line {
-                text: String::from("ab\tcd"),
-                render: String::from("ab  cd"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+            push_line(&mut editor, "Hello");
+            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+
+            editor.cursor_position = CursorPosition {
+                x: 1 + editor.num_columns_for_line_number as u16,
+                y: 0,
+                render_x: 1 + editor.num_columns_for_line_number as u16,
+            };
+
+            editor.delete_character_from_line();
+
+            assert_eq!(editor.buffer.line(0), "ello");
+            assert!(editor.rows[0].render.contains("ello"));
+        }
+
+        #[test]
+        #[should_panic(expected = "attempt to subtract with overflow")]
+        fn test_delete_character_from_line_panic_out_of_bounds() {
+            let mut editor = EditorInstance::new(get_populated_termios());
 
+            push_line(&mut editor, "Test");
             editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
 
-            assert_eq!(editor.render_x_to_cursor_x(0), 0);
-            assert_eq!(editor.render_x_to_cursor_x(2), 2);
-            assert_eq!(editor.render_x_to_cursor_x(4), 3);
-            assert_eq!(editor.render_x_to_cursor_x(6), 5);
+            editor.cursor_position = CursorPosition {
+                x: editor.num_columns_for_line_number as u16,
+                y: 0,
+                render_x: editor.num_columns_for_line_number as u16,
+            };
+
+            editor.delete_character_from_line();
         }
+    }
+
+    mod test_delete_character {
+        use super::*;
 
         #[test]
-        fn test_render_x_to_cursor_x_empty_line() {
+        fn test_delete_character_middle_of_line() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.lines.push(Line {
-                text: String::from(""),
-                render: String::from(""),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+            push_line(&mut editor, "Hello World");
 
             editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
 
-            assert_eq!(editor.render_x_to_cursor_x(0), 0);
-            // Cursor can not move to index 1 as there are no characters
+            editor.cursor_position = CursorPosition {
+                x: 7 + editor.num_columns_for_line_number as u16,
+                y: 0,
+                render_x: 7 + editor.num_columns_for_line_number as u16,
+            };
+
+            editor.delete_character();
+
+            assert_eq!(editor.buffer.line(0), "Hello orld");
+            assert_eq!(
+                editor.cursor_position.x,
+                6 + editor.num_columns_for_line_number as u16
+            );
+            assert!(editor.edited);
         }
 
         #[test]
-        fn test_render_x_to_cursor_x_out_of_bounds() {
+        fn test_delete_character_start_of_line() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.lines.push(Line {
-                text: String::from("abc"),
-                render: String::from("abc"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+            push_line(&mut editor, "First line");
+            push_line(&mut editor, "Second line");
 
             editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
 
-            assert_eq!(editor.render_x_to_cursor_x(10), 3);
-        }
-    }
-
-    mod test_scroll {
-        use super::*;
-
-        #[test]
-        fn test_scroll_no_scroll_needed() {
-            let mut editor = EditorInstance::new(get_populated_termios());
-
-            editor.window_size = WindowSize {
-                rows: 10,
-                columns: 20,
-            };
-            editor.lines = vec![
-                Line {
-                    text: String::from("This is a test."),
-                    render: String::from("This is a test."),
-                    highlight: vec![],
-                    index: 0,
-                    has_open_multiline_comment: false,
-                };
-                5
-            ];
             editor.cursor_position = CursorPosition {
-                x: 4,
-                y: 2,
-                render_x: 4,
+                x: editor.num_columns_for_line_number as u16,
+                y: 1,
+                render_x: editor.num_columns_for_line_number as u16,
             };
 
-            editor.scroll();
+            editor.delete_character();
 
-            assert_eq!(editor.line_scrolled_to, 0);
-            assert_eq!(editor.column_scrolled_to, 0);
+            assert_eq!(editor.buffer.line_count(), 1);
+            assert_eq!(editor.buffer.line(0), "First lineSecond line");
+            assert_eq!(
+                editor.cursor_position.x,
+                10 + editor.num_columns_for_line_number as u16
+            );
+            assert_eq!(editor.cursor_position.y, 0);
+            assert!(editor.edited);
         }
 
         #[test]
-        fn test_scroll_vertical_scrolling() {
+        fn test_delete_character_no_op_past_last_line() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.window_size = WindowSize {
-                rows: 3,
-                columns: 20,
-            };
-            editor.lines = vec![
-                Line {
-                    text: String::from("Line of text"),
-                    render: String::from("Line of text"),
-                    highlight: vec![],
-                    index: 0,
-                    has_open_multiline_comment: false,
-                };
-                10
-            ];
+            push_line(&mut editor, "Only line");
+
             editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+
             editor.cursor_position = CursorPosition {
                 x: editor.num_columns_for_line_number as u16,
-                y: 5,
+                y: 1,
                 render_x: editor.num_columns_for_line_number as u16,
             };
 
-            editor.scroll();
+            editor.delete_character();
 
-            assert_eq!(editor.line_scrolled_to, 3);
-            assert_eq!(editor.column_scrolled_to, 0);
+            assert_eq!(editor.buffer.line_count(), 1);
+            assert_eq!(editor.buffer.line(0), "Only line");
+            assert_eq!(
+                editor.cursor_position.x,
+                editor.num_columns_for_line_number as u16
+            );
+            assert_eq!(editor.cursor_position.y, 1);
+            assert!(!editor.edited);
         }
 
         #[test]
-        fn test_scroll_horizontal_scrolling() {
+        fn test_delete_character_no_op_start_of_first_line() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.window_size = WindowSize {
-                rows: 10,
-                columns: 10,
-            };
-            editor.lines.push(Line {
-                text: String::from("This is a very long line of text."),
-                render: String::from("This is a very long line of text."),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+            push_line(&mut editor, "Only line");
+
             editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+
             editor.cursor_position = CursorPosition {
-                x: 10,
+                x: editor.num_columns_for_line_number as u16,
                 y: 0,
-                render_x: 10,
+                render_x: editor.num_columns_for_line_number as u16,
             };
 
-            editor.scroll();
+            editor.delete_character();
 
-            assert_eq!(editor.line_scrolled_to, 0);
-            assert_eq!(editor.column_scrolled_to, 1);
+            assert_eq!(editor.buffer.line_count(), 1);
+            assert_eq!(editor.buffer.line(0), "Only line");
+            assert_eq!(
+                editor.cursor_position.x,
+                editor.num_columns_for_line_number as u16
+            );
+            assert_eq!(editor.cursor_position.y, 0);
+            assert!(!editor.edited);
         }
     }
 
-    mod test_insert_character_into_line {
+    mod test_insert_line {
         use super::*;
 
         #[test]
-        fn test_insert_character_into_empty_line() {
+        fn test_insert_line_at_line_start() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.lines.push(Line {
-                text: String::new(),
-                render: String::new(),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+            push_line(&mut editor, "Existing line");
+
             editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+
             editor.cursor_position = CursorPosition {
                 x: editor.num_columns_for_line_number as u16,
                 y: 0,
                 render_x: editor.num_columns_for_line_number as u16,
             };
 
-            editor.insert_character_into_line('a');
+            editor.insert_line();
 
-            assert_eq!(editor.lines[0].text, "a");
-            assert_eq!(editor.lines[0].render, "a");
+            assert_eq!(editor.buffer.line_count(), 2);
+            assert_eq!(editor.buffer.line(0), "");
+            assert_eq!(editor.buffer.line(1), "Existing line");
+            assert_eq!(editor.cursor_position.y, 1);
+            assert_eq!(
+                editor.cursor_position.x,
+                editor.num_columns_for_line_number as u16
+            );
+            assert!(editor.edited);
         }
 
         #[test]
-        fn test_insert_character_into_non_empty_line() {
+        fn test_insert_line_in_middle_of_line() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.lines.push(Line {
-                text: String::from("Hello"),
-                render: String::from("Hello"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+            push_line(&mut editor, "Hello, World");
+
             editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
 
             editor.cursor_position = CursorPosition {
-                x: 2 + editor.num_columns_for_line_number as u16,
+                x: 7 + editor.num_columns_for_line_number as u16,
                 y: 0,
-                render_x: 2 + editor.num_columns_for_line_number as u16,
+                render_x: 7 + editor.num_columns_for_line_number as u16,
             };
 
-            editor.insert_character_into_line('a');
+            editor.insert_line();
 
-            assert_eq!(editor.lines[0].text, "Heallo");
-            assert_eq!(editor.lines[0].render, "Heallo");
+            assert_eq!(editor.buffer.line_count(), 2);
+            assert_eq!(editor.buffer.line(0), "Hello, ");
+            assert_eq!(editor.buffer.line(1), "World");
+            assert_eq!(editor.cursor_position.y, 1);
+            assert_eq!(
+                editor.cursor_position.x,
+                editor.num_columns_for_line_number as u16
+            );
+            assert!(editor.edited);
         }
 
         #[test]
-        fn test_insert_character_at_end_of_line() {
+        fn test_insert_line_at_end_of_line() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.lines.push(Line {
-                text: String::from("Hello"),
-                render: String::from("Hello"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+            push_line(&mut editor, "Hello");
+
             editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+
             editor.cursor_position = CursorPosition {
                 x: 5 + editor.num_columns_for_line_number as u16,
                 y: 0,
                 render_x: 5 + editor.num_columns_for_line_number as u16,
             };
 
-            editor.insert_character_into_line('!');
+            editor.insert_line();
 
-            assert_eq!(editor.lines[0].text, "Hello!");
-            assert_eq!(editor.lines[0].render, "Hello!");
+            assert_eq!(editor.buffer.line_count(), 2);
+            assert_eq!(editor.buffer.line(0), "Hello");
+            assert_eq!(editor.buffer.line(1), "");
+            assert_eq!(editor.cursor_position.y, 1);
+            assert_eq!(
+                editor.cursor_position.x,
+                editor.num_columns_for_line_number as u16
+            );
+            assert!(editor.edited);
         }
 
         #[test]
-        fn test_insert_character_updates_render_text() {
+        fn test_insert_line_empty_editor() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.lines.push(Line {
-                text: String::from("Tab\t"),
-                render: String::from("Tab    "),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
-            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            set_lines(&mut editor, &[]);
+
             editor.cursor_position = CursorPosition {
-                x: 3 + editor.num_columns_for_line_number as u16,
+                x: editor.num_columns_for_line_number as u16,
                 y: 0,
-                render_x: 6 + editor.num_columns_for_line_number as u16,
+                render_x: 0,
             };
 
-            editor.insert_character('s');
+            editor.insert_line();
 
-            assert_eq!(editor.lines[0].text, "Tabs\t");
-            assert_eq!(editor.lines[0].render, "Tabs    ");
+            assert_eq!(editor.buffer.line_count(), 1);
+            assert_eq!(editor.buffer.line(0), "");
+            assert_eq!(editor.cursor_position.y, 1);
+            assert_eq!(
+                editor.cursor_position.x,
+                editor.num_columns_for_line_number as u16
+            );
             assert!(editor.edited);
         }
+    }
+
+    mod test_find_text_callback {
+        use super::*;
 
         #[test]
-        fn test_insert_character_updates_highlight() {
+        fn test_find_text_callback_forward_match() {
             let mut editor = EditorInstance::new(get_populated_termios());
 
-            editor.lines.push(Line {
-                text: String::from("Hello"),
-                render: String::from("Hello"),
-                highlight: vec![HighlightType::Normal; 5],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
-            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
-            editor.cursor_position = CursorPosition {
-                x: 5 + editor.num_columns_for_line_number as u16,
-                y: 0,
-                render_x: 5 + editor.num_columns_for_line_number as u16,
-            };
+            set_lines(&mut editor, &["This is a test", "Another test line"]);
 
-            editor.insert_character_into_line('!');
+            editor.find_text_callback("test", Key::Custom(EditorKey::ArrowDown));
 
-            assert_eq!(editor.lines[0].text, "Hello!");
-            assert_eq!(editor.lines[0].render, "Hello!");
-            assert_eq!(editor.lines[0].highlight.len(), 6);
+            assert_eq!(
+                editor.previous_search_match,
+                Some(TextPosition {
+                    line: 0,
+                    column: 10
+                })
+            );
+            assert_eq!(editor.cursor_position.y, 0);
+            assert_eq!(
+                editor.cursor_position.x,
+                10 + editor.num_columns_for_line_number as u16
+            );
+            assert_eq!(
+                editor.rows[0].highlight[10..14],
+                vec![
+                    HighlightType::SearchMatch,
+                    HighlightType::SearchMatch,
+                    HighlightType::SearchMatch,
+                    HighlightType::SearchMatch
+                ]
+            );
         }
-    }
 
-    mod test_insert_character {
-        use super::*;
+        #[test]
+        fn test_find_text_callback_backward_match() {
+            let mut editor = EditorInstance::new(get_populated_termios());
+            set_lines(&mut editor, &["This is a test", "Another test line"]);
+
+            editor.previous_search_match = Some(TextPosition { line: 1, column: 8 });
+
+            editor.find_text_callback("test", Key::Custom(EditorKey::ArrowUp));
+
+            assert_eq!(
+                editor.previous_search_match,
+                Some(TextPosition {
+                    line: 0,
+                    column: 10
+                })
+            );
+            assert_eq!(editor.cursor_position.y, 0);
+            assert_eq!(
+                editor.cursor_position.x,
+                10 + editor.num_columns_for_line_number as u16
+            );
+            assert_eq!(
+                editor.rows[0].highlight[10..14],
+                vec![
+                    HighlightType::SearchMatch,
+                    HighlightType::SearchMatch,
+                    HighlightType::SearchMatch,
+                    HighlightType::SearchMatch
+                ]
+            );
+        }
+
+        #[test]
+        fn test_find_text_callback_regex_maps_to_text_columns() {
+            let mut editor = editor_with_lines(&["\tfoo Bar"]);
+
+            editor.find_text_callback("\\cb.r", Key::U8(b'r'));
+
+            assert_eq!(editor.get_text_position().column, 5);
+
+            // The tab renders as four spaces
+            let highlighted: Vec<usize> = editor.rows[0]
+                .highlight
+                .iter()
+                .enumerate()
+                .filter(|(_, highlight)| **highlight == HighlightType::SearchMatch)
+                .map(|(index, _)| index)
+                .collect();
+
+            assert_eq!(highlighted, vec![8, 9, 10]);
+        }
+
+        #[test]
+        fn test_find_text_callback_no_match() {
+            let mut editor = EditorInstance::new(get_populated_termios());
+            set_lines(&mut editor, &["This is a test", "Another test line"]);
+
+            editor.find_text_callback("nonexistent", Key::Custom(EditorKey::ArrowDown));
+
+            assert_eq!(editor.previous_search_match, None);
+            assert_eq!(editor.cursor_position.y, 0);
+            assert_eq!(editor.cursor_position.x, 0);
+            for row in &editor.rows {
+                assert!(!row.highlight.contains(&HighlightType::SearchMatch));
+            }
+        }
+
+        #[test]
+        fn test_find_text_callback_escape_key() {
+            let mut editor = EditorInstance::new(get_populated_termios());
+            set_lines(&mut editor, &["This is a test"]);
+
+            editor.previous_search_match = Some(TextPosition::default());
+
+            editor.find_text_callback("test", Key::U8(b'\x1b'));
+
+            assert_eq!(editor.previous_search_match, None);
+            assert_eq!(editor.search_direction, SearchDirection::Forward);
+            assert!(editor.saved_highlights.is_empty());
+        }
+
+        fn highlighted_columns(editor: &EditorInstance, line_index: usize) -> Vec<usize> {
+            editor.rows[line_index]
+                .highlight
+                .iter()
+                .enumerate()
+                .filter(|(_, highlight)| **highlight == HighlightType::SearchMatch)
+                .map(|(index, _)| index)
+                .collect()
+        }
+
+        #[test]
+        fn test_find_text_callback_steps_through_matches_on_a_line() {
+            let mut editor = editor_with_lines(&["ab ab", "x ab"]);
+            let down = || Key::Custom(EditorKey::ArrowDown);
+            let up = || Key::Custom(EditorKey::ArrowUp);
+
+            editor.find_text_callback("ab", Key::U8(b'b'));
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 0, column: 0 }
+            );
+
+            editor.find_text_callback("ab", down());
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 0, column: 3 }
+            );
+
+            editor.find_text_callback("ab", down());
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 1, column: 2 }
+            );
+            assert_eq!(editor.get_prompt_hint(), " (match 3 of 3)");
+
+            editor.find_text_callback("ab", up());
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 0, column: 3 }
+            );
+
+            editor.find_text_callback("ab", up());
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 0, column: 0 }
+            );
+        }
+
+        #[test]
+        fn test_find_text_callback_wraps_around() {
+            let mut editor = editor_with_lines(&["ab ab", "x ab"]);
+
+            editor.previous_search_match = Some(TextPosition { line: 1, column: 2 });
+            editor.find_text_callback("ab", Key::Custom(EditorKey::ArrowDown));
+
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 0, column: 0 }
+            );
+            assert_eq!(editor.get_prompt_hint(), " (match 1 of 3, search wrapped)");
+
+            editor.find_text_callback("ab", Key::Custom(EditorKey::ArrowUp));
+
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 1, column: 2 }
+            );
+            assert_eq!(editor.get_prompt_hint(), " (match 3 of 3, search wrapped)");
+        }
 
         #[test]
-        fn test_insert_character_into_new_line() {
-            let mut editor = EditorInstance::new(get_populated_termios());
+        fn test_find_text_callback_single_match_wraps_to_itself() {
+            let mut editor = editor_with_lines(&["x", "ab x"]);
 
-            editor.cursor_position = CursorPosition {
-                x: 0,
-                y: 0,
-                render_x: 0,
-            };
-            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
-            editor.lines.clear();
-            editor.insert_character('a');
+            editor.find_text_callback("ab", Key::U8(b'b'));
+            editor.find_text_callback("ab", Key::Custom(EditorKey::ArrowDown));
 
-            assert_eq!(editor.lines.len(), 1);
-            assert_eq!(editor.lines[0].text, "a");
             assert_eq!(
-                editor.cursor_position.x,
-                1 + editor.num_columns_for_line_number as u16
+                editor.get_text_position(),
+                TextPosition { line: 1, column: 0 }
             );
-            assert!(editor.edited);
+            assert_eq!(editor.get_prompt_hint(), " (match 1 of 1, search wrapped)");
         }
 
         #[test]
-        fn test_insert_character_into_existing_line() {
-            let mut editor = EditorInstance::new(get_populated_termios());
+        fn test_find_text_callback_highlights_all_matches() {
+            let mut editor = editor_with_lines(&["ab ab", "x", "ab"]);
 
-            editor.lines.push(Line {
-                text: String::from("Hello"),
-                render: String::from("Hello"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
-            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
-            editor.cursor_position = CursorPosition {
-                x: 5 + editor.num_columns_for_line_number as u16,
-                y: 0,
-                render_x: 5 + editor.num_columns_for_line_number as u16,
-            };
+            editor.find_text_callback("ab", Key::U8(b'b'));
 
-            editor.insert_character('!');
+            assert_eq!(highlighted_columns(&editor, 0), vec![0, 1, 3, 4]);
+            assert_eq!(highlighted_columns(&editor, 1), Vec::<usize>::new());
+            assert_eq!(highlighted_columns(&editor, 2), vec![0, 1]);
+        }
 
-            assert_eq!(editor.lines.len(), 1);
-            assert_eq!(editor.lines[0].text, "Hello!");
-            assert_eq!(
-                editor.cursor_position.x,
-                6 + editor.num_columns_for_line_number as u16
-            );
-            assert!(editor.edited);
+        #[test]
+        fn test_find_text_callback_match_count() {
+            let mut editor = editor_with_lines(&["ab ab", "x", "ab"]);
+
+            editor.find_text_callback("ab", Key::U8(b'b'));
+            assert_eq!(editor.get_prompt_hint(), " (match 1 of 3)");
+
+            editor.find_text_callback("ab", Key::Custom(EditorKey::ArrowDown));
+            assert_eq!(editor.get_prompt_hint(), " (match 2 of 3)");
+
+            editor.find_text_callback("abc", Key::U8(b'c'));
+            assert_eq!(editor.get_prompt_hint(), " (no matches)");
         }
 
         #[test]
-        fn test_insert_character_with_new_line_creation() {
-            let mut editor = EditorInstance::new(get_populated_termios());
+        fn test_find_text_callback_pattern_too_long() {
+            let mut editor = editor_with_lines(&["a"]);
+            let query = "a(".repeat(100_000);
 
-            editor.lines.push(Line {
-                text: String::from("Line 1"),
-                render: String::from("Line 1"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
-            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
-            editor.cursor_position = CursorPosition {
-                x: editor.num_columns_for_line_number as u16,
-                y: 1,
-                render_x: editor.num_columns_for_line_number as u16,
-            }; // Below the existing line
+            editor.find_text_callback(&query, Key::U8(b'('));
+            assert_eq!(editor.get_prompt_hint(), " (Search pattern is too long)");
+        }
 
-            editor.insert_character('A');
+        #[test]
+        fn test_find_text_callback_match_count_limit() {
+            let lines = vec!["a a"; 600];
+            let mut editor = editor_with_lines(&lines);
 
-            assert_eq!(editor.lines.len(), 2); // A new line is created
-            assert_eq!(editor.lines[1].text, "A");
+            editor.find_text_callback("a", Key::U8(b'a'));
+            assert_eq!(editor.get_prompt_hint(), " (match 1 of 999+)");
+
+            editor.find_text_callback("a", Key::Custom(EditorKey::ArrowUp));
             assert_eq!(
-                editor.cursor_position.x,
-                1 + editor.num_columns_for_line_number as u16
+                editor.get_prompt_hint(),
+                " (match 999+ of 999+, search wrapped)"
             );
-            assert!(editor.edited);
-        }
-    }
 
-    mod test_append_string_to_previous_line {
-        use super::*;
+            editor.find_text_callback("a", Key::Custom(EditorKey::ArrowDown));
+            assert_eq!(
+                editor.get_prompt_hint(),
+                " (match 1 of 999+, search wrapped)"
+            );
+        }
 
         #[test]
-        fn test_append_string_to_previous_line() {
-            let mut editor = EditorInstance::new(get_populated_termios());
-
-            editor.lines.push(Line {
-                text: String::from("Hello"),
-                render: String::from("Hello"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+        fn test_search_highlight_persists_until_escape() {
+            let mut editor = editor_with_lines(&["ab ab"]);
 
-            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            editor.find_text_callback("ab", Key::U8(b'b'));
+            editor.find_text_callback("ab", Key::U8(b'\r'));
 
-            editor.cursor_position = CursorPosition {
-                x: editor.num_columns_for_line_number as u16,
-                y: 1,
-                render_x: editor.num_columns_for_line_number as u16,
-            };
+            editor.draw_rows();
+            assert_eq!(highlighted_columns(&editor, 0), vec![0, 1, 3, 4]);
 
-            editor.append_string_to_previous_line(" World");
+            editor.editor_mode = EditorMode::Normal;
+            editor.process_key(Key::U8(b'\x1b'));
+            assert_eq!(highlighted_columns(&editor, 0), Vec::<usize>::new());
 
-            assert_eq!(editor.lines.len(), 1); // No new lines should be added
-            assert_eq!(editor.lines[0].text, "Hello World");
-            assert!(editor.lines[0].render.contains("Hello World")); // Render updates
+            editor.draw_rows();
+            assert_eq!(highlighted_columns(&editor, 0), Vec::<usize>::new());
         }
 
         #[test]
-        #[should_panic(expected = "attempt to subtract with overflow")]
-        fn test_append_string_to_previous_line_panic_on_first_line() {
-            let mut editor = EditorInstance::new(get_populated_termios());
-
-            editor.lines.push(Line {
-                text: String::from("First line"),
-                render: String::from("First line"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+        fn test_search_highlight_follows_edits() {
+            let mut editor = editor_with_lines(&["ab ab"]);
 
-            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            editor.find_text_callback("ab", Key::U8(b'b'));
+            editor.find_text_callback("ab", Key::U8(b'\r'));
 
-            editor.cursor_position = CursorPosition {
-                x: editor.num_columns_for_line_number as u16,
-                y: 0,
-                render_x: editor.num_columns_for_line_number as u16,
-            };
+            editor.editor_mode = EditorMode::Insert;
+            editor.process_key(Key::U8(b'x'));
+            editor.draw_rows();
 
-            editor.append_string_to_previous_line(" Should fail");
+            assert_eq!(texts(&editor), vec!["xab ab"]);
+            assert_eq!(editor.rows[0].highlight.len(), 6);
+            assert_eq!(highlighted_columns(&editor, 0), vec![1, 2, 4, 5]);
         }
     }
 
-    mod test_delete_character_from_line {
+    mod test_command_line {
         use super::*;
+        use tempfile::tempdir;
 
         #[test]
-        fn test_delete_character_from_line_end() {
-            let mut editor = EditorInstance::new(get_populated_termios());
+        fn test_go_to_line() {
+            let mut editor = editor_with_lines(&["a", "  b", "c"]);
 
-            editor.lines.push(Line {
-                text: String::from("Test"),
-                render: String::from("Test"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
-            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            editor.run_command("2");
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 1, column: 2 }
+            );
 
-            editor.cursor_position = CursorPosition {
-                x: 4 + editor.num_columns_for_line_number as u16,
-                y: 0,
-                render_x: 4 + editor.num_columns_for_line_number as u16,
-            };
+            editor.run_command("$");
+            assert_eq!(editor.get_text_position().line, 2);
 
-            editor.delete_character_from_line();
+            editor.run_command("99");
+            assert_eq!(editor.get_text_position().line, 2);
 
-            assert_eq!(editor.lines[0].text, "Tes");
-            assert!(editor.lines[0].render.contains("Tes"));
+            editor.run_command(".-2");
+            assert_eq!(editor.get_text_position().line, 0);
         }
 
         #[test]
-        fn test_delete_character_from_line_middle() {
-            let mut editor = EditorInstance::new(get_populated_termios());
+        fn test_delete_range() {
+            let mut editor = editor_with_lines(&["a", "b", "c", "d"]);
 
-            editor.lines.push(Line {
-                text: String::from("Hello World"),
-                render: String::from("Hello World"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
-            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            editor.run_command("2,3d");
+            assert_eq!(texts(&editor), vec!["a", "d"]);
+            assert_eq!(editor.registers.get(None).unwrap().unwrap().text, "b\nc\n");
 
-            editor.cursor_position = CursorPosition {
-                x: 7 + editor.num_columns_for_line_number as u16,
-                y: 0,
-                render_x: 7 + editor.num_columns_for_line_number as u16,
-            };
+            editor.undo();
+            assert_eq!(texts(&editor), vec!["a", "b", "c", "d"]);
 
-            editor.delete_character_from_line();
+            // A backwards range is swapped
+            editor.run_command("$,3d");
+            assert_eq!(texts(&editor), vec!["a", "b"]);
 
-            assert_eq!(editor.lines[0].text, "Hello orld");
-            assert!(editor.lines[0].render.contains("Hello orld"));
+            editor.run_command("2,5d");
+            assert_eq!(texts(&editor), vec!["a", "b"]);
+            assert_eq!(status(&editor), (String::from("Invalid range"), true));
         }
 
         #[test]
-        fn test_delete_character_from_line_start() {
-            let mut editor = EditorInstance::new(get_populated_termios());
+        fn test_substitute() {
+            let mut editor = editor_with_lines(&["foo foo", "bar", "foo"]);
 
-            editor.lines.push(Line {
-                text: String::from("Hello"),
-                render: String::from("Hello"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
-            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            editor.run_command("%s/foo/baz/");
+            assert_eq!(texts(&editor), vec!["baz foo", "bar", "baz"]);
+            assert_eq!(
+                status(&editor),
+                (String::from("2 replacements made"), false)
+            );
 
-            editor.cursor_position = CursorPosition {
-                x: 1 + editor.num_columns_for_line_number as u16,
-                y: 0,
-                render_x: 1 + editor.num_columns_for_line_number as u16,
-            };
+            editor.run_command("1s/foo/qux/g");
+            assert_eq!(texts(&editor), vec!["baz qux", "bar", "baz"]);
 
-            editor.delete_character_from_line();
+            editor.run_command("2,3s/b(a)/$1/g");
+            assert_eq!(texts(&editor), vec!["baz qux", "ar", "az"]);
 
-            assert_eq!(editor.lines[0].text, "ello");
-            assert!(editor.lines[0].render.contains("ello"));
+            editor.run_command("%s/nothing/x/");
+            assert_eq!(
+                status(&editor),
+                (String::from("Pattern not found: nothing"), true)
+            );
         }
 
         #[test]
-        #[should_panic(expected = "attempt to subtract with overflow")]
-        fn test_delete_character_from_line_panic_out_of_bounds() {
-            let mut editor = EditorInstance::new(get_populated_termios());
+        fn test_substitute_defaults_to_current_line() {
+            let mut editor = editor_with_lines(&["a", "a"]);
+            editor.set_cursor_from_text_position(TextPosition { line: 1, column: 0 });
 
-            editor.lines.push(Line {
-                text: String::from("Test"),
-                render: String::from("Test"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
-            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            editor.run_command("s/a/b/");
+            assert_eq!(texts(&editor), vec!["a", "b"]);
+        }
 
-            editor.cursor_position = CursorPosition {
-                x: editor.num_columns_for_line_number as u16,
-                y: 0,
-                render_x: editor.num_columns_for_line_number as u16,
-            };
+        #[test]
+        fn test_unknown_command() {
+            let mut editor = editor_with_lines(&["a"]);
 
-            editor.delete_character_from_line();
+            editor.run_command("frobnicate");
+            assert_eq!(
+                status(&editor),
+                (String::from("Not an editor command: frobnicate"), true)
+            );
         }
-    }
-
-    mod test_delete_character {
-        use super::*;
 
         #[test]
-        fn test_delete_character_middle_of_line() {
-            let mut editor = EditorInstance::new(get_populated_termios());
+        fn test_write_to_new_name() {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("renamed.rs");
+            let mut editor = editor_with_lines(&["fn main() {}"]);
+            editor.edited = true;
 
-            editor.lines.push(Line {
-                text: String::from("Hello World"),
-                render: String::from("Hello World"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+            editor.run_command("w");
+            assert_eq!(status(&editor), (String::from("No file name"), true));
 
-            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            editor.run_command(&format!("w {}", path.to_str().unwrap()));
+            assert_eq!(fs::read_to_string(&path).unwrap(), "fn main() {}\n");
+            assert_eq!(editor.file.as_ref().unwrap().name, "renamed.rs");
+            assert_eq!(editor.syntax.as_ref().unwrap().file_type, "Rust");
+            assert!(!editor.edited);
+        }
 
-            editor.cursor_position = CursorPosition {
-                x: 7 + editor.num_columns_for_line_number as u16,
-                y: 0,
-                render_x: 7 + editor.num_columns_for_line_number as u16,
-            };
+        #[test]
+        fn test_write_to_other_file_keeps_name() {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("file.txt");
+            let other_path = dir.path().join("other.txt");
+            let mut editor = editor_with_file(&["a"], &path);
+            editor.edited = true;
+
+            editor.run_command(&format!("w {}", other_path.to_str().unwrap()));
+            assert_eq!(fs::read_to_string(&other_path).unwrap(), "a\n");
+            assert_eq!(editor.file.as_ref().unwrap().name, "file.txt");
+            assert!(editor.edited);
+            assert!(!path.exists());
+        }
 
-            editor.delete_character();
+        #[test]
+        fn test_failed_write_to_new_name_keeps_no_name() {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("missing").join("x.txt");
+            let mut editor = editor_with_lines(&["a"]);
+            editor.edited = true;
 
-            assert_eq!(editor.lines[0].text, "Hello orld");
-            assert_eq!(
-                editor.cursor_position.x,
-                6 + editor.num_columns_for_line_number as u16
-            );
+            editor.run_command(&format!("w {}", path.to_str().unwrap()));
+            assert!(status(&editor).1);
+            assert!(editor.file.is_none());
             assert!(editor.edited);
         }
 
         #[test]
-        fn test_delete_character_start_of_line() {
-            let mut editor = EditorInstance::new(get_populated_termios());
+        fn test_quit_with_unsaved_changes() {
+            let mut editor = editor_with_lines(&["a"]);
+            editor.edited = true;
 
-            editor.lines.push(Line {
-                text: String::from("First line"),
-                render: String::from("First line"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
-            editor.lines.push(Line {
-                text: String::from("Second line"),
-                render: String::from("Second line"),
-                highlight: vec![],
-                index: 1,
-                has_open_multiline_comment: false,
-            });
+            editor.run_command("q");
+            assert_eq!(
+                status(&editor),
+                (
+                    String::from("No write since last change (add ! to override)"),
+                    true
+                )
+            );
+        }
 
-            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+        #[test]
+        fn test_edit_file() {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("other.txt");
+            fs::write(&path, "other\n").unwrap();
 
-            editor.cursor_position = CursorPosition {
-                x: editor.num_columns_for_line_number as u16,
-                y: 1,
-                render_x: editor.num_columns_for_line_number as u16,
-            };
+            let mut editor = editor_with_lines(&["a", "b"]);
+            editor.set_cursor_from_text_position(TextPosition { line: 1, column: 0 });
+            editor.edited = true;
 
-            editor.delete_character();
+            editor.run_command(&format!("e {}", path.to_str().unwrap()));
+            assert_eq!(texts(&editor), vec!["a", "b"]);
+            assert!(status(&editor).1);
 
-            assert_eq!(editor.lines.len(), 1);
-            assert_eq!(editor.lines[0].text, "First lineSecond line");
-            assert_eq!(
-                editor.cursor_position.x,
-                10 + editor.num_columns_for_line_number as u16
-            );
-            assert_eq!(editor.cursor_position.y, 0);
-            assert!(editor.edited);
+            editor.run_command(&format!("e! {}", path.to_str().unwrap()));
+            assert_eq!(texts(&editor), vec!["other"]);
+            assert_eq!(editor.get_text_position(), TextPosition::default());
+            assert!(!editor.edited);
+
+            editor.run_command(&format!("e {}", dir.path().to_str().unwrap()));
+            assert!(status(&editor).0.ends_with("is a directory"));
         }
 
         #[test]
-        fn test_delete_character_no_op_past_last_line() {
-            let mut editor = EditorInstance::new(get_populated_termios());
+        fn test_edit_new_file() {
+            let dir = tempdir().unwrap();
+            let path = dir.path().join("new.txt");
+            let mut editor = editor_with_lines(&["a"]);
 
-            editor.lines.push(Line {
-                text: String::from("Only line"),
-                render: String::from("Only line"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+            editor.run_command(&format!("e {}", path.to_str().unwrap()));
+            assert_eq!(editor.buffer.line_count(), 0);
+            assert_eq!(editor.file.as_ref().unwrap().name, "new.txt");
+            assert!(!path.exists());
+        }
 
-            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+        #[test]
+        fn test_set_tabstop() {
+            let mut editor = editor_with_lines(&["\tx"]);
 
-            editor.cursor_position = CursorPosition {
-                x: editor.num_columns_for_line_number as u16,
-                y: 1,
-                render_x: editor.num_columns_for_line_number as u16,
-            };
+            editor.run_command("set tabstop=2");
+            assert_eq!(editor.rows[0].render, "  x");
 
-            editor.delete_character();
+            editor.run_command("set ts?");
+            assert_eq!(status(&editor), (String::from("tabstop=2"), false));
 
-            assert_eq!(editor.lines.len(), 1);
-            assert_eq!(editor.lines[0].text, "Only line");
+            editor.run_command("set tabstop=0");
             assert_eq!(
-                editor.cursor_position.x,
-                editor.num_columns_for_line_number as u16
+                status(&editor),
+                (String::from("Invalid argument: tabstop=0"), true)
             );
-            assert_eq!(editor.cursor_position.y, 1);
-            assert!(!editor.edited);
+            assert_eq!(editor.settings.tab_size, 2);
         }
 
         #[test]
-        fn test_delete_character_no_op_start_of_first_line() {
-            let mut editor = EditorInstance::new(get_populated_termios());
+        fn test_set_expandtab() {
+            let mut editor = editor_with_lines(&["ab", "c"]);
 
-            editor.lines.push(Line {
-                text: String::from("Only line"),
-                render: String::from("Only line"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+            editor.run_command("set expandtab");
+            editor.set_cursor_from_text_position(TextPosition { line: 0, column: 1 });
+            editor.process_key(Key::U8(b'\t'));
+            assert_eq!(texts(&editor), vec!["a   b", "c"]);
 
-            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            editor.undo();
+            assert_eq!(texts(&editor), vec!["ab", "c"]);
 
-            editor.cursor_position = CursorPosition {
-                x: editor.num_columns_for_line_number as u16,
-                y: 0,
-                render_x: editor.num_columns_for_line_number as u16,
-            };
+            editor.run_command("set ts=2");
+            editor.editor_mode = EditorMode::Normal;
+            for key in "j>>".bytes() {
+                editor.process_key(Key::U8(key));
+            }
+            assert_eq!(texts(&editor), vec!["ab", "  c"]);
+        }
 
-            editor.delete_character();
+        #[test]
+        fn test_set_number() {
+            let mut editor = editor_with_lines(&["abc"]);
+            editor.set_cursor_from_text_position(TextPosition { line: 0, column: 2 });
 
-            assert_eq!(editor.lines.len(), 1);
-            assert_eq!(editor.lines[0].text, "Only line");
+            editor.run_command("set nonumber");
+            assert_eq!(editor.num_columns_for_line_number, 0);
+            assert_eq!(editor.cursor_position.x, 2);
+
+            editor.run_command("set nu");
             assert_eq!(
-                editor.cursor_position.x,
-                editor.num_columns_for_line_number as u16
+                editor.num_columns_for_line_number,
+                1 + LINE_NUMBER_GAP as usize
             );
-            assert_eq!(editor.cursor_position.y, 0);
-            assert!(!editor.edited);
+            assert_eq!(editor.get_text_position().column, 2);
         }
-    }
 
-    mod test_insert_line {
-        use super::*;
+        #[test]
+        fn test_set_quit_confirmations() {
+            let mut editor = editor_with_lines(&["a"]);
+            editor.edited = true;
+
+            editor.run_command("set quitconfirmations=2");
+            editor.process_key(Key::U8(ctrl_key('q')));
+            assert_eq!(
+                status(&editor),
+                (
+                    String::from(
+                        "WARNING: File has unsaved changes! Press Ctrl-Q 2 more times to quit."
+                    ),
+                    true
+                )
+            );
+        }
 
         #[test]
-        fn test_insert_line_at_line_start() {
-            let mut editor = EditorInstance::new(get_populated_termios());
+        fn test_set_default_mode() {
+            let directory = tempdir().unwrap();
+            let mut editor = editor_with_lines(&["a"]);
 
-            editor.lines.push(Line {
-                text: String::from("Existing line"),
-                render: String::from("Existing line"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+            editor.run_command("set defaultmode=insert");
+            editor.run_command(&format!("e! {}", directory.path().join("new").display()));
+            assert_eq!(editor.editor_mode, EditorMode::Insert);
 
-            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            editor.run_command("set defaultmode?");
+            assert_eq!(status(&editor), (String::from("defaultmode=insert"), false));
+        }
 
-            editor.cursor_position = CursorPosition {
-                x: editor.num_columns_for_line_number as u16,
-                y: 0,
-                render_x: editor.num_columns_for_line_number as u16,
-            };
+        #[test]
+        fn test_set_backup() {
+            let mut editor = editor_with_lines(&["a"]);
 
-            editor.insert_line();
+            editor.run_command("set backup");
+            assert!(editor.settings.create_backup_file);
 
-            assert_eq!(editor.lines.len(), 2);
-            assert_eq!(editor.lines[0].text, "");
-            assert_eq!(editor.lines[1].text, "Existing line");
-            assert_eq!(editor.lines[1].index, 1);
-            assert_eq!(editor.cursor_position.y, 1);
+            editor.run_command("set nobackup");
+            assert!(!editor.settings.create_backup_file);
+
+            editor.run_command("set");
+            assert!(status(&editor).0.starts_with("nobackup "));
+
+            editor.run_command("set wrap");
             assert_eq!(
-                editor.cursor_position.x,
-                editor.num_columns_for_line_number as u16
+                status(&editor),
+                (String::from("Unknown option: wrap"), true)
             );
-            assert!(editor.edited);
+        }
+    }
+
+    mod test_themes {
+        use super::*;
+        use crate::theme::Color;
+
+        /// The text that would be shown, without escape codes
+        fn strip_escape_codes(text: &str) -> String {
+            let mut stripped = String::new();
+            let mut chars = text.chars();
+
+            while let Some(char) = chars.next() {
+                match char {
+                    '\x1b' => {
+                        chars.by_ref().find(|char| char.is_ascii_alphabetic());
+                    }
+                    char => stripped.push(char),
+                }
+            }
+
+            stripped
         }
 
         #[test]
-        fn test_insert_line_in_middle_of_line() {
-            let mut editor = EditorInstance::new(get_populated_termios());
+        fn test_draw_rows_with_theme() {
+            let mut editor = editor_with_lines(&["if x"]);
+            editor.syntax = editor.syntaxes.find_by_file_name("test.c");
+            editor.update_line_highlights();
+            editor.color_support = ColorSupport::TrueColor;
+            editor.window_size.rows = 2;
 
-            editor.lines.push(Line {
-                text: String::from("Hello, World"),
-                render: String::from("Hello, World"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+            editor.run_command("set theme=gruvbox-dark");
+            assert_eq!(editor.settings.theme.name, "gruvbox-dark");
 
-            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            let mut buffer = String::new();
+            editor.write_rows(&mut buffer);
+
+            let theme = editor.settings.theme;
+            let keyword = editor.get_style_code(theme.normal.patch(theme.keyword));
+            assert!(buffer.contains(&format!("{}if", keyword)));
+            assert!(keyword.contains("38;2;251;73;52"));
+            assert!(keyword.contains("48;2;40;40;40")); // The normal background
+            assert!(buffer.contains(&editor.get_style_code(theme.normal.patch(theme.non_text))));
+            assert_eq!(strip_escape_codes(&buffer), "1   if x\r\n~\r\n");
+        }
 
-            editor.cursor_position = CursorPosition {
-                x: 7 + editor.num_columns_for_line_number as u16,
-                y: 0,
-                render_x: 7 + editor.num_columns_for_line_number as u16,
-            };
+        #[test]
+        fn test_color_overrides_theme() {
+            let mut editor = editor_with_lines(&[""]);
 
-            editor.insert_line();
+            editor.run_command("set theme=gruvbox-dark");
+            editor.run_command("set colors.keyword=blue");
 
-            assert_eq!(editor.lines.len(), 2);
-            assert_eq!(editor.lines[0].text, "Hello, ");
-            assert_eq!(editor.lines[1].text, "World");
-            assert_eq!(editor.lines[1].index, 1);
-            assert_eq!(editor.cursor_position.y, 1);
+            let style = editor.get_highlight_style(&HighlightType::Keyword);
+            assert_eq!(style.foreground, Some(Color::Ansi(4)));
+            assert_eq!(style.background, editor.settings.theme.normal.background);
             assert_eq!(
-                editor.cursor_position.x,
-                editor.num_columns_for_line_number as u16
+                editor.get_highlight_style(&HighlightType::Type),
+                editor
+                    .settings
+                    .theme
+                    .normal
+                    .patch(editor.settings.theme.type_name)
             );
-            assert!(editor.edited);
         }
 
         #[test]
-        fn test_insert_line_at_end_of_line() {
-            let mut editor = EditorInstance::new(get_populated_termios());
+        fn test_status_bar_fits_window() {
+            let mut editor = editor_with_lines(&["a"]);
+            editor.run_command("set theme=solarized-dark");
 
-            editor.lines.push(Line {
-                text: String::from("Hello"),
-                render: String::from("Hello"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+            for (columns, edited) in [(80, false), (80, true), (30, true), (10, false)] {
+                editor.window_size.columns = columns;
+                editor.edited = edited;
 
-            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+                let mut buffer = String::new();
+                editor.write_status_bar(&mut buffer);
 
-            editor.cursor_position = CursorPosition {
-                x: 5 + editor.num_columns_for_line_number as u16,
-                y: 0,
-                render_x: 5 + editor.num_columns_for_line_number as u16,
-            };
+                let text = strip_escape_codes(&buffer);
+                assert_eq!(text.chars().count(), columns as usize + 2, "{:?}", text);
+                assert!(text.ends_with("\r\n"));
+            }
+        }
+    }
 
-            editor.insert_line();
+    mod test_replace {
+        use super::*;
 
-            assert_eq!(editor.lines.len(), 2);
-            assert_eq!(editor.lines[0].text, "Hello");
-            assert_eq!(editor.lines[1].text, "");
-            assert_eq!(editor.lines[1].index, 1);
-            assert_eq!(editor.cursor_position.y, 1);
-            assert_eq!(
-                editor.cursor_position.x,
-                editor.num_columns_for_line_number as u16
-            );
-            assert!(editor.edited);
+        fn replace(
+            editor: &mut EditorInstance,
+            query: &str,
+            replacement: &str,
+            line_range: Range<usize>,
+            choices: &[ReplaceChoice],
+        ) -> usize {
+            let mut choices = choices.iter();
+
+            editor.replace_matches(
+                &SearchQuery::parse(query).compile().unwrap(),
+                replacement,
+                line_range,
+                true,
+                |_| *choices.next().expect("Asked to confirm too many matches"),
+            )
         }
 
         #[test]
-        fn test_insert_line_empty_editor() {
-            let mut editor = EditorInstance::new(get_populated_termios());
+        fn test_replace_all_is_undone_together() {
+            let mut editor = editor_with_lines(&["foo bar foo", "foo"]);
 
-            editor.lines = vec![];
+            let num_replacements = replace(&mut editor, "foo", "baz", 0..2, &[ReplaceChoice::All]);
 
-            editor.cursor_position = CursorPosition {
-                x: editor.num_columns_for_line_number as u16,
-                y: 0,
-                render_x: 0,
-            };
+            assert_eq!(num_replacements, 3);
+            assert_eq!(texts(&editor), vec!["baz bar baz", "baz"]);
+            assert!(editor.edited);
 
-            editor.insert_line();
+            editor.undo();
+            assert_eq!(texts(&editor), vec!["foo bar foo", "foo"]);
+        }
 
-            assert_eq!(editor.lines.len(), 1);
-            assert_eq!(editor.lines[0].text, "");
-            assert_eq!(editor.lines[0].index, 0);
-            assert_eq!(editor.cursor_position.y, 1);
-            assert_eq!(
-                editor.cursor_position.x,
-                editor.num_columns_for_line_number as u16
+        #[test]
+        fn test_replace_with_confirmation() {
+            let mut editor = editor_with_lines(&["a a", "a a"]);
+
+            let num_replacements = replace(
+                &mut editor,
+                "a",
+                "b",
+                0..2,
+                &[
+                    ReplaceChoice::Yes,
+                    ReplaceChoice::No,
+                    ReplaceChoice::Yes,
+                    ReplaceChoice::Quit,
+                ],
             );
-            assert!(editor.edited);
-        }
-    }
 
-    mod test_find_text_callback {
-        use super::*;
+            assert_eq!(num_replacements, 2);
+            assert_eq!(texts(&editor), vec!["b a", "b a"]);
+            assert!(editor.saved_highlights.is_empty());
+        }
 
         #[test]
-        fn test_find_text_callback_forward_match() {
-            let mut editor = EditorInstance::new(get_populated_termios());
+        fn test_replace_in_line_range() {
+            let mut editor = editor_with_lines(&["x", "x", "x", "x"]);
 
-            editor.lines = vec![
-                Line {
-                    text: String::from("This is a test"),
-                    render: String::from("This is a test"),
-                    highlight: vec![HighlightType::Normal; 14],
-                    index: 0,
-                    has_open_multiline_comment: false,
-                },
-                Line {
-                    text: String::from("Another test line"),
-                    render: String::from("Another test line"),
-                    highlight: vec![HighlightType::Normal; 18],
-                    index: 1,
-                    has_open_multiline_comment: false,
-                },
-            ];
+            replace(&mut editor, "x", "y", 1..3, &[ReplaceChoice::All]);
 
-            editor.find_text_callback("test", Key::Custom(EditorKey::ArrowDown));
+            assert_eq!(texts(&editor), vec!["x", "y", "y", "x"]);
+        }
 
-            assert_eq!(editor.previous_search_match_line_index, Some(0));
-            assert_eq!(editor.cursor_position.y, 0);
-            assert_eq!(
-                editor.cursor_position.x,
-                10 + editor.num_columns_for_line_number as u16
-            );
-            assert_eq!(
-                editor.lines[0].highlight[10..14],
-                vec![
-                    HighlightType::SearchMatch,
-                    HighlightType::SearchMatch,
-                    HighlightType::SearchMatch,
-                    HighlightType::SearchMatch
-                ]
+        #[test]
+        fn test_replace_with_capture_groups() {
+            let mut editor = editor_with_lines(&["a=1, b=2"]);
+
+            replace(
+                &mut editor,
+                "(\\w)=(\\d)",
+                "$2=$1",
+                0..1,
+                &[ReplaceChoice::All],
             );
+
+            assert_eq!(texts(&editor), vec!["1=a, 2=b"]);
         }
 
         #[test]
-        fn test_find_text_callback_backward_match() {
-            let mut editor = EditorInstance::new(get_populated_termios());
-            editor.lines = vec![
-                Line {
-                    text: String::from("This is a test"),
-                    render: String::from("This is a test"),
-                    highlight: vec![HighlightType::Normal; 14],
-                    index: 0,
-                    has_open_multiline_comment: false,
-                },
-                Line {
-                    text: String::from("Another test line"),
-                    render: String::from("Another test line"),
-                    highlight: vec![HighlightType::Normal; 18],
-                    index: 1,
-                    has_open_multiline_comment: false,
-                },
-            ];
-
-            editor.previous_search_match_line_index = Some(1);
+        fn test_replace_empty_matches() {
+            let mut editor = editor_with_lines(&["one", "", "two"]);
 
-            editor.find_text_callback("test", Key::Custom(EditorKey::ArrowUp));
+            let num_replacements = replace(&mut editor, "^", "> ", 0..3, &[ReplaceChoice::All]);
 
-            assert_eq!(editor.previous_search_match_line_index, Some(0));
-            assert_eq!(editor.cursor_position.y, 0);
-            assert_eq!(
-                editor.cursor_position.x,
-                10 + editor.num_columns_for_line_number as u16
-            );
-            assert_eq!(
-                editor.lines[0].highlight[10..14],
-                vec![
-                    HighlightType::SearchMatch,
-                    HighlightType::SearchMatch,
-                    HighlightType::SearchMatch,
-                    HighlightType::SearchMatch
-                ]
-            );
+            assert_eq!(num_replacements, 3);
+            assert_eq!(texts(&editor), vec!["> one", "> ", "> two"]);
         }
 
         #[test]
-        fn test_find_text_callback_no_match() {
-            let mut editor = EditorInstance::new(get_populated_termios());
-            editor.lines = vec![
-                Line {
-                    text: String::from("This is a test"),
-                    render: String::from("This is a test"),
-                    highlight: vec![HighlightType::Normal; 14],
-                    index: 0,
-                    has_open_multiline_comment: false,
-                },
-                Line {
-                    text: String::from("Another test line"),
-                    render: String::from("Another test line"),
-                    highlight: vec![HighlightType::Normal; 18],
-                    index: 1,
-                    has_open_multiline_comment: false,
-                },
-            ];
+        fn test_replacement_containing_match() {
+            let mut editor = editor_with_lines(&["aa"]);
 
-            editor.find_text_callback("nonexistent", Key::Custom(EditorKey::ArrowDown));
+            let num_replacements = replace(&mut editor, "a", "aa", 0..1, &[ReplaceChoice::All]);
 
-            assert_eq!(editor.previous_search_match_line_index, None);
-            assert_eq!(editor.cursor_position.y, 0);
-            assert_eq!(editor.cursor_position.x, 0);
-            for line in &editor.lines {
-                assert!(!line
-                    .highlight
-                    .iter()
-                    .any(|h| *h == HighlightType::SearchMatch));
-            }
+            assert_eq!(num_replacements, 2);
+            assert_eq!(texts(&editor), vec!["aaaa"]);
         }
 
         #[test]
-        fn test_find_text_callback_escape_key() {
-            let mut editor = EditorInstance::new(get_populated_termios());
-            editor.lines = vec![Line {
-                text: String::from("This is a test"),
-                render: String::from("This is a test"),
-                highlight: vec![HighlightType::Normal; 14],
-                index: 0,
-                has_open_multiline_comment: false,
-            }];
+        fn test_empty_match_after_match_is_skipped() {
+            let mut editor = editor_with_lines(&["abc", "abc"]);
 
-            editor.previous_search_match_line_index = Some(0);
+            let choices = [ReplaceChoice::No; 3];
+            assert_eq!(replace(&mut editor, "b*", "-", 0..1, &choices), 0);
 
-            editor.find_text_callback("test", Key::U8(b'\x1b'));
+            let num_replacements = replace(&mut editor, "b*", "-", 0..1, &[ReplaceChoice::All]);
+            assert_eq!(num_replacements, 3);
 
-            assert_eq!(editor.previous_search_match_line_index, None);
-            assert_eq!(editor.search_direction, SearchDirection::Forward);
-            assert!(editor.saved_highlight.is_none());
+            editor.run_command("2s/b*/-/g");
+            assert_eq!(texts(&editor), vec!["-a-c-", "-a-c-"]);
+        }
+
+        #[test]
+        fn test_parse_line_range() {
+            let mut editor = editor_with_lines(&["1", "2", "3", "4"]);
+            editor.cursor_position.y = 2;
+
+            assert_eq!(editor.parse_line_range(""), Some(0..4));
+            assert_eq!(editor.parse_line_range("%"), Some(0..4));
+            assert_eq!(editor.parse_line_range("."), Some(2..3));
+            assert_eq!(editor.parse_line_range("2"), Some(1..2));
+            assert_eq!(editor.parse_line_range("2, 3"), Some(1..3));
+            assert_eq!(editor.parse_line_range("3,2"), None);
+            assert_eq!(editor.parse_line_range("0,2"), None);
+            assert_eq!(editor.parse_line_range("abc"), None);
         }
     }
 
@@ -3101,7 +7745,12 @@ mod unit_tests {
             let mut message = String::from("Brendan's text editor --- version 1.0.0");
             editor.add_welcome_message_to_buffer(&mut buffer, &mut message);
 
-            let expected_message = "~    Brendan's text editor --- version 1.0.0";
+            let theme = editor.settings.theme;
+            let expected_message = format!(
+                "{}~{}    Brendan's text editor --- version 1.0.0",
+                editor.get_style_code(theme.normal.patch(theme.non_text)),
+                editor.get_style_code(theme.normal),
+            );
             assert_eq!(buffer, expected_message);
         }
     }
@@ -3112,7 +7761,7 @@ mod unit_tests {
         #[test]
         fn test_no_lines_sets_zero_columns() {
             let mut editor = EditorInstance::new(get_populated_termios());
-            editor.lines.clear();
+            set_lines(&mut editor, &[]);
             editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
 
             assert_eq!(editor.num_columns_for_line_number, 0);
@@ -3121,14 +7770,8 @@ mod unit_tests {
         #[test]
         fn test_single_line_sets_proper_columns() {
             let mut editor = EditorInstance::new(get_populated_termios());
-            editor.lines.clear();
-            editor.lines.push(Line {
-                text: String::from("Sample text"),
-                render: String::from("Sample text"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            });
+            set_lines(&mut editor, &[]);
+            push_line(&mut editor, "Sample text");
 
             editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
 
@@ -3138,16 +7781,10 @@ mod unit_tests {
         #[test]
         fn test_multiple_lines_increase_columns() {
             let mut editor = EditorInstance::new(get_populated_termios());
-            editor.lines.clear();
+            set_lines(&mut editor, &[]);
 
             for i in 0..100 {
-                editor.lines.push(Line {
-                    text: format!("Line {}", i),
-                    render: format!("Line {}", i),
-                    highlight: vec![],
-                    index: i,
-                    has_open_multiline_comment: false,
-                });
+                push_line(&mut editor, &format!("Line {}", i));
             }
 
             editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
@@ -3158,16 +7795,10 @@ mod unit_tests {
         #[test]
         fn test_cursor_adjusted_if_less_than_num_columns() {
             let mut editor = EditorInstance::new(get_populated_termios());
-            editor.lines.clear();
+            set_lines(&mut editor, &[]);
 
             for i in 0..10 {
-                editor.lines.push(Line {
-                    text: format!("Line {}", i),
-                    render: format!("Line {}", i),
-                    highlight: vec![],
-                    index: i,
-                    has_open_multiline_comment: false,
-                });
+                push_line(&mut editor, &format!("Line {}", i));
             }
 
             editor.cursor_position.x = 1;
@@ -3181,20 +7812,313 @@ mod unit_tests {
     mod test_get_editor_mode_display {
         use super::*;
 
+        fn get_editor_mode_text(editor: &EditorInstance) -> String {
+            editor
+                .get_editor_mode_display()
+                .into_iter()
+                .map(|(_, text)| text)
+                .collect()
+        }
+
         #[test]
         fn test_get_editor_mode_display_normal_mode() {
             let mut editor = EditorInstance::new(get_populated_termios());
             editor.editor_mode = EditorMode::Normal;
-            let display = editor.get_editor_mode_display();
+            let display = get_editor_mode_text(&editor);
             assert!(display.contains("INSERT (i)"));
+            assert!(editor.get_editor_mode_display().contains(&(
+                editor.settings.theme.status_bar_mode,
+                String::from(" NORMAL ")
+            )));
         }
 
         #[test]
         fn test_get_editor_mode_display_insert_mode() {
             let mut editor = EditorInstance::new(get_populated_termios());
             editor.editor_mode = EditorMode::Insert;
-            let display = editor.get_editor_mode_display();
+            let display = get_editor_mode_text(&editor);
+            assert!(display.contains("NORMAL (<esc>)"));
+        }
+
+        #[test]
+        fn test_get_editor_mode_display_visual_modes() {
+            let mut editor = EditorInstance::new(get_populated_termios());
+
+            editor.editor_mode = EditorMode::Visual(SelectionKind::Characters);
+            assert!(get_editor_mode_text(&editor).contains(" VISUAL "));
+
+            editor.editor_mode = EditorMode::Visual(SelectionKind::Lines);
+            assert!(get_editor_mode_text(&editor).contains(" VISUAL LINE "));
+
+            editor.editor_mode = EditorMode::Visual(SelectionKind::Block);
+            let display = get_editor_mode_text(&editor);
+            assert!(display.contains(" VISUAL BLOCK "));
             assert!(display.contains("NORMAL (<esc>)"));
         }
     }
+
+    mod test_undo_redo {
+        use super::*;
+
+        #[test]
+        fn test_undo_redo_typing_in_insert_mode() {
+            let mut editor = editor_with_lines(&["Hello"]);
+            editor.cursor_position.x += 5;
+
+            editor.process_key(Key::U8(b'!'));
+            editor.process_key(Key::U8(b'!'));
+            assert_eq!(texts(&editor), vec!["Hello!!"]);
+
+            editor.process_key(Key::U8(ctrl_key('z')));
+            assert_eq!(texts(&editor), vec!["Hello"]);
+            assert_eq!(editor.get_text_position().column, 5);
+            assert!(!editor.edited);
+
+            editor.process_key(Key::U8(ctrl_key('y')));
+            assert_eq!(texts(&editor), vec!["Hello!!"]);
+            assert_eq!(editor.get_text_position().column, 7);
+            assert!(editor.edited);
+        }
+
+        #[test]
+        fn test_undo_groups_are_split_by_cursor_movement() {
+            let mut editor = editor_with_lines(&["ab"]);
+
+            editor.process_key(Key::U8(b'x'));
+            editor.process_key(Key::Custom(EditorKey::ArrowRight));
+            editor.process_key(Key::U8(b'y'));
+            assert_eq!(texts(&editor), vec!["xayb"]);
+
+            editor.process_key(Key::U8(ctrl_key('z')));
+            assert_eq!(texts(&editor), vec!["xab"]);
+
+            editor.process_key(Key::U8(ctrl_key('z')));
+            assert_eq!(texts(&editor), vec!["ab"]);
+        }
+
+        #[test]
+        fn test_undo_redo_line_split_and_join() {
+            let mut editor = editor_with_lines(&["HelloWorld", "Next"]);
+            editor.cursor_position.x += 5;
+
+            editor.process_key(Key::U8(b'\r'));
+            assert_eq!(texts(&editor), vec!["Hello", "World", "Next"]);
+
+            editor.process_key(Key::U8(ctrl_key('z')));
+            assert_eq!(texts(&editor), vec!["HelloWorld", "Next"]);
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 0, column: 5 }
+            );
+
+            editor.cursor_position.y = 1;
+            editor.cursor_position.x = editor.num_columns_for_line_number as u16;
+            editor.process_key(Key::Custom(EditorKey::Backspace));
+            assert_eq!(texts(&editor), vec!["HelloWorldNext"]);
+
+            editor.process_key(Key::U8(ctrl_key('z')));
+            assert_eq!(texts(&editor), vec!["HelloWorld", "Next"]);
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition { line: 1, column: 0 }
+            );
+
+            editor.process_key(Key::U8(ctrl_key('y')));
+            assert_eq!(texts(&editor), vec!["HelloWorldNext"]);
+            assert_eq!(
+                editor.get_text_position(),
+                TextPosition {
+                    line: 0,
+                    column: 10
+                }
+            );
+        }
+
+        #[test]
+        fn test_undo_typing_past_last_line() {
+            let mut editor = editor_with_lines(&["abc"]);
+            editor.cursor_position.y = 1;
+
+            editor.process_key(Key::U8(b'd'));
+            assert_eq!(texts(&editor), vec!["abc", "d"]);
+
+            editor.process_key(Key::U8(ctrl_key('z')));
+            assert_eq!(texts(&editor), vec!["abc"]);
+            assert_eq!(editor.cursor_position.y, 1);
+        }
+
+        #[test]
+        fn test_undo_to_saved_state_clears_edited() {
+            let mut editor = editor_with_lines(&["abc"]);
+
+            editor.process_key(Key::U8(b'x'));
+            editor.history.mark_saved();
+            editor.edited = false;
+
+            editor.process_key(Key::Custom(EditorKey::ArrowRight));
+            editor.process_key(Key::U8(b'y'));
+            assert!(editor.edited);
+
+            editor.process_key(Key::U8(ctrl_key('z')));
+            assert_eq!(texts(&editor), vec!["xabc"]);
+            assert!(!editor.edited);
+
+            editor.process_key(Key::U8(ctrl_key('z')));
+            assert_eq!(texts(&editor), vec!["abc"]);
+            assert!(editor.edited);
+        }
+
+        #[test]
+        fn test_undo_redo_in_normal_mode() {
+            let mut editor = editor_with_lines(&["abc"]);
+
+            editor.process_key(Key::U8(b'x'));
+            editor.process_key(Key::U8(b'\x1b'));
+            assert_eq!(editor.editor_mode, EditorMode::Normal);
+
+            editor.process_key(Key::U8(b'u'));
+            assert_eq!(texts(&editor), vec!["abc"]);
+
+            editor.process_key(Key::U8(ctrl_key('r')));
+            assert_eq!(texts(&editor), vec!["xabc"]);
+        }
+
+        #[test]
+        fn test_undo_with_empty_history() {
+            let mut editor = editor_with_lines(&["abc"]);
+
+            editor.undo();
+            assert_eq!(texts(&editor), vec!["abc"]);
+            assert_eq!(
+                editor.status_message.as_ref().unwrap().message,
+                "Already at oldest change"
+            );
+
+            editor.redo();
+            assert_eq!(
+                editor.status_message.as_ref().unwrap().message,
+                "Already at newest change"
+            );
+        }
+
+        #[test]
+        fn test_multi_line_edits_keep_rows_in_sync() {
+            let mut editor = editor_with_lines(&["first", "second", "third"]);
+            editor.cursor_position.y = 1;
+            editor.cursor_position.x += 3;
+
+            editor.process_key(Key::U8(b'\r'));
+            editor.process_key(Key::U8(b'\r'));
+            editor.process_key(Key::U8(b'x'));
+            assert_eq!(texts(&editor), vec!["first", "sec", "", "xond", "third"]);
+
+            editor.process_key(Key::Custom(EditorKey::Home));
+            editor.process_key(Key::Custom(EditorKey::Backspace));
+            assert_eq!(texts(&editor), vec!["first", "sec", "xond", "third"]);
+
+            editor.undo();
+            editor.undo();
+            assert_eq!(texts(&editor), vec!["first", "second", "third"]);
+
+            assert_eq!(editor.rows.len(), editor.buffer.line_count());
+
+            for (line_index, row) in editor.rows.iter().enumerate() {
+                assert_eq!(row.render, editor.buffer.line(line_index));
+                assert_eq!(row.highlight.len(), row.render.chars().count());
+            }
+        }
+    }
+
+    mod test_unicode {
+        use super::*;
+
+        fn editor_with_line(text: &str) -> EditorInstance {
+            let mut editor = EditorInstance::new(get_populated_termios());
+
+            push_line(&mut editor, text);
+
+            editor.set_line_highlight(0);
+            editor.set_num_columns_for_line_number(LINE_NUMBER_GAP);
+            editor
+        }
+
+        #[test]
+        fn test_insert_multi_byte_characters() {
+            let mut editor = editor_with_line("café");
+            editor.cursor_position.x += 4;
+
+            editor.process_key(Key::Char('ñ'));
+            editor.process_key(Key::Char('🙂'));
+            assert_eq!(editor.buffer.line(0), "caféñ🙂");
+            assert_eq!(editor.get_text_position().column, 6);
+
+            editor.process_key(Key::Custom(EditorKey::Backspace));
+            editor.process_key(Key::Custom(EditorKey::Backspace));
+            assert_eq!(editor.buffer.line(0), "café");
+        }
+
+        #[test]
+        fn test_combining_character_joins_previous_grapheme() {
+            let mut editor = editor_with_line("");
+
+            editor.process_key(Key::U8(b'e'));
+            editor.process_key(Key::Char('\u{301}'));
+            assert_eq!(editor.buffer.line(0), "e\u{301}");
+            assert_eq!(editor.get_text_position().column, 1);
+
+            editor.history.commit();
+            editor.process_key(Key::Custom(EditorKey::Backspace));
+            assert_eq!(editor.buffer.line(0), "");
+
+            editor.process_key(Key::U8(ctrl_key('z')));
+            assert_eq!(editor.buffer.line(0), "e\u{301}");
+        }
+
+        #[test]
+        fn test_insert_line_in_multi_byte_line() {
+            let mut editor = editor_with_line("日本語");
+            editor.cursor_position.x += 1;
+
+            editor.insert_line();
+            assert_eq!(editor.buffer.line(0), "日");
+            assert_eq!(editor.buffer.line(1), "本語");
+        }
+
+        #[test]
+        fn test_wide_characters_render_width() {
+            let editor = editor_with_line("中\tx");
+
+            assert_eq!(editor.rows[0].render, "中  x");
+            assert_eq!(editor.cursor_x_to_render_x(1), 2);
+            assert_eq!(editor.cursor_x_to_render_x(2), 4);
+            assert_eq!(editor.render_x_to_cursor_x(1), 0);
+            assert_eq!(editor.render_x_to_cursor_x(2), 1);
+            assert_eq!(editor.render_x_to_cursor_x(4), 2);
+        }
+
+        #[test]
+        fn test_cursor_movement_over_graphemes() {
+            let mut editor = editor_with_line("👍🏽é");
+
+            editor.move_cursor(CursorMovement::Right);
+            assert_eq!(editor.get_text_position().column, 1);
+
+            editor.process_key(Key::Custom(EditorKey::End));
+            assert_eq!(editor.get_text_position().column, 2);
+        }
+
+        #[test]
+        fn test_find_after_multi_byte_characters() {
+            let mut editor = editor_with_line("naïve café test");
+
+            editor.find_text_callback("café", Key::Custom(EditorKey::ArrowDown));
+
+            assert_eq!(editor.get_text_position().column, 6);
+            assert_eq!(
+                editor.rows[0].highlight[6..10],
+                vec![HighlightType::SearchMatch; 4]
+            );
+            assert_eq!(editor.rows[0].highlight[10], HighlightType::Normal);
+        }
+    }
 }
@@ -0,0 +1,819 @@
+use crate::{
+    input::Key,
+    keymap::{self, Command, Mode},
+    settings::OPTIONS,
+    theme::theme_names,
+};
+use std::{fs, path::Path};
+
+/// Command names with the length of their shortest accepted abbreviation, e.g. `:s`
+const COMMANDS: &[(&str, usize)] = &[
+    ("delete", 1),
+    ("edit", 1),
+    ("imap", 2),
+    ("iunmap", 2),
+    ("map", 3),
+    ("nmap", 2),
+    ("nunmap", 3),
+    ("quit", 1),
+    ("set", 2),
+    ("setfiletype", 4),
+    ("substitute", 1),
+    ("unmap", 3),
+    ("vmap", 2),
+    ("vunmap", 2),
+    ("wq", 2),
+    ("write", 1),
+    ("xit", 1),
+];
+
+/// A line number as typed, before it is resolved against the buffer
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Address {
+    Line(usize), // 1-based
+    Current,     // .
+    Last,        // $
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineAddress {
+    pub base: Address,
+    pub offset: isize, // e.g. `.+3` or `$-1`
+}
+
+/// `%`, a single address or `start,end`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LineRange {
+    pub start: LineAddress,
+    pub end: LineAddress,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SetAction {
+    Show,           // `:set name?`
+    Enable,         // `:set name`
+    Disable,        // `:set noname`
+    Assign(String), // `:set name=value`
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetArgument {
+    pub option: String,
+    pub action: SetAction,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExCommand {
+    GoToLine, // A range on its own, e.g. `:42`
+    Delete,
+    Substitute {
+        pattern: String,
+        replacement: String,
+        global: bool,  // Every match on a line rather than the first
+        confirm: bool, // Ask about each match
+    },
+    Write {
+        file_path: Option<String>,
+        quit: bool, // `:wq` and `:x`
+    },
+    Quit {
+        force: bool, // Discards unsaved changes
+    },
+    Edit {
+        file_path: Option<String>, // Reloads the current file if `None`
+        force: bool,
+    },
+    Set(Vec<SetArgument>),
+    SetFileType(String), // Highlights the buffer as this language, e.g. `rust`
+    Map {
+        modes: &'static [Mode],
+        keys: Option<Vec<Key>>,   // Lists the bindings if `None`
+        command: Option<Command>, // Shows the binding of `keys` if `None`
+    },
+    Unmap {
+        modes: &'static [Mode],
+        keys: Vec<Key>,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommandLine {
+    pub range: Option<LineRange>,
+    pub command: ExCommand,
+}
+
+impl LineAddress {
+    pub fn current_line() -> Self {
+        LineAddress {
+            base: Address::Current,
+            offset: 0,
+        }
+    }
+
+    /// Returns the 1-based line number, which may lie outside the buffer
+    pub fn resolve(&self, current_line_number: usize, num_lines: usize) -> Option<usize> {
+        let base = match self.base {
+            Address::Line(line_number) => line_number,
+            Address::Current => current_line_number,
+            Address::Last => num_lines,
+        };
+
+        base.checked_add_signed(self.offset)
+    }
+}
+
+fn take_number(input: &mut &str) -> Option<usize> {
+    let num_digits = input.chars().take_while(char::is_ascii_digit).count();
+
+    if num_digits == 0 {
+        return None;
+    }
+
+    let (digits, rest) = input.split_at(num_digits);
+    *input = rest;
+
+    digits.parse().ok()
+}
+
+/// Parses `N`, `.`, `$` or nothing (the current line), followed by any number of `+N`/`-N`
+fn parse_address(input: &mut &str) -> Option<LineAddress> {
+    let base = if let Some(line_number) = take_number(input) {
+        Some(Address::Line(line_number))
+    } else if let Some(rest) = input.strip_prefix('.') {
+        *input = rest;
+        Some(Address::Current)
+    } else if let Some(rest) = input.strip_prefix('$') {
+        *input = rest;
+        Some(Address::Last)
+    } else {
+        None
+    };
+
+    let mut offset = 0;
+    let mut has_offset = false;
+
+    while let Some(sign) = input
+        .chars()
+        .next()
+        .filter(|char| *char == '+' || *char == '-')
+    {
+        *input = &input[1..];
+
+        let amount = take_number(input).unwrap_or(1) as isize;
+        offset += if sign == '+' { amount } else { -amount };
+        has_offset = true;
+    }
+
+    match (base, has_offset) {
+        (None, false) => None,
+        (base, _) => Some(LineAddress {
+            base: base.unwrap_or(Address::Current),
+            offset,
+        }),
+    }
+}
+
+fn parse_range(input: &mut &str) -> Option<LineRange> {
+    if let Some(rest) = input.strip_prefix('%') {
+        *input = rest;
+
+        return Some(LineRange {
+            start: LineAddress {
+                base: Address::Line(1),
+                offset: 0,
+            },
+            end: LineAddress {
+                base: Address::Last,
+                offset: 0,
+            },
+        });
+    }
+
+    let start = parse_address(input)?;
+
+    let end = match input.strip_prefix(',') {
+        Some(rest) => {
+            *input = rest;
+            parse_address(input).unwrap_or(start)
+        }
+        None => start,
+    };
+
+    Some(LineRange { start, end })
+}
+
+fn find_command_name(name: &str) -> Option<&'static str> {
+    COMMANDS
+        .iter()
+        .find(|(command_name, min_length)| {
+            name.len() >= *min_length && command_name.starts_with(name)
+        })
+        .map(|(command_name, _)| *command_name)
+}
+
+/// Splits `/pattern/replacement/flags` at the delimiter that follows the command name
+/// A backslash escapes the delimiter.
+fn split_substitute_arguments(arguments: &str) -> Option<Vec<String>> {
+    let mut chars = arguments.chars();
+    let delimiter = chars
+        .next()
+        .filter(|char| !char.is_alphanumeric() && !char.is_whitespace() && *char != '\\')?;
+
+    let mut parts = vec![String::new()];
+
+    while let Some(char) = chars.next() {
+        match char {
+            '\\' => match chars.next() {
+                Some(next) if next == delimiter => parts.last_mut()?.push(next),
+                Some(next) => {
+                    parts.last_mut()?.push('\\');
+                    parts.last_mut()?.push(next);
+                }
+                None => parts.last_mut()?.push('\\'),
+            },
+            char if char == delimiter && parts.len() < 3 => parts.push(String::new()),
+            char => parts.last_mut()?.push(char),
+        }
+    }
+
+    Some(parts)
+}
+
+fn parse_substitute(arguments: &str) -> Result<ExCommand, String> {
+    let parts = split_substitute_arguments(arguments)
+        .ok_or_else(|| String::from("Usage: :s/pattern/replacement/flags"))?;
+
+    let pattern = parts[0].clone();
+
+    if pattern.is_empty() {
+        return Err(String::from("Empty search pattern"));
+    }
+
+    let mut global = false;
+    let mut confirm = false;
+
+    for flag in parts.get(2).map_or("", String::as_str).chars() {
+        match flag {
+            'g' => global = true,
+            'c' => confirm = true,
+            _ => return Err(format!("Invalid flag: {}", flag)),
+        }
+    }
+
+    Ok(ExCommand::Substitute {
+        pattern,
+        replacement: parts.get(1).cloned().unwrap_or_default(),
+        global,
+        confirm,
+    })
+}
+
+/// The modes that `:map`, `:unmap` and their mode-specific variants apply to
+fn get_map_modes(command_name: &str) -> Option<&'static [Mode]> {
+    match command_name {
+        "map" | "unmap" => Some(&[Mode::Normal, Mode::Visual]),
+        "nmap" | "nunmap" => Some(&[Mode::Normal]),
+        "imap" | "iunmap" => Some(&[Mode::Insert]),
+        "vmap" | "vunmap" => Some(&[Mode::Visual]),
+        _ => None,
+    }
+}
+
+/// Parses `{keys} {command}`, either of which may be left out
+fn parse_map(modes: &'static [Mode], arguments: &str) -> Result<ExCommand, String> {
+    let (keys, command) = match arguments.trim().split_once(char::is_whitespace) {
+        Some((keys, command)) => (keys, command.trim()),
+        None => (arguments.trim(), ""),
+    };
+
+    Ok(ExCommand::Map {
+        modes,
+        keys: match keys {
+            "" => None,
+            keys => Some(keymap::parse_keys(keys)?),
+        },
+        command: match command {
+            "" => None,
+            command => Some(keymap::find_command(command)?),
+        },
+    })
+}
+
+fn parse_unmap(modes: &'static [Mode], arguments: &str) -> Result<ExCommand, String> {
+    match arguments.split_whitespace().collect::<Vec<&str>>()[..] {
+        [] => Err(String::from("Argument required")),
+        [keys] => Ok(ExCommand::Unmap {
+            modes,
+            keys: keymap::parse_keys(keys)?,
+        }),
+        [_, ref rest @ ..] => Err(format!("Trailing characters: {}", rest.join(" "))),
+    }
+}
+
+fn parse_set_argument(argument: &str) -> SetArgument {
+    if let Some((option, value)) = argument.split_once('=') {
+        return SetArgument {
+            option: option.to_string(),
+            action: SetAction::Assign(value.to_string()),
+        };
+    }
+
+    if let Some(option) = argument.strip_suffix('?') {
+        return SetArgument {
+            option: option.to_string(),
+            action: SetAction::Show,
+        };
+    }
+
+    match argument.strip_prefix("no") {
+        Some(option) if !OPTIONS.contains(&argument) => SetArgument {
+            option: option.to_string(),
+            action: SetAction::Disable,
+        },
+        _ => SetArgument {
+            option: argument.to_string(),
+            action: SetAction::Enable,
+        },
+    }
+}
+
+/// Parses what was typed after `:`, returning an error message for the status bar if it is
+/// not a valid command
+pub fn parse(input: &str) -> Result<CommandLine, String> {
+    let mut rest = input.trim_start();
+    let range = parse_range(&mut rest);
+
+    let name_length = rest.chars().take_while(char::is_ascii_alphabetic).count();
+    let (name, mut arguments) = rest.split_at(name_length);
+
+    if name.is_empty() && arguments.trim().is_empty() {
+        return match range {
+            Some(range) => Ok(CommandLine {
+                range: Some(range),
+                command: ExCommand::GoToLine,
+            }),
+            None => Err(String::from("No command given")),
+        };
+    }
+
+    let command_name = find_command_name(name)
+        .ok_or_else(|| format!("Not an editor command: {}", input.trim()))?;
+
+    let force = match arguments.strip_prefix('!') {
+        Some(stripped) => {
+            arguments = stripped;
+            true
+        }
+        None => false,
+    };
+
+    let file_path = match arguments.trim() {
+        "" => None,
+        file_path => Some(file_path.to_string()),
+    };
+
+    if range.is_some() && !matches!(command_name, "delete" | "substitute") {
+        return Err(String::from("No range allowed"));
+    }
+
+    if force
+        && (matches!(
+            command_name,
+            "delete" | "substitute" | "set" | "setfiletype"
+        ) || get_map_modes(command_name).is_some())
+    {
+        return Err(String::from("No ! allowed"));
+    }
+
+    let command = match command_name {
+        "delete" => match file_path {
+            Some(arguments) => return Err(format!("Trailing characters: {}", arguments)),
+            None => ExCommand::Delete,
+        },
+        "substitute" => parse_substitute(arguments)?,
+        "write" => ExCommand::Write {
+            file_path,
+            quit: false,
+        },
+        "wq" | "xit" => ExCommand::Write {
+            file_path,
+            quit: true,
+        },
+        "quit" => match file_path {
+            Some(arguments) => return Err(format!("Trailing characters: {}", arguments)),
+            None => ExCommand::Quit { force },
+        },
+        "edit" => ExCommand::Edit { file_path, force },
+        "set" => ExCommand::Set(
+            arguments
+                .split_whitespace()
+                .map(parse_set_argument)
+                .collect(),
+        ),
+        "setfiletype" => match arguments.split_whitespace().collect::<Vec<&str>>()[..] {
+            [] => return Err(String::from("Argument required")),
+            [file_type] => ExCommand::SetFileType(file_type.to_string()),
+            [_, ref rest @ ..] => return Err(format!("Trailing characters: {}", rest.join(" "))),
+        },
+        "map" | "nmap" | "imap" | "vmap" => parse_map(
+            get_map_modes(command_name).expect("Map commands have modes"),
+            arguments,
+        )?,
+        "unmap" | "nunmap" | "iunmap" | "vunmap" => parse_unmap(
+            get_map_modes(command_name).expect("Unmap commands have modes"),
+            arguments,
+        )?,
+        _ => unreachable!("Every command name is handled"),
+    };
+
+    Ok(CommandLine { range, command })
+}
+
+/// Lists the files and directories starting with `partial_path`, with a `/` after directories
+/// Hidden files are only listed once their name has been started with `.`.
+fn complete_file_path(partial_path: &str) -> Vec<String> {
+    let (directory, prefix) = match partial_path.rfind('/') {
+        Some(index) => partial_path.split_at(index + 1),
+        None => ("", partial_path),
+    };
+
+    let entries = match fs::read_dir(match directory {
+        "" => Path::new("."),
+        directory => Path::new(directory),
+    }) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+
+    let mut completions: Vec<String> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+
+            let is_directory = entry.file_type().is_ok_and(|file_type| file_type.is_dir());
+
+            Some(format!(
+                "{}{}{}",
+                directory,
+                name,
+                if is_directory { "/" } else { "" }
+            ))
+        })
+        .collect();
+
+    completions.sort();
+    completions
+}
+
+/// Returns the possible completions of the whole of `input`: command names, then file paths
+/// for `:w` and `:e`, option names and themes for `:set` and editor commands for `:map`
+pub fn complete(input: &str) -> Vec<String> {
+    let mut rest = input.trim_start();
+    parse_range(&mut rest);
+
+    let name_length = rest.chars().take_while(char::is_ascii_alphabetic).count();
+    let (name, arguments) = rest.split_at(name_length);
+    let before_name = &input[..input.len() - rest.len()];
+
+    if arguments.is_empty() {
+        return COMMANDS
+            .iter()
+            .filter(|(command_name, _)| command_name.starts_with(name))
+            .map(|(command_name, _)| format!("{}{}", before_name, command_name))
+            .collect();
+    }
+
+    if !arguments.starts_with([' ', '!']) {
+        return vec![]; // The name is not finished, e.g. `:s/`
+    }
+
+    let argument_start = arguments
+        .trim_start_matches('!')
+        .trim_start()
+        .rsplit(' ')
+        .next()
+        .unwrap_or_default();
+    let before_argument = &input[..input.len() - argument_start.len()];
+
+    match find_command_name(name) {
+        Some("write" | "wq" | "xit" | "edit") => complete_file_path(argument_start),
+        Some("set") => match argument_start.strip_prefix("theme=") {
+            Some(theme_start) => theme_names()
+                .filter(|theme| theme.starts_with(theme_start))
+                .map(|theme| format!("theme={}", theme))
+                .collect(),
+            None => OPTIONS
+                .iter()
+                .filter(|option| option.starts_with(argument_start))
+                .map(|option| option.to_string())
+                .collect(),
+        },
+        // The command follows the keys
+        Some("map" | "nmap" | "imap" | "vmap") if arguments.trim_start().contains(' ') => {
+            keymap::command_names()
+                .filter(|name| name.starts_with(argument_start))
+                .map(|name| name.to_string())
+                .collect()
+        }
+        _ => vec![],
+    }
+    .into_iter()
+    .map(|completion| format!("{}{}", before_argument, completion))
+    .collect()
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::input::EditorKey;
+    use tempfile::tempdir;
+
+    fn line(line_number: usize) -> LineAddress {
+        LineAddress {
+            base: Address::Line(line_number),
+            offset: 0,
+        }
+    }
+
+    fn command(input: &str) -> ExCommand {
+        parse(input).unwrap().command
+    }
+
+    #[test]
+    fn test_parse_ranges() {
+        assert_eq!(
+            parse("10,20d").unwrap(),
+            CommandLine {
+                range: Some(LineRange {
+                    start: line(10),
+                    end: line(20)
+                }),
+                command: ExCommand::Delete,
+            }
+        );
+
+        let range = parse("%d").unwrap().range.unwrap();
+        assert_eq!(range.start, line(1));
+        assert_eq!(range.end.base, Address::Last);
+
+        let range = parse(".,+2d").unwrap().range.unwrap();
+        assert_eq!(range.start.base, Address::Current);
+        assert_eq!(
+            range.end,
+            LineAddress {
+                base: Address::Current,
+                offset: 2
+            }
+        );
+
+        let range = parse("$-1").unwrap().range.unwrap();
+        assert_eq!(range.start.resolve(1, 10), Some(9));
+    }
+
+    #[test]
+    fn test_parse_go_to_line() {
+        let command_line = parse("42").unwrap();
+
+        assert_eq!(command_line.command, ExCommand::GoToLine);
+        assert_eq!(command_line.range.unwrap().start, line(42));
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn test_parse_abbreviations() {
+        assert_eq!(command("d"), ExCommand::Delete);
+        assert_eq!(command("del"), ExCommand::Delete);
+        assert_eq!(command("q"), ExCommand::Quit { force: false });
+        assert_eq!(command("quit!"), ExCommand::Quit { force: true });
+        assert_eq!(command("se tabstop=2"), command("set tabstop=2"));
+        assert_eq!(
+            parse("deletex"),
+            Err(String::from("Not an editor command: deletex"))
+        );
+    }
+
+    #[test]
+    fn test_parse_write_and_edit() {
+        assert_eq!(
+            command("w"),
+            ExCommand::Write {
+                file_path: None,
+                quit: false
+            }
+        );
+        assert_eq!(
+            command("w new name.txt"),
+            ExCommand::Write {
+                file_path: Some(String::from("new name.txt")),
+                quit: false
+            }
+        );
+        assert_eq!(command("x"), command("wq"));
+        assert_eq!(
+            command("e! other.rs"),
+            ExCommand::Edit {
+                file_path: Some(String::from("other.rs")),
+                force: true
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_substitute() {
+        assert_eq!(
+            command("s/foo/bar/g"),
+            ExCommand::Substitute {
+                pattern: String::from("foo"),
+                replacement: String::from("bar"),
+                global: true,
+                confirm: false,
+            }
+        );
+        assert_eq!(
+            command("s#a\\#b#c"),
+            ExCommand::Substitute {
+                pattern: String::from("a#b"),
+                replacement: String::from("c"),
+                global: false,
+                confirm: false,
+            }
+        );
+        assert_eq!(
+            command("s/\\d+/"),
+            ExCommand::Substitute {
+                pattern: String::from("\\d+"),
+                replacement: String::new(),
+                global: false,
+                confirm: false,
+            }
+        );
+        assert_eq!(parse("s/a/b/x"), Err(String::from("Invalid flag: x")));
+        assert!(parse("s//b/").is_err());
+        assert!(parse("s").is_err());
+    }
+
+    #[test]
+    fn test_parse_set() {
+        assert_eq!(
+            command("set tabstop=2 nobackup tabstop?"),
+            ExCommand::Set(vec![
+                SetArgument {
+                    option: String::from("tabstop"),
+                    action: SetAction::Assign(String::from("2")),
+                },
+                SetArgument {
+                    option: String::from("backup"),
+                    action: SetAction::Disable,
+                },
+                SetArgument {
+                    option: String::from("tabstop"),
+                    action: SetAction::Show,
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_parse_setfiletype() {
+        assert_eq!(
+            command("setf python"),
+            ExCommand::SetFileType(String::from("python"))
+        );
+        assert_eq!(
+            command("setfiletype Rust"),
+            ExCommand::SetFileType(String::from("Rust"))
+        );
+        assert_eq!(parse("setf"), Err(String::from("Argument required")));
+        assert_eq!(
+            parse("setf c rust"),
+            Err(String::from("Trailing characters: rust"))
+        );
+        assert_eq!(parse("setf! c"), Err(String::from("No ! allowed")));
+    }
+
+    #[test]
+    fn test_parse_map() {
+        assert_eq!(
+            command("nmap ,w save"),
+            ExCommand::Map {
+                modes: &[Mode::Normal],
+                keys: Some(vec![Key::U8(b','), Key::U8(b'w')]),
+                command: Some(Command::Save),
+            }
+        );
+        assert_eq!(
+            command("map <F5>   quit "),
+            ExCommand::Map {
+                modes: &[Mode::Normal, Mode::Visual],
+                keys: Some(vec![Key::Custom(EditorKey::Function(5))]),
+                command: Some(Command::Quit),
+            }
+        );
+        assert_eq!(
+            command("im jk"),
+            ExCommand::Map {
+                modes: &[Mode::Insert],
+                keys: Some(vec![Key::U8(b'j'), Key::U8(b'k')]),
+                command: None,
+            }
+        );
+        assert_eq!(
+            command("vmap"),
+            ExCommand::Map {
+                modes: &[Mode::Visual],
+                keys: None,
+                command: None,
+            }
+        );
+        assert_eq!(
+            command("iunmap jk"),
+            ExCommand::Unmap {
+                modes: &[Mode::Insert],
+                keys: vec![Key::U8(b'j'), Key::U8(b'k')],
+            }
+        );
+
+        assert_eq!(
+            parse("nmap ,w sve"),
+            Err(String::from("Unknown command: sve"))
+        );
+        assert_eq!(
+            parse("nmap <Nope> save"),
+            Err(String::from("Unknown key: <Nope>"))
+        );
+        assert_eq!(parse("unmap"), Err(String::from("Argument required")));
+        assert_eq!(
+            parse("unmap ,w save"),
+            Err(String::from("Trailing characters: save"))
+        );
+        assert_eq!(parse("map! ,w save"), Err(String::from("No ! allowed")));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse("foo"),
+            Err(String::from("Not an editor command: foo"))
+        );
+        assert_eq!(parse("1,2w"), Err(String::from("No range allowed")));
+        assert_eq!(parse("d!"), Err(String::from("No ! allowed")));
+        assert_eq!(
+            parse("q now"),
+            Err(String::from("Trailing characters: now"))
+        );
+    }
+
+    #[test]
+    fn test_complete_command_names() {
+        assert_eq!(complete("s"), vec!["set", "setfiletype", "substitute"]);
+        assert_eq!(complete("%su"), vec!["%substitute"]);
+        assert_eq!(complete("w"), vec!["wq", "write"]);
+        assert!(complete("s/").is_empty());
+    }
+
+    #[test]
+    fn test_complete_file_paths() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("main.rs"), "").unwrap();
+        fs::write(dir.path().join(".hidden"), "").unwrap();
+        fs::create_dir(dir.path().join("modules")).unwrap();
+
+        let dir_path = dir.path().to_str().unwrap();
+
+        assert_eq!(
+            complete(&format!("e {}/m", dir_path)),
+            vec![
+                format!("e {}/main.rs", dir_path),
+                format!("e {}/modules/", dir_path)
+            ]
+        );
+        assert_eq!(
+            complete(&format!("w! {}/.h", dir_path)),
+            vec![format!("w! {}/.hidden", dir_path)]
+        );
+        assert_eq!(complete(&format!("w {}/", dir_path)).len(), 2);
+    }
+
+    #[test]
+    fn test_complete_options() {
+        assert_eq!(complete("set tab"), vec!["set tabstop"]);
+        assert_eq!(complete("set tabstop=2 b"), vec!["set tabstop=2 backup"]);
+        assert_eq!(
+            complete("set theme=g"),
+            vec!["set theme=gruvbox-dark", "set theme=github-light"]
+        );
+    }
+
+    #[test]
+    fn test_complete_map_commands() {
+        assert_eq!(complete("nm"), vec!["nmap"]);
+        assert_eq!(complete("nmap ,w sa"), vec!["nmap ,w save"]);
+        assert_eq!(complete("imap jk normal"), vec!["imap jk normal_mode"]);
+        assert!(complete("nmap ,w ").contains(&String::from("nmap ,w go_to_line")));
+        assert_eq!(complete("nmap sa"), Vec::<String>::new());
+    }
+}
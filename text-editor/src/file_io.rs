@@ -0,0 +1,266 @@
+use crate::text_buffer::TextBuffer;
+use std::{
+    ffi::OsString,
+    fs::{self, File, Metadata, OpenOptions},
+    io::{self, ErrorKind},
+    os::unix::fs::{fchown, MetadataExt, OpenOptionsExt},
+    path::{Path, PathBuf},
+    process,
+};
+
+const NEW_FILE_MODE: u32 = 0o644; // Owner R/W; others R
+const PRIVATE_FILE_MODE: u32 = 0o600; // Owner R/W only
+const MAX_TEMP_FILE_ATTEMPTS: u32 = 100;
+
+pub fn get_backup_path(path: &Path) -> PathBuf {
+    let mut backup_path = path.as_os_str().to_owned();
+    backup_path.push("~");
+    PathBuf::from(backup_path)
+}
+
+/// Returns the file that saving to `path` should replace
+/// Symlinks are followed so that the link itself survives the save.
+fn resolve_symlinks(path: &Path) -> io::Result<PathBuf> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => match fs::canonicalize(path) {
+            Ok(target) => Ok(target),
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                // Dangling link: create the file it points to
+                let link_target = fs::read_link(path)?;
+                Ok(path.parent().unwrap_or(Path::new("")).join(link_target))
+            }
+            Err(e) => Err(e),
+        },
+        _ => Ok(path.to_path_buf()),
+    }
+}
+
+/// Creates a hidden file next to `target`, so that it can be renamed over it
+fn create_temp_file(target: &Path, mode: u32) -> io::Result<(PathBuf, File)> {
+    let file_name = target
+        .file_name()
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "path does not name a file"))?;
+
+    let mut attempt = 0;
+
+    loop {
+        let mut temp_file_name = OsString::from(".");
+        temp_file_name.push(file_name);
+        temp_file_name.push(format!(".{}.{}.tmp", process::id(), attempt));
+
+        let temp_path = target.with_file_name(temp_file_name);
+
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(mode)
+            .open(&temp_path)
+        {
+            Ok(temp_file) => return Ok((temp_path, temp_file)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists && attempt < MAX_TEMP_FILE_ATTEMPTS => {
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+fn write_temp_file(
+    temp_file: &File,
+    buffer: &TextBuffer,
+    original_metadata: Option<&Metadata>,
+) -> io::Result<()> {
+    buffer.write_to(temp_file)?;
+
+    if let Some(metadata) = original_metadata {
+        // The mode passed on creation is subject to the umask
+        temp_file.set_permissions(metadata.permissions())?;
+
+        // Changing the owner requires privileges, so fall back to keeping just the group
+        if fchown(temp_file, Some(metadata.uid()), Some(metadata.gid())).is_err() {
+            let _ = fchown(temp_file, None, Some(metadata.gid()));
+        }
+    }
+
+    temp_file.sync_all()
+}
+
+/// Flushes the rename to disk; not all file systems support this, so failures are ignored
+fn sync_parent_directory(path: &Path) {
+    let directory = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    if let Ok(directory) = File::open(directory) {
+        let _ = directory.sync_all();
+    }
+}
+
+/// Saves `buffer` to `path` by writing a temporary file alongside it and renaming it into place,
+/// so that the original is left untouched if anything fails part way through
+/// The original's permissions, owner (where possible) and symlinks are preserved.
+/// Returns the number of bytes written.
+pub fn write_file_atomically(
+    path: &Path,
+    buffer: &TextBuffer,
+    create_backup: bool,
+) -> io::Result<usize> {
+    write_atomically(path, buffer, create_backup, None)
+}
+
+/// Saves `buffer` to `path` as `write_file_atomically` does, but readable only by the owner,
+/// whatever the permissions of a file it replaces
+pub fn write_private_file_atomically(path: &Path, buffer: &TextBuffer) -> io::Result<usize> {
+    write_atomically(path, buffer, false, Some(PRIVATE_FILE_MODE))
+}
+
+fn write_atomically(
+    path: &Path,
+    buffer: &TextBuffer,
+    create_backup: bool,
+    mode: Option<u32>,
+) -> io::Result<usize> {
+    let target = resolve_symlinks(path)?;
+
+    let original_metadata = match fs::metadata(&target) {
+        Ok(metadata) => Some(metadata),
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => return Err(e),
+    };
+
+    if create_backup && original_metadata.is_some() {
+        fs::copy(&target, get_backup_path(&target))?;
+    }
+
+    // A given mode replaces the original's permissions and owner
+    let original_metadata = original_metadata.filter(|_| mode.is_none());
+    let mode = mode.unwrap_or_else(|| {
+        original_metadata
+            .as_ref()
+            .map_or(NEW_FILE_MODE, |metadata| metadata.mode())
+    });
+
+    let (temp_path, temp_file) = create_temp_file(&target, mode)?;
+
+    let result = write_temp_file(&temp_file, buffer, original_metadata.as_ref())
+        .and_then(|_| fs::rename(&temp_path, &target));
+
+    if let Err(e) = result {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e);
+    }
+
+    sync_parent_directory(&target);
+
+    Ok(buffer.len_bytes())
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    use tempfile::tempdir;
+
+    fn buffer_from(text: &str) -> TextBuffer {
+        TextBuffer::from_reader(text.as_bytes()).unwrap()
+    }
+
+    fn file_names_in(directory: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_write_new_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("new.txt");
+
+        let bytes = write_file_atomically(&path, &buffer_from("Hello\nWorld\n"), false).unwrap();
+
+        assert_eq!(bytes, 12);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Hello\nWorld\n");
+        assert_eq!(file_names_in(dir.path()), vec!["new.txt"]);
+    }
+
+    #[test]
+    fn test_write_preserves_permissions() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("script.sh");
+
+        fs::write(&path, "echo old\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        write_file_atomically(&path, &buffer_from("echo new\n"), false).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "echo new\n");
+    }
+
+    #[test]
+    fn test_write_creates_backup() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+
+        fs::write(&path, "original\n").unwrap();
+
+        write_file_atomically(&path, &buffer_from("updated\n"), true).unwrap();
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "updated\n");
+        assert_eq!(
+            fs::read_to_string(dir.path().join("notes.txt~")).unwrap(),
+            "original\n"
+        );
+    }
+
+    #[test]
+    fn test_write_without_original_skips_backup() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("notes.txt");
+
+        write_file_atomically(&path, &buffer_from("new\n"), true).unwrap();
+
+        assert_eq!(file_names_in(dir.path()), vec!["notes.txt"]);
+    }
+
+    #[test]
+    fn test_write_follows_symlink() {
+        let dir = tempdir().unwrap();
+        let target = dir.path().join("target.txt");
+        let link = dir.path().join("link.txt");
+
+        fs::write(&target, "old\n").unwrap();
+        symlink(&target, &link).unwrap();
+
+        write_file_atomically(&link, &buffer_from("new\n"), false).unwrap();
+
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(fs::read_to_string(&target).unwrap(), "new\n");
+    }
+
+    #[test]
+    fn test_write_failure_leaves_no_files() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("missing").join("file.txt");
+
+        assert!(write_file_atomically(&path, &buffer_from("text\n"), false).is_err());
+        assert!(file_names_in(dir.path()).is_empty());
+    }
+
+    #[test]
+    fn test_get_backup_path() {
+        assert_eq!(
+            get_backup_path(Path::new("/tmp/file.rs")),
+            PathBuf::from("/tmp/file.rs~")
+        );
+    }
+}
@@ -0,0 +1,326 @@
+/// A cursor location in text coordinates: a line index and a grapheme column that is
+/// not offset by the line number gutter
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TextPosition {
+    pub line: usize,
+    pub column: usize,
+}
+
+/// A single reversible change to the buffer at a `char` index. `text` may span lines.
+/// `char` indices (rather than grapheme columns) stay valid when combining characters merge
+/// with their neighbours.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Edit {
+    Insert { char_index: usize, text: String },
+    Delete { char_index: usize, text: String },
+}
+
+impl Edit {
+    pub fn inverse(&self) -> Edit {
+        match self {
+            Edit::Insert { char_index, text } => Edit::Delete {
+                char_index: *char_index,
+                text: text.clone(),
+            },
+            Edit::Delete { char_index, text } => Edit::Insert {
+                char_index: *char_index,
+                text: text.clone(),
+            },
+        }
+    }
+
+    /// Attempts to fold `next` into `self` so that runs of typing or backspacing
+    /// are stored as one edit. Returns `false` if the edits are not contiguous.
+    fn merge(&mut self, next: &Edit) -> bool {
+        match (self, next) {
+            (
+                Edit::Insert { char_index, text },
+                Edit::Insert {
+                    char_index: next_char_index,
+                    text: next_text,
+                },
+            ) if !text.contains('\n')
+                && !next_text.contains('\n')
+                && *next_char_index == *char_index + text.chars().count() =>
+            {
+                text.push_str(next_text);
+                true
+            }
+            (
+                Edit::Delete { char_index, text },
+                Edit::Delete {
+                    char_index: next_char_index,
+                    text: next_text,
+                },
+            ) if !next_text.contains('\n') => {
+                if next_char_index + next_text.chars().count() == *char_index {
+                    // Backspace
+                    text.insert_str(0, next_text);
+                    *char_index = *next_char_index;
+                    true
+                } else if next_char_index == char_index && !text.contains('\n') {
+                    // Delete
+                    text.push_str(next_text);
+                    true
+                } else {
+                    false
+                }
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A group of edits that are undone and redone together
+#[derive(Clone, Debug, PartialEq)]
+pub struct Transaction {
+    id: usize,
+    pub edits: Vec<Edit>,
+    pub cursor_before: TextPosition,
+    pub cursor_after: TextPosition,
+}
+
+pub struct History {
+    undo_stack: Vec<Transaction>,
+    redo_stack: Vec<Transaction>,
+    pending: Option<Transaction>,
+    next_id: usize,
+    saved_id: Option<usize>, // `None` once the saved state can no longer be reached
+}
+
+impl History {
+    pub fn new() -> Self {
+        History {
+            undo_stack: vec![],
+            redo_stack: vec![],
+            pending: None,
+            next_id: 1,
+            saved_id: Some(0),
+        }
+    }
+
+    /// Adds an edit to the open transaction, starting a new one if required
+    pub fn record(&mut self, edit: Edit, cursor_before: TextPosition, cursor_after: TextPosition) {
+        if self
+            .redo_stack
+            .iter()
+            .any(|transaction| Some(transaction.id) == self.saved_id)
+        {
+            self.saved_id = None;
+        }
+
+        self.redo_stack.clear();
+
+        match &mut self.pending {
+            Some(transaction) => {
+                let merged = transaction
+                    .edits
+                    .last_mut()
+                    .is_some_and(|last| last.merge(&edit));
+
+                if !merged {
+                    transaction.edits.push(edit);
+                }
+
+                transaction.cursor_after = cursor_after;
+            }
+            None => {
+                self.pending = Some(Transaction {
+                    id: self.next_id,
+                    edits: vec![edit],
+                    cursor_before,
+                    cursor_after,
+                });
+
+                self.next_id += 1;
+            }
+        }
+    }
+
+    /// Closes the open transaction so that subsequent edits are undone separately
+    pub fn commit(&mut self) {
+        if let Some(transaction) = self.pending.take() {
+            self.undo_stack.push(transaction);
+        }
+    }
+
+    /// Returns the transaction to revert, moving it onto the redo stack
+    pub fn undo(&mut self) -> Option<Transaction> {
+        self.commit();
+
+        let transaction = self.undo_stack.pop()?;
+        self.redo_stack.push(transaction.clone());
+
+        Some(transaction)
+    }
+
+    /// Returns the transaction to re-apply, moving it back onto the undo stack
+    pub fn redo(&mut self) -> Option<Transaction> {
+        self.commit();
+
+        let transaction = self.redo_stack.pop()?;
+        self.undo_stack.push(transaction.clone());
+
+        Some(transaction)
+    }
+
+    fn current_id(&self) -> usize {
+        match &self.pending {
+            Some(transaction) => transaction.id,
+            None => self
+                .undo_stack
+                .last()
+                .map_or(0, |transaction| transaction.id),
+        }
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.commit();
+        self.saved_id = Some(self.current_id());
+    }
+
+    pub fn is_at_saved_state(&self) -> bool {
+        self.saved_id == Some(self.current_id())
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    fn position(line: usize, column: usize) -> TextPosition {
+        TextPosition { line, column }
+    }
+
+    fn insert(char_index: usize, text: &str) -> Edit {
+        Edit::Insert {
+            char_index,
+            text: text.to_string(),
+        }
+    }
+
+    fn delete(char_index: usize, text: &str) -> Edit {
+        Edit::Delete {
+            char_index,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_edit_inverse() {
+        assert_eq!(insert(12, "ab").inverse(), delete(12, "ab"));
+        assert_eq!(delete(0, "\n").inverse(), insert(0, "\n"));
+    }
+
+    #[test]
+    fn test_consecutive_inserts_are_merged() {
+        let mut history = History::new();
+
+        history.record(insert(0, "a"), position(0, 0), position(0, 1));
+        history.record(insert(1, "b"), position(0, 1), position(0, 2));
+        history.record(insert(5, "c"), position(0, 5), position(0, 6));
+
+        let transaction = history.undo().unwrap();
+        assert_eq!(transaction.edits, vec![insert(0, "ab"), insert(5, "c")]);
+        assert_eq!(transaction.cursor_before, position(0, 0));
+        assert_eq!(transaction.cursor_after, position(0, 6));
+    }
+
+    #[test]
+    fn test_consecutive_backspaces_are_merged() {
+        let mut history = History::new();
+
+        history.record(delete(2, "c"), position(0, 3), position(0, 2));
+        history.record(delete(1, "b"), position(0, 2), position(0, 1));
+
+        assert_eq!(history.undo().unwrap().edits, vec![delete(1, "bc")]);
+    }
+
+    #[test]
+    fn test_consecutive_forward_deletes_are_merged() {
+        let mut history = History::new();
+
+        history.record(delete(1, "b"), position(0, 1), position(0, 1));
+        history.record(delete(1, "c"), position(0, 1), position(0, 1));
+
+        assert_eq!(history.undo().unwrap().edits, vec![delete(1, "bc")]);
+    }
+
+    #[test]
+    fn test_multi_byte_inserts_are_merged() {
+        let mut history = History::new();
+
+        history.record(insert(0, "é"), position(0, 0), position(0, 1));
+        history.record(insert(1, "🙂"), position(0, 1), position(0, 2));
+
+        assert_eq!(history.undo().unwrap().edits, vec![insert(0, "é🙂")]);
+    }
+
+    #[test]
+    fn test_undo_redo_order() {
+        let mut history = History::new();
+
+        history.record(insert(0, "a"), position(0, 0), position(0, 1));
+        history.commit();
+        history.record(insert(1, "b"), position(0, 1), position(0, 2));
+        history.commit();
+
+        assert_eq!(history.undo().unwrap().edits, vec![insert(1, "b")]);
+        assert_eq!(history.undo().unwrap().edits, vec![insert(0, "a")]);
+        assert!(history.undo().is_none());
+
+        assert_eq!(history.redo().unwrap().edits, vec![insert(0, "a")]);
+        assert_eq!(history.redo().unwrap().edits, vec![insert(1, "b")]);
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn test_new_edit_clears_redo_stack() {
+        let mut history = History::new();
+
+        history.record(insert(0, "a"), position(0, 0), position(0, 1));
+        history.undo();
+        history.record(insert(0, "b"), position(0, 0), position(0, 1));
+
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn test_saved_state_tracking() {
+        let mut history = History::new();
+        assert!(history.is_at_saved_state());
+
+        history.record(insert(0, "a"), position(0, 0), position(0, 1));
+        assert!(!history.is_at_saved_state());
+
+        history.mark_saved();
+        assert!(history.is_at_saved_state());
+
+        history.record(insert(1, "b"), position(0, 1), position(0, 2));
+        assert!(!history.is_at_saved_state());
+
+        history.undo();
+        assert!(history.is_at_saved_state());
+
+        history.undo();
+        assert!(!history.is_at_saved_state());
+
+        history.redo();
+        assert!(history.is_at_saved_state());
+    }
+
+    #[test]
+    fn test_saved_state_unreachable_after_diverging() {
+        let mut history = History::new();
+
+        history.record(insert(0, "a"), position(0, 0), position(0, 1));
+        history.mark_saved();
+        history.undo();
+        history.record(insert(0, "b"), position(0, 0), position(0, 1));
+        history.commit();
+
+        assert!(!history.is_at_saved_state());
+        history.undo();
+        assert!(!history.is_at_saved_state());
+    }
+}
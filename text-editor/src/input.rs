@@ -1,7 +1,10 @@
-use crate::{editor_instance::EditorInstance, globals::get_buffer_lock};
-use std::io::{self, Read};
+use crate::{editor_instance::EditorInstance, globals::get_buffer_lock, utils::ctrl_key};
+use std::{
+    io::{self, Read},
+    ops::BitOr,
+};
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum EditorKey {
     ArrowLeft,
     ArrowRight,
@@ -13,14 +16,85 @@ pub enum EditorKey {
     PageUp,
     PageDown,
     Backspace,
+    Insert,
+    BackTab,      // Shift-Tab
+    Function(u8), // F1-F12
 }
 
-#[derive(PartialEq)]
+/// Modifier keys held with a key, as a bitset
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Modifiers(u8);
+
+impl Modifiers {
+    pub const SHIFT: Modifiers = Modifiers(1);
+    pub const ALT: Modifiers = Modifiers(2);
+    pub const CTRL: Modifiers = Modifiers(4);
+
+    /// Decodes the modifier parameter of xterm and kitty sequences, which is 1 plus the bits
+    /// Other modifiers, such as Super and Caps Lock, are ignored.
+    fn from_parameter(parameter: u32) -> Self {
+        Modifiers((parameter.saturating_sub(1) & 0b111) as u8)
+    }
+
+    pub fn contains(self, modifiers: Modifiers) -> bool {
+        self.0 & modifiers.0 == modifiers.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for Modifiers {
+    type Output = Modifiers;
+
+    fn bitor(self, other: Modifiers) -> Modifiers {
+        Modifiers(self.0 | other.0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MouseButton {
+    Left,
+    Middle,
+    Right,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MouseEventKind {
+    Press(MouseButton),
+    Drag(MouseButton), // Moved while the button is held
+    Release,
+    ScrollUp,
+    ScrollDown,
+}
+
+/// A mouse event reported in SGR format, e.g. `\x1b[<0;12;5M`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MouseEvent {
+    pub kind: MouseEventKind,
+    pub column: u16, // Screen column, from 0
+    pub row: u32,    // Screen row, from 0
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Key {
     U8(u8),
+    Char(char), // Decoded multi-byte UTF-8 character
     Custom(EditorKey),
+    Modified(EditorKey, Modifiers), // Held with Shift, Alt and/or Ctrl
+    Alt(char),                      // Sent as Escape followed by the character
+    Paste(String),                  // Text pasted while bracketed paste mode is on
+    Mouse(MouseEvent),
 }
 
+const ESCAPE: u8 = b'\x1b';
+const MAX_ESCAPE_SEQUENCE_LENGTH: usize = 16;
+
+const PASTE_END: &[u8] = b"\x1b[201~";
+const MAX_PASTE_READ_TIMEOUTS: u8 = 10; // Gives up on a paste that never ends after ~1 second
+const MAX_MOUSE_EVENT_LENGTH: usize = 16; // `<button>;<column>;<row>`
+
 fn read_single_key() -> Option<u8> {
     let mut buffer = *get_buffer_lock();
 
@@ -31,71 +105,555 @@ fn read_single_key() -> Option<u8> {
     }
 }
 
-pub fn read_key_input() -> Option<Key> {
-    let esc = Key::U8(b'\x1b');
-
-    match read_single_key() {
-        Some(key) => match key {
-            b'\x1b' => {
-                let first = match read_single_key() {
-                    Some(key) => key,
-                    None => return Some(esc),
-                };
-
-                let second = match read_single_key() {
-                    Some(key) => key,
-                    None => return Some(esc),
-                };
-
-                match first {
-                    b'[' => match second {
-                        b'0'..=b'9' => {
-                            let third = match read_single_key() {
-                                Some(key) => key,
-                                None => return Some(esc),
-                            };
-
-                            match third {
-                                b'~' => match second {
-                                    b'1' => Some(Key::Custom(EditorKey::Home)),
-                                    b'3' => Some(Key::Custom(EditorKey::Delete)),
-                                    b'4' => Some(Key::Custom(EditorKey::End)),
-                                    b'5' => Some(Key::Custom(EditorKey::PageUp)),
-                                    b'6' => Some(Key::Custom(EditorKey::PageDown)),
-                                    b'7' => Some(Key::Custom(EditorKey::Home)),
-                                    b'8' => Some(Key::Custom(EditorKey::End)),
-                                    _ => Some(esc),
-                                },
-                                _ => Some(esc),
-                            }
-                        }
-                        _ => match second {
-                            b'A' => Some(Key::Custom(EditorKey::ArrowUp)),
-                            b'B' => Some(Key::Custom(EditorKey::ArrowDown)),
-                            b'C' => Some(Key::Custom(EditorKey::ArrowRight)),
-                            b'D' => Some(Key::Custom(EditorKey::ArrowLeft)),
-                            b'H' => Some(Key::Custom(EditorKey::Home)),
-                            b'F' => Some(Key::Custom(EditorKey::End)),
-                            _ => Some(esc),
-                        },
-                    },
-                    b'O' => match second {
-                        b'H' => Some(Key::Custom(EditorKey::Home)),
-                        b'F' => Some(Key::Custom(EditorKey::End)),
-                        _ => Some(esc),
-                    },
-                    _ => Some(esc),
-                }
+/// Decodes a multi-byte UTF-8 sequence whose leading byte has already been read
+fn read_utf8_character(first_byte: u8, mut read_next_byte: impl FnMut() -> Option<u8>) -> Key {
+    let sequence_length = match first_byte {
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF7 => 4,
+        _ => return Key::Char(char::REPLACEMENT_CHARACTER), // Stray continuation byte
+    };
+
+    let mut bytes = vec![first_byte];
+
+    for _ in 1..sequence_length {
+        match read_next_byte() {
+            Some(byte) => bytes.push(byte),
+            None => break,
+        }
+    }
+
+    match std::str::from_utf8(&bytes)
+        .ok()
+        .and_then(|string| string.chars().next())
+    {
+        Some(char) => Key::Char(char),
+        None => Key::Char(char::REPLACEMENT_CHARACTER),
+    }
+}
+
+/// Reads pasted text up to the `\x1b[201~` marker that ends it
+/// Terminals send newlines in pastes as carriage returns; these are converted to `\n`.
+fn read_paste(mut read_next_byte: impl FnMut() -> Option<u8>) -> String {
+    let mut bytes = vec![];
+    let mut num_timeouts = 0;
+
+    while !bytes.ends_with(PASTE_END) {
+        match read_next_byte() {
+            Some(byte) => {
+                bytes.push(byte);
+                num_timeouts = 0;
             }
-            127 => Some(Key::Custom(EditorKey::Backspace)),
-            _ => Some(Key::U8(key)),
+            None if num_timeouts < MAX_PASTE_READ_TIMEOUTS => num_timeouts += 1,
+            None => break,
+        }
+    }
+
+    if bytes.ends_with(PASTE_END) {
+        bytes.truncate(bytes.len() - PASTE_END.len());
+    }
+
+    String::from_utf8_lossy(&bytes)
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+}
+
+/// Reads the rest of an SGR mouse event following `\x1b[<`: `<button>;<column>;<row>`, then
+/// `M` for a press, drag or scroll, or `m` for a release
+/// The button's bits add 32 for motion and 64 for the wheel; modifier keys (4, 8, 16) are
+/// ignored. Returns `None` for malformed or unsupported events.
+fn read_mouse_event(mut read_next_byte: impl FnMut() -> Option<u8>) -> Option<MouseEvent> {
+    let mut parameters = vec![];
+
+    let terminator = loop {
+        match read_next_byte()? {
+            byte @ (b'M' | b'm') => break byte,
+            byte if parameters.len() < MAX_MOUSE_EVENT_LENGTH => parameters.push(byte),
+            _ => return None,
+        }
+    };
+
+    let parameters = std::str::from_utf8(&parameters).ok()?;
+    let mut numbers = parameters
+        .split(';')
+        .map(|number| number.parse::<u32>().ok());
+
+    let (button, column, row) = match (numbers.next(), numbers.next(), numbers.next()) {
+        (Some(Some(button)), Some(Some(column)), Some(Some(row))) => (button, column, row),
+        _ => return None,
+    };
+
+    if numbers.next().is_some() {
+        return None;
+    }
+
+    let pressed_button = match button & 0b11 {
+        0 => Some(MouseButton::Left),
+        1 => Some(MouseButton::Middle),
+        2 => Some(MouseButton::Right),
+        _ => None, // Motion with no button held
+    };
+
+    let kind = match (button & 64 != 0, button & 32 != 0, terminator) {
+        (true, _, _) => match button & 0b11 {
+            0 => MouseEventKind::ScrollUp,
+            1 => MouseEventKind::ScrollDown,
+            _ => return None, // Horizontal scrolling
+        },
+        (false, _, b'm') => MouseEventKind::Release,
+        (false, true, _) => MouseEventKind::Drag(pressed_button?),
+        (false, false, _) => MouseEventKind::Press(pressed_button?),
+    };
+
+    Some(MouseEvent {
+        kind,
+        column: u16::try_from(column.checked_sub(1)?).ok()?,
+        row: row.checked_sub(1)?,
+    })
+}
+
+/// Decodes a key from its first byte, reading the rest of its bytes
+fn decode_key(first_byte: u8, read_next_byte: impl FnMut() -> Option<u8>) -> Option<Key> {
+    match first_byte {
+        ESCAPE => read_escape_sequence(read_next_byte),
+        127 => Some(Key::Custom(EditorKey::Backspace)),
+        0x80.. => Some(read_utf8_character(first_byte, read_next_byte)),
+        _ => Some(Key::U8(first_byte)),
+    }
+}
+
+/// Reads what follows an Escape: a CSI (`\x1b[`) or SS3 (`\x1bO`) sequence, or a key held
+/// with Alt (e.g. `\x1bx`)
+/// Escape on its own is told apart by nothing following it within the read timeout (`VTIME`).
+/// Unrecognized sequences are dropped rather than read as Escape.
+fn read_escape_sequence(mut read_next_byte: impl FnMut() -> Option<u8>) -> Option<Key> {
+    let introducer = match read_next_byte() {
+        Some(byte) => byte,
+        None => return Some(Key::U8(ESCAPE)),
+    };
+
+    match introducer {
+        b'[' | b'O' => {
+            let first_byte = match read_next_byte() {
+                Some(byte) => byte,
+                None => return Some(Key::Alt(introducer as char)),
+            };
+
+            match introducer {
+                b'[' => read_csi_sequence(first_byte, read_next_byte),
+                _ => read_ss3_sequence(first_byte, read_next_byte),
+            }
+        }
+        _ => decode_key(introducer, read_next_byte).map(with_alt),
+    }
+}
+
+/// Reads a CSI sequence: parameters such as `1;5`, then a final byte such as `C` (Ctrl-Right)
+fn read_csi_sequence(
+    first_byte: u8,
+    mut read_next_byte: impl FnMut() -> Option<u8>,
+) -> Option<Key> {
+    if first_byte == b'<' {
+        return read_mouse_event(read_next_byte).map(Key::Mouse);
+    }
+
+    let mut parameter_bytes = vec![];
+    let mut final_byte = first_byte;
+
+    while !(0x40..=0x7E).contains(&final_byte) {
+        if !(0x20..=0x3F).contains(&final_byte)
+            || parameter_bytes.len() >= MAX_ESCAPE_SEQUENCE_LENGTH
+        {
+            return None;
+        }
+
+        parameter_bytes.push(final_byte);
+        final_byte = read_next_byte()?;
+    }
+
+    // Sub-parameters after a `:` (e.g. kitty's alternate keys) are ignored
+    let parameters = std::str::from_utf8(&parameter_bytes)
+        .ok()?
+        .split(';')
+        .map(|parameter| match parameter.split(':').next() {
+            Some("") | None => Some(0),
+            Some(number) => number.parse::<u32>().ok(),
+        })
+        .collect::<Option<Vec<u32>>>()?;
+
+    let parameter = |index: usize| parameters.get(index).copied().unwrap_or(0);
+    let modifiers = Modifiers::from_parameter(parameter(1));
+
+    match final_byte {
+        // Sent with a first parameter of 1 when modified, e.g. `\x1b[1;5C`
+        b'A'..=b'Z' if parameter(0) > 1 => None, // e.g. a Cursor Position Report
+        b'Z' => Some(with_modifiers(EditorKey::BackTab, modifiers)),
+        b'A'..=b'Z' => ss3_key(final_byte).map(|key| with_modifiers(key, modifiers)),
+        b'~' => match parameter(0) {
+            200 => Some(Key::Paste(read_paste(read_next_byte))),
+            27 => decode_code_point(parameter(2), modifiers), // xterm's modifyOtherKeys
+            number => tilde_key(number).map(|key| with_modifiers(key, modifiers)),
         },
-        None => None,
+        b'u' => decode_code_point(parameter(0), modifiers), // The kitty keyboard protocol
+        _ => None,
     }
 }
 
+/// Reads an SS3 sequence, which some terminals send for arrows and F1-F4, e.g. `\x1bOA`
+/// Older xterms put a modifier parameter first, e.g. `\x1bO5P` for Ctrl-F1.
+fn read_ss3_sequence(
+    first_byte: u8,
+    mut read_next_byte: impl FnMut() -> Option<u8>,
+) -> Option<Key> {
+    let (modifiers, final_byte) = match first_byte {
+        b'0'..=b'9' => (
+            Modifiers::from_parameter((first_byte - b'0').into()),
+            read_next_byte()?,
+        ),
+        _ => (Modifiers::default(), first_byte),
+    };
+
+    ss3_key(final_byte).map(|key| with_modifiers(key, modifiers))
+}
+
+/// The key named by the final byte of an SS3 sequence, which CSI sequences share
+fn ss3_key(final_byte: u8) -> Option<EditorKey> {
+    match final_byte {
+        b'A' => Some(EditorKey::ArrowUp),
+        b'B' => Some(EditorKey::ArrowDown),
+        b'C' => Some(EditorKey::ArrowRight),
+        b'D' => Some(EditorKey::ArrowLeft),
+        b'H' => Some(EditorKey::Home),
+        b'F' => Some(EditorKey::End),
+        b'P'..=b'S' => Some(EditorKey::Function(final_byte - b'P' + 1)),
+        _ => None,
+    }
+}
+
+/// The key named by the number in a sequence such as `\x1b[5~`
+fn tilde_key(number: u32) -> Option<EditorKey> {
+    match number {
+        1 | 7 => Some(EditorKey::Home),
+        2 => Some(EditorKey::Insert),
+        3 => Some(EditorKey::Delete),
+        4 | 8 => Some(EditorKey::End),
+        5 => Some(EditorKey::PageUp),
+        6 => Some(EditorKey::PageDown),
+        // F1-F12 skip 16 and 22
+        11..=15 => Some(EditorKey::Function(number as u8 - 10)),
+        17..=21 => Some(EditorKey::Function(number as u8 - 11)),
+        23 | 24 => Some(EditorKey::Function(number as u8 - 12)),
+        _ => None,
+    }
+}
+
+pub fn with_modifiers(key: EditorKey, modifiers: Modifiers) -> Key {
+    match modifiers.is_empty() {
+        true => Key::Custom(key),
+        false => Key::Modified(key, modifiers),
+    }
+}
+
+fn with_alt(key: Key) -> Key {
+    match key {
+        Key::U8(byte) => Key::Alt(byte as char),
+        Key::Char(char) => Key::Alt(char),
+        Key::Custom(key) => Key::Modified(key, Modifiers::ALT),
+        Key::Modified(key, modifiers) => Key::Modified(key, modifiers | Modifiers::ALT),
+        key => key,
+    }
+}
+
+/// Decodes a key reported by its code point, e.g. `\x1b[97;5u` for Ctrl-A, as the key that
+/// terminals otherwise send
+pub fn decode_code_point(code_point: u32, modifiers: Modifiers) -> Option<Key> {
+    let key = match code_point {
+        9 if modifiers.contains(Modifiers::SHIFT) => Key::Custom(EditorKey::BackTab),
+        8 | 127 => return Some(with_modifiers(EditorKey::Backspace, modifiers)),
+        9 | 13 | 27 => Key::U8(code_point as u8),
+        0xE000..=0xF8FF => return None, // Keys without a character, e.g. on the keypad
+        _ => {
+            let mut char = char::from_u32(code_point).filter(|char| !char.is_control())?;
+
+            if modifiers.contains(Modifiers::SHIFT) {
+                char = char.to_uppercase().next().unwrap_or(char);
+            }
+
+            match char {
+                char if modifiers.contains(Modifiers::CTRL)
+                    && (char.is_ascii_alphabetic() || "@[\\]^_".contains(char)) =>
+                {
+                    Key::U8(ctrl_key(char))
+                }
+                char if char.is_ascii() => Key::U8(char as u8),
+                char => Key::Char(char),
+            }
+        }
+    };
+
+    Some(match modifiers.contains(Modifiers::ALT) {
+        true => with_alt(key),
+        false => key,
+    })
+}
+
+pub fn read_key_input() -> Option<Key> {
+    decode_key(read_single_key()?, read_single_key)
+}
+
 pub fn process_keypress(editor: &mut EditorInstance) {
-    if let Some(key) = read_key_input() {
-        editor.process_key(key);
+    match read_key_input() {
+        Some(key) => editor.process_key(key),
+        None => editor.check_key_sequence_timeout(),
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    fn decode(bytes: &[u8]) -> Key {
+        let mut remaining = bytes[1..].iter().copied();
+        read_utf8_character(bytes[0], || remaining.next())
+    }
+
+    #[test]
+    fn test_read_utf8_character_valid_sequences() {
+        assert!(decode("é".as_bytes()) == Key::Char('é'));
+        assert!(decode("中".as_bytes()) == Key::Char('中'));
+        assert!(decode("🙂".as_bytes()) == Key::Char('🙂'));
+    }
+
+    #[test]
+    fn test_read_paste() {
+        let read = |bytes: &[u8]| {
+            let mut remaining = bytes.iter().copied();
+            read_paste(|| remaining.next())
+        };
+
+        assert_eq!(read(b"fn main() {\r\tx\x1b[201~"), "fn main() {\n\tx");
+        assert_eq!(read("a\r\nb é\x1b[201~j".as_bytes()), "a\nb é");
+
+        // Escape sequences within the paste are kept as text
+        assert_eq!(read(b"\x1b[A:q\x1b[201~"), "\x1b[A:q");
+
+        // A paste that is never ended is cut short
+        assert_eq!(read(b"abc"), "abc");
+    }
+
+    fn decode_bytes(bytes: &[u8]) -> Option<Key> {
+        let mut remaining = bytes[1..].iter().copied();
+        decode_key(bytes[0], || remaining.next())
+    }
+
+    #[test]
+    fn test_decode_escape_sequences() {
+        assert_eq!(decode_bytes(b"\x1b"), Some(Key::U8(ESCAPE)));
+        assert_eq!(
+            decode_bytes(b"\x1b[A"),
+            Some(Key::Custom(EditorKey::ArrowUp))
+        );
+        assert_eq!(
+            decode_bytes(b"\x1bOD"),
+            Some(Key::Custom(EditorKey::ArrowLeft))
+        );
+        assert_eq!(
+            decode_bytes(b"\x1b[5~"),
+            Some(Key::Custom(EditorKey::PageUp))
+        );
+        assert_eq!(
+            decode_bytes(b"\x1b[2~"),
+            Some(Key::Custom(EditorKey::Insert))
+        );
+        assert_eq!(
+            decode_bytes(b"\x1b[Z"),
+            Some(Key::Custom(EditorKey::BackTab))
+        );
+        assert_eq!(
+            decode_bytes(b"\x1b[<0;1;1M"),
+            Some(Key::Mouse(MouseEvent {
+                kind: MouseEventKind::Press(MouseButton::Left),
+                column: 0,
+                row: 0,
+            }))
+        );
+        assert_eq!(
+            decode_bytes(b"\x1b[200~hi\x1b[201~"),
+            Some(Key::Paste(String::from("hi")))
+        );
+    }
+
+    #[test]
+    fn test_decode_function_keys() {
+        assert_eq!(
+            decode_bytes(b"\x1bOP"),
+            Some(Key::Custom(EditorKey::Function(1)))
+        );
+        assert_eq!(
+            decode_bytes(b"\x1b[15~"),
+            Some(Key::Custom(EditorKey::Function(5)))
+        );
+        assert_eq!(
+            decode_bytes(b"\x1b[21~"),
+            Some(Key::Custom(EditorKey::Function(10)))
+        );
+        assert_eq!(
+            decode_bytes(b"\x1b[24~"),
+            Some(Key::Custom(EditorKey::Function(12)))
+        );
+    }
+
+    #[test]
+    fn test_decode_modifiers() {
+        let modified = |key, modifiers| Some(Key::Modified(key, modifiers));
+
+        assert_eq!(
+            decode_bytes(b"\x1b[1;5C"),
+            modified(EditorKey::ArrowRight, Modifiers::CTRL)
+        );
+        assert_eq!(
+            decode_bytes(b"\x1b[1;2D"),
+            modified(EditorKey::ArrowLeft, Modifiers::SHIFT)
+        );
+        assert_eq!(
+            decode_bytes(b"\x1b[1;8A"),
+            modified(
+                EditorKey::ArrowUp,
+                Modifiers::SHIFT | Modifiers::ALT | Modifiers::CTRL
+            )
+        );
+        assert_eq!(
+            decode_bytes(b"\x1b[3;5~"),
+            modified(EditorKey::Delete, Modifiers::CTRL)
+        );
+        assert_eq!(
+            decode_bytes(b"\x1b[1;5P"),
+            modified(EditorKey::Function(1), Modifiers::CTRL)
+        );
+        assert_eq!(
+            decode_bytes(b"\x1bO2S"),
+            modified(EditorKey::Function(4), Modifiers::SHIFT)
+        );
+
+        // Lock keys are ignored
+        assert_eq!(
+            decode_bytes(b"\x1b[1;69C"),
+            modified(EditorKey::ArrowRight, Modifiers::CTRL)
+        );
+        assert_eq!(
+            decode_bytes(b"\x1b[1;1C"),
+            Some(Key::Custom(EditorKey::ArrowRight))
+        );
+    }
+
+    #[test]
+    fn test_decode_alt() {
+        assert_eq!(decode_bytes(b"\x1bx"), Some(Key::Alt('x')));
+        assert_eq!(decode_bytes("\x1bé".as_bytes()), Some(Key::Alt('é')));
+        assert_eq!(decode_bytes(b"\x1b["), Some(Key::Alt('[')));
+        assert_eq!(decode_bytes(b"\x1b\x1b"), Some(Key::Alt('\x1b')));
+        assert_eq!(
+            decode_bytes(b"\x1b\x1b[A"),
+            Some(Key::Modified(EditorKey::ArrowUp, Modifiers::ALT))
+        );
+    }
+
+    #[test]
+    fn test_decode_code_points() {
+        // The kitty keyboard protocol
+        assert_eq!(decode_bytes(b"\x1b[97;5u"), Some(Key::U8(ctrl_key('a'))));
+        assert_eq!(decode_bytes(b"\x1b[97;2u"), Some(Key::U8(b'A')));
+        assert_eq!(decode_bytes(b"\x1b[120;3u"), Some(Key::Alt('x')));
+        assert_eq!(decode_bytes(b"\x1b[27u"), Some(Key::U8(ESCAPE)));
+        assert_eq!(decode_bytes(b"\x1b[13;1u"), Some(Key::U8(b'\r')));
+        assert_eq!(
+            decode_bytes(b"\x1b[9;2u"),
+            Some(Key::Custom(EditorKey::BackTab))
+        );
+        assert_eq!(
+            decode_bytes(b"\x1b[127;5u"),
+            Some(Key::Modified(EditorKey::Backspace, Modifiers::CTRL))
+        );
+        assert_eq!(decode_bytes(b"\x1b[233;1u"), Some(Key::Char('é')));
+        assert_eq!(
+            decode_bytes(b"\x1b[107:75;6u"),
+            Some(Key::U8(ctrl_key('k')))
+        );
+
+        // xterm's modifyOtherKeys
+        assert_eq!(
+            decode_bytes(b"\x1b[27;5;115~"),
+            Some(Key::U8(ctrl_key('s')))
+        );
+    }
+
+    #[test]
+    fn test_decode_unrecognized_sequences() {
+        assert_eq!(decode_bytes(b"\x1b[99~"), None);
+        assert_eq!(decode_bytes(b"\x1b[I"), None); // Focus in
+        assert_eq!(decode_bytes(b"\x1b[24;80R"), None); // Cursor Position Report
+        assert_eq!(decode_bytes(b"\x1b[?1;2c"), None); // Device Attributes
+        assert_eq!(decode_bytes(b"\x1b[57399u"), None); // Keypad 0
+        assert_eq!(decode_bytes(b"\x1b[1;5"), None); // Cut short
+        assert_eq!(decode_bytes(b"\x1bOx"), None);
+        assert_eq!(decode_bytes(b"\x1b[<0;1M"), None);
+    }
+
+    #[test]
+    fn test_read_mouse_event() {
+        let read = |bytes: &[u8]| {
+            let mut remaining = bytes.iter().copied();
+            read_mouse_event(|| remaining.next())
+        };
+
+        let event = |kind, column, row| Some(MouseEvent { kind, column, row });
+
+        assert_eq!(
+            read(b"0;12;5M"),
+            event(MouseEventKind::Press(MouseButton::Left), 11, 4)
+        );
+        assert_eq!(
+            read(b"2;1;1M"),
+            event(MouseEventKind::Press(MouseButton::Right), 0, 0)
+        );
+        assert_eq!(
+            read(b"32;3;2M"),
+            event(MouseEventKind::Drag(MouseButton::Left), 2, 1)
+        );
+        assert_eq!(read(b"0;3;2m"), event(MouseEventKind::Release, 2, 1));
+        assert_eq!(read(b"64;1;9M"), event(MouseEventKind::ScrollUp, 0, 8));
+        assert_eq!(read(b"65;1;9M"), event(MouseEventKind::ScrollDown, 0, 8));
+
+        // Modifier keys are ignored
+        assert_eq!(
+            read(b"16;4;4M"),
+            event(MouseEventKind::Press(MouseButton::Left), 3, 3)
+        );
+    }
+
+    #[test]
+    fn test_read_mouse_event_unsupported_or_malformed() {
+        let read = |bytes: &[u8]| {
+            let mut remaining = bytes.iter().copied();
+            read_mouse_event(|| remaining.next())
+        };
+
+        assert_eq!(read(b"35;4;4M"), None); // Motion with no button held
+        assert_eq!(read(b"66;4;4M"), None); // Horizontal scrolling
+        assert_eq!(read(b"0;0;4M"), None);
+        assert_eq!(read(b"0;4M"), None);
+        assert_eq!(read(b"0;4;4;4M"), None);
+        assert_eq!(read(b"0;x;4M"), None);
+        assert_eq!(read(b"0;4;4"), None); // Cut short
+        assert_eq!(read(b"0;4;44444444444444444M"), None);
+    }
+
+    #[test]
+    fn test_read_utf8_character_invalid_sequences() {
+        let replacement = Key::Char(char::REPLACEMENT_CHARACTER);
+
+        assert!(decode(&[0x80]) == replacement); // Continuation byte without a leading byte
+        assert!(decode(&[0xC3]) == replacement); // Truncated sequence
+        assert!(decode(&[0xE4, 0x41, 0x41]) == replacement); // Invalid continuation bytes
     }
 }
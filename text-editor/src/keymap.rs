@@ -0,0 +1,634 @@
+use crate::input::{decode_code_point, with_modifiers, EditorKey, Key, Modifiers};
+
+/// The modes that have their own key bindings
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    Normal,
+    Insert,
+    Visual,
+}
+
+/// Mode names, as used for the `[keymap.<mode>]` tables of the config file
+pub const MODES: &[(&str, Mode)] = &[
+    ("normal", Mode::Normal),
+    ("insert", Mode::Insert),
+    ("visual", Mode::Visual),
+];
+
+const ALL_MODES: &[Mode] = &[Mode::Normal, Mode::Insert, Mode::Visual];
+
+/// An editor action that keys can be bound to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command {
+    Find,
+    Replace,
+    GoToLine,
+    Undo,
+    Redo,
+    Save,
+    Quit,
+    Help,
+    ExCommandLine,
+    InsertRegister, // Inserts the register named by the next key
+    InsertMode,
+    NormalMode, // As Escape does
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    WordForward,
+    WordBackward,
+    LineStart,
+    LineEnd,
+    PageUp,
+    PageDown,
+    FileStart,
+    FileEnd,
+}
+
+/// Command names, as used in the config file and `:map`
+const COMMANDS: &[(&str, Command)] = &[
+    ("find", Command::Find),
+    ("replace", Command::Replace),
+    ("go_to_line", Command::GoToLine),
+    ("undo", Command::Undo),
+    ("redo", Command::Redo),
+    ("save", Command::Save),
+    ("quit", Command::Quit),
+    ("help", Command::Help),
+    ("command_line", Command::ExCommandLine),
+    ("insert_register", Command::InsertRegister),
+    ("insert_mode", Command::InsertMode),
+    ("normal_mode", Command::NormalMode),
+    ("move_left", Command::MoveLeft),
+    ("move_right", Command::MoveRight),
+    ("move_up", Command::MoveUp),
+    ("move_down", Command::MoveDown),
+    ("word_forward", Command::WordForward),
+    ("word_backward", Command::WordBackward),
+    ("line_start", Command::LineStart),
+    ("line_end", Command::LineEnd),
+    ("page_up", Command::PageUp),
+    ("page_down", Command::PageDown),
+    ("file_start", Command::FileStart),
+    ("file_end", Command::FileEnd),
+];
+
+/// The commands listed by the help message, in order
+const HELP_COMMANDS: &[(Command, &str)] = &[
+    (Command::Find, "find"),
+    (Command::Replace, "replace"),
+    (Command::GoToLine, "go to line"),
+    (Command::Undo, "undo"),
+    (Command::Redo, "redo"),
+    (Command::Save, "save"),
+    (Command::Quit, "quit"),
+];
+
+#[rustfmt::skip]
+const DEFAULT_BINDINGS: &[(&[Mode], &str, Command)] = &[
+    (ALL_MODES,       "<C-f>",      Command::Find),
+    (ALL_MODES,       "<C-e>",      Command::Replace),
+    (ALL_MODES,       "<C-g>",      Command::GoToLine),
+    (ALL_MODES,       "<C-z>",      Command::Undo),
+    (ALL_MODES,       "<C-y>",      Command::Redo),
+    (&[Mode::Normal], "<C-r>",      Command::Redo),
+    (&[Mode::Insert], "<C-r>",      Command::InsertRegister),
+    (ALL_MODES,       "<C-s>",      Command::Save),
+    (ALL_MODES,       "<C-q>",      Command::Quit),
+    (ALL_MODES,       "<C-h>",      Command::Help),
+    (ALL_MODES,       "<F1>",       Command::Help),
+    (&[Mode::Normal], "<Insert>",   Command::InsertMode),
+    (ALL_MODES,       "<Left>",     Command::MoveLeft),
+    (ALL_MODES,       "<Right>",    Command::MoveRight),
+    (ALL_MODES,       "<Up>",       Command::MoveUp),
+    (ALL_MODES,       "<Down>",     Command::MoveDown),
+    (ALL_MODES,       "<C-Right>",  Command::WordForward),
+    (ALL_MODES,       "<S-Right>",  Command::WordForward),
+    (ALL_MODES,       "<C-Left>",   Command::WordBackward),
+    (ALL_MODES,       "<S-Left>",   Command::WordBackward),
+    (ALL_MODES,       "<Home>",     Command::LineStart),
+    (ALL_MODES,       "<End>",      Command::LineEnd),
+    (ALL_MODES,       "<PageUp>",   Command::PageUp),
+    (ALL_MODES,       "<S-Up>",     Command::PageUp),
+    (ALL_MODES,       "<C-Up>",     Command::PageUp),
+    (ALL_MODES,       "<PageDown>", Command::PageDown),
+    (ALL_MODES,       "<S-Down>",   Command::PageDown),
+    (ALL_MODES,       "<C-Down>",   Command::PageDown),
+    (ALL_MODES,       "<C-Home>",   Command::FileStart),
+    (ALL_MODES,       "<C-End>",    Command::FileEnd),
+];
+
+pub struct Binding {
+    pub keys: Vec<Key>,
+    pub command: Command,
+}
+
+/// How the keys typed so far relate to the bindings of a mode
+#[derive(Debug, PartialEq)]
+pub enum KeymapMatch {
+    Command(Command),
+    Prefix, // More keys may complete a binding
+    None,
+}
+
+/// Key bindings for each mode, in the order they were made
+pub struct Keymap {
+    normal: Vec<Binding>,
+    insert: Vec<Binding>,
+    visual: Vec<Binding>,
+}
+
+pub fn find_command(name: &str) -> Result<Command, String> {
+    COMMANDS
+        .iter()
+        .find(|(command_name, _)| *command_name == name)
+        .map(|(_, command)| *command)
+        .ok_or_else(|| format!("Unknown command: {}", name))
+}
+
+pub fn get_command_name(command: Command) -> &'static str {
+    COMMANDS
+        .iter()
+        .find(|(_, named_command)| *named_command == command)
+        .map(|(name, _)| *name)
+        .expect("Every command has a name")
+}
+
+pub fn command_names() -> impl Iterator<Item = &'static str> {
+    COMMANDS.iter().map(|(name, _)| *name)
+}
+
+fn character_key(character: char) -> Key {
+    match character.is_ascii() {
+        true => Key::U8(character as u8),
+        false => Key::Char(character),
+    }
+}
+
+/// Parses the inside of `<...>`, e.g. `C-s`, `S-Left` or `F5`
+fn parse_special_key(name: &str) -> Option<Key> {
+    let mut modifiers = Modifiers::default();
+    let mut name = name;
+
+    // A name of `-` on its own is the key, e.g. `<C-->`
+    while name.len() > 2 && name.as_bytes()[1] == b'-' {
+        modifiers = modifiers
+            | match name.as_bytes()[0].to_ascii_uppercase() {
+                b'C' => Modifiers::CTRL,
+                b'A' | b'M' => Modifiers::ALT,
+                b'S' => Modifiers::SHIFT,
+                _ => return None,
+            };
+
+        name = &name[2..];
+    }
+
+    let lowercase_name = name.to_ascii_lowercase();
+
+    let editor_key = match lowercase_name.as_str() {
+        "left" => Some(EditorKey::ArrowLeft),
+        "right" => Some(EditorKey::ArrowRight),
+        "up" => Some(EditorKey::ArrowUp),
+        "down" => Some(EditorKey::ArrowDown),
+        "del" | "delete" => Some(EditorKey::Delete),
+        "home" => Some(EditorKey::Home),
+        "end" => Some(EditorKey::End),
+        "pageup" => Some(EditorKey::PageUp),
+        "pagedown" => Some(EditorKey::PageDown),
+        "bs" | "backspace" => Some(EditorKey::Backspace),
+        "insert" | "ins" => Some(EditorKey::Insert),
+        function_key if function_key.starts_with('f') => function_key[1..]
+            .parse()
+            .ok()
+            .filter(|number| (1..=12).contains(number))
+            .map(EditorKey::Function),
+        _ => None,
+    };
+
+    if let Some(editor_key) = editor_key {
+        return Some(with_modifiers(editor_key, modifiers));
+    }
+
+    let character = match lowercase_name.as_str() {
+        "esc" => '\x1b',
+        "cr" | "enter" | "return" => '\r',
+        "tab" => '\t',
+        "space" => ' ',
+        "lt" => '<',
+        "bar" => '|',
+        "bslash" => '\\',
+        _ if name.chars().count() == 1 && !modifiers.is_empty() => name.chars().next()?,
+        _ => return None,
+    };
+
+    decode_code_point(character.into(), modifiers)
+}
+
+/// Parses keys written in vim's notation, e.g. `<C-s>`, `gd` or `<Space>w`
+/// A `<` that does not start a key name is taken as itself.
+pub fn parse_keys(notation: &str) -> Result<Vec<Key>, String> {
+    let mut keys = vec![];
+    let mut rest = notation;
+
+    while let Some(character) = rest.chars().next() {
+        if let Some(end) = rest.find('>').filter(|end| character == '<' && *end > 1) {
+            let name = &rest[1..end];
+
+            if !name.contains(['<', ' ']) {
+                keys.push(
+                    parse_special_key(name).ok_or_else(|| format!("Unknown key: <{}>", name))?,
+                );
+                rest = &rest[end + 1..];
+                continue;
+            }
+        }
+
+        keys.push(character_key(character));
+        rest = &rest[character.len_utf8()..];
+    }
+
+    match keys.is_empty() {
+        true => Err(String::from("No keys given")),
+        false => Ok(keys),
+    }
+}
+
+fn get_editor_key_name(key: EditorKey) -> String {
+    match key {
+        EditorKey::ArrowLeft => String::from("Left"),
+        EditorKey::ArrowRight => String::from("Right"),
+        EditorKey::ArrowUp => String::from("Up"),
+        EditorKey::ArrowDown => String::from("Down"),
+        EditorKey::Delete => String::from("Del"),
+        EditorKey::Home => String::from("Home"),
+        EditorKey::End => String::from("End"),
+        EditorKey::PageUp => String::from("PageUp"),
+        EditorKey::PageDown => String::from("PageDown"),
+        EditorKey::Backspace => String::from("BS"),
+        EditorKey::Insert => String::from("Insert"),
+        EditorKey::BackTab => String::from("S-Tab"),
+        EditorKey::Function(number) => format!("F{}", number),
+    }
+}
+
+/// Writes modifiers with the names given for Ctrl, Alt and Shift, e.g. `C-S-`
+fn format_modifiers(modifiers: Modifiers, names: [&str; 3]) -> String {
+    [Modifiers::CTRL, Modifiers::ALT, Modifiers::SHIFT]
+        .iter()
+        .zip(names)
+        .filter(|(modifier, _)| modifiers.contains(**modifier))
+        .map(|(_, name)| name)
+        .collect()
+}
+
+fn format_key(key: &Key) -> String {
+    match key {
+        Key::U8(b'\x1b') => String::from("<Esc>"),
+        Key::U8(b'\r') => String::from("<CR>"),
+        Key::U8(b'\t') => String::from("<Tab>"),
+        Key::U8(b' ') => String::from("<Space>"),
+        Key::U8(b'<') => String::from("<lt>"),
+        Key::U8(byte) if byte.is_ascii_control() => {
+            format!("<C-{}>", ((byte | 0x40) as char).to_ascii_lowercase())
+        }
+        Key::U8(byte) => (*byte as char).to_string(),
+        Key::Char(character) => character.to_string(),
+        Key::Custom(key) => format!("<{}>", get_editor_key_name(*key)),
+        Key::Modified(key, modifiers) => format!(
+            "<{}{}>",
+            format_modifiers(*modifiers, ["C-", "A-", "S-"]),
+            get_editor_key_name(*key)
+        ),
+        Key::Alt(character) => {
+            let key = format_key(&character_key(*character));
+
+            match key.strip_prefix('<') {
+                Some(name) => format!("<A-{}", name),
+                None => format!("<A-{}>", key),
+            }
+        }
+        Key::Paste(_) | Key::Mouse(_) => String::new(),
+    }
+}
+
+/// Writes keys in the notation read by `parse_keys`
+pub fn format_keys(keys: &[Key]) -> String {
+    keys.iter().map(format_key).collect()
+}
+
+/// Describes a key for the help message, e.g. `Ctrl-F` or `Shift-Left`
+fn describe_key(key: &Key) -> String {
+    match key {
+        Key::U8(byte) if byte.is_ascii_control() && !matches!(byte, b'\x1b' | b'\r' | b'\t') => {
+            format!("Ctrl-{}", (byte | 0x40) as char)
+        }
+        Key::Custom(EditorKey::BackTab) => String::from("Shift-Tab"),
+        Key::Custom(key) => get_editor_key_name(*key),
+        Key::Modified(key, modifiers) => format!(
+            "{}{}",
+            format_modifiers(*modifiers, ["Ctrl-", "Alt-", "Shift-"]),
+            get_editor_key_name(*key)
+        ),
+        Key::Alt(character) => format!("Alt-{}", describe_key(&character_key(*character))),
+        key => format_key(key),
+    }
+}
+
+fn describe_keys(keys: &[Key]) -> String {
+    let descriptions: Vec<String> = keys.iter().map(describe_key).collect();
+
+    // Keys named by more than a character are spaced out, e.g. `Ctrl-W v`
+    match descriptions
+        .iter()
+        .all(|description| description.chars().count() == 1)
+    {
+        true => descriptions.concat(),
+        false => descriptions.join(" "),
+    }
+}
+
+impl Keymap {
+    pub fn new() -> Self {
+        let mut keymap = Keymap {
+            normal: vec![],
+            insert: vec![],
+            visual: vec![],
+        };
+
+        for (modes, notation, command) in DEFAULT_BINDINGS {
+            let keys = parse_keys(notation).expect("Failed to parse default key binding");
+
+            for mode in *modes {
+                keymap.bind(*mode, keys.clone(), *command);
+            }
+        }
+
+        keymap
+    }
+
+    fn bindings(&self, mode: Mode) -> &Vec<Binding> {
+        match mode {
+            Mode::Normal => &self.normal,
+            Mode::Insert => &self.insert,
+            Mode::Visual => &self.visual,
+        }
+    }
+
+    fn bindings_mut(&mut self, mode: Mode) -> &mut Vec<Binding> {
+        match mode {
+            Mode::Normal => &mut self.normal,
+            Mode::Insert => &mut self.insert,
+            Mode::Visual => &mut self.visual,
+        }
+    }
+
+    /// Binds keys to a command, replacing any existing binding of the same keys
+    pub fn bind(&mut self, mode: Mode, keys: Vec<Key>, command: Command) {
+        let bindings = self.bindings_mut(mode);
+
+        match bindings.iter_mut().find(|binding| binding.keys == keys) {
+            Some(binding) => binding.command = command,
+            None => bindings.push(Binding { keys, command }),
+        }
+    }
+
+    /// Returns whether the keys were bound
+    pub fn unbind(&mut self, mode: Mode, keys: &[Key]) -> bool {
+        let bindings = self.bindings_mut(mode);
+        let num_bindings = bindings.len();
+
+        bindings.retain(|binding| binding.keys != keys);
+        bindings.len() < num_bindings
+    }
+
+    pub fn get(&self, mode: Mode, keys: &[Key]) -> Option<Command> {
+        self.bindings(mode)
+            .iter()
+            .find(|binding| binding.keys == keys)
+            .map(|binding| binding.command)
+    }
+
+    /// Keys that are bound but also start a longer binding are a prefix, as the next key
+    /// decides which binding is meant
+    pub fn lookup(&self, mode: Mode, keys: &[Key]) -> KeymapMatch {
+        let bindings = self.bindings(mode);
+
+        if bindings
+            .iter()
+            .any(|binding| binding.keys.len() > keys.len() && binding.keys.starts_with(keys))
+        {
+            return KeymapMatch::Prefix;
+        }
+
+        match self.get(mode, keys) {
+            Some(command) => KeymapMatch::Command(command),
+            None => KeymapMatch::None,
+        }
+    }
+
+    /// Lists every binding of a mode, e.g. `<C-s> save | ,w save`
+    pub fn list(&self, mode: Mode) -> String {
+        self.bindings(mode)
+            .iter()
+            .map(|binding| {
+                format!(
+                    "{} {}",
+                    format_keys(&binding.keys),
+                    get_command_name(binding.command)
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(" | ")
+    }
+
+    /// Describes the first keys bound to a command, e.g. `Ctrl-Q`
+    pub fn describe_binding(&self, mode: Mode, command: Command) -> Option<String> {
+        self.bindings(mode)
+            .iter()
+            .find(|binding| binding.command == command)
+            .map(|binding| describe_keys(&binding.keys))
+    }
+
+    /// Lists the keys of the main commands, e.g. `Ctrl-F: find | Ctrl-S: save`
+    pub fn get_help(&self, mode: Mode) -> String {
+        HELP_COMMANDS
+            .iter()
+            .filter_map(|(command, description)| {
+                self.describe_binding(mode, *command)
+                    .map(|keys| format!("{}: {}", keys, description))
+            })
+            .collect::<Vec<String>>()
+            .join(" | ")
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use crate::utils::ctrl_key;
+
+    fn keys(notation: &str) -> Vec<Key> {
+        parse_keys(notation).unwrap()
+    }
+
+    #[test]
+    fn test_parse_keys() {
+        assert_eq!(keys("gd"), vec![Key::U8(b'g'), Key::U8(b'd')]);
+        assert_eq!(keys("<C-s>"), vec![Key::U8(ctrl_key('s'))]);
+        assert_eq!(keys("<c-S>"), vec![Key::U8(ctrl_key('s'))]);
+        assert_eq!(keys("<A-x>"), vec![Key::Alt('x')]);
+        assert_eq!(keys("<S-x>"), vec![Key::U8(b'X')]);
+        assert_eq!(
+            keys("<Space>w<CR>"),
+            vec![Key::U8(b' '), Key::U8(b'w'), Key::U8(b'\r')]
+        );
+        assert_eq!(keys("é"), vec![Key::Char('é')]);
+        assert_eq!(
+            keys("<C-S-Left><F5><S-Tab>"),
+            vec![
+                Key::Modified(EditorKey::ArrowLeft, Modifiers::CTRL | Modifiers::SHIFT),
+                Key::Custom(EditorKey::Function(5)),
+                Key::Custom(EditorKey::BackTab),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_keys_with_literal_angle_brackets() {
+        assert_eq!(keys("<<"), vec![Key::U8(b'<'), Key::U8(b'<')]);
+        assert_eq!(keys("<>"), vec![Key::U8(b'<'), Key::U8(b'>')]);
+        assert_eq!(keys("<lt>"), vec![Key::U8(b'<')]);
+        assert_eq!(keys("<C-->"), vec![Key::U8(b'-')]); // Ctrl has no effect on `-`
+    }
+
+    #[test]
+    fn test_parse_keys_errors() {
+        assert_eq!(parse_keys(""), Err(String::from("No keys given")));
+        assert_eq!(parse_keys("<Foo>"), Err(String::from("Unknown key: <Foo>")));
+        assert_eq!(parse_keys("<F13>"), Err(String::from("Unknown key: <F13>")));
+        assert_eq!(parse_keys("<X-a>"), Err(String::from("Unknown key: <X-a>")));
+    }
+
+    #[test]
+    fn test_format_keys_round_trip() {
+        for notation in [
+            "<C-s>",
+            "gd",
+            "<Space>w",
+            "<Esc><CR><Tab><lt>",
+            "<A-x><A-C-s>",
+            "<C-S-Left><F12><S-Tab><Del><BS>",
+            "é",
+        ] {
+            assert_eq!(format_keys(&keys(notation)), notation);
+        }
+    }
+
+    #[test]
+    fn test_bind_and_lookup() {
+        let mut keymap = Keymap::new();
+
+        assert_eq!(
+            keymap.lookup(Mode::Insert, &keys("<C-s>")),
+            KeymapMatch::Command(Command::Save)
+        );
+        assert_eq!(keymap.lookup(Mode::Insert, &keys("j")), KeymapMatch::None);
+
+        keymap.bind(Mode::Insert, keys("jk"), Command::NormalMode);
+        keymap.bind(Mode::Insert, keys("j"), Command::MoveDown);
+
+        assert_eq!(keymap.lookup(Mode::Insert, &keys("j")), KeymapMatch::Prefix);
+        assert_eq!(
+            keymap.get(Mode::Insert, &keys("j")),
+            Some(Command::MoveDown)
+        );
+        assert_eq!(
+            keymap.lookup(Mode::Insert, &keys("jk")),
+            KeymapMatch::Command(Command::NormalMode)
+        );
+        assert_eq!(keymap.lookup(Mode::Insert, &keys("jj")), KeymapMatch::None);
+        assert_eq!(keymap.lookup(Mode::Normal, &keys("j")), KeymapMatch::None);
+
+        // Rebinding replaces
+        keymap.bind(Mode::Insert, keys("<C-s>"), Command::Quit);
+        assert_eq!(
+            keymap.get(Mode::Insert, &keys("<C-s>")),
+            Some(Command::Quit)
+        );
+
+        assert!(keymap.unbind(Mode::Insert, &keys("<C-s>")));
+        assert!(!keymap.unbind(Mode::Insert, &keys("<C-s>")));
+        assert_eq!(keymap.get(Mode::Insert, &keys("<C-s>")), None);
+        assert_eq!(
+            keymap.get(Mode::Normal, &keys("<C-s>")),
+            Some(Command::Save)
+        );
+    }
+
+    #[test]
+    fn test_per_mode_defaults() {
+        let keymap = Keymap::new();
+
+        assert_eq!(
+            keymap.get(Mode::Normal, &keys("<C-r>")),
+            Some(Command::Redo)
+        );
+        assert_eq!(
+            keymap.get(Mode::Insert, &keys("<C-r>")),
+            Some(Command::InsertRegister)
+        );
+        assert_eq!(keymap.get(Mode::Visual, &keys("<C-r>")), None);
+    }
+
+    #[test]
+    fn test_help_is_generated_from_bindings() {
+        let mut keymap = Keymap::new();
+
+        assert_eq!(
+            keymap.get_help(Mode::Normal),
+            "Ctrl-F: find | Ctrl-E: replace | Ctrl-G: go to line | Ctrl-Z: undo | \
+             Ctrl-Y: redo | Ctrl-S: save | Ctrl-Q: quit"
+        );
+
+        keymap.unbind(Mode::Normal, &keys("<C-f>"));
+        keymap.unbind(Mode::Normal, &keys("<C-s>"));
+        keymap.bind(Mode::Normal, keys(",w"), Command::Save);
+        keymap.unbind(Mode::Normal, &keys("<C-q>"));
+        keymap.bind(Mode::Normal, keys("<C-w>q"), Command::Quit);
+
+        assert_eq!(
+            keymap.get_help(Mode::Normal),
+            "Ctrl-E: replace | Ctrl-G: go to line | Ctrl-Z: undo | Ctrl-Y: redo | ,w: save | \
+             Ctrl-W q: quit"
+        );
+    }
+
+    #[test]
+    fn test_list() {
+        let mut keymap = Keymap {
+            normal: vec![],
+            insert: vec![],
+            visual: vec![],
+        };
+
+        keymap.bind(Mode::Visual, keys("<C-s>"), Command::Save);
+        keymap.bind(Mode::Visual, keys(",q"), Command::Quit);
+
+        assert_eq!(keymap.list(Mode::Visual), "<C-s> save | ,q quit");
+        assert_eq!(keymap.list(Mode::Normal), "");
+    }
+
+    #[test]
+    fn test_command_names() {
+        assert_eq!(find_command("go_to_line"), Ok(Command::GoToLine));
+        assert_eq!(
+            find_command("go to line"),
+            Err(String::from("Unknown command: go to line"))
+        );
+
+        for name in command_names() {
+            assert_eq!(get_command_name(find_command(name).unwrap()), name);
+        }
+    }
+}
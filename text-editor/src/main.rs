@@ -1,17 +1,34 @@
+use config::{get_config_path, get_syntax_directory, read_config_file};
 use editor_instance::EditorInstance;
-use globals::DEFAULT_STATUS_BAR_MESSAGE;
+use globals::KITTY_KEYBOARD_PROTOCOL;
 use input::process_keypress;
-use output::{clear_display, refresh_screen};
+use output::{
+    clear_display, enable_bracketed_paste, enable_kitty_keyboard_protocol, enable_mouse_reporting,
+    refresh_screen,
+};
 use std::error::Error;
 use std::sync::{Arc, RwLock};
 use terminal::{enable_raw_mode, get_populated_termios};
 use utils::{get_window_size, set_panic_hook, watch_for_window_size_change};
 
+mod config;
 mod editor_instance;
+mod ex_command;
+mod file_io;
 mod globals;
+mod history;
 mod input;
+mod keymap;
+mod normal_mode;
 mod output;
+mod registers;
+mod search;
+mod settings;
+mod swap;
+mod syntax;
 mod terminal;
+mod text_buffer;
+mod theme;
 mod utils;
 
 #[derive(Clone, Copy, PartialEq)]
@@ -26,18 +43,33 @@ fn main() -> Result<(), Box<dyn Error>> {
     let termios = get_populated_termios();
     set_panic_hook(termios);
     enable_raw_mode(termios);
+    enable_bracketed_paste();
+    enable_mouse_reporting();
+
+    if KITTY_KEYBOARD_PROTOCOL {
+        enable_kitty_keyboard_protocol();
+    }
 
     let window_size = Arc::new(RwLock::new(get_window_size()));
     watch_for_window_size_change(Arc::clone(&window_size));
 
     let mut active_editor = EditorInstance::new(termios);
 
+    let config_error = read_config_file(&get_config_path())
+        .map(|config| active_editor.apply_config(config))
+        .and(active_editor.load_syntax_directory(&get_syntax_directory()))
+        .err();
+
     let args = std::env::args();
     if args.len() > 1 {
         active_editor.open(&args.collect::<Vec<String>>()[1]);
     }
 
-    active_editor.set_status_message(DEFAULT_STATUS_BAR_MESSAGE, false);
+    active_editor.show_help();
+
+    if let Some(config_error) = config_error {
+        active_editor.set_status_message(&config_error, true);
+    }
 
     loop {
         let window_size = *window_size
@@ -51,5 +83,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         refresh_screen(&mut active_editor);
 
         process_keypress(&mut active_editor);
+
+        active_editor.update_swap_file();
     }
 }
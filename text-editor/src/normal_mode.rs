@@ -0,0 +1,642 @@
+use crate::{globals::MAX_COUNT, registers::is_register_name};
+use std::cmp::min;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Delete,     // d
+    Change,     // c
+    Yank,       // y
+    Indent,     // >
+    Outdent,    // <
+    ToggleCase, // g~, or ~ in Visual mode
+    Lowercase,  // gu, or u in Visual mode
+    Uppercase,  // gU, or U in Visual mode
+}
+
+/// The Visual modes: `v`, `V` and Ctrl-V
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SelectionKind {
+    Characters,
+    Lines,
+    Block,
+}
+
+/// `f`, `t`, `F` or `T` and the character to look for on the line
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct CharacterFind {
+    pub character: char,
+    pub backward: bool, // `F` and `T`
+    pub till: bool,     // `t` and `T` stop next to the character
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Motion {
+    Left,                         // h
+    Down,                         // j
+    Up,                           // k
+    Right,                        // l
+    WordForward,                  // w
+    WordBackward,                 // b
+    WordEnd,                      // e
+    BigWordForward,               // W
+    BigWordBackward,              // B
+    BigWordEnd,                   // E
+    LineStart,                    // 0
+    FirstNonBlank,                // ^
+    LineEnd,                      // $
+    FileStart,                    // gg
+    FileEnd,                      // G
+    ParagraphBackward,            // {
+    ParagraphForward,             // }
+    ScreenTop,                    // H
+    ScreenMiddle,                 // M
+    ScreenBottom,                 // L
+    FindCharacter(CharacterFind), // f, t, F, T
+    RepeatCharacterFind,          // ;
+    RepeatCharacterFindReversed,  // ,
+    MatchingBracket,              // %
+}
+
+/// How an operator treats the text between the cursor and the end of a motion
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MotionKind {
+    Exclusive, // The character at the end is left alone
+    Inclusive,
+    Linewise, // Whole lines
+}
+
+/// What an operator acts on
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum OperatorTarget {
+    Motion(Motion),
+    Lines,     // A doubled operator, e.g. `dd`
+    Selection, // In Visual mode
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum NormalCommand {
+    Move(Motion),
+    Operate(Operator, OperatorTarget),
+    DeleteCharacters,        // x
+    PutAfter,                // p
+    PutBefore,               // P
+    OpenLineBelow,           // o
+    OpenLineAbove,           // O
+    Insert,                  // i
+    Append,                  // a
+    AppendToLine,            // A
+    InsertAtLineStart,       // I
+    JoinLines,               // J
+    ReplaceCharacters(char), // r
+    Undo,                    // u
+    Select(SelectionKind),   // v, V, Ctrl-V
+    SwapSelectionEnds,       // o in Visual mode
+    CommandLine,             // :
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ParsedCommand {
+    pub command: NormalCommand,
+    pub count: Option<usize>, // Counts before and after an operator multiply
+    pub register: Option<char>, // Named with `"x`
+}
+
+impl ParsedCommand {
+    pub fn count_or_one(&self) -> usize {
+        self.count.unwrap_or(1)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum ParseResult {
+    Incomplete,
+    Complete(ParsedCommand),
+    Invalid,
+}
+
+const CTRL_V: char = '\u{16}';
+
+impl Operator {
+    /// Returns the operator at the start of `keys` and the keys after it
+    fn parse(keys: &[char]) -> Option<(Operator, &[char])> {
+        let operator = match keys {
+            ['d', ..] => Operator::Delete,
+            ['c', ..] => Operator::Change,
+            ['y', ..] => Operator::Yank,
+            ['>', ..] => Operator::Indent,
+            ['<', ..] => Operator::Outdent,
+            ['g', '~', ..] => Operator::ToggleCase,
+            ['g', 'u', ..] => Operator::Lowercase,
+            ['g', 'U', ..] => Operator::Uppercase,
+            _ => return None,
+        };
+
+        let num_keys = match keys[0] {
+            'g' => 2,
+            _ => 1,
+        };
+
+        Some((operator, &keys[num_keys..]))
+    }
+}
+
+enum MotionParseResult {
+    Incomplete,
+    Complete(Motion),
+    Invalid,
+}
+
+impl Motion {
+    fn parse(keys: &[char]) -> MotionParseResult {
+        let (&key, rest) = match keys.split_first() {
+            Some(split) => split,
+            None => return MotionParseResult::Incomplete,
+        };
+
+        let motion = match key {
+            'h' => Motion::Left,
+            'j' => Motion::Down,
+            'k' => Motion::Up,
+            'l' => Motion::Right,
+            'w' => Motion::WordForward,
+            'b' => Motion::WordBackward,
+            'e' => Motion::WordEnd,
+            'W' => Motion::BigWordForward,
+            'B' => Motion::BigWordBackward,
+            'E' => Motion::BigWordEnd,
+            '0' => Motion::LineStart,
+            '^' => Motion::FirstNonBlank,
+            '$' => Motion::LineEnd,
+            'G' => Motion::FileEnd,
+            '{' => Motion::ParagraphBackward,
+            '}' => Motion::ParagraphForward,
+            'H' => Motion::ScreenTop,
+            'M' => Motion::ScreenMiddle,
+            'L' => Motion::ScreenBottom,
+            ';' => Motion::RepeatCharacterFind,
+            ',' => Motion::RepeatCharacterFindReversed,
+            '%' => Motion::MatchingBracket,
+            'g' => match rest.first() {
+                Some('g') => Motion::FileStart,
+                Some(_) => return MotionParseResult::Invalid,
+                None => return MotionParseResult::Incomplete,
+            },
+            'f' | 't' | 'F' | 'T' => match rest.first() {
+                Some(&character) => Motion::FindCharacter(CharacterFind {
+                    character,
+                    backward: key == 'F' || key == 'T',
+                    till: key == 't' || key == 'T',
+                }),
+                None => return MotionParseResult::Incomplete,
+            },
+            _ => return MotionParseResult::Invalid,
+        };
+
+        MotionParseResult::Complete(motion)
+    }
+
+    /// `;` and `,` take the kind of the find they repeat, which only the editor knows
+    pub fn kind(&self) -> MotionKind {
+        match self {
+            Motion::Down
+            | Motion::Up
+            | Motion::FileStart
+            | Motion::FileEnd
+            | Motion::ScreenTop
+            | Motion::ScreenMiddle
+            | Motion::ScreenBottom => MotionKind::Linewise,
+            Motion::WordEnd | Motion::BigWordEnd | Motion::LineEnd | Motion::MatchingBracket => {
+                MotionKind::Inclusive
+            }
+            Motion::FindCharacter(find) if !find.backward => MotionKind::Inclusive,
+            _ => MotionKind::Exclusive,
+        }
+    }
+}
+
+impl CharacterFind {
+    pub fn reversed(&self) -> CharacterFind {
+        CharacterFind {
+            backward: !self.backward,
+            ..*self
+        }
+    }
+}
+
+/// Takes a count off the front of `keys`, up to `MAX_COUNT`; `0` cannot start a count
+fn take_count(keys: &mut &[char]) -> Option<usize> {
+    let num_digits = keys
+        .iter()
+        .enumerate()
+        .take_while(|(i, key)| key.is_ascii_digit() && (*i > 0 || **key != '0'))
+        .count();
+
+    if num_digits == 0 {
+        return None;
+    }
+
+    let count = keys[..num_digits].iter().fold(0usize, |count, key| {
+        min(
+            count * 10 + key.to_digit(10).unwrap_or_default() as usize,
+            MAX_COUNT,
+        )
+    });
+
+    *keys = &keys[num_digits..];
+    Some(count)
+}
+
+/// Takes a count and a `"x` register name, in either order, off the front of `keys`
+/// A missing or invalid register name ends parsing with the `ParseResult` returned.
+fn take_count_and_register(
+    keys: &mut &[char],
+) -> Result<(Option<usize>, Option<char>), ParseResult> {
+    let count = take_count(keys);
+
+    let register = match *keys {
+        ['"'] => return Err(ParseResult::Incomplete),
+        ['"', name, ref rest @ ..] => match is_register_name(*name) {
+            true => {
+                *keys = rest;
+                Some(*name)
+            }
+            false => return Err(ParseResult::Invalid),
+        },
+        _ => None,
+    };
+
+    Ok((multiply_counts(count, take_count(keys)), register))
+}
+
+fn multiply_counts(first: Option<usize>, second: Option<usize>) -> Option<usize> {
+    match (first, second) {
+        (Some(first), Some(second)) => Some(min(first.saturating_mul(second), MAX_COUNT)),
+        (first, second) => first.or(second),
+    }
+}
+
+/// Whether `keys` are at most a count and register, so the next key starts a command
+pub fn is_command_start(keys: &[char]) -> bool {
+    let mut keys = keys;
+    take_count_and_register(&mut keys).is_ok() && keys.is_empty()
+}
+
+/// Parses the keys typed in Normal mode since the last complete command, following vim's
+/// grammar: `[count] command`, `[count] operator [count] motion` or `[count] operator operator`,
+/// e.g. `5j`, `d2l`, `3dd` or `rx`, any of which may name a register first, e.g. `"a3dd`
+pub fn parse(keys: &[char]) -> ParseResult {
+    let mut keys = keys;
+
+    let (count, register) = match take_count_and_register(&mut keys) {
+        Ok(count_and_register) => count_and_register,
+        Err(parse_result) => return parse_result,
+    };
+
+    let (&key, rest) = match keys.split_first() {
+        Some(split) => split,
+        None => return ParseResult::Incomplete,
+    };
+
+    let complete = |command, count| {
+        ParseResult::Complete(ParsedCommand {
+            command,
+            count,
+            register,
+        })
+    };
+
+    if let Some((operator, mut rest)) = Operator::parse(keys) {
+        // The last key of the operator repeats it: `dd`, `>>`, `g~~`, `gUU`...
+        let doubled_key = keys[keys.len() - rest.len() - 1];
+
+        let motion_count = take_count(&mut rest);
+        let count = multiply_counts(count, motion_count);
+
+        if rest.first() == Some(&doubled_key) {
+            return complete(
+                NormalCommand::Operate(operator, OperatorTarget::Lines),
+                count,
+            );
+        }
+
+        return match Motion::parse(rest) {
+            MotionParseResult::Incomplete => ParseResult::Incomplete,
+            MotionParseResult::Complete(motion) => complete(
+                NormalCommand::Operate(operator, OperatorTarget::Motion(motion)),
+                count,
+            ),
+            MotionParseResult::Invalid => ParseResult::Invalid,
+        };
+    }
+
+    let command = match key {
+        'x' => NormalCommand::DeleteCharacters,
+        'p' => NormalCommand::PutAfter,
+        'P' => NormalCommand::PutBefore,
+        'o' => NormalCommand::OpenLineBelow,
+        'O' => NormalCommand::OpenLineAbove,
+        'i' => NormalCommand::Insert,
+        'a' => NormalCommand::Append,
+        'A' => NormalCommand::AppendToLine,
+        'I' => NormalCommand::InsertAtLineStart,
+        'J' => NormalCommand::JoinLines,
+        'u' => NormalCommand::Undo,
+        ':' => NormalCommand::CommandLine,
+        'v' => NormalCommand::Select(SelectionKind::Characters),
+        'V' => NormalCommand::Select(SelectionKind::Lines),
+        CTRL_V => NormalCommand::Select(SelectionKind::Block),
+        'r' => match rest.first() {
+            Some(&replacement) => NormalCommand::ReplaceCharacters(replacement),
+            None => return ParseResult::Incomplete,
+        },
+        _ => {
+            return match Motion::parse(keys) {
+                MotionParseResult::Incomplete => ParseResult::Incomplete,
+                MotionParseResult::Complete(motion) => complete(NormalCommand::Move(motion), count),
+                MotionParseResult::Invalid => ParseResult::Invalid,
+            }
+        }
+    };
+
+    complete(command, count)
+}
+
+/// Parses the keys typed in Visual mode, where operators act on the selection at once
+pub fn parse_visual(keys: &[char]) -> ParseResult {
+    let mut keys = keys;
+
+    let (count, register) = match take_count_and_register(&mut keys) {
+        Ok(count_and_register) => count_and_register,
+        Err(parse_result) => return parse_result,
+    };
+
+    let key = match keys.first() {
+        Some(&key) => key,
+        None => return ParseResult::Incomplete,
+    };
+
+    let complete = |command| {
+        ParseResult::Complete(ParsedCommand {
+            command,
+            count,
+            register,
+        })
+    };
+
+    let operator = match key {
+        'd' | 'x' => Some(Operator::Delete),
+        'c' | 's' => Some(Operator::Change),
+        'y' => Some(Operator::Yank),
+        '>' => Some(Operator::Indent),
+        '<' => Some(Operator::Outdent),
+        '~' => Some(Operator::ToggleCase),
+        'u' => Some(Operator::Lowercase),
+        'U' => Some(Operator::Uppercase),
+        _ => None,
+    };
+
+    if let Some(operator) = operator {
+        return complete(NormalCommand::Operate(operator, OperatorTarget::Selection));
+    }
+
+    match key {
+        'o' => complete(NormalCommand::SwapSelectionEnds),
+        'v' => complete(NormalCommand::Select(SelectionKind::Characters)),
+        'V' => complete(NormalCommand::Select(SelectionKind::Lines)),
+        CTRL_V => complete(NormalCommand::Select(SelectionKind::Block)),
+        _ => match Motion::parse(keys) {
+            MotionParseResult::Incomplete => ParseResult::Incomplete,
+            MotionParseResult::Complete(motion) => complete(NormalCommand::Move(motion)),
+            MotionParseResult::Invalid => ParseResult::Invalid,
+        },
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    fn parse_str(keys: &str) -> ParseResult {
+        parse(&keys.chars().collect::<Vec<_>>())
+    }
+
+    fn command(command: NormalCommand, count: Option<usize>) -> ParseResult {
+        ParseResult::Complete(ParsedCommand {
+            command,
+            count,
+            register: None,
+        })
+    }
+
+    #[test]
+    fn test_parse_motions_with_counts() {
+        assert_eq!(
+            parse_str("j"),
+            command(NormalCommand::Move(Motion::Down), None)
+        );
+        assert_eq!(
+            parse_str("15l"),
+            command(NormalCommand::Move(Motion::Right), Some(15))
+        );
+        assert_eq!(parse_str("1"), ParseResult::Incomplete);
+        assert_eq!(
+            parse_str("0"),
+            command(NormalCommand::Move(Motion::LineStart), None)
+        );
+        assert_eq!(
+            parse_str("10G"),
+            command(NormalCommand::Move(Motion::FileEnd), Some(10))
+        );
+    }
+
+    #[test]
+    fn test_parse_large_counts() {
+        assert_eq!(
+            parse_str("99999999999999999999p"),
+            command(NormalCommand::PutAfter, Some(MAX_COUNT))
+        );
+        assert_eq!(
+            parse_str("99999d99999d"),
+            command(
+                NormalCommand::Operate(Operator::Delete, OperatorTarget::Lines),
+                Some(MAX_COUNT)
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_multi_key_motions() {
+        assert_eq!(parse_str("g"), ParseResult::Incomplete);
+        assert_eq!(
+            parse_str("gg"),
+            command(NormalCommand::Move(Motion::FileStart), None)
+        );
+        assert_eq!(parse_str("gx"), ParseResult::Invalid);
+        assert_eq!(parse_str("dt"), ParseResult::Incomplete);
+        assert_eq!(
+            parse_str("2dT)"),
+            command(
+                NormalCommand::Operate(
+                    Operator::Delete,
+                    OperatorTarget::Motion(Motion::FindCharacter(CharacterFind {
+                        character: ')',
+                        backward: true,
+                        till: true,
+                    }))
+                ),
+                Some(2)
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_case_operators() {
+        assert_eq!(parse_str("g~"), ParseResult::Incomplete);
+        assert_eq!(
+            parse_str("gUU"),
+            command(
+                NormalCommand::Operate(Operator::Uppercase, OperatorTarget::Lines),
+                None
+            )
+        );
+        assert_eq!(
+            parse_str("gu2w"),
+            command(
+                NormalCommand::Operate(
+                    Operator::Lowercase,
+                    OperatorTarget::Motion(Motion::WordForward)
+                ),
+                Some(2)
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_visual() {
+        let parse_visual_str = |keys: &str| parse_visual(&keys.chars().collect::<Vec<_>>());
+
+        assert_eq!(
+            parse_visual_str("x"),
+            command(
+                NormalCommand::Operate(Operator::Delete, OperatorTarget::Selection),
+                None
+            )
+        );
+        assert_eq!(
+            parse_visual_str("3>"),
+            command(
+                NormalCommand::Operate(Operator::Indent, OperatorTarget::Selection),
+                Some(3)
+            )
+        );
+        assert_eq!(
+            parse_visual_str("\u{16}"),
+            command(NormalCommand::Select(SelectionKind::Block), None)
+        );
+        assert_eq!(
+            parse_visual_str("2w"),
+            command(NormalCommand::Move(Motion::WordForward), Some(2))
+        );
+        assert_eq!(parse_visual_str("f"), ParseResult::Incomplete);
+        assert_eq!(parse_visual_str("p"), ParseResult::Invalid);
+    }
+
+    #[test]
+    fn test_motion_kinds() {
+        assert_eq!(Motion::WordForward.kind(), MotionKind::Exclusive);
+        assert_eq!(Motion::WordEnd.kind(), MotionKind::Inclusive);
+        assert_eq!(Motion::FileEnd.kind(), MotionKind::Linewise);
+
+        let find = CharacterFind {
+            character: 'x',
+            backward: false,
+            till: true,
+        };
+
+        assert_eq!(Motion::FindCharacter(find).kind(), MotionKind::Inclusive);
+        assert_eq!(
+            Motion::FindCharacter(find.reversed()).kind(),
+            MotionKind::Exclusive
+        );
+    }
+
+    #[test]
+    fn test_parse_operators() {
+        assert_eq!(parse_str("d"), ParseResult::Incomplete);
+        assert_eq!(parse_str("d2"), ParseResult::Incomplete);
+        assert_eq!(
+            parse_str("dj"),
+            command(
+                NormalCommand::Operate(Operator::Delete, OperatorTarget::Motion(Motion::Down)),
+                None
+            )
+        );
+        assert_eq!(
+            parse_str("2y3l"),
+            command(
+                NormalCommand::Operate(Operator::Yank, OperatorTarget::Motion(Motion::Right)),
+                Some(6)
+            )
+        );
+        assert_eq!(
+            parse_str("3>>"),
+            command(
+                NormalCommand::Operate(Operator::Indent, OperatorTarget::Lines),
+                Some(3)
+            )
+        );
+        assert_eq!(parse_str("dc"), ParseResult::Invalid);
+        assert_eq!(parse_str("dz"), ParseResult::Invalid);
+    }
+
+    #[test]
+    fn test_parse_registers() {
+        let with_register = |command, count, register| {
+            ParseResult::Complete(ParsedCommand {
+                command,
+                count,
+                register: Some(register),
+            })
+        };
+
+        assert_eq!(
+            parse_str("\"ayy"),
+            with_register(
+                NormalCommand::Operate(Operator::Yank, OperatorTarget::Lines),
+                None,
+                'a'
+            )
+        );
+        assert_eq!(
+            parse_str("2\"+3p"),
+            with_register(NormalCommand::PutAfter, Some(6), '+')
+        );
+        assert_eq!(parse_str("\""), ParseResult::Incomplete);
+        assert_eq!(parse_str("\"a"), ParseResult::Incomplete);
+        assert_eq!(parse_str("\"!"), ParseResult::Invalid);
+        assert_eq!(
+            parse_visual(&['"', 'A', 'd']),
+            with_register(
+                NormalCommand::Operate(Operator::Delete, OperatorTarget::Selection),
+                None,
+                'A'
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(
+            parse_str("3x"),
+            command(NormalCommand::DeleteCharacters, Some(3))
+        );
+        assert_eq!(parse_str("P"), command(NormalCommand::PutBefore, None));
+        assert_eq!(parse_str("r"), ParseResult::Incomplete);
+        assert_eq!(
+            parse_str("2ré"),
+            command(NormalCommand::ReplaceCharacters('é'), Some(2))
+        );
+        assert_eq!(parse_str(":"), command(NormalCommand::CommandLine, None));
+        assert_eq!(parse_str("Z"), ParseResult::Invalid);
+    }
+}
@@ -3,45 +3,52 @@ use crate::{
     input::{read_key_input, EditorKey, Key},
     utils::flush_stdout,
 };
+use base64::{engine::general_purpose::STANDARD, Engine};
 use std::io::{self, Write};
 
 #[rustfmt::skip]
 pub enum AnsiEscapeCode {
-    BackgroundGreen,  // \x1b[42m
-    BackgroundRed,    // \x1b[41m
-    ClearScreen,      // \x1b[2J   (J: Erase in Display, 2: clear entire screen)
-    CursorHide,       // \x1b[?25l (?: private mode setting, 25: cursor visibility, l: reset/disable)
-    CursorReport,     // \x1b[6n   (Cursor Position Report [reply e.g. `\x1b[24;80R`])
-    CursorShow,       // \x1b[?25h (?: private mode setting, 25: cursor visibility, h: set/enable)
-    CursorToTopLeft,  // \x1b[H    (H: Cursor Position, e.g. `<esc>[1;1H]`)
-    DefaultColor,     // \x1b[39m  (m: Select Graphic Rendition [39: default color])
-    EraseLineToRight, // \x1b[K    (K: Erase In Line (2: whole, 1: to left, 0: to right [default])
-    ForegroundBlack,  // \x1b[30m
-    Reset,            // \x1b[m
-    ReverseMode,      // \x1b[7m
+    BracketedPasteOff, // \x1b[?2004l
+    BracketedPasteOn,  // \x1b[?2004h (Pastes are sent between `\x1b[200~` and `\x1b[201~`)
+    Bell,              // \x07     (Ends an Operating System Command)
+    ClearScreen,       // \x1b[2J   (J: Erase in Display, 2: clear entire screen)
+    CursorHide,        // \x1b[?25l (?: private mode setting, 25: cursor visibility, l: reset/disable)
+    CursorReport,      // \x1b[6n   (Cursor Position Report [reply e.g. `\x1b[24;80R`])
+    CursorShow,        // \x1b[?25h (?: private mode setting, 25: cursor visibility, h: set/enable)
+    CursorToTopLeft,   // \x1b[H    (H: Cursor Position, e.g. `<esc>[1;1H]`)
+    EraseLineToRight,  // \x1b[K    (K: Erase In Line (2: whole, 1: to left, 0: to right [default])
+    KittyKeyboardOff,  // \x1b[<u   (Restore the keyboard mode pushed by `KittyKeyboardOn`)
+    KittyKeyboardOn,   // \x1b[>1u  (Push a keyboard mode [1: disambiguate], e.g. Escape is sent as `\x1b[27u`)
+    MouseTrackingOff,  // \x1b[?1002l
+    MouseTrackingOn,   // \x1b[?1002h (Report presses, releases, scrolling and drags)
+    OscSetClipboard,   // \x1b]52;c; (OSC 52: set the clipboard [c] to the base64 text that follows)
+    Reset,             // \x1b[m
+    SgrMouseModeOff,   // \x1b[?1006l
+    SgrMouseModeOn,    // \x1b[?1006h (Report mouse events as e.g. `\x1b[<0;12;5M`, without a column limit)
 }
 
 impl AnsiEscapeCode {
     pub fn as_str(&self) -> &'static str {
         match self {
-            AnsiEscapeCode::BackgroundGreen => "\x1b[42m",
-            AnsiEscapeCode::BackgroundRed => "\x1b[41m",
+            AnsiEscapeCode::BracketedPasteOff => "\x1b[?2004l",
+            AnsiEscapeCode::BracketedPasteOn => "\x1b[?2004h",
+            AnsiEscapeCode::Bell => "\x07",
             AnsiEscapeCode::ClearScreen => "\x1b[2J",
             AnsiEscapeCode::CursorHide => "\x1b[?25l",
             AnsiEscapeCode::CursorReport => "\x1b[6n",
             AnsiEscapeCode::CursorShow => "\x1b[?25h",
             AnsiEscapeCode::CursorToTopLeft => "\x1b[H",
-            AnsiEscapeCode::DefaultColor => "\x1b[39m",
             AnsiEscapeCode::EraseLineToRight => "\x1b[K",
-            AnsiEscapeCode::ForegroundBlack => "\x1b[30m",
+            AnsiEscapeCode::KittyKeyboardOff => "\x1b[<u",
+            AnsiEscapeCode::KittyKeyboardOn => "\x1b[>1u",
+            AnsiEscapeCode::MouseTrackingOff => "\x1b[?1002l",
+            AnsiEscapeCode::MouseTrackingOn => "\x1b[?1002h",
+            AnsiEscapeCode::OscSetClipboard => "\x1b]52;c;",
             AnsiEscapeCode::Reset => "\x1b[m",
-            AnsiEscapeCode::ReverseMode => "\x1b[7m",
+            AnsiEscapeCode::SgrMouseModeOff => "\x1b[?1006l",
+            AnsiEscapeCode::SgrMouseModeOn => "\x1b[?1006h",
         }
     }
-
-    pub fn as_string(&self) -> String {
-        self.as_str().to_string()
-    }
 }
 
 pub fn move_cursor_to_top_left() {
@@ -50,6 +57,78 @@ pub fn move_cursor_to_top_left() {
     flush_stdout();
 }
 
+pub fn enable_bracketed_paste() {
+    write!(
+        io::stdout(),
+        "{}",
+        AnsiEscapeCode::BracketedPasteOn.as_str()
+    )
+    .expect("Failed to enable bracketed paste");
+    flush_stdout();
+}
+
+pub fn disable_bracketed_paste() {
+    write!(
+        io::stdout(),
+        "{}",
+        AnsiEscapeCode::BracketedPasteOff.as_str()
+    )
+    .expect("Failed to disable bracketed paste");
+    flush_stdout();
+}
+
+pub fn enable_mouse_reporting() {
+    write!(
+        io::stdout(),
+        "{}{}",
+        AnsiEscapeCode::MouseTrackingOn.as_str(),
+        AnsiEscapeCode::SgrMouseModeOn.as_str()
+    )
+    .expect("Failed to enable mouse reporting");
+    flush_stdout();
+}
+
+pub fn disable_mouse_reporting() {
+    write!(
+        io::stdout(),
+        "{}{}",
+        AnsiEscapeCode::SgrMouseModeOff.as_str(),
+        AnsiEscapeCode::MouseTrackingOff.as_str()
+    )
+    .expect("Failed to disable mouse reporting");
+    flush_stdout();
+}
+
+/// Terminals that do not support the kitty keyboard protocol ignore the sequence
+pub fn enable_kitty_keyboard_protocol() {
+    write!(io::stdout(), "{}", AnsiEscapeCode::KittyKeyboardOn.as_str())
+        .expect("Failed to enable the kitty keyboard protocol");
+    flush_stdout();
+}
+
+pub fn disable_kitty_keyboard_protocol() {
+    write!(
+        io::stdout(),
+        "{}",
+        AnsiEscapeCode::KittyKeyboardOff.as_str()
+    )
+    .expect("Failed to disable the kitty keyboard protocol");
+    flush_stdout();
+}
+
+/// Terminals that do not support OSC 52 ignore the sequence
+pub fn copy_to_terminal_clipboard(text: &str) {
+    write!(
+        io::stdout(),
+        "{}{}{}",
+        AnsiEscapeCode::OscSetClipboard.as_str(),
+        STANDARD.encode(text),
+        AnsiEscapeCode::Bell.as_str()
+    )
+    .expect("Failed to write clipboard escape sequence");
+    flush_stdout();
+}
+
 pub fn clear_display() {
     write!(io::stdout(), "{}", AnsiEscapeCode::ClearScreen.as_str())
         .expect("Failed to clear screen");
@@ -86,11 +165,55 @@ pub fn prompt_user<F: Fn(&mut EditorInstance, &str, Key)>(
     editor_instance: &mut EditorInstance,
     prompt: &str,
     callback: Option<F>,
+) -> Option<String> {
+    read_prompt_input(editor_instance, prompt, callback, None, false)
+}
+
+/// Like `prompt_user`, but Enter also accepts an empty response
+pub fn prompt_user_allowing_empty(
+    editor_instance: &mut EditorInstance,
+    prompt: &str,
+) -> Option<String> {
+    read_prompt_input::<fn(&mut EditorInstance, &str, Key)>(
+        editor_instance,
+        prompt,
+        None,
+        None,
+        true,
+    )
+}
+
+/// Like `prompt_user`, but Tab replaces the input with each of its completions in turn
+pub fn prompt_user_with_completion(
+    editor_instance: &mut EditorInstance,
+    prompt: &str,
+    complete: fn(&str) -> Vec<String>,
+) -> Option<String> {
+    read_prompt_input::<fn(&mut EditorInstance, &str, Key)>(
+        editor_instance,
+        prompt,
+        None,
+        Some(complete),
+        false,
+    )
+}
+
+fn read_prompt_input<F: Fn(&mut EditorInstance, &str, Key)>(
+    editor_instance: &mut EditorInstance,
+    prompt: &str,
+    callback: Option<F>,
+    complete: Option<fn(&str) -> Vec<String>>,
+    allow_empty: bool,
 ) -> Option<String> {
     let mut buffer = String::new();
+    let mut completions: Vec<String> = vec![];
+    let mut completion_index = 0;
 
     loop {
-        editor_instance.set_status_message(&format!("{}{}", prompt, buffer), false);
+        editor_instance.set_status_message(
+            &format!("{}{}{}", prompt, buffer, editor_instance.get_prompt_hint()),
+            false,
+        );
         refresh_screen(editor_instance);
 
         if let Some(key) = read_key_input() {
@@ -102,23 +225,41 @@ pub fn prompt_user<F: Fn(&mut EditorInstance, &str, Key)>(
                         callback(editor_instance, &buffer, key);
                     }
 
+                    editor_instance.clear_prompt_hint();
                     return None;
                 }
                 Key::Custom(EditorKey::Backspace) => {
                     buffer.pop();
                 }
-                Key::U8(b'\r') => {
-                    if !buffer.is_empty() {
-                        editor_instance.set_status_message("", false);
+                Key::U8(b'\r') if allow_empty || !buffer.is_empty() => {
+                    editor_instance.set_status_message("", false);
 
-                        if let Some(callback) = &callback {
-                            callback(editor_instance, &buffer, key);
-                        }
+                    if let Some(callback) = &callback {
+                        callback(editor_instance, &buffer, key);
+                    }
 
-                        return Some(buffer);
+                    editor_instance.clear_prompt_hint();
+                    return Some(buffer);
+                }
+                Key::U8(b'\t') if complete.is_some() => {
+                    // Pressing Tab again cycles through the completions, unless there was only
+                    // one, which may be a directory to complete inside of
+                    if completions.len() > 1 && completions.get(completion_index) == Some(&buffer) {
+                        completion_index = (completion_index + 1) % completions.len();
+                    } else {
+                        completions = complete.map_or(vec![], |complete| complete(&buffer));
+                        completion_index = 0;
+                    }
+
+                    if let Some(completion) = completions.get(completion_index) {
+                        buffer = completion.clone();
                     }
                 }
                 Key::U8(byte) if !(byte as char).is_ascii_control() => buffer.push(byte as char),
+                Key::Char(char) => buffer.push(char),
+                Key::Paste(ref text) => {
+                    buffer.extend(text.chars().filter(|char| !char.is_control()));
+                }
                 _ => {}
             }
 
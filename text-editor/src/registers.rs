@@ -0,0 +1,356 @@
+use crate::output::copy_to_terminal_clipboard;
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+};
+
+const NUM_NUMBERED_REGISTERS: usize = 9;
+
+/// Text that was deleted or yanked, for putting with `p` and `P`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Register {
+    pub text: String,
+    pub linewise: bool, // Whole lines, put above or below the cursor line
+}
+
+/// The system clipboard, written with OSC 52 and, if configured, an external command
+/// Terminals cannot be relied on to answer OSC 52 queries, so without a paste command the
+/// clipboard reads back what was last copied from the editor.
+pub struct Clipboard {
+    copy_command: Vec<String>,  // Given the text on stdin, e.g. `wl-copy`
+    paste_command: Vec<String>, // Prints the clipboard, e.g. `wl-paste --no-newline`
+    last_copied: Option<Register>,
+}
+
+/// The unnamed register `"`, named registers `a`-`z` (appended to with `A`-`Z`), the last
+/// yank `0`, deletes `1`-`9` (newest first), small deletes `-`, the clipboard `+` (or `*`)
+/// and the black hole `_`
+pub struct Registers {
+    unnamed: Option<Register>,
+    named: [Option<Register>; 26],
+    yanked: Option<Register>,
+    numbered: [Option<Register>; NUM_NUMBERED_REGISTERS],
+    small_delete: Option<Register>,
+    pub clipboard: Clipboard,
+}
+
+pub fn is_register_name(name: char) -> bool {
+    name.is_ascii_alphanumeric() || matches!(name, '"' | '-' | '+' | '*' | '_')
+}
+
+fn run_command(command: &[String], input: Option<&str>) -> Result<String, String> {
+    let (program, arguments) = command
+        .split_first()
+        .ok_or_else(|| String::from("No clipboard command configured"))?;
+
+    let describe_error = |e: std::io::Error| format!("Clipboard command {} failed: {}", program, e);
+
+    let mut child = Command::new(program)
+        .args(arguments)
+        .stdin(match input {
+            Some(_) => Stdio::piped(),
+            None => Stdio::null(),
+        })
+        .stdout(match input {
+            Some(_) => Stdio::null(),
+            None => Stdio::piped(),
+        })
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(describe_error)?;
+
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes()).map_err(describe_error)?;
+    }
+
+    // Copy commands such as `xclip` leave a process serving the clipboard, so only the paste
+    // command's output is waited for
+    let (status, stdout) = match input {
+        Some(_) => (child.wait().map_err(describe_error)?, vec![]),
+        None => {
+            let output = child.wait_with_output().map_err(describe_error)?;
+            (output.status, output.stdout)
+        }
+    };
+
+    match status.success() {
+        true => Ok(String::from_utf8_lossy(&stdout).into_owned()),
+        false => Err(format!("Clipboard command {} failed: {}", program, status)),
+    }
+}
+
+impl Clipboard {
+    pub fn new(copy_command: &[&str], paste_command: &[&str]) -> Self {
+        Clipboard {
+            copy_command: copy_command.iter().map(|arg| arg.to_string()).collect(),
+            paste_command: paste_command.iter().map(|arg| arg.to_string()).collect(),
+            last_copied: None,
+        }
+    }
+
+    pub fn set_commands(&mut self, copy_command: &[String], paste_command: &[String]) {
+        self.copy_command = copy_command.to_vec();
+        self.paste_command = paste_command.to_vec();
+    }
+
+    pub fn copy(&mut self, register: Register) -> Result<(), String> {
+        copy_to_terminal_clipboard(&register.text);
+
+        let result = match self.copy_command.is_empty() {
+            true => Ok(()),
+            false => run_command(&self.copy_command, Some(&register.text)).map(|_| ()),
+        };
+
+        self.last_copied = Some(register);
+        result
+    }
+
+    pub fn paste(&self) -> Result<Option<Register>, String> {
+        if self.paste_command.is_empty() {
+            return Ok(self.last_copied.clone());
+        }
+
+        let text = run_command(&self.paste_command, None)?;
+
+        // What the editor copied keeps its linewise-ness; other text is linewise if it is
+        // made of whole lines
+        Ok(match &self.last_copied {
+            Some(register) if register.text == text => Some(register.clone()),
+            _ if text.is_empty() => None,
+            _ => Some(Register {
+                linewise: text.ends_with('\n'),
+                text,
+            }),
+        })
+    }
+}
+
+impl Registers {
+    pub fn new(clipboard: Clipboard) -> Self {
+        Registers {
+            unnamed: None,
+            named: Default::default(),
+            yanked: None,
+            numbered: Default::default(),
+            small_delete: None,
+            clipboard,
+        }
+    }
+
+    /// Returns the contents of the register `name`, or of the unnamed register if `None`
+    pub fn get(&self, name: Option<char>) -> Result<Option<Register>, String> {
+        Ok(match name.unwrap_or('"') {
+            '"' => self.unnamed.clone(),
+            name @ 'a'..='z' | name @ 'A'..='Z' => {
+                self.named[(name.to_ascii_lowercase() as u8 - b'a') as usize].clone()
+            }
+            '0' => self.yanked.clone(),
+            name @ '1'..='9' => self.numbered[(name as u8 - b'1') as usize].clone(),
+            '-' => self.small_delete.clone(),
+            '+' | '*' => return self.clipboard.paste(),
+            _ => None,
+        })
+    }
+
+    /// Writes a named register, or the clipboard
+    /// The unnamed register is updated by the caller.
+    fn set(&mut self, name: char, register: Register) -> Result<(), String> {
+        match name {
+            'a'..='z' => self.named[(name as u8 - b'a') as usize] = Some(register),
+            'A'..='Z' => {
+                let named = &mut self.named[(name.to_ascii_lowercase() as u8 - b'a') as usize];
+
+                *named = Some(match named.take() {
+                    // Lines appended to characters start on a line of their own
+                    Some(existing) => Register {
+                        text: match !existing.linewise && register.linewise {
+                            true => format!("{}\n{}", existing.text, register.text),
+                            false => format!("{}{}", existing.text, register.text),
+                        },
+                        linewise: existing.linewise || register.linewise,
+                    },
+                    None => register,
+                });
+            }
+            '0' => self.yanked = Some(register),
+            '1'..='9' => self.numbered[(name as u8 - b'1') as usize] = Some(register),
+            '-' => self.small_delete = Some(register),
+            '+' | '*' => return self.clipboard.copy(register),
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Stores yanked text in the register `name` if given, otherwise in `0`
+    pub fn yank(&mut self, name: Option<char>, register: Register) -> Result<(), String> {
+        self.store(name.unwrap_or('0'), register)
+    }
+
+    /// Stores deleted text in the register `name` if given, otherwise in `1` (shifting older
+    /// deletes along), or `-` for deletes within a line
+    pub fn delete(&mut self, name: Option<char>, register: Register) -> Result<(), String> {
+        match name {
+            Some(name) => self.store(name, register),
+            None if register.linewise || register.text.contains('\n') => {
+                self.numbered.rotate_right(1);
+                self.store('1', register)
+            }
+            None => self.store('-', register),
+        }
+    }
+
+    fn store(&mut self, name: char, register: Register) -> Result<(), String> {
+        if name == '_' {
+            return Ok(());
+        }
+
+        self.unnamed = Some(register.clone());
+        let result = self.set(name, register);
+
+        // Appending leaves the unnamed register with the whole of the named one
+        if name.is_ascii_uppercase() {
+            self.unnamed = self.named[(name.to_ascii_lowercase() as u8 - b'a') as usize].clone();
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn registers() -> Registers {
+        Registers::new(Clipboard::new(&[], &[]))
+    }
+
+    fn characters(text: &str) -> Register {
+        Register {
+            text: text.to_string(),
+            linewise: false,
+        }
+    }
+
+    fn lines(text: &str) -> Register {
+        Register {
+            text: text.to_string(),
+            linewise: true,
+        }
+    }
+
+    fn text(registers: &Registers, name: char) -> Option<String> {
+        registers
+            .get(Some(name))
+            .unwrap()
+            .map(|register| register.text)
+    }
+
+    #[test]
+    fn test_yank_fills_unnamed_and_yank_registers() {
+        let mut registers = registers();
+
+        registers.yank(None, characters("abc")).unwrap();
+
+        assert_eq!(registers.get(None).unwrap(), Some(characters("abc")));
+        assert_eq!(text(&registers, '0'), Some(String::from("abc")));
+        assert_eq!(text(&registers, '1'), None);
+    }
+
+    #[test]
+    fn test_deletes_shift_through_numbered_registers() {
+        let mut registers = registers();
+
+        for line in 1..=10 {
+            registers
+                .delete(None, lines(&format!("line {}\n", line)))
+                .unwrap();
+        }
+
+        assert_eq!(text(&registers, '1'), Some(String::from("line 10\n")));
+        assert_eq!(text(&registers, '9'), Some(String::from("line 2\n")));
+        assert_eq!(text(&registers, '"'), Some(String::from("line 10\n")));
+
+        // Deletes within a line go to the small delete register instead
+        registers.delete(None, characters("x")).unwrap();
+        assert_eq!(text(&registers, '-'), Some(String::from("x")));
+        assert_eq!(text(&registers, '1'), Some(String::from("line 10\n")));
+        assert_eq!(text(&registers, '"'), Some(String::from("x")));
+    }
+
+    #[test]
+    fn test_named_registers() {
+        let mut registers = registers();
+
+        registers.yank(Some('a'), characters("foo")).unwrap();
+        registers.delete(Some('b'), lines("bar\n")).unwrap();
+
+        assert_eq!(text(&registers, 'a'), Some(String::from("foo")));
+        assert_eq!(registers.get(Some('b')).unwrap(), Some(lines("bar\n")));
+        assert_eq!(text(&registers, '0'), None);
+        assert_eq!(text(&registers, '1'), None);
+        assert_eq!(text(&registers, '"'), Some(String::from("bar\n")));
+    }
+
+    #[test]
+    fn test_append_to_named_register() {
+        let mut registers = registers();
+
+        registers.yank(Some('A'), characters("foo")).unwrap();
+        registers.yank(Some('A'), characters("bar")).unwrap();
+        assert_eq!(text(&registers, 'a'), Some(String::from("foobar")));
+
+        registers.yank(Some('A'), lines("baz\n")).unwrap();
+        assert_eq!(
+            registers.get(Some('a')).unwrap(),
+            Some(lines("foobar\nbaz\n"))
+        );
+        assert_eq!(text(&registers, '"'), Some(String::from("foobar\nbaz\n")));
+    }
+
+    #[test]
+    fn test_black_hole_register() {
+        let mut registers = registers();
+
+        registers.yank(None, characters("kept")).unwrap();
+        registers.delete(Some('_'), lines("gone\n")).unwrap();
+
+        assert_eq!(text(&registers, '"'), Some(String::from("kept")));
+        assert_eq!(text(&registers, '_'), None);
+    }
+
+    #[test]
+    fn test_clipboard_without_commands_reads_back_last_copy() {
+        let mut registers = registers();
+
+        registers.yank(Some('+'), lines("copied\n")).unwrap();
+
+        assert_eq!(registers.get(Some('*')).unwrap(), Some(lines("copied\n")));
+        assert_eq!(text(&registers, '"'), Some(String::from("copied\n")));
+    }
+
+    #[test]
+    fn test_clipboard_commands() {
+        let mut clipboard = Clipboard::new(&["cat"], &["echo", "pasted"]);
+
+        clipboard.copy(characters("copied")).unwrap();
+        assert_eq!(clipboard.paste().unwrap(), Some(lines("pasted\n")));
+
+        let mut clipboard = Clipboard::new(&["false"], &["false"]);
+        assert!(clipboard.copy(characters("copied")).is_err());
+        assert!(clipboard.paste().is_err());
+
+        let clipboard = Clipboard::new(&[], &["text-editor-missing-clipboard-command"]);
+        assert!(clipboard.paste().is_err());
+    }
+
+    #[test]
+    fn test_copy_command_left_running_in_background() {
+        let mut clipboard = Clipboard::new(&["sh", "-c", "cat >/dev/null; sleep 10 &"], &[]);
+        let start = Instant::now();
+
+        clipboard.copy(characters("copied")).unwrap();
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+}
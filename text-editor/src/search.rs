@@ -0,0 +1,282 @@
+use crate::utils::byte_index_to_column;
+use regex::{Regex, RegexBuilder};
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum CaseSensitivity {
+    Sensitive,
+    Insensitive,
+    Smart, // Insensitive unless the pattern contains an uppercase letter
+}
+
+/// A search as typed into the prompt
+/// Prefixes toggle options and may be combined, e.g. `\c\<word`:
+/// - `\c`: ignore case
+/// - `\C`: match case (the default is smart case)
+/// - `\<`: match whole words only
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchQuery {
+    pub pattern: String,
+    pub case_sensitivity: CaseSensitivity,
+    pub whole_word: bool,
+}
+
+/// A match within a line of text
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchMatch {
+    pub byte_range: Range<usize>,
+    pub column_range: Range<usize>, // Grapheme columns of the text, not of the render
+}
+
+pub struct Searcher {
+    regex: Regex,
+}
+
+impl SearchQuery {
+    pub fn parse(input: &str) -> Self {
+        let mut query = SearchQuery {
+            pattern: String::new(),
+            case_sensitivity: CaseSensitivity::Smart,
+            whole_word: false,
+        };
+
+        let mut rest = input;
+
+        loop {
+            if let Some(stripped) = rest.strip_prefix("\\c") {
+                query.case_sensitivity = CaseSensitivity::Insensitive;
+                rest = stripped;
+            } else if let Some(stripped) = rest.strip_prefix("\\C") {
+                query.case_sensitivity = CaseSensitivity::Sensitive;
+                rest = stripped;
+            } else if let Some(stripped) = rest.strip_prefix("\\<") {
+                query.whole_word = true;
+                rest = stripped;
+            } else {
+                break;
+            }
+        }
+
+        query.pattern = rest.to_string();
+        query
+    }
+
+    fn is_case_insensitive(&self) -> bool {
+        match self.case_sensitivity {
+            CaseSensitivity::Sensitive => false,
+            CaseSensitivity::Insensitive => true,
+            CaseSensitivity::Smart => !has_unescaped_uppercase(&self.pattern),
+        }
+    }
+
+    /// Patterns that are not valid regular expressions (e.g. `foo(`) are matched literally
+    /// Fails when even the literal pattern is too long to compile.
+    pub fn compile(&self) -> Result<Searcher, String> {
+        let build = |pattern: &str| {
+            let pattern = match self.whole_word {
+                true => format!(r"\b(?:{})\b", pattern),
+                false => pattern.to_string(),
+            };
+
+            RegexBuilder::new(&pattern)
+                .case_insensitive(self.is_case_insensitive())
+                .build()
+        };
+
+        let regex = build(&self.pattern)
+            .or_else(|_| build(&regex::escape(&self.pattern)))
+            .map_err(|error| match error {
+                regex::Error::CompiledTooBig(_) => String::from("Search pattern is too long"),
+                error => format!("Invalid search pattern: {}", error),
+            })?;
+
+        Ok(Searcher { regex })
+    }
+}
+
+/// Escapes such as `\W` or `\S` are character classes rather than uppercase text
+fn has_unescaped_uppercase(pattern: &str) -> bool {
+    let mut chars = pattern.chars();
+
+    while let Some(char) = chars.next() {
+        match char {
+            '\\' => {
+                chars.next();
+            }
+            char if char.is_uppercase() => return true,
+            _ => {}
+        }
+    }
+
+    false
+}
+
+impl Searcher {
+    fn to_search_match(text: &str, byte_range: Range<usize>) -> SearchMatch {
+        SearchMatch {
+            column_range: byte_index_to_column(text, byte_range.start)
+                ..byte_index_to_column(text, byte_range.end),
+            byte_range,
+        }
+    }
+
+    pub fn find_all(&self, text: &str) -> Vec<SearchMatch> {
+        self.regex
+            .find_iter(text)
+            .map(|found| Searcher::to_search_match(text, found.range()))
+            .collect()
+    }
+
+    pub fn count(&self, text: &str) -> usize {
+        self.regex.find_iter(text).count()
+    }
+
+    /// Counts the matches starting at or before `byte_index`
+    pub fn count_until(&self, text: &str, byte_index: usize) -> usize {
+        self.regex
+            .find_iter(text)
+            .take_while(|found| found.start() <= byte_index)
+            .count()
+    }
+
+    /// Searches from `start` (a byte index), treating the text before it as context for
+    /// anchors and word boundaries
+    pub fn find_at(&self, text: &str, start: usize) -> Option<SearchMatch> {
+        self.regex
+            .find_at(text, start)
+            .map(|found| Searcher::to_search_match(text, found.range()))
+    }
+
+    /// Expands `$1`, `${name}` etc. in `replacement` using the capture groups of a match
+    /// previously found in `text`
+    pub fn expand_replacement(
+        &self,
+        text: &str,
+        search_match: &SearchMatch,
+        replacement: &str,
+    ) -> String {
+        let mut expanded = String::new();
+
+        if let Some(captures) = self
+            .regex
+            .captures_at(text, search_match.byte_range.start)
+            .filter(|captures| captures.get_match().range() == search_match.byte_range)
+        {
+            captures.expand(replacement, &mut expanded);
+        }
+
+        expanded
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    fn find_all(input: &str, text: &str) -> Vec<Range<usize>> {
+        SearchQuery::parse(input)
+            .compile()
+            .unwrap()
+            .find_all(text)
+            .into_iter()
+            .map(|search_match| search_match.column_range)
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_prefixes() {
+        let query = SearchQuery::parse("\\c\\<foo");
+        assert_eq!(query.pattern, "foo");
+        assert_eq!(query.case_sensitivity, CaseSensitivity::Insensitive);
+        assert!(query.whole_word);
+
+        let query = SearchQuery::parse("\\Cfoo\\c");
+        assert_eq!(query.pattern, "foo\\c");
+        assert_eq!(query.case_sensitivity, CaseSensitivity::Sensitive);
+        assert!(!query.whole_word);
+    }
+
+    #[test]
+    fn test_regex_search() {
+        assert_eq!(find_all("t[aeiou]st", "test tost tst"), vec![0..4, 5..9]);
+        assert_eq!(find_all("^a", "aaa"), vec![0..1]);
+    }
+
+    #[test]
+    fn test_count() {
+        let searcher = SearchQuery::parse("a").compile().unwrap();
+
+        assert_eq!(searcher.count("a ba a"), 3);
+        assert_eq!(searcher.count_until("a ba a", 3), 2);
+        assert_eq!(searcher.count_until("a ba a", 2), 1);
+    }
+
+    #[test]
+    fn test_invalid_regex_is_literal() {
+        assert_eq!(find_all("foo(", "call foo(x)"), vec![5..9]);
+    }
+
+    #[test]
+    fn test_pattern_too_long() {
+        let query = SearchQuery::parse(&"a(".repeat(100_000));
+
+        assert_eq!(
+            query.compile().err().as_deref(),
+            Some("Search pattern is too long")
+        );
+    }
+
+    #[test]
+    fn test_smart_case() {
+        assert_eq!(find_all("foo", "Foo foo"), vec![0..3, 4..7]);
+        assert_eq!(find_all("Foo", "Foo foo"), vec![0..3]);
+        assert_eq!(find_all("\\Wfoo", "x Foo"), vec![1..5]);
+    }
+
+    #[test]
+    fn test_case_prefixes() {
+        assert_eq!(find_all("\\cFoo", "Foo foo"), vec![0..3, 4..7]);
+        assert_eq!(find_all("\\Cfoo", "Foo foo"), vec![4..7]);
+    }
+
+    #[test]
+    fn test_whole_word() {
+        assert_eq!(
+            find_all("\\<cat", "cat concat cats cat"),
+            vec![0..3, 16..19]
+        );
+        assert_eq!(find_all("\\<a|b", "a ab b"), vec![0..1, 5..6]);
+    }
+
+    #[test]
+    fn test_match_columns_are_text_columns() {
+        // Columns count graphemes of the text, so tabs and multi-byte characters count once
+        assert_eq!(find_all("x", "\tcafé x"), vec![6..7]);
+        assert_eq!(find_all("é", "e\u{301}é"), vec![1..2]);
+    }
+
+    #[test]
+    fn test_find_at_uses_preceding_context() {
+        let searcher = SearchQuery::parse("\\<cat").compile().unwrap();
+
+        assert_eq!(
+            searcher.find_at("concat cat", 3).unwrap().column_range,
+            7..10
+        );
+    }
+
+    #[test]
+    fn test_expand_replacement() {
+        let searcher = SearchQuery::parse("(\\w+)@(?<domain>\\w+)")
+            .compile()
+            .unwrap();
+        let text = "mail: user@example";
+        let search_match = searcher.find_at(text, 0).unwrap();
+
+        assert_eq!(search_match.byte_range, 6..18);
+        assert_eq!(
+            searcher.expand_replacement(text, &search_match, "${domain}/$1"),
+            "example/user"
+        );
+    }
+}
@@ -0,0 +1,324 @@
+use crate::{
+    ex_command::SetAction,
+    globals::{
+        CREATE_BACKUP_FILE, EXPAND_TABS, QUIT_CONFIRMATION_COUNT, SCROLL_MARGIN, SHOW_LINE_NUMBERS,
+        TAB_SIZE,
+    },
+    keymap::Mode,
+    theme::{find_theme, format_color, parse_color, Color, Theme, THEMES},
+};
+
+/// Option names, as used by `:set` and the config file
+pub const OPTIONS: &[&str] = &[
+    "backup",
+    "clipboardcopy",
+    "clipboardpaste",
+    "colors.comment",
+    "colors.keyword",
+    "colors.number",
+    "colors.search_match",
+    "colors.string",
+    "colors.type",
+    "defaultmode",
+    "expandtab",
+    "number",
+    "quitconfirmations",
+    "scrolloff",
+    "tabstop",
+    "theme",
+];
+
+const OPTION_ABBREVIATIONS: &[(&str, &str)] = &[
+    ("et", "expandtab"),
+    ("nu", "number"),
+    ("so", "scrolloff"),
+    ("ts", "tabstop"),
+];
+
+/// Foreground colours for kinds of highlighted text, in place of the theme's
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Colors {
+    pub number: Option<Color>,
+    pub string: Option<Color>,
+    pub comment: Option<Color>,
+    pub keyword: Option<Color>,
+    pub type_name: Option<Color>,
+    pub search_match: Option<Color>,
+}
+
+/// Options that can be set in the config file or with `:set`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    pub tab_size: u8,
+    pub expand_tabs: bool, // Tab inserts spaces, as does indenting
+    pub line_numbers: bool,
+    pub default_mode: Mode, // On starting and opening files; Normal or Insert
+    pub quit_confirmations: u8, // Extra presses of quit needed with unsaved changes
+    pub scroll_margin: u8,  // Lines kept visible above and below the cursor
+    pub create_backup_file: bool, // Copy the original to `<file>~` before saving
+    // The `"+` register always copies with an OSC 52 escape sequence, which works over SSH
+    // These commands also reach the system clipboard, e.g. `wl-copy` and `wl-paste -n`
+    pub clipboard_copy_command: Vec<String>, // Given the text on stdin
+    pub clipboard_paste_command: Vec<String>, // Prints the clipboard
+    pub theme: &'static Theme,
+    pub colors: Colors,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            tab_size: TAB_SIZE,
+            expand_tabs: EXPAND_TABS,
+            line_numbers: SHOW_LINE_NUMBERS,
+            default_mode: Mode::Normal,
+            quit_confirmations: QUIT_CONFIRMATION_COUNT,
+            scroll_margin: SCROLL_MARGIN,
+            create_backup_file: CREATE_BACKUP_FILE,
+            clipboard_copy_command: vec![],
+            clipboard_paste_command: vec![],
+            theme: &THEMES[0],
+            colors: Colors::default(),
+        }
+    }
+}
+
+/// Returns the full name of an option, which may be abbreviated, e.g. `ts` for `tabstop`
+fn find_option(name: &str) -> Option<&'static str> {
+    OPTIONS
+        .iter()
+        .copied()
+        .find(|option| *option == name)
+        .or_else(|| {
+            OPTION_ABBREVIATIONS
+                .iter()
+                .find(|(abbreviation, _)| *abbreviation == name)
+                .map(|(_, option)| *option)
+        })
+}
+
+impl Colors {
+    fn get(&self, option: &str) -> Option<Color> {
+        match option {
+            "colors.comment" => self.comment,
+            "colors.keyword" => self.keyword,
+            "colors.number" => self.number,
+            "colors.search_match" => self.search_match,
+            "colors.string" => self.string,
+            "colors.type" => self.type_name,
+            _ => unreachable!("Not a colour option: {}", option),
+        }
+    }
+
+    fn get_mut(&mut self, option: &str) -> &mut Option<Color> {
+        match option {
+            "colors.comment" => &mut self.comment,
+            "colors.keyword" => &mut self.keyword,
+            "colors.number" => &mut self.number,
+            "colors.search_match" => &mut self.search_match,
+            "colors.string" => &mut self.string,
+            "colors.type" => &mut self.type_name,
+            _ => unreachable!("Not a colour option: {}", option),
+        }
+    }
+}
+
+impl Settings {
+    /// Describes the value of an option as `:set` would set it, e.g. `tabstop=4` or `nobackup`
+    pub fn get(&self, name: &str) -> Result<String, String> {
+        let option = find_option(name).ok_or_else(|| format!("Unknown option: {}", name))?;
+
+        let flag = |enabled: bool| match enabled {
+            true => option.to_string(),
+            false => format!("no{}", option),
+        };
+
+        Ok(match option {
+            "backup" => flag(self.create_backup_file),
+            "expandtab" => flag(self.expand_tabs),
+            "number" => flag(self.line_numbers),
+            option => {
+                let value = match option {
+                    "clipboardcopy" => self.clipboard_copy_command.join(" "),
+                    "clipboardpaste" => self.clipboard_paste_command.join(" "),
+                    "defaultmode" => String::from(match self.default_mode {
+                        Mode::Insert => "insert",
+                        _ => "normal",
+                    }),
+                    "quitconfirmations" => self.quit_confirmations.to_string(),
+                    "scrolloff" => self.scroll_margin.to_string(),
+                    "tabstop" => self.tab_size.to_string(),
+                    "theme" => self.theme.name.to_string(),
+                    color => self.colors.get(color).map(format_color).unwrap_or_default(),
+                };
+
+                format!("{}={}", option, value)
+            }
+        })
+    }
+
+    /// Lists the value of every option other than the colours
+    pub fn describe(&self) -> String {
+        OPTIONS
+            .iter()
+            .filter(|option| !option.starts_with("colors."))
+            .map(|option| self.get(option).expect("Options are known"))
+            .collect::<Vec<String>>()
+            .join(" ")
+    }
+
+    /// Enables, disables or assigns an option, leaving it unchanged if that is not valid
+    pub fn set(&mut self, name: &str, action: &SetAction) -> Result<(), String> {
+        let option = find_option(name).ok_or_else(|| format!("Unknown option: {}", name))?;
+
+        let invalid_argument = || match action {
+            SetAction::Assign(value) => format!("Invalid argument: {}={}", name, value),
+            _ => format!("Invalid argument: {}", name),
+        };
+
+        let parse_number = |value: &str, minimum: u8| match value.parse::<u8>() {
+            Ok(number) if number >= minimum => Ok(number),
+            _ => Err(invalid_argument()),
+        };
+
+        match (option, action) {
+            ("backup", SetAction::Enable | SetAction::Disable) => {
+                self.create_backup_file = *action == SetAction::Enable;
+            }
+            ("expandtab", SetAction::Enable | SetAction::Disable) => {
+                self.expand_tabs = *action == SetAction::Enable;
+            }
+            ("number", SetAction::Enable | SetAction::Disable) => {
+                self.line_numbers = *action == SetAction::Enable;
+            }
+            ("clipboardcopy", SetAction::Assign(value)) => {
+                self.clipboard_copy_command = value.split_whitespace().map(String::from).collect();
+            }
+            ("clipboardpaste", SetAction::Assign(value)) => {
+                self.clipboard_paste_command = value.split_whitespace().map(String::from).collect();
+            }
+            ("defaultmode", SetAction::Assign(value)) => {
+                self.default_mode = match value.as_str() {
+                    "normal" => Mode::Normal,
+                    "insert" => Mode::Insert,
+                    _ => return Err(invalid_argument()),
+                };
+            }
+            ("quitconfirmations", SetAction::Assign(value)) => {
+                self.quit_confirmations = parse_number(value, 0)?;
+            }
+            ("scrolloff", SetAction::Assign(value)) => {
+                self.scroll_margin = parse_number(value, 0)?;
+            }
+            ("tabstop", SetAction::Assign(value)) => self.tab_size = parse_number(value, 1)?,
+            ("theme", SetAction::Assign(value)) => {
+                self.theme = find_theme(value).ok_or_else(invalid_argument)?;
+            }
+            // An empty colour leaves it to the theme
+            (color, SetAction::Assign(value)) if color.starts_with("colors.") => {
+                *self.colors.get_mut(color) = match value.as_str() {
+                    "" => None,
+                    value => Some(parse_color(value).ok_or_else(invalid_argument)?),
+                };
+            }
+            _ => return Err(invalid_argument()),
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    fn assign(value: &str) -> SetAction {
+        SetAction::Assign(value.to_string())
+    }
+
+    #[test]
+    fn test_set_and_get() {
+        let mut settings = Settings::default();
+
+        settings.set("ts", &assign("8")).unwrap();
+        settings.set("expandtab", &SetAction::Enable).unwrap();
+        settings.set("nu", &SetAction::Disable).unwrap();
+        settings.set("defaultmode", &assign("insert")).unwrap();
+        settings
+            .set("clipboardcopy", &assign("xclip -selection clipboard"))
+            .unwrap();
+        settings.set("colors.keyword", &assign("#ff8800")).unwrap();
+        settings.set("theme", &assign("gruvbox-dark")).unwrap();
+
+        assert_eq!(settings.tab_size, 8);
+        assert_eq!(settings.get("tabstop"), Ok(String::from("tabstop=8")));
+        assert_eq!(settings.get("et"), Ok(String::from("expandtab")));
+        assert_eq!(settings.get("number"), Ok(String::from("nonumber")));
+        assert_eq!(settings.default_mode, Mode::Insert);
+        assert_eq!(
+            settings.clipboard_copy_command,
+            vec!["xclip", "-selection", "clipboard"]
+        );
+        assert_eq!(settings.colors.keyword, Some(Color::Rgb(0xff, 0x88, 0x00)));
+        assert_eq!(
+            settings.get("colors.keyword"),
+            Ok(String::from("colors.keyword=#ff8800"))
+        );
+        assert_eq!(
+            settings.get("colors.type"),
+            Ok(String::from("colors.type="))
+        );
+        assert_eq!(settings.theme.name, "gruvbox-dark");
+
+        settings.set("colors.keyword", &assign("")).unwrap();
+        assert_eq!(settings.colors.keyword, None);
+    }
+
+    #[test]
+    fn test_set_errors() {
+        let mut settings = Settings::default();
+
+        assert_eq!(
+            settings.set("tabstop", &assign("0")),
+            Err(String::from("Invalid argument: tabstop=0"))
+        );
+        assert_eq!(
+            settings.set("so", &assign("-1")),
+            Err(String::from("Invalid argument: so=-1"))
+        );
+        assert_eq!(
+            settings.set("number", &assign("1")),
+            Err(String::from("Invalid argument: number=1"))
+        );
+        assert_eq!(
+            settings.set("tabstop", &SetAction::Enable),
+            Err(String::from("Invalid argument: tabstop"))
+        );
+        assert_eq!(
+            settings.set("defaultmode", &assign("visual")),
+            Err(String::from("Invalid argument: defaultmode=visual"))
+        );
+        assert_eq!(
+            settings.set("colors.string", &assign("mauve")),
+            Err(String::from("Invalid argument: colors.string=mauve"))
+        );
+        assert_eq!(
+            settings.set("theme", &assign("neon")),
+            Err(String::from("Invalid argument: theme=neon"))
+        );
+        assert_eq!(
+            settings.set("wrap", &SetAction::Enable),
+            Err(String::from("Unknown option: wrap"))
+        );
+
+        assert_eq!(settings, Settings::default());
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(
+            Settings::default().describe(),
+            "nobackup clipboardcopy= clipboardpaste= defaultmode=normal noexpandtab number \
+             quitconfirmations=1 scrolloff=0 tabstop=4 theme=default"
+        );
+    }
+}
@@ -0,0 +1,227 @@
+use crate::{file_io::write_private_file_atomically, text_buffer::TextBuffer};
+use std::{
+    cell::RefCell,
+    env, fs,
+    io::{self, BufReader, ErrorKind},
+    path::{Path, PathBuf},
+    process,
+};
+
+thread_local! {
+    /// The latest unsaved buffer and the swap file it belongs in, for the panic hook to dump
+    /// Panic hooks run on the panicking thread, which for the editor is the main thread.
+    /// Cloning a `TextBuffer` shares its rope, so keeping this current is cheap.
+    static EMERGENCY_SNAPSHOT: RefCell<Option<(PathBuf, TextBuffer)>> = const { RefCell::new(None) };
+}
+
+/// Directory for swap files of buffers that have not been saved to a file yet
+fn get_state_directory() -> PathBuf {
+    match env::var_os("XDG_STATE_HOME") {
+        Some(state_home) if !state_home.is_empty() => PathBuf::from(state_home),
+        _ => PathBuf::from(env::var_os("HOME").unwrap_or_default()).join(".local/state"),
+    }
+    .join("text-editor")
+}
+
+/// The swap files a buffer may use, in the order they are tried: `.name.swp` next to the file,
+/// then `.name.swo`, `.name.swn` and so on down to `.name.swa` while those are taken, as in Vim
+/// New files use one per-process file in the state directory.
+pub fn get_swap_paths(file_path: Option<&str>) -> Vec<PathBuf> {
+    match file_path {
+        Some(file_path) => {
+            let path = Path::new(file_path);
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+
+            ('a'..='p')
+                .rev()
+                .map(|letter| path.with_file_name(format!(".{}.sw{}", file_name, letter)))
+                .collect()
+        }
+        None => vec![get_state_directory().join(format!("new-file.{}.swp", process::id()))],
+    }
+}
+
+pub fn read_swap_file(swap_path: &Path) -> io::Result<TextBuffer> {
+    TextBuffer::from_reader(BufReader::new(fs::File::open(swap_path)?))
+}
+
+/// Writes the swap file readable only by its owner, as the file it is for may be private
+pub fn write_swap_file(swap_path: &Path, buffer: &TextBuffer) -> io::Result<()> {
+    if let Some(directory) = swap_path.parent() {
+        if !directory.as_os_str().is_empty() {
+            fs::create_dir_all(directory)?;
+        }
+    }
+
+    write_private_file_atomically(swap_path, buffer).map(|_| ())
+}
+
+pub fn remove_swap_file(swap_path: &Path) -> io::Result<()> {
+    match fs::remove_file(swap_path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+pub fn set_emergency_snapshot(snapshot: Option<(PathBuf, TextBuffer)>) {
+    EMERGENCY_SNAPSHOT.with(|emergency_snapshot| *emergency_snapshot.borrow_mut() = snapshot);
+}
+
+/// Called from the panic hook; returns the path of the swap file if one was written
+pub fn write_emergency_swap_file() -> Option<PathBuf> {
+    EMERGENCY_SNAPSHOT
+        .try_with(|emergency_snapshot| {
+            let snapshot = emergency_snapshot.try_borrow().ok()?;
+            let (swap_path, buffer) = snapshot.as_ref()?;
+
+            write_swap_file(swap_path, buffer).ok()?;
+
+            Some(swap_path.clone())
+        })
+        .ok()
+        .flatten()
+}
+
+/// Summarizes how a swap file differs from the file on disk, by the lines between their
+/// common beginning and end
+pub fn describe_differences(original: &TextBuffer, swap: &TextBuffer) -> String {
+    let original_lines: Vec<_> = (0..original.line_count())
+        .map(|line_index| original.line(line_index))
+        .collect();
+
+    let swap_lines: Vec<_> = (0..swap.line_count())
+        .map(|line_index| swap.line(line_index))
+        .collect();
+
+    let num_common_leading_lines = original_lines
+        .iter()
+        .zip(&swap_lines)
+        .take_while(|(original_line, swap_line)| original_line == swap_line)
+        .count();
+
+    if num_common_leading_lines == original_lines.len()
+        && num_common_leading_lines == swap_lines.len()
+    {
+        return String::from("Swap file matches the file on disk");
+    }
+
+    let num_common_trailing_lines = original_lines[num_common_leading_lines..]
+        .iter()
+        .rev()
+        .zip(swap_lines[num_common_leading_lines..].iter().rev())
+        .take_while(|(original_line, swap_line)| original_line == swap_line)
+        .count();
+
+    let num_changed_original_lines =
+        original_lines.len() - num_common_leading_lines - num_common_trailing_lines;
+
+    let num_changed_swap_lines =
+        swap_lines.len() - num_common_leading_lines - num_common_trailing_lines;
+
+    format!(
+        "Swap file differs from line {}: {} line{} on disk, {} in swap file",
+        num_common_leading_lines + 1,
+        num_changed_original_lines,
+        if num_changed_original_lines == 1 {
+            ""
+        } else {
+            "s"
+        },
+        num_changed_swap_lines,
+    )
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn buffer_from(text: &str) -> TextBuffer {
+        TextBuffer::from_reader(text.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_get_swap_paths_for_file() {
+        let swap_paths = get_swap_paths(Some("/home/user/notes.txt"));
+
+        assert_eq!(swap_paths.len(), 16);
+        assert_eq!(swap_paths[0], PathBuf::from("/home/user/.notes.txt.swp"));
+        assert_eq!(swap_paths[1], PathBuf::from("/home/user/.notes.txt.swo"));
+        assert_eq!(swap_paths[15], PathBuf::from("/home/user/.notes.txt.swa"));
+        assert_eq!(
+            get_swap_paths(Some("notes.txt"))[0],
+            PathBuf::from(".notes.txt.swp")
+        );
+    }
+
+    #[test]
+    fn test_get_swap_paths_for_new_file() {
+        let swap_paths = get_swap_paths(None);
+
+        assert_eq!(swap_paths.len(), 1);
+        assert!(swap_paths[0].ends_with(format!("text-editor/new-file.{}.swp", process::id())));
+    }
+
+    #[test]
+    fn test_write_read_and_remove_swap_file() {
+        let dir = tempdir().unwrap();
+        let swap_path = dir.path().join("nested").join(".file.swp");
+
+        write_swap_file(&swap_path, &buffer_from("unsaved\n")).unwrap();
+        assert_eq!(read_swap_file(&swap_path).unwrap().to_string(), "unsaved\n");
+
+        remove_swap_file(&swap_path).unwrap();
+        assert!(!swap_path.exists());
+
+        // Removing a missing swap file is not an error
+        remove_swap_file(&swap_path).unwrap();
+    }
+
+    #[test]
+    fn test_swap_file_is_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempdir().unwrap();
+        let swap_path = dir.path().join(".file.swp");
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        write_swap_file(&swap_path, &buffer_from("secret\n")).unwrap();
+        assert_eq!(mode(&swap_path), 0o600);
+
+        // An existing swap file readable by others is replaced by a private one
+        fs::set_permissions(&swap_path, fs::Permissions::from_mode(0o644)).unwrap();
+        write_swap_file(&swap_path, &buffer_from("secret\n")).unwrap();
+        assert_eq!(mode(&swap_path), 0o600);
+    }
+
+    #[test]
+    fn test_write_emergency_swap_file() {
+        let dir = tempdir().unwrap();
+        let swap_path = dir.path().join(".file.swp");
+
+        set_emergency_snapshot(Some((swap_path.clone(), buffer_from("rescued\n"))));
+        assert_eq!(write_emergency_swap_file(), Some(swap_path.clone()));
+        assert_eq!(fs::read_to_string(&swap_path).unwrap(), "rescued\n");
+
+        set_emergency_snapshot(None);
+        assert_eq!(write_emergency_swap_file(), None);
+    }
+
+    #[test]
+    fn test_describe_differences() {
+        let original = buffer_from("a\nb\nc\nd\n");
+
+        assert_eq!(
+            describe_differences(&original, &buffer_from("a\nb\nc\nd\n")),
+            "Swap file matches the file on disk"
+        );
+        assert_eq!(
+            describe_differences(&original, &buffer_from("a\nx\ny\nc\nd\n")),
+            "Swap file differs from line 2: 1 line on disk, 2 in swap file"
+        );
+        assert_eq!(
+            describe_differences(&original, &buffer_from("a\nb\n")),
+            "Swap file differs from line 3: 2 lines on disk, 0 in swap file"
+        );
+    }
+}
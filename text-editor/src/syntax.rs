@@ -0,0 +1,1541 @@
+use crate::{
+    config::parse_toml,
+    editor_instance::{EditorInstance, HighlightType},
+};
+use std::{cmp::min, fs, io::ErrorKind, path::Path, sync::Arc};
+use toml::{Table, Value};
+
+pub const HIGHLIGHT_NUMBERS: i32 = 1 << 0;
+pub const HIGHLIGHT_STRINGS: i32 = 1 << 1;
+pub const HIGHLIGHT_JSX: i32 = 1 << 2;
+
+const FLAGS: &[(&str, i32)] = &[
+    ("numbers", HIGHLIGHT_NUMBERS),
+    ("strings", HIGHLIGHT_STRINGS),
+    ("jsx", HIGHLIGHT_JSX),
+];
+
+/// Definitions built into the editor, which are searched after the user's
+const BUNDLED_SYNTAXES: &[&str] = &[
+    include_str!("../syntaxes/c.toml"),
+    include_str!("../syntaxes/rust.toml"),
+    include_str!("../syntaxes/javascript.toml"),
+    include_str!("../syntaxes/typescript.toml"),
+    include_str!("../syntaxes/tsx.toml"),
+    include_str!("../syntaxes/python.toml"),
+    include_str!("../syntaxes/shell.toml"),
+    include_str!("../syntaxes/make.toml"),
+    include_str!("../syntaxes/dockerfile.toml"),
+];
+
+/// How to highlight a language, read from a syntax definition, e.g.
+/// ```toml
+/// name = "Go"
+/// extensions = ["go"]
+/// interpreters = ["gorun"]
+/// keywords = ["func", "if", "return"]
+/// types = ["int", "string"]
+/// flags = ["numbers", "strings"]
+///
+/// [comments]
+/// line = "//"
+/// block = ["/*", "*/"]
+///
+/// [strings]
+/// delimiters = ["\""]
+/// raw_delimiters = ["`"]
+/// char_delimiter = "'"
+///
+/// [numbers]
+/// decimal_point = true
+/// exponents = true
+/// radix_prefixes = ["0x", "0o", "0b"]
+/// digit_separators = ["_"]
+/// ```
+/// A definition may also start from another, e.g. `extends = "C"`; its keywords and types are
+/// then added to the other's, and its other settings replace the other's.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Syntax {
+    pub file_type: String,
+    pub extensions: Vec<String>,   // Without the `.`
+    pub file_names: Vec<String>,   // Matched exactly, e.g. `Makefile`
+    pub interpreters: Vec<String>, // Named by a shebang, with or without a version, e.g. `python3`
+    pub keywords: Vec<String>,
+    pub types: Vec<String>,
+    pub single_line_comment_start: String, // Empty if the language has none
+    pub multi_line_comment_start: String,
+    pub multi_line_comment_end: String,
+    pub nested_comments: bool, // Block comments within block comments, as in Rust
+    pub string_delimiters: Vec<char>,
+    pub raw_string_delimiters: Vec<char>, // Without escapes, e.g. Go's `` ` ``
+    pub long_string_delimiters: Vec<String>, // Span lines, e.g. Python's `"""`
+    pub template_delimiters: Vec<char>,   // Span lines, with code inside, e.g. JavaScript's `` ` ``
+    pub interpolation_start: String,      // Starts code in a template, up to a `}`, e.g. `${`
+    pub multi_line_strings: bool,         // Any string may span lines
+    pub string_prefixes: Vec<String>,     // Matched ignoring case, e.g. the `b` of `b"bytes"`
+    pub raw_string_prefixes: Vec<String>, // Without escapes, and between any number of `#`, e.g. `r#"`
+    pub char_delimiter: Option<char>, // Around one character, or else starts a label, e.g. `'a'` and `'a`
+    pub number_decimal_point: bool,   // e.g. `1.5`, though not the `1..` of a range
+    pub number_exponents: bool,       // e.g. `1e9` and `2.5E-3`
+    pub number_radix_prefixes: Vec<String>, // e.g. `0x`; binary if ending in `b`, octal in `o`
+    pub number_digit_separators: Vec<char>, // Between digits, e.g. the `_` of `1_000`
+    pub flags: i32,
+}
+
+/// Something a line can end inside of
+#[derive(Clone, Debug, PartialEq)]
+pub enum Span {
+    Comment(usize), // How many block comments are open, more than one if nested
+    String {
+        closing: String, // e.g. `"#` after `r#"`
+        escapes: bool,
+        multi_line: bool,
+        interpolated: bool, // A template, which code can be put inside
+    },
+    Code {
+        braces: usize,
+    }, // Inside a template or JSX, up to an unmatched `}`, e.g. `${x}`
+    Tag {
+        closing: bool,
+    }, // A JSX tag, e.g. `<a href="/">` or `</a>`
+    Children, // Between a JSX element's tags, e.g. the text of `<p>Hi</p>`
+}
+
+/// What a line ends inside of, innermost last, and so what the next line starts inside of;
+/// empty in code
+pub type LineState = Vec<Span>;
+
+fn parse_string(value: &Value, key: &str) -> Result<String, String> {
+    value
+        .as_str()
+        .map(String::from)
+        .ok_or_else(|| format!("{} should be a string", key))
+}
+
+fn parse_strings(value: &Value, key: &str) -> Result<Vec<String>, String> {
+    value
+        .as_array()
+        .and_then(|values| {
+            values
+                .iter()
+                .map(|value| value.as_str().map(String::from))
+                .collect()
+        })
+        .ok_or_else(|| format!("{} should be a list of strings", key))
+}
+
+fn parse_chars(value: &Value, key: &str) -> Result<Vec<char>, String> {
+    parse_strings(value, key)?
+        .iter()
+        .map(|text| {
+            let mut chars = text.chars();
+
+            match (chars.next(), chars.next()) {
+                (Some(char), None) => Ok(char),
+                _ => Err(format!("{} should be a list of single characters", key)),
+            }
+        })
+        .collect()
+}
+
+fn parse_char(value: &Value, key: &str) -> Result<char, String> {
+    let text = parse_string(value, key)?;
+    let mut chars = text.chars();
+
+    match (chars.next(), chars.next()) {
+        (Some(char), None) => Ok(char),
+        _ => Err(format!("{} should be a single character", key)),
+    }
+}
+
+fn parse_bool(value: &Value, key: &str) -> Result<bool, String> {
+    value
+        .as_bool()
+        .ok_or_else(|| format!("{} should be true or false", key))
+}
+
+fn parse_table<'a>(value: &'a Value, key: &str) -> Result<&'a Table, String> {
+    value
+        .as_table()
+        .ok_or_else(|| format!("{} should be a table", key))
+}
+
+fn parse_strings_table(syntax: &mut Syntax, strings: &Table) -> Result<(), String> {
+    for (key, value) in strings {
+        let name = format!("strings.{}", key);
+
+        match key.as_str() {
+            "delimiters" => syntax.string_delimiters = parse_chars(value, &name)?,
+            "raw_delimiters" => syntax.raw_string_delimiters = parse_chars(value, &name)?,
+            "long_delimiters" => syntax.long_string_delimiters = parse_strings(value, &name)?,
+            "template_delimiters" => syntax.template_delimiters = parse_chars(value, &name)?,
+            "interpolation" => syntax.interpolation_start = parse_string(value, &name)?,
+            "multi_line" => syntax.multi_line_strings = parse_bool(value, &name)?,
+            "prefixes" => syntax.string_prefixes = parse_strings(value, &name)?,
+            "raw_prefixes" => syntax.raw_string_prefixes = parse_strings(value, &name)?,
+            "char_delimiter" => syntax.char_delimiter = Some(parse_char(value, &name)?),
+            _ => return Err(format!("Unknown key: {}", name)),
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_numbers(syntax: &mut Syntax, numbers: &Table) -> Result<(), String> {
+    for (key, value) in numbers {
+        let name = format!("numbers.{}", key);
+
+        match key.as_str() {
+            "decimal_point" => syntax.number_decimal_point = parse_bool(value, &name)?,
+            "exponents" => syntax.number_exponents = parse_bool(value, &name)?,
+            "radix_prefixes" => syntax.number_radix_prefixes = parse_strings(value, &name)?,
+            "digit_separators" => syntax.number_digit_separators = parse_chars(value, &name)?,
+            _ => return Err(format!("Unknown key: {}", name)),
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_comments(syntax: &mut Syntax, comments: &Table) -> Result<(), String> {
+    for (key, value) in comments {
+        match key.as_str() {
+            "line" => syntax.single_line_comment_start = parse_string(value, "comments.line")?,
+            "block" => match parse_strings(value, "comments.block")?.as_slice() {
+                [start, end] if !start.is_empty() && !end.is_empty() => {
+                    syntax.multi_line_comment_start = start.clone();
+                    syntax.multi_line_comment_end = end.clone();
+                }
+                _ => return Err(String::from("comments.block should be a start and an end")),
+            },
+            "nested" => syntax.nested_comments = parse_bool(value, "comments.nested")?,
+            key => return Err(format!("Unknown key: comments.{}", key)),
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a syntax definition, in which only the name is required
+/// `find_syntax` finds the definition named by `extends`, whose name, extensions, file names
+/// and interpreters are not inherited.
+pub fn parse(
+    text: &str,
+    find_syntax: impl Fn(&str) -> Option<Arc<Syntax>>,
+) -> Result<Syntax, String> {
+    let table = parse_toml(text)?;
+
+    let mut syntax = match table.get("extends") {
+        Some(value) => {
+            let name = parse_string(value, "extends")?;
+            let parent = find_syntax(&name).ok_or_else(|| format!("Unknown syntax: {}", name))?;
+
+            Syntax {
+                file_type: String::new(),
+                extensions: vec![],
+                file_names: vec![],
+                interpreters: vec![],
+                ..Syntax::clone(&parent)
+            }
+        }
+        None => Syntax::default(),
+    };
+
+    for (key, value) in &table {
+        match key.as_str() {
+            "name" => syntax.file_type = parse_string(value, key)?,
+            "extends" => {}
+            "extensions" => syntax.extensions = parse_strings(value, key)?,
+            "file_names" => syntax.file_names = parse_strings(value, key)?,
+            "interpreters" => syntax.interpreters = parse_strings(value, key)?,
+            "keywords" => syntax.keywords.extend(parse_strings(value, key)?),
+            "types" => syntax.types.extend(parse_strings(value, key)?),
+            "flags" => {
+                syntax.flags = 0;
+
+                for flag in parse_strings(value, key)? {
+                    syntax.flags |= FLAGS
+                        .iter()
+                        .find(|(name, _)| *name == flag)
+                        .map(|(_, bit)| *bit)
+                        .ok_or_else(|| format!("Unknown flag: {}", flag))?;
+                }
+            }
+            "comments" => parse_comments(&mut syntax, parse_table(value, key)?)?,
+            "strings" => parse_strings_table(&mut syntax, parse_table(value, key)?)?,
+            "numbers" => parse_numbers(&mut syntax, parse_table(value, key)?)?,
+            key => return Err(format!("Unknown key: {}", key)),
+        }
+    }
+
+    if syntax.file_type.is_empty() {
+        return Err(String::from("name is missing"));
+    }
+
+    Ok(syntax)
+}
+
+fn starts_with(chars: &[char], index: usize, text: &str) -> bool {
+    text.chars()
+        .enumerate()
+        .all(|(offset, char)| chars.get(index + offset) == Some(&char))
+}
+
+fn starts_with_ignoring_case(chars: &[char], index: usize, text: &str) -> bool {
+    text.chars().enumerate().all(|(offset, char)| {
+        chars
+            .get(index + offset)
+            .is_some_and(|other| other.eq_ignore_ascii_case(&char))
+    })
+}
+
+fn find_word_end(chars: &[char], mut index: usize) -> usize {
+    while index < chars.len() && !EditorInstance::is_separator(chars[index]) {
+        index += 1;
+    }
+
+    index
+}
+
+/// Binary for a prefix ending in `b`, e.g. `0b`, octal for one ending in `o`, and otherwise
+/// hexadecimal, e.g. `0x`
+fn get_radix(radix_prefix: &str) -> u32 {
+    match radix_prefix
+        .chars()
+        .last()
+        .map(|char| char.to_ascii_lowercase())
+    {
+        Some('b') => 2,
+        Some('o') => 8,
+        _ => 16,
+    }
+}
+
+fn find_identifier_end(chars: &[char], mut index: usize) -> usize {
+    while index < chars.len() && (chars[index].is_alphanumeric() || chars[index] == '_') {
+        index += 1;
+    }
+
+    index
+}
+
+/// Returns where the string contents from `index` end, at the closing delimiter or else at
+/// code inside, if they end on this line
+fn find_string_end(
+    chars: &[char],
+    mut index: usize,
+    closing: &str,
+    escapes: bool,
+    interpolation_start: &str,
+) -> Option<usize> {
+    while index < chars.len() {
+        if escapes && chars[index] == '\\' {
+            index += 2;
+        } else if starts_with(chars, index, closing)
+            || !interpolation_start.is_empty() && starts_with(chars, index, interpolation_start)
+        {
+            return Some(index);
+        } else {
+            index += 1;
+        }
+    }
+
+    None
+}
+
+/// Returns where the character literal at `index` ends, if it is one, e.g. `'a'` or `'\n'`
+fn find_char_literal_end(chars: &[char], index: usize) -> Option<usize> {
+    let quote = chars[index];
+
+    match chars.get(index + 1)? {
+        // Up to `'\u{10FFFF}'`
+        '\\' => (index + 3..min(chars.len(), index + 12))
+            .find(|i| chars[*i] == quote)
+            .map(|i| i + 1),
+        char if *char != quote && chars.get(index + 2) == Some(&quote) => Some(index + 3),
+        _ => None,
+    }
+}
+
+fn is_tag_name_char(char: char) -> bool {
+    char.is_alphanumeric() || matches!(char, '_' | '$' | '-' | '.' | ':')
+}
+
+/// Highlights the name of the JSX tag starting at `index`, a `<`, and returns where it ends
+fn highlight_tag_start(
+    chars: &[char],
+    index: usize,
+    highlight: &mut [HighlightType],
+    spans: &mut LineState,
+) -> usize {
+    let is_closing = chars.get(index + 1) == Some(&'/');
+    let name_start = index + 1 + usize::from(is_closing);
+    let mut name_end = name_start;
+
+    while name_end < chars.len() && is_tag_name_char(chars[name_end]) {
+        name_end += 1;
+    }
+
+    // Components are capitalised, e.g. `<App>` rather than `<div>`
+    let highlight_type = match chars
+        .get(name_start)
+        .is_some_and(|char| char.is_uppercase())
+    {
+        true => HighlightType::Type,
+        false => HighlightType::Keyword,
+    };
+
+    highlight[name_start..name_end].fill(highlight_type);
+    spans.push(Span::Tag {
+        closing: is_closing,
+    });
+
+    name_end
+}
+
+impl Syntax {
+    /// Highlights a line of render text, starting inside of `state`, and returns what the line
+    /// ends inside of
+    pub fn highlight_line(&self, text: &str, state: &LineState) -> (Vec<HighlightType>, LineState) {
+        let chars: Vec<char> = text.chars().collect();
+        let mut highlight = vec![HighlightType::Normal; chars.len()];
+        let mut spans = state.clone();
+        let mut string_start = 0;
+        let mut i = 0;
+
+        while i < chars.len() {
+            match spans.last_mut() {
+                None | Some(Span::Code { .. }) => {
+                    i = self.highlight_token(&chars, i, &mut highlight, &mut spans);
+                    string_start = i;
+                }
+                Some(Span::Comment(depth)) => {
+                    let (end, open_comments) = self.find_comment_end(&chars, i, *depth);
+                    highlight[i..end].fill(HighlightType::MultiLineComment);
+
+                    match open_comments {
+                        0 => _ = spans.pop(),
+                        open_comments => *depth = open_comments,
+                    }
+
+                    i = end;
+                }
+                Some(Span::String {
+                    closing,
+                    escapes,
+                    interpolated,
+                    ..
+                }) => {
+                    let interpolation_start = match interpolated {
+                        true => self.interpolation_start.as_str(),
+                        false => "",
+                    };
+
+                    match find_string_end(&chars, i, closing, *escapes, interpolation_start) {
+                        Some(end) if starts_with(&chars, end, closing) => {
+                            let end = end + closing.chars().count();
+                            highlight[i..end].fill(HighlightType::String);
+                            spans.pop();
+                            i = end;
+                        }
+                        Some(end) => {
+                            highlight[i..end].fill(HighlightType::String);
+                            spans.push(Span::Code { braces: 0 });
+                            i = end + interpolation_start.chars().count();
+                        }
+                        None => {
+                            highlight[i..].fill(HighlightType::String);
+                            i = chars.len();
+                        }
+                    }
+                }
+                Some(Span::Tag { closing }) => {
+                    let is_closing_tag = *closing;
+
+                    match (chars[i], chars.get(i + 1)) {
+                        ('>', _) => {
+                            spans.pop();
+
+                            match is_closing_tag {
+                                // Also ends the element whose children it follows
+                                true if spans.last() == Some(&Span::Children) => _ = spans.pop(),
+                                true => {}
+                                false => spans.push(Span::Children),
+                            }
+
+                            i += 1;
+                        }
+                        ('/', Some('>')) => {
+                            spans.pop();
+                            i += 2;
+                        }
+                        ('{', _) => {
+                            spans.push(Span::Code { braces: 0 });
+                            i += 1;
+                        }
+                        (quote @ ('"' | '\''), _) => {
+                            highlight[i] = HighlightType::String;
+                            spans.push(Span::String {
+                                closing: quote.to_string(),
+                                escapes: false,
+                                multi_line: true,
+                                interpolated: false,
+                            });
+                            i += 1;
+                        }
+                        _ => i += 1,
+                    }
+                }
+                Some(Span::Children) => match (chars[i], chars.get(i + 1)) {
+                    ('<', Some(&next)) if next.is_alphabetic() || next == '/' || next == '>' => {
+                        i = highlight_tag_start(&chars, i, &mut highlight, &mut spans);
+                    }
+                    ('{', _) => {
+                        spans.push(Span::Code { braces: 0 });
+                        i += 1;
+                    }
+                    _ => i += 1,
+                },
+            }
+        }
+
+        // Other strings only continue onto the next line after a `\`
+        if let Some(Span::String {
+            escapes,
+            multi_line: false,
+            ..
+        }) = spans.last()
+        {
+            let trailing_backslashes = chars[string_start..]
+                .iter()
+                .rev()
+                .take_while(|char| **char == '\\')
+                .count();
+
+            if !escapes || trailing_backslashes % 2 == 0 {
+                spans.pop();
+            }
+        }
+
+        (highlight, spans)
+    }
+
+    /// Whether the `<` at `index` starts a JSX element rather than comparing, judging by what is
+    /// before it, e.g. `return <p>` but not `a <b`
+    fn is_element_start(&self, chars: &[char], index: usize) -> bool {
+        if !chars
+            .get(index + 1)
+            .is_some_and(|char| char.is_alphabetic() || *char == '>')
+        {
+            return false;
+        }
+
+        let Some(previous) = chars[..index]
+            .iter()
+            .rposition(|char| !char.is_whitespace())
+        else {
+            return true;
+        };
+
+        match chars[previous] {
+            ')' | ']' | '<' => false,
+            // Only a keyword, e.g. `return`, rather than a name being compared
+            char if char.is_alphanumeric() || char == '_' || char == '$' => {
+                let word_start = chars[..previous]
+                    .iter()
+                    .rposition(|char| EditorInstance::is_separator(*char))
+                    .map_or(0, |index| index + 1);
+                let word: String = chars[word_start..=previous].iter().collect();
+
+                self.keywords.contains(&word)
+            }
+            _ => true,
+        }
+    }
+
+    /// Highlights the token at `index`, in code, and returns where it ends
+    fn highlight_token(
+        &self,
+        chars: &[char],
+        index: usize,
+        highlight: &mut [HighlightType],
+        spans: &mut LineState,
+    ) -> usize {
+        let is_word_start = index == 0 || EditorInstance::is_separator(chars[index - 1]);
+
+        if !self.single_line_comment_start.is_empty()
+            && starts_with(chars, index, &self.single_line_comment_start)
+        {
+            highlight[index..].fill(HighlightType::Comment);
+            return chars.len();
+        }
+
+        if !self.multi_line_comment_start.is_empty()
+            && starts_with(chars, index, &self.multi_line_comment_start)
+        {
+            let end = index + self.multi_line_comment_start.chars().count();
+            highlight[index..end].fill(HighlightType::MultiLineComment);
+            spans.push(Span::Comment(1));
+            return end;
+        }
+
+        if let Some(Span::Code { braces }) = spans.last_mut() {
+            match chars[index] {
+                '{' => *braces += 1,
+                '}' if *braces == 0 => {
+                    spans.pop();
+                    return index + 1;
+                }
+                '}' => *braces -= 1,
+                _ => {}
+            }
+        }
+
+        if (self.flags & HIGHLIGHT_JSX) != 0
+            && chars[index] == '<'
+            && self.is_element_start(chars, index)
+        {
+            return highlight_tag_start(chars, index, highlight, spans);
+        }
+
+        if (self.flags & HIGHLIGHT_STRINGS) != 0 {
+            if let Some((end, span)) = self.match_string_start(chars, index, is_word_start) {
+                highlight[index..end].fill(HighlightType::String);
+                spans.extend(span);
+                return end;
+            }
+
+            if self.char_delimiter == Some(chars[index]) {
+                return match find_char_literal_end(chars, index) {
+                    Some(end) => {
+                        highlight[index..end].fill(HighlightType::String);
+                        end
+                    }
+                    // A lifetime or label, e.g. `'a` or `'outer:`
+                    None => find_word_end(chars, index + 1),
+                };
+            }
+        }
+
+        if (self.flags & HIGHLIGHT_NUMBERS) != 0 && is_word_start && chars[index].is_ascii_digit() {
+            let end = self.find_number_end(chars, index);
+            highlight[index..end].fill(HighlightType::Number);
+            return end;
+        }
+
+        // Not a field or method, e.g. the `type` of `node.type`
+        if is_word_start && (index == 0 || chars[index - 1] != '.') {
+            let words = self
+                .keywords
+                .iter()
+                .map(|keyword| (keyword, HighlightType::Keyword))
+                .chain(
+                    self.types
+                        .iter()
+                        .map(|type_name| (type_name, HighlightType::Type)),
+                );
+
+            for (word, highlight_type) in words {
+                let end = index + word.chars().count();
+
+                if !word.is_empty()
+                    && starts_with(chars, index, word)
+                    && chars
+                        .get(end)
+                        .is_none_or(|char| EditorInstance::is_separator(*char))
+                {
+                    highlight[index..end].fill(highlight_type);
+                    return end;
+                }
+            }
+        }
+
+        index + 1
+    }
+
+    /// Returns where the block comments open at `index` end, or else the end of the line, and
+    /// how many are still open there
+    fn find_comment_end(
+        &self,
+        chars: &[char],
+        mut index: usize,
+        mut depth: usize,
+    ) -> (usize, usize) {
+        while index < chars.len() {
+            if self.nested_comments && starts_with(chars, index, &self.multi_line_comment_start) {
+                index += self.multi_line_comment_start.chars().count();
+                depth += 1;
+            } else if starts_with(chars, index, &self.multi_line_comment_end) {
+                index += self.multi_line_comment_end.chars().count();
+                depth -= 1;
+
+                if depth == 0 {
+                    break;
+                }
+            } else {
+                index += 1;
+            }
+        }
+
+        (index, depth)
+    }
+
+    /// Matches a string's opening delimiter at `index`, after any prefix, returning where its
+    /// contents start and the span they are in, if the string isn't already complete
+    fn match_string_start(
+        &self,
+        chars: &[char],
+        index: usize,
+        is_word_start: bool,
+    ) -> Option<(usize, Option<Span>)> {
+        // The longest prefix, e.g. `br` rather than `b`
+        let (prefix_length, is_raw) = self
+            .string_prefixes
+            .iter()
+            .map(|prefix| (prefix, false))
+            .chain(self.raw_string_prefixes.iter().map(|prefix| (prefix, true)))
+            .filter(|(prefix, _)| is_word_start && starts_with_ignoring_case(chars, index, prefix))
+            .map(|(prefix, is_raw)| (prefix.chars().count(), is_raw))
+            .max_by_key(|(prefix_length, _)| *prefix_length)
+            .unwrap_or((0, false));
+
+        let start = index + prefix_length;
+
+        let string = |closing: String, escapes: bool| {
+            Some(Span::String {
+                closing,
+                escapes,
+                multi_line: self.multi_line_strings,
+                interpolated: false,
+            })
+        };
+
+        if is_raw {
+            let hashes = chars[start..]
+                .iter()
+                .take_while(|char| **char == '#')
+                .count();
+            let quote = *chars.get(start + hashes)?;
+
+            return self.string_delimiters.contains(&quote).then(|| {
+                let closing = format!("{}{}", quote, "#".repeat(hashes));
+                (start + hashes + 1, string(closing, false))
+            });
+        }
+
+        if let Some(delimiter) = self
+            .long_string_delimiters
+            .iter()
+            .find(|delimiter| !delimiter.is_empty() && starts_with(chars, start, delimiter))
+        {
+            let span = Span::String {
+                closing: delimiter.clone(),
+                escapes: true,
+                multi_line: true,
+                interpolated: false,
+            };
+
+            return Some((start + delimiter.chars().count(), Some(span)));
+        }
+
+        let quote = *chars.get(start)?;
+
+        if self.string_delimiters.contains(&quote) {
+            Some((start + 1, string(quote.to_string(), true)))
+        } else if self.raw_string_delimiters.contains(&quote) {
+            Some((start + 1, string(quote.to_string(), false)))
+        } else if self.template_delimiters.contains(&quote) {
+            let span = Span::String {
+                closing: quote.to_string(),
+                escapes: true,
+                multi_line: true,
+                interpolated: true,
+            };
+
+            Some((start + 1, Some(span)))
+        } else if prefix_length > 0 && self.char_delimiter == Some(quote) {
+            // e.g. `b'a'`
+            find_char_literal_end(chars, start).map(|end| (end, None))
+        } else {
+            None
+        }
+    }
+
+    /// Returns where the number at `index`, which is a digit, ends, including any suffix, e.g.
+    /// `0xff`, `1_000`, `2.5e-3` or `10u8`
+    fn find_number_end(&self, chars: &[char], index: usize) -> usize {
+        let skip_digits = |mut index: usize, radix: u32| {
+            while index < chars.len()
+                && (chars[index].is_digit(radix)
+                    || self.number_digit_separators.contains(&chars[index])
+                        && chars
+                            .get(index + 1)
+                            .is_some_and(|char| char.is_ascii_alphanumeric()))
+            {
+                index += 1;
+            }
+
+            index
+        };
+
+        let radix_prefix = self
+            .number_radix_prefixes
+            .iter()
+            .find(|prefix| !prefix.is_empty() && starts_with_ignoring_case(chars, index, prefix));
+
+        let end = match radix_prefix {
+            Some(prefix) => skip_digits(index + prefix.chars().count(), get_radix(prefix)),
+            None => {
+                let mut end = skip_digits(index, 10);
+
+                // Not the `1..` of a range, nor the `1.max` of a method call
+                if self.number_decimal_point
+                    && chars.get(end) == Some(&'.')
+                    && chars.get(end + 1).is_none_or(|char| {
+                        char.is_ascii_digit() || EditorInstance::is_separator(*char) && *char != '.'
+                    })
+                {
+                    end = skip_digits(end + 1, 10);
+                }
+
+                if self.number_exponents && matches!(chars.get(end), Some('e' | 'E')) {
+                    let sign_length = usize::from(matches!(chars.get(end + 1), Some('+' | '-')));
+
+                    if chars
+                        .get(end + 1 + sign_length)
+                        .is_some_and(|char| char.is_ascii_digit())
+                    {
+                        end = skip_digits(end + 1 + sign_length, 10);
+                    }
+                }
+
+                end
+            }
+        };
+
+        // e.g. `u8`, `f32` or `ULL`, but not the `2` of `0b12`, which is not part of the number
+        match chars.get(end).is_some_and(|char| char.is_ascii_digit()) {
+            true => end,
+            false => find_identifier_end(chars, end),
+        }
+    }
+}
+
+/// Every syntax definition the editor knows: the user's, then the bundled ones they don't replace
+#[derive(Debug)]
+pub struct SyntaxRegistry {
+    syntaxes: Vec<Arc<Syntax>>,
+}
+
+impl SyntaxRegistry {
+    /// Each bundled definition may extend those listed before it
+    pub fn bundled() -> Self {
+        let mut registry = SyntaxRegistry { syntaxes: vec![] };
+
+        for text in BUNDLED_SYNTAXES {
+            let syntax = parse(text, |name| registry.find_by_file_type(name))
+                .expect("Failed to parse bundled syntax");
+
+            registry.syntaxes.push(Arc::new(syntax));
+        }
+
+        registry
+    }
+
+    /// Adds the definitions in `directory`, each a `.toml` file, ahead of those already known
+    /// Returns the first error, after adding every definition that could be read
+    pub fn load_directory(&mut self, directory: &Path) -> Result<(), String> {
+        let entries = match fs::read_dir(directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(format!("Failed to read {}: {}", directory.display(), e)),
+        };
+
+        let mut paths: Vec<_> = entries
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "toml")
+            })
+            .collect();
+
+        paths.sort();
+
+        let mut texts = vec![];
+        let mut first_error = None;
+
+        for path in paths {
+            match fs::read_to_string(&path) {
+                Ok(text) => texts.push((path, text)),
+                Err(e) => {
+                    first_error.get_or_insert(format!("Failed to read {}: {}", path.display(), e));
+                }
+            }
+        }
+
+        // A definition may extend one read after it, so they are parsed again until no more
+        // can be, preferring to extend the user's definitions
+        let mut user_registry = SyntaxRegistry { syntaxes: vec![] };
+        let mut errors = vec![];
+
+        while !texts.is_empty() {
+            let num_texts = texts.len();
+
+            errors.clear();
+            texts.retain(|(path, text)| {
+                let syntax = parse(text, |name| {
+                    user_registry
+                        .find_by_file_type(name)
+                        .or_else(|| self.find_by_file_type(name))
+                });
+
+                match syntax {
+                    Ok(syntax) => {
+                        user_registry.syntaxes.push(Arc::new(syntax));
+                        false
+                    }
+                    Err(e) => {
+                        errors.push(format!("Error in {}: {}", path.display(), e));
+                        true
+                    }
+                }
+            });
+
+            if texts.len() == num_texts {
+                break;
+            }
+        }
+
+        if let Some(e) = errors.into_iter().next() {
+            first_error.get_or_insert(e);
+        }
+
+        let syntaxes = user_registry.syntaxes;
+
+        // A definition replaces any other of the same language
+        self.syntaxes.retain(|syntax| {
+            !syntaxes.iter().any(|user_syntax| {
+                user_syntax
+                    .file_type
+                    .eq_ignore_ascii_case(&syntax.file_type)
+            })
+        });
+        self.syntaxes.splice(0..0, syntaxes);
+
+        first_error.map_or(Ok(()), Err)
+    }
+
+    /// Finds a syntax by its name, ignoring case, e.g. `rust`
+    fn find_by_file_type(&self, name: &str) -> Option<Arc<Syntax>> {
+        self.syntaxes
+            .iter()
+            .find(|syntax| syntax.file_type.eq_ignore_ascii_case(name))
+            .cloned()
+    }
+
+    /// Finds a syntax by its name or one of its extensions, ignoring case, e.g. `rust` or `rs`
+    pub fn find_by_name(&self, name: &str) -> Option<Arc<Syntax>> {
+        self.find_by_file_type(name).or_else(|| {
+            self.syntaxes
+                .iter()
+                .find(|syntax| {
+                    syntax
+                        .extensions
+                        .iter()
+                        .any(|extension| extension.eq_ignore_ascii_case(name))
+                })
+                .cloned()
+        })
+    }
+
+    /// Finds the syntax for a script from the interpreter in its shebang, e.g.
+    /// `#!/usr/bin/env python3` or `#!/bin/sh`
+    pub fn find_by_shebang(&self, first_line: &str) -> Option<Arc<Syntax>> {
+        let mut words = first_line.strip_prefix("#!")?.split_whitespace();
+        let mut interpreter = words.next()?.rsplit('/').next()?;
+
+        // `env` takes options and variables before the program it runs
+        if interpreter == "env" {
+            interpreter = words.find(|word| !word.starts_with('-') && !word.contains('='))?;
+        }
+
+        self.syntaxes
+            .iter()
+            .find(|syntax| {
+                syntax.interpreters.iter().any(|name| {
+                    interpreter
+                        .strip_prefix(name.as_str())
+                        .is_some_and(|version| {
+                            version
+                                .chars()
+                                .all(|char| char.is_ascii_digit() || char == '.')
+                        })
+                })
+            })
+            .cloned()
+    }
+
+    /// Finds the syntax for a file by its exact name, or else by its extension
+    pub fn find_by_file_name(&self, file_name: &str) -> Option<Arc<Syntax>> {
+        let extension = file_name
+            .rfind('.')
+            .map(|index| &file_name[index + 1..])
+            .filter(|extension| !extension.is_empty());
+
+        self.syntaxes
+            .iter()
+            .find(|syntax| syntax.file_names.iter().any(|name| name == file_name))
+            .or_else(|| {
+                self.syntaxes.iter().find(|syntax| {
+                    extension.is_some_and(|extension| {
+                        syntax.extensions.iter().any(|other| other == extension)
+                    })
+                })
+            })
+            .cloned()
+    }
+}
+
+/// Returns the file type set by a Vim or Emacs modeline in `line`, e.g. `// vim: ft=rust`,
+/// `# vim: set filetype=python :` or `# -*- mode: python -*-`
+pub fn get_modeline_file_type(line: &str) -> Option<&str> {
+    if let Some((_, rest)) = line.split_once("-*-") {
+        let (variables, _) = rest.split_once("-*-")?;
+
+        return match variables.contains(':') {
+            true => variables.split(';').find_map(|variable| {
+                let (name, value) = variable.split_once(':')?;
+                (name.trim() == "mode").then_some(value.trim())
+            }),
+            false => Some(variables.trim()),
+        }
+        .filter(|mode| !mode.is_empty());
+    }
+
+    // `vi:` and `ex:` must follow a space, as `vim:` may also start the line
+    let options = ["vim:", "vi:", "ex:"].iter().find_map(|marker| {
+        line.match_indices(marker)
+            .find(|(index, _)| match line[..*index].chars().next_back() {
+                Some(char) => char.is_whitespace(),
+                None => *marker == "vim:",
+            })
+            .map(|(index, _)| &line[index + marker.len()..])
+    })?;
+
+    let options = options.trim_start();
+    let options = options
+        .strip_prefix("set ")
+        .or_else(|| options.strip_prefix("se "))
+        .unwrap_or(options);
+
+    options
+        .split(|char: char| char.is_whitespace() || char == ':')
+        .find_map(|option| {
+            let (name, value) = option.split_once('=')?;
+            matches!(name, "ft" | "filetype" | "syn" | "syntax").then_some(value)
+        })
+        .filter(|file_type| !file_type.is_empty())
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_parse() {
+        let syntax = parse(
+            r#"
+            name = "Go"
+            extensions = ["go"]
+            file_names = ["go.work"]
+            interpreters = ["gorun"]
+            keywords = ["func", "return"]
+            types = ["int"]
+            flags = ["numbers", "strings"]
+
+            [comments]
+            line = "//"
+            block = ["/*", "*/"]
+            nested = true
+
+            [strings]
+            delimiters = ["\""]
+            raw_delimiters = ["'"]
+            long_delimiters = ["\"\"\""]
+            template_delimiters = ["`"]
+            interpolation = "${"
+            multi_line = true
+            prefixes = ["b"]
+            raw_prefixes = ["r"]
+            char_delimiter = "'"
+
+            [numbers]
+            decimal_point = true
+            exponents = true
+            radix_prefixes = ["0x"]
+            digit_separators = ["_"]
+            "#,
+            |_| None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            syntax,
+            Syntax {
+                file_type: String::from("Go"),
+                extensions: vec![String::from("go")],
+                file_names: vec![String::from("go.work")],
+                interpreters: vec![String::from("gorun")],
+                keywords: vec![String::from("func"), String::from("return")],
+                types: vec![String::from("int")],
+                single_line_comment_start: String::from("//"),
+                multi_line_comment_start: String::from("/*"),
+                multi_line_comment_end: String::from("*/"),
+                nested_comments: true,
+                string_delimiters: vec!['"'],
+                raw_string_delimiters: vec!['\''],
+                long_string_delimiters: vec![String::from("\"\"\"")],
+                template_delimiters: vec!['`'],
+                interpolation_start: String::from("${"),
+                multi_line_strings: true,
+                string_prefixes: vec![String::from("b")],
+                raw_string_prefixes: vec![String::from("r")],
+                char_delimiter: Some('\''),
+                number_decimal_point: true,
+                number_exponents: true,
+                number_radix_prefixes: vec![String::from("0x")],
+                number_digit_separators: vec!['_'],
+                flags: HIGHLIGHT_NUMBERS | HIGHLIGHT_STRINGS,
+            }
+        );
+
+        assert_eq!(parse("name = \"Text\"", |_| None).unwrap().flags, 0);
+    }
+
+    #[test]
+    fn test_parse_extends() {
+        let registry = SyntaxRegistry::bundled();
+        let c = registry.find_by_name("C").unwrap();
+
+        let syntax = parse(
+            "name = \"CUDA\"\nextends = \"c\"\nextensions = [\"cu\"]\nkeywords = [\"__global__\"]\nflags = []",
+            |name| registry.find_by_file_type(name),
+        )
+        .unwrap();
+
+        assert_eq!(syntax.file_type, "CUDA");
+        assert_eq!(syntax.extensions, vec![String::from("cu")]);
+        assert!(syntax.interpreters.is_empty());
+        assert_eq!(syntax.keywords.len(), c.keywords.len() + 1);
+        assert_eq!(syntax.keywords.last().unwrap(), "__global__");
+        assert_eq!(syntax.types, c.types);
+        assert_eq!(syntax.single_line_comment_start, "//");
+        assert_eq!(syntax.flags, 0);
+
+        let typescript = registry.find_by_name("ts").unwrap();
+        let tsx = registry.find_by_name("tsx").unwrap();
+
+        assert!(typescript.keywords.iter().any(|keyword| keyword == "await"));
+        assert_eq!(typescript.flags & HIGHLIGHT_JSX, 0);
+        assert_eq!(tsx.keywords, typescript.keywords);
+        assert_ne!(tsx.flags & HIGHLIGHT_JSX, 0);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse("extensions = [\"go\"]", |_| None),
+            Err(String::from("name is missing"))
+        );
+        assert_eq!(
+            parse("name = \"Go\"\nflags = [\"colours\"]", |_| None),
+            Err(String::from("Unknown flag: colours"))
+        );
+        assert_eq!(
+            parse("name = \"Go\"\nkeyword = []", |_| None),
+            Err(String::from("Unknown key: keyword"))
+        );
+        assert_eq!(
+            parse("name = \"Go\"\nextends = \"C\"", |_| None),
+            Err(String::from("Unknown syntax: C"))
+        );
+        assert_eq!(
+            parse("name = \"Go\"\nkeywords = \"func\"", |_| None),
+            Err(String::from("keywords should be a list of strings"))
+        );
+        assert_eq!(
+            parse("name = \"Go\"\n[strings]\ndelimiters = [\"''\"]", |_| None),
+            Err(String::from(
+                "strings.delimiters should be a list of single characters"
+            ))
+        );
+        assert_eq!(
+            parse("name = \"Go\"\n[strings]\nchar_delimiter = \"''\"", |_| {
+                None
+            }),
+            Err(String::from(
+                "strings.char_delimiter should be a single character"
+            ))
+        );
+        assert_eq!(
+            parse("name = \"Go\"\n[comments]\nblock = [\"/*\"]", |_| None),
+            Err(String::from("comments.block should be a start and an end"))
+        );
+        assert_eq!(
+            parse("name = \"Go\"\n[numbers]\nexponent = true", |_| None),
+            Err(String::from("Unknown key: numbers.exponent"))
+        );
+        assert!(parse("name = ", |_| None)
+            .unwrap_err()
+            .starts_with("line 1, column 8: "));
+    }
+
+    /// Highlights `lines` with the bundled syntax for `file_name`, marking each character with
+    /// the first letter of its highlight type, or a space if it is normal
+    fn highlight_lines(file_name: &str, lines: &[&str]) -> Vec<String> {
+        let syntax = SyntaxRegistry::bundled()
+            .find_by_file_name(file_name)
+            .unwrap();
+        let mut state = LineState::new();
+
+        lines
+            .iter()
+            .map(|line| {
+                let (highlight, end_state) = syntax.highlight_line(line, &state);
+                state = end_state;
+
+                highlight
+                    .iter()
+                    .map(|highlight| match highlight {
+                        HighlightType::Normal => ' ',
+                        HighlightType::Number => 'n',
+                        HighlightType::String => 's',
+                        HighlightType::Comment => 'c',
+                        HighlightType::MultiLineComment => 'm',
+                        HighlightType::Keyword => 'k',
+                        HighlightType::Type => 't',
+                        HighlightType::SearchMatch => '?',
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_highlight_rust_lifetimes_and_chars() {
+        assert_eq!(
+            highlight_lines("main.rs", &["fn f<'a>(x: &'a str) -> char { '\\'' }"]),
+            vec!["kk              ttt     tttt   ssss  "]
+        );
+        assert_eq!(
+            highlight_lines("main.rs", &["'outer: loop { b'x' '\\u{1F600}' }"]),
+            vec!["        kkkk   ssss sssssssssss  "]
+        );
+    }
+
+    #[test]
+    fn test_highlight_rust_strings() {
+        assert_eq!(
+            highlight_lines(
+                "main.rs",
+                &[
+                    "let s = r#\"say \"hi\"\"#;",
+                    "let t = \"a \\\" b",
+                    "c\"; b\"x\" cr\"\\\" 1",
+                ]
+            ),
+            vec![
+                "kkk     sssssssssssss ",
+                "kkk     sssssss",
+                "ss  ssss sssss n",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_highlight_rust_nested_comments() {
+        assert_eq!(
+            highlight_lines("main.rs", &["/* a /* b", "c */ let", "*/ let"]),
+            vec!["mmmmmmmmm", "mmmmmmmm", "mm kkk"]
+        );
+    }
+
+    #[test]
+    fn test_highlight_numbers() {
+        assert_eq!(
+            highlight_lines(
+                "main.rs",
+                &["0xFF_u8 1_000 1e9 2.5e-3 1..5 1.max(2) x2 x.0"]
+            ),
+            vec!["nnnnnnn nnnnn nnn nnnnnn n  n n     n       n"]
+        );
+        assert_eq!(
+            highlight_lines("main.rs", &["0b12ff 0o78 0b1010u8"]),
+            vec!["nnn    nnn  nnnnnnnn"]
+        );
+        assert_eq!(
+            highlight_lines("main.c", &["0x1FUL 1'000 1.5f 017 'a'"]),
+            vec!["nnnnnn nnnnn nnnn nnn sss"]
+        );
+        assert_eq!(
+            highlight_lines("main.py", &["0o17 1_000j 1E-9 1."]),
+            vec!["nnnn nnnnnn nnnn nn"]
+        );
+    }
+
+    #[test]
+    fn test_highlight_python_strings() {
+        assert_eq!(
+            highlight_lines(
+                "main.py",
+                &[
+                    "x = \"\"\"it's",
+                    "\"quoted\" \"\"\" if",
+                    "f'{x}' rb\"\\d\" 'it\\'s'"
+                ]
+            ),
+            vec!["    sssssss", "ssssssssssss kk", "ssssss ssssss sssssss"]
+        );
+    }
+
+    #[test]
+    fn test_highlight_continued_strings() {
+        assert_eq!(
+            highlight_lines("main.c", &["char *s = \"a\\", "b\"; int", "\"c", "int"]),
+            vec!["tttt      sss", "ss  ttt", "ss", "ttt"]
+        );
+        assert_eq!(
+            highlight_lines("run.sh", &["echo 'C:\\' \"a", "b\" if"]),
+            vec!["     sssss ss", "ss kk"]
+        );
+    }
+
+    #[test]
+    fn test_highlight_javascript_keywords() {
+        assert_eq!(
+            highlight_lines(
+                "index.js",
+                &["async function f() { return await new Promise(); } fn mut"]
+            ),
+            vec!["kkkkk kkkkkkkk       kkkkkk kkkkk kkk ttttttt            "]
+        );
+    }
+
+    #[test]
+    fn test_highlight_typescript_types() {
+        assert_eq!(
+            highlight_lines(
+                "app.ts",
+                &[
+                    "interface A<T extends keyof B> { x: string; }",
+                    "let y = <number>z;"
+                ]
+            ),
+            vec![
+                "kkkkkkkkk     kkkkkkk kkkkk         tttttt   ",
+                "kkk      tttttt   ",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_highlight_template_literals() {
+        assert_eq!(
+            highlight_lines(
+                "index.js",
+                &["`a ${b ? `c${1}` : {}} d", "e ${'}'} ${", "if}` if"]
+            ),
+            vec!["sss      ss  n s      ss", "ss  sss s  ", "kk s kk"]
+        );
+    }
+
+    #[test]
+    fn test_highlight_jsx() {
+        assert_eq!(
+            highlight_lines(
+                "App.tsx",
+                &[
+                    "return <div title=\"it's\">",
+                    "  Don't {x ? <Item /> : null} if",
+                    "</div>; if (a <b) {}",
+                ]
+            ),
+            vec![
+                "kkkkkk  kkk       ssssss ",
+                "              tttt      kkkk    ",
+                "  kkk   kk          ",
+            ]
+        );
+        assert_eq!(
+            highlight_lines("app.ts", &["let x = <T>y; 'a'"]),
+            vec!["kkk           sss"]
+        );
+    }
+
+    #[test]
+    fn test_find_by_file_name() {
+        let registry = SyntaxRegistry::bundled();
+
+        let file_type = |file_name| {
+            registry
+                .find_by_file_name(file_name)
+                .map(|syntax| syntax.file_type.clone())
+        };
+
+        assert_eq!(file_type("main.rs"), Some(String::from("Rust")));
+        assert_eq!(file_type("a.b.py"), Some(String::from("Python")));
+        assert_eq!(file_type("header.h"), Some(String::from("C")));
+        assert_eq!(file_type("index.mjs"), Some(String::from("JavaScript")));
+        assert_eq!(file_type("App.jsx"), Some(String::from("JavaScript")));
+        assert_eq!(file_type("app.ts"), Some(String::from("TypeScript")));
+        assert_eq!(file_type("App.tsx"), Some(String::from("TSX")));
+        assert_eq!(file_type("Makefile"), Some(String::from("Make")));
+        assert_eq!(file_type("Dockerfile"), Some(String::from("Dockerfile")));
+        assert_eq!(file_type(".bashrc"), Some(String::from("Shell")));
+        assert_eq!(file_type("rs"), None);
+        assert_eq!(file_type("notes.txt"), None);
+        assert_eq!(file_type("trailing."), None);
+    }
+
+    #[test]
+    fn test_find_by_name() {
+        let registry = SyntaxRegistry::bundled();
+
+        let file_type = |name| {
+            registry
+                .find_by_name(name)
+                .map(|syntax| syntax.file_type.clone())
+        };
+
+        assert_eq!(file_type("rust"), Some(String::from("Rust")));
+        assert_eq!(file_type("JavaScript"), Some(String::from("JavaScript")));
+        assert_eq!(file_type("sh"), Some(String::from("Shell")));
+        assert_eq!(file_type("make"), Some(String::from("Make")));
+        assert_eq!(file_type("cobol"), None);
+    }
+
+    #[test]
+    fn test_find_by_shebang() {
+        let registry = SyntaxRegistry::bundled();
+
+        let file_type = |first_line| {
+            registry
+                .find_by_shebang(first_line)
+                .map(|syntax| syntax.file_type.clone())
+        };
+
+        assert_eq!(file_type("#!/bin/sh"), Some(String::from("Shell")));
+        assert_eq!(
+            file_type("#!/usr/bin/env bash"),
+            Some(String::from("Shell"))
+        );
+        assert_eq!(
+            file_type("#!/usr/bin/env python3"),
+            Some(String::from("Python"))
+        );
+        assert_eq!(
+            file_type("#!/usr/local/bin/python3.12 -u"),
+            Some(String::from("Python"))
+        );
+        assert_eq!(
+            file_type("#!/usr/bin/env -S NODE_ENV=test node --inspect"),
+            Some(String::from("JavaScript"))
+        );
+        assert_eq!(file_type("#!/usr/bin/env pythonista"), None);
+        assert_eq!(file_type("#!/usr/bin/env"), None);
+        assert_eq!(file_type("# bin/sh"), None);
+    }
+
+    #[test]
+    fn test_get_modeline_file_type() {
+        assert_eq!(get_modeline_file_type("// vim: ft=rust"), Some("rust"));
+        assert_eq!(
+            get_modeline_file_type("# vim: set ts=4 filetype=python :"),
+            Some("python")
+        );
+        assert_eq!(get_modeline_file_type("vim:syntax=c"), Some("c"));
+        assert_eq!(
+            get_modeline_file_type("/* vi: set sw=2 ft=javascript: */"),
+            Some("javascript")
+        );
+        assert_eq!(get_modeline_file_type("# ex:ft=sh:ts=8"), Some("sh"));
+        assert_eq!(
+            get_modeline_file_type("# -*- mode: python; coding: utf-8 -*-"),
+            Some("python")
+        );
+        assert_eq!(get_modeline_file_type(";; -*- lisp -*-"), Some("lisp"));
+        assert_eq!(get_modeline_file_type("# vim: ts=4"), None);
+        assert_eq!(get_modeline_file_type("vi:ft=c"), None);
+        assert_eq!(get_modeline_file_type("index:ft=c"), None);
+        assert_eq!(
+            get_modeline_file_type("let x = 1; // -*- unterminated"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_load_directory() {
+        let directory = tempdir().unwrap();
+        let mut registry = SyntaxRegistry::bundled();
+
+        assert_eq!(
+            registry.load_directory(&directory.path().join("missing")),
+            Ok(())
+        );
+
+        fs::write(
+            directory.path().join("rust.toml"),
+            "name = \"rust\"\nextensions = [\"rs\", \"ron\"]",
+        )
+        .unwrap();
+        // Extending a definition read after it, which extends a bundled one
+        fs::write(
+            directory.path().join("a.toml"),
+            "name = \"Go template\"\nextends = \"Go\"\nextensions = [\"gotmpl\"]",
+        )
+        .unwrap();
+        fs::write(
+            directory.path().join("go.toml"),
+            "name = \"Go\"\nextends = \"C\"\nextensions = [\"go\"]\nfile_names = [\"go.work\"]",
+        )
+        .unwrap();
+        fs::write(directory.path().join("broken.toml"), "name = 1").unwrap();
+        fs::write(
+            directory.path().join("cycle.toml"),
+            "name = \"Cycle\"\nextends = \"Cycle\"",
+        )
+        .unwrap();
+        fs::write(directory.path().join("notes.txt"), "name = 1").unwrap();
+
+        assert_eq!(
+            registry.load_directory(directory.path()),
+            Err(format!(
+                "Error in {}: name should be a string",
+                directory.path().join("broken.toml").display()
+            ))
+        );
+
+        let file_type = |file_name| {
+            registry
+                .find_by_file_name(file_name)
+                .map(|syntax| syntax.file_type.clone())
+        };
+
+        assert_eq!(file_type("main.go"), Some(String::from("Go")));
+        assert_eq!(file_type("page.gotmpl"), Some(String::from("Go template")));
+        assert_eq!(
+            registry
+                .find_by_name("go template")
+                .unwrap()
+                .single_line_comment_start,
+            "//"
+        );
+        assert_eq!(file_type("go.work"), Some(String::from("Go")));
+        assert_eq!(file_type("config.ron"), Some(String::from("rust")));
+        assert_eq!(file_type("main.rs"), Some(String::from("rust")));
+        assert_eq!(file_type("main.c"), Some(String::from("C")));
+        assert_eq!(registry.syntaxes.len(), BUNDLED_SYNTAXES.len() + 2);
+    }
+}
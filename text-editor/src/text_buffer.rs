@@ -0,0 +1,172 @@
+use ropey::Rope;
+use std::{
+    borrow::Cow,
+    io::{self, BufRead, BufWriter, Write},
+    ops::Range,
+};
+
+/// Rope-backed storage for the contents of a file
+/// Every line, including the last, is terminated by `\n`, so an empty buffer has no lines.
+/// Indices passed to and returned from this API are `char` indices unless stated otherwise.
+#[derive(Clone, Default)]
+pub struct TextBuffer {
+    rope: Rope,
+}
+
+impl TextBuffer {
+    pub fn new() -> Self {
+        TextBuffer { rope: Rope::new() }
+    }
+
+    /// Reads lines as `BufRead::lines` does, so `\r\n` line endings are normalized to `\n`
+    pub fn from_reader(reader: impl BufRead) -> io::Result<Self> {
+        let mut content = String::new();
+
+        for line in reader.lines() {
+            content.push_str(&line?);
+            content.push('\n');
+        }
+
+        Ok(TextBuffer {
+            rope: Rope::from_str(&content),
+        })
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.rope.len_lines() - 1 // The rope counts the empty line following the final `\n`
+    }
+
+    /// Returns the text of a line without its trailing `\n`
+    pub fn line(&self, line_index: usize) -> Cow<'_, str> {
+        let line = self.rope.line(line_index);
+        let line = line.slice(..line.len_chars().saturating_sub(1));
+
+        match line.as_str() {
+            Some(text) => Cow::Borrowed(text),
+            None => Cow::Owned(line.to_string()), // Line spans multiple rope chunks
+        }
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.rope.len_chars()
+    }
+
+    pub fn len_bytes(&self) -> usize {
+        self.rope.len_bytes()
+    }
+
+    /// Index of the first `char` of a line; `line_count()` maps to the end of the buffer
+    pub fn line_to_char(&self, line_index: usize) -> usize {
+        self.rope.line_to_char(line_index)
+    }
+
+    /// Index of the line containing a `char`; the end of the buffer maps to `line_count()`
+    pub fn char_to_line(&self, char_index: usize) -> usize {
+        self.rope.char_to_line(char_index)
+    }
+
+    pub fn char_to_byte(&self, char_index: usize) -> usize {
+        self.rope.char_to_byte(char_index)
+    }
+
+    pub fn insert(&mut self, char_index: usize, text: &str) {
+        self.rope.insert(char_index, text);
+    }
+
+    pub fn remove(&mut self, char_range: Range<usize>) {
+        self.rope.remove(char_range);
+    }
+
+    pub fn slice(&self, char_range: Range<usize>) -> String {
+        self.rope.slice(char_range).to_string()
+    }
+
+    /// Writes the buffer chunk by chunk, without building the whole file in memory
+    pub fn write_to(&self, writer: impl Write) -> io::Result<()> {
+        let mut writer = BufWriter::new(writer);
+
+        for chunk in self.rope.chunks() {
+            writer.write_all(chunk.as_bytes())?;
+        }
+
+        writer.flush()
+    }
+}
+
+impl std::fmt::Display for TextBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.rope)
+    }
+}
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    fn buffer_from(text: &str) -> TextBuffer {
+        TextBuffer::from_reader(text.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_empty_buffer() {
+        let buffer = TextBuffer::new();
+
+        assert_eq!(buffer.line_count(), 0);
+        assert_eq!(buffer.len_chars(), 0);
+        assert_eq!(buffer.to_string(), "");
+    }
+
+    #[test]
+    fn test_from_reader_normalizes_line_endings() {
+        let buffer = buffer_from("first\r\nsecond\nthird");
+
+        assert_eq!(buffer.line_count(), 3);
+        assert_eq!(buffer.to_string(), "first\nsecond\nthird\n");
+    }
+
+    #[test]
+    fn test_line_excludes_newline() {
+        let buffer = buffer_from("Line 1\n\nLine 3\n");
+
+        assert_eq!(buffer.line(0), "Line 1");
+        assert_eq!(buffer.line(1), "");
+        assert_eq!(buffer.line(2), "Line 3");
+    }
+
+    #[test]
+    fn test_line_and_char_conversions() {
+        let buffer = buffer_from("ab\ncdé\n");
+
+        assert_eq!(buffer.line_to_char(0), 0);
+        assert_eq!(buffer.line_to_char(1), 3);
+        assert_eq!(buffer.line_to_char(2), 7);
+        assert_eq!(buffer.char_to_line(2), 0);
+        assert_eq!(buffer.char_to_line(3), 1);
+        assert_eq!(buffer.char_to_line(7), 2);
+        assert_eq!(buffer.char_to_byte(6), 7);
+    }
+
+    #[test]
+    fn test_insert_and_remove() {
+        let mut buffer = buffer_from("Hello\nWorld\n");
+
+        buffer.insert(5, ",\nnew");
+        assert_eq!(buffer.to_string(), "Hello,\nnew\nWorld\n");
+        assert_eq!(buffer.line_count(), 3);
+
+        buffer.remove(5..10);
+        assert_eq!(buffer.to_string(), "Hello\nWorld\n");
+        assert_eq!(buffer.slice(6..11), "World");
+    }
+
+    #[test]
+    fn test_write_to() {
+        let buffer = buffer_from(&"0123456789\n".repeat(10_000));
+        let mut output = vec![];
+
+        buffer.write_to(&mut output).unwrap();
+
+        assert_eq!(output.len(), buffer.len_bytes());
+        assert_eq!(String::from_utf8(output).unwrap(), buffer.to_string());
+    }
+}
@@ -0,0 +1,481 @@
+use std::env;
+
+/// A colour as a theme gives it, shown as closely as the terminal allows
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Color {
+    Ansi(u8),        // One of the 16 basic colours, 8-15 being the bright ones
+    Indexed(u8),     // From the 256-colour palette
+    Rgb(u8, u8, u8), // 24-bit
+}
+
+/// How many colours the terminal can show
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorSupport {
+    Ansi16,
+    Indexed256,
+    TrueColor,
+}
+
+/// Colours and attributes for a kind of text; unset colours are the terminal's defaults
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Style {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub reverse: bool,
+}
+
+/// Styles for highlighted text and the parts of the screen around it
+#[derive(Debug, PartialEq)]
+pub struct Theme {
+    pub name: &'static str,
+    pub normal: Style, // Text that is not highlighted, under everything else
+    pub number: Style,
+    pub string: Style,
+    pub comment: Style,
+    pub keyword: Style,
+    pub type_name: Style,
+    pub search_match: Style,
+    pub selection: Style, // Drawn over the syntax highlighting
+    pub gutter: Style,    // Line numbers
+    pub non_text: Style,  // `~` past the end of the buffer
+    pub status_bar: Style,
+    pub status_bar_mode: Style,
+    pub status_bar_modified: Style,
+    pub error_message: Style,
+}
+
+/// Names of the 16 basic colours, in the order of their SGR parameters
+const ANSI_COLOR_NAMES: [&str; 16] = [
+    "black",
+    "red",
+    "green",
+    "yellow",
+    "blue",
+    "magenta",
+    "cyan",
+    "white",
+    "bright_black",
+    "bright_red",
+    "bright_green",
+    "bright_yellow",
+    "bright_blue",
+    "bright_magenta",
+    "bright_cyan",
+    "bright_white",
+];
+
+/// xterm's values for the 16 basic colours, to find the nearest to other colours
+const ANSI_COLOR_VALUES: [(u8, u8, u8); 16] = [
+    (0, 0, 0),
+    (205, 0, 0),
+    (0, 205, 0),
+    (205, 205, 0),
+    (0, 0, 238),
+    (205, 0, 205),
+    (0, 205, 205),
+    (229, 229, 229),
+    (127, 127, 127),
+    (255, 0, 0),
+    (0, 255, 0),
+    (255, 255, 0),
+    (92, 92, 255),
+    (255, 0, 255),
+    (0, 255, 255),
+    (255, 255, 255),
+];
+
+/// Levels of each component in the 6x6x6 colour cube of the 256-colour palette
+const COLOR_CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl Style {
+    pub const fn new() -> Self {
+        Style {
+            foreground: None,
+            background: None,
+            bold: false,
+            italic: false,
+            underline: false,
+            reverse: false,
+        }
+    }
+
+    pub const fn fg(self, color: Color) -> Self {
+        Style {
+            foreground: Some(color),
+            ..self
+        }
+    }
+
+    pub const fn bg(self, color: Color) -> Self {
+        Style {
+            background: Some(color),
+            ..self
+        }
+    }
+
+    pub const fn bold(self) -> Self {
+        Style { bold: true, ..self }
+    }
+
+    pub const fn italic(self) -> Self {
+        Style {
+            italic: true,
+            ..self
+        }
+    }
+
+    pub const fn underline(self) -> Self {
+        Style {
+            underline: true,
+            ..self
+        }
+    }
+
+    pub const fn reverse(self) -> Self {
+        Style {
+            reverse: true,
+            ..self
+        }
+    }
+
+    /// Lays `other` over this style, keeping the colours it leaves unset
+    pub fn patch(self, other: Style) -> Style {
+        Style {
+            foreground: other.foreground.or(self.foreground),
+            background: other.background.or(self.background),
+            bold: self.bold || other.bold,
+            italic: self.italic || other.italic,
+            underline: self.underline || other.underline,
+            reverse: self.reverse || other.reverse,
+        }
+    }
+
+    /// Returns the SGR sequence that resets the terminal's style and then sets this one
+    pub fn to_escape_code(self, color_support: ColorSupport) -> String {
+        let mut parameters = vec![String::from("0")];
+
+        for (enabled, parameter) in [
+            (self.bold, "1"),
+            (self.italic, "3"),
+            (self.underline, "4"),
+            (self.reverse, "7"),
+        ] {
+            if enabled {
+                parameters.push(parameter.to_string());
+            }
+        }
+
+        if let Some(color) = self.foreground {
+            parameters.push(get_color_parameters(color, color_support, false));
+        }
+
+        if let Some(color) = self.background {
+            parameters.push(get_color_parameters(color, color_support, true));
+        }
+
+        format!("\x1b[{}m", parameters.join(";"))
+    }
+}
+
+fn get_squared_distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    [(a.0, b.0), (a.1, b.1), (a.2, b.2)]
+        .iter()
+        .map(|(a, b)| (*a as i32 - *b as i32).pow(2) as u32)
+        .sum()
+}
+
+fn get_indexed_color_value(index: u8) -> (u8, u8, u8) {
+    match index {
+        0..=15 => ANSI_COLOR_VALUES[index as usize],
+        16..=231 => {
+            let index = index - 16;
+            (
+                COLOR_CUBE_LEVELS[(index / 36) as usize],
+                COLOR_CUBE_LEVELS[(index / 6 % 6) as usize],
+                COLOR_CUBE_LEVELS[(index % 6) as usize],
+            )
+        }
+        _ => {
+            let level = 8 + (index - 232) * 10;
+            (level, level, level)
+        }
+    }
+}
+
+/// Finds the nearest colour in the cube or greyscale ramp of the 256-colour palette
+fn get_nearest_indexed_color(value: (u8, u8, u8)) -> u8 {
+    (16..=255)
+        .min_by_key(|index| get_squared_distance(get_indexed_color_value(*index), value))
+        .expect("The palette is not empty")
+}
+
+fn get_nearest_ansi_color(value: (u8, u8, u8)) -> u8 {
+    (0..16)
+        .min_by_key(|index| get_squared_distance(ANSI_COLOR_VALUES[*index as usize], value))
+        .expect("There are 16 basic colours")
+}
+
+/// Replaces a colour that the terminal cannot show with the nearest one it can
+fn get_supported_color(color: Color, color_support: ColorSupport) -> Color {
+    match (color, color_support) {
+        (Color::Rgb(r, g, b), ColorSupport::Indexed256) => {
+            Color::Indexed(get_nearest_indexed_color((r, g, b)))
+        }
+        (Color::Rgb(r, g, b), ColorSupport::Ansi16) => {
+            Color::Ansi(get_nearest_ansi_color((r, g, b)))
+        }
+        (Color::Indexed(index), ColorSupport::Ansi16) => Color::Ansi(match index {
+            0..=15 => index,
+            _ => get_nearest_ansi_color(get_indexed_color_value(index)),
+        }),
+        (color, _) => color,
+    }
+}
+
+fn get_color_parameters(color: Color, color_support: ColorSupport, background: bool) -> String {
+    match get_supported_color(color, color_support) {
+        Color::Ansi(index) => {
+            let base = match (index < 8, background) {
+                (true, false) => 30,
+                (true, true) => 40,
+                (false, false) => 90 - 8,
+                (false, true) => 100 - 8,
+            };
+            (base + index).to_string()
+        }
+        Color::Indexed(index) => format!("{};5;{}", if background { 48 } else { 38 }, index),
+        Color::Rgb(r, g, b) => format!("{};2;{};{};{}", if background { 48 } else { 38 }, r, g, b),
+    }
+}
+
+fn get_color_support(colorterm: Option<&str>, term: Option<&str>) -> ColorSupport {
+    match (colorterm, term) {
+        (Some("truecolor" | "24bit"), _) => ColorSupport::TrueColor,
+        (_, Some(term)) if term.ends_with("-direct") => ColorSupport::TrueColor,
+        (_, Some(term)) if term.contains("256color") => ColorSupport::Indexed256,
+        _ => ColorSupport::Ansi16,
+    }
+}
+
+/// Detects the colours the terminal supports from `COLORTERM` and `TERM`
+pub fn detect_color_support() -> ColorSupport {
+    get_color_support(
+        env::var("COLORTERM").ok().as_deref(),
+        env::var("TERM").ok().as_deref(),
+    )
+}
+
+/// Parses a colour name such as `red` or `bright_blue`, a 256-colour palette index or
+/// `#rrggbb`
+pub fn parse_color(text: &str) -> Option<Color> {
+    if let Some(index) = ANSI_COLOR_NAMES.iter().position(|name| *name == text) {
+        return Some(Color::Ansi(index as u8));
+    }
+
+    if let Some(hex) = text.strip_prefix('#') {
+        let component = |range| u8::from_str_radix(hex.get(range)?, 16).ok();
+
+        return match hex.len() {
+            6 => Some(Color::Rgb(
+                component(0..2)?,
+                component(2..4)?,
+                component(4..6)?,
+            )),
+            _ => None,
+        };
+    }
+
+    text.parse::<u8>().ok().map(Color::Indexed)
+}
+
+pub fn format_color(color: Color) -> String {
+    match color {
+        Color::Ansi(index) => ANSI_COLOR_NAMES[index as usize].to_string(),
+        Color::Indexed(index) => index.to_string(),
+        Color::Rgb(r, g, b) => format!("#{:02x}{:02x}{:02x}", r, g, b),
+    }
+}
+
+pub fn find_theme(name: &str) -> Option<&'static Theme> {
+    THEMES.iter().find(|theme| theme.name == name)
+}
+
+pub fn theme_names() -> impl Iterator<Item = &'static str> {
+    THEMES.iter().map(|theme| theme.name)
+}
+
+const fn rgb(value: u32) -> Color {
+    Color::Rgb((value >> 16) as u8, (value >> 8) as u8, value as u8)
+}
+
+pub static THEMES: &[Theme] = &[
+    // The terminal's own colours, which follow its palette
+    Theme {
+        name: "default",
+        normal: Style::new(),
+        number: Style::new().fg(Color::Ansi(11)),
+        string: Style::new().fg(Color::Ansi(3)),
+        comment: Style::new().fg(Color::Ansi(6)),
+        keyword: Style::new().fg(Color::Ansi(13)),
+        type_name: Style::new().fg(Color::Ansi(10)),
+        search_match: Style::new().fg(Color::Ansi(4)),
+        selection: Style::new().reverse(),
+        gutter: Style::new(),
+        non_text: Style::new(),
+        status_bar: Style::new().reverse(),
+        status_bar_mode: Style::new().fg(Color::Ansi(0)).bg(Color::Ansi(2)),
+        status_bar_modified: Style::new().bg(Color::Ansi(1)).reverse(),
+        error_message: Style::new().bg(Color::Ansi(1)),
+    },
+    Theme {
+        name: "solarized-dark",
+        normal: Style::new().fg(rgb(0x839496)).bg(rgb(0x002b36)),
+        number: Style::new().fg(rgb(0xd33682)),
+        string: Style::new().fg(rgb(0x2aa198)),
+        comment: Style::new().fg(rgb(0x586e75)).italic(),
+        keyword: Style::new().fg(rgb(0x859900)),
+        type_name: Style::new().fg(rgb(0xb58900)),
+        search_match: Style::new().fg(rgb(0x002b36)).bg(rgb(0xb58900)),
+        selection: Style::new().bg(rgb(0x073642)),
+        gutter: Style::new().fg(rgb(0x586e75)).bg(rgb(0x073642)),
+        non_text: Style::new().fg(rgb(0x586e75)),
+        status_bar: Style::new().fg(rgb(0x93a1a1)).bg(rgb(0x073642)),
+        status_bar_mode: Style::new().fg(rgb(0x002b36)).bg(rgb(0x268bd2)).bold(),
+        status_bar_modified: Style::new().fg(rgb(0x002b36)).bg(rgb(0xcb4b16)),
+        error_message: Style::new().fg(rgb(0xfdf6e3)).bg(rgb(0xdc322f)),
+    },
+    Theme {
+        name: "gruvbox-dark",
+        normal: Style::new().fg(rgb(0xebdbb2)).bg(rgb(0x282828)),
+        number: Style::new().fg(rgb(0xd3869b)),
+        string: Style::new().fg(rgb(0xb8bb26)),
+        comment: Style::new().fg(rgb(0x928374)).italic(),
+        keyword: Style::new().fg(rgb(0xfb4934)),
+        type_name: Style::new().fg(rgb(0xfabd2f)),
+        search_match: Style::new().fg(rgb(0x282828)).bg(rgb(0xfabd2f)),
+        selection: Style::new().bg(rgb(0x504945)),
+        gutter: Style::new().fg(rgb(0x7c6f64)),
+        non_text: Style::new().fg(rgb(0x7c6f64)),
+        status_bar: Style::new().fg(rgb(0xebdbb2)).bg(rgb(0x504945)),
+        status_bar_mode: Style::new().fg(rgb(0x282828)).bg(rgb(0xa89984)).bold(),
+        status_bar_modified: Style::new().fg(rgb(0x282828)).bg(rgb(0xfe8019)),
+        error_message: Style::new().fg(rgb(0x282828)).bg(rgb(0xfb4934)),
+    },
+    Theme {
+        name: "github-light",
+        normal: Style::new().fg(rgb(0x24292f)).bg(rgb(0xffffff)),
+        number: Style::new().fg(rgb(0x0550ae)),
+        string: Style::new().fg(rgb(0x0a3069)),
+        comment: Style::new().fg(rgb(0x6e7781)).italic(),
+        keyword: Style::new().fg(rgb(0xcf222e)),
+        type_name: Style::new().fg(rgb(0x8250df)),
+        search_match: Style::new().bg(rgb(0xfff8c5)).underline(),
+        selection: Style::new().bg(rgb(0xddf4ff)),
+        gutter: Style::new().fg(rgb(0x8c959f)),
+        non_text: Style::new().fg(rgb(0x8c959f)),
+        status_bar: Style::new().fg(rgb(0x24292f)).bg(rgb(0xeaeef2)),
+        status_bar_mode: Style::new().fg(rgb(0xffffff)).bg(rgb(0x0969da)).bold(),
+        status_bar_modified: Style::new().fg(rgb(0xffffff)).bg(rgb(0xbc4c00)),
+        error_message: Style::new().fg(rgb(0xffffff)).bg(rgb(0xcf222e)),
+    },
+];
+
+#[cfg(test)]
+mod unit_tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_codes() {
+        let style = Style::new().fg(Color::Ansi(11)).bg(Color::Ansi(1)).bold();
+        assert_eq!(
+            style.to_escape_code(ColorSupport::Ansi16),
+            "\x1b[0;1;93;41m"
+        );
+
+        let style = Style::new().fg(Color::Indexed(208)).underline();
+        assert_eq!(
+            style.to_escape_code(ColorSupport::Indexed256),
+            "\x1b[0;4;38;5;208m"
+        );
+
+        let style = Style::new().bg(rgb(0x282828)).italic().reverse();
+        assert_eq!(
+            style.to_escape_code(ColorSupport::TrueColor),
+            "\x1b[0;3;7;48;2;40;40;40m"
+        );
+
+        assert_eq!(
+            Style::new().to_escape_code(ColorSupport::TrueColor),
+            "\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_colors_are_reduced_to_those_supported() {
+        let style = Style::new().fg(rgb(0xfb4934)).bg(rgb(0x282828));
+
+        assert_eq!(
+            style.to_escape_code(ColorSupport::Indexed256),
+            "\x1b[0;38;5;203;48;5;235m"
+        );
+        assert_eq!(style.to_escape_code(ColorSupport::Ansi16), "\x1b[0;91;40m");
+
+        let style = Style::new().fg(Color::Indexed(231)).bg(Color::Indexed(4));
+        assert_eq!(style.to_escape_code(ColorSupport::Ansi16), "\x1b[0;97;44m");
+    }
+
+    #[test]
+    fn test_patch() {
+        let normal = Style::new().fg(Color::Ansi(7)).bg(Color::Ansi(0));
+        let selection = Style::new().bg(Color::Ansi(4)).bold();
+
+        assert_eq!(
+            normal.patch(selection),
+            Style::new().fg(Color::Ansi(7)).bg(Color::Ansi(4)).bold()
+        );
+    }
+
+    #[test]
+    fn test_get_color_support() {
+        assert_eq!(
+            get_color_support(Some("truecolor"), Some("xterm-256color")),
+            ColorSupport::TrueColor
+        );
+        assert_eq!(
+            get_color_support(None, Some("xterm-direct")),
+            ColorSupport::TrueColor
+        );
+        assert_eq!(
+            get_color_support(None, Some("screen-256color")),
+            ColorSupport::Indexed256
+        );
+        assert_eq!(get_color_support(None, Some("xterm")), ColorSupport::Ansi16);
+        assert_eq!(get_color_support(None, None), ColorSupport::Ansi16);
+    }
+
+    #[test]
+    fn test_parse_and_format_colors() {
+        for (text, color) in [
+            ("bright_blue", Color::Ansi(12)),
+            ("208", Color::Indexed(208)),
+            ("#Fb4934", Color::Rgb(0xfb, 0x49, 0x34)),
+        ] {
+            assert_eq!(parse_color(text), Some(color));
+            assert_eq!(parse_color(&format_color(color)), Some(color));
+        }
+
+        for text in ["mauve", "256", "#fff", "#gggggg", ""] {
+            assert_eq!(parse_color(text), None);
+        }
+    }
+
+    #[test]
+    fn test_find_theme() {
+        assert_eq!(find_theme("gruvbox-dark").unwrap().name, "gruvbox-dark");
+        assert!(find_theme("nope").is_none());
+        assert_eq!(theme_names().next(), Some("default"));
+    }
+}
@@ -1,6 +1,9 @@
-use crate::editor_instance::Line;
-use crate::globals::get_buffer_lock;
-use crate::output::{move_cursor_to_top_left, AnsiEscapeCode};
+use crate::globals::{get_buffer_lock, KITTY_KEYBOARD_PROTOCOL};
+use crate::output::{
+    disable_bracketed_paste, disable_kitty_keyboard_protocol, disable_mouse_reporting,
+    move_cursor_to_top_left, AnsiEscapeCode,
+};
+use crate::swap::write_emergency_swap_file;
 use crate::WindowSize;
 use crate::{output::clear_display, terminal::disable_raw_mode};
 use signal_hook::consts::SIGWINCH;
@@ -11,6 +14,8 @@ use std::sync::{Arc, RwLock};
 use std::{panic, thread};
 use termion::terminal_size;
 use termios::Termios;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
 #[allow(dead_code)]
 pub fn debug_input(key: u8) {
@@ -29,11 +34,22 @@ pub fn set_panic_hook(original_termios: Termios) {
     let default_panic_hook = panic::take_hook();
 
     panic::set_hook(Box::new(move |info| {
+        disable_bracketed_paste();
+        disable_mouse_reporting();
+
+        if KITTY_KEYBOARD_PROTOCOL {
+            disable_kitty_keyboard_protocol();
+        }
+
         clear_display();
         move_cursor_to_top_left();
         disable_raw_mode(original_termios);
 
         default_panic_hook(info);
+
+        if let Some(swap_path) = write_emergency_swap_file() {
+            eprintln!("Unsaved changes were written to {}", swap_path.display());
+        }
     }));
 }
 
@@ -140,21 +156,52 @@ pub fn flush_stdout() {
     io::stdout().flush().expect("Failed to flush stdout");
 }
 
-pub fn lines_to_string(lines: &Vec<Line>) -> String {
-    let mut string = String::new();
+pub fn grapheme_count(text: &str) -> usize {
+    text.graphemes(true).count()
+}
 
-    for line in lines {
-        string.push_str(&line.text);
-        string.push('\n');
+/// Converts a grapheme column into a byte index usable for slicing `text`
+pub fn column_to_byte_index(text: &str, column: usize) -> usize {
+    text.grapheme_indices(true)
+        .nth(column)
+        .map_or(text.len(), |(index, _)| index)
+}
+
+/// Converts a byte index into a grapheme column, rounding up if it falls inside a grapheme
+pub fn byte_index_to_column(text: &str, byte_index: usize) -> usize {
+    text.grapheme_indices(true)
+        .take_while(|(index, _)| *index < byte_index)
+        .count()
+}
+
+/// Number of terminal columns a grapheme occupies (East Asian wide characters take 2)
+/// Control characters are drawn as a single reversed character, e.g. `^A` -> `A`
+pub fn grapheme_width(grapheme: &str) -> usize {
+    match grapheme.chars().next() {
+        Some(char) if char.is_ascii_control() => 1,
+        _ => grapheme.width(),
     }
+}
 
-    string
+/// Truncates `string` to at most `max_bytes` without splitting a character
+pub fn truncate_at_char_boundary(string: &mut String, max_bytes: usize) {
+    if max_bytes >= string.len() {
+        return;
+    }
+
+    let mut boundary = max_bytes;
+
+    while !string.is_char_boundary(boundary) {
+        boundary -= 1;
+    }
+
+    string.truncate(boundary);
 }
 
 pub fn get_file_name_from_path(file_path: &str) -> String {
     file_path
         .split('/')
-        .last()
+        .next_back()
         .expect("Failed to parse file from provided file path")
         .into()
 }
@@ -162,7 +209,6 @@ pub fn get_file_name_from_path(file_path: &str) -> String {
 #[cfg(test)]
 mod unit_tests {
     use super::*;
-    use crate::editor_instance::Line;
 
     mod test_ctrl_key {
         use super::*;
@@ -203,80 +249,85 @@ mod unit_tests {
         }
     }
 
-    mod test_lines_to_string {
+    mod test_column_to_byte_index {
         use super::*;
 
         #[test]
-        fn test_lines_to_string_empty() {
-            let lines: Vec<Line> = Vec::new();
-            assert_eq!(lines_to_string(&lines), "");
+        fn test_column_to_byte_index_ascii() {
+            assert_eq!(column_to_byte_index("hello", 0), 0);
+            assert_eq!(column_to_byte_index("hello", 3), 3);
+            assert_eq!(column_to_byte_index("hello", 5), 5);
         }
 
         #[test]
-        fn test_lines_to_string_single_line() {
-            let lines = vec![Line {
-                text: String::from("Line 1"),
-                render: String::from("Line 1"),
-                highlight: vec![],
-                index: 0,
-                has_open_multiline_comment: false,
-            }];
-
-            assert_eq!(lines_to_string(&lines), "Line 1\n");
+        fn test_column_to_byte_index_multi_byte() {
+            assert_eq!(column_to_byte_index("héllo", 2), 3);
+            assert_eq!(column_to_byte_index("日本", 1), 3);
         }
 
         #[test]
-        fn test_lines_to_string_multiple_lines() {
-            let lines = vec![
-                Line {
-                    text: String::from("Line 1"),
-                    render: String::from("Line 1"),
-                    highlight: vec![],
-                    index: 0,
-                    has_open_multiline_comment: false,
-                },
-                Line {
-                    text: String::from("Line 2"),
-                    render: String::from("Line 2"),
-                    highlight: vec![],
-                    index: 1,
-                    has_open_multiline_comment: false,
-                },
-            ];
+        fn test_column_to_byte_index_graphemes() {
+            assert_eq!(column_to_byte_index("e\u{301}x", 1), 3); // Combining acute accent
+            assert_eq!(column_to_byte_index("👍🏽!", 1), 8); // Emoji with skin tone modifier
+        }
 
-            assert_eq!(lines_to_string(&lines), "Line 1\nLine 2\n");
+        #[test]
+        fn test_column_to_byte_index_past_end() {
+            assert_eq!(column_to_byte_index("abc", 10), 3);
+            assert_eq!(column_to_byte_index("", 1), 0);
         }
+    }
+
+    mod test_byte_index_to_column {
+        use super::*;
 
         #[test]
-        fn test_lines_to_string_special_characters() {
-            let lines = vec![
-                Line {
-                    text: String::from("Line with spaces    "),
-                    render: String::from("Line with spaces    "),
-                    highlight: vec![],
-                    index: 0,
-                    has_open_multiline_comment: false,
-                },
-                Line {
-                    text: String::from("Line\twith\ttabs"),
-                    render: String::from("Line\twith\ttabs"),
-                    highlight: vec![],
-                    index: 1,
-                    has_open_multiline_comment: false,
-                },
-                Line {
-                    text: String::from("Line\nwith\nnewlines"),
-                    render: String::from("Line\nwith\nnewlines"),
-                    highlight: vec![],
-                    index: 2,
-                    has_open_multiline_comment: false,
-                },
-            ];
+        fn test_byte_index_to_column_boundaries() {
+            assert_eq!(byte_index_to_column("héllo", 0), 0);
+            assert_eq!(byte_index_to_column("héllo", 3), 2);
+            assert_eq!(byte_index_to_column("héllo", 6), 5);
+        }
 
-            assert_eq!(
-                lines_to_string(&lines),
-                "Line with spaces    \nLine\twith\ttabs\nLine\nwith\nnewlines\n"
-            );
+        #[test]
+        fn test_byte_index_to_column_inside_grapheme() {
+            assert_eq!(byte_index_to_column("e\u{301}x", 1), 1);
+            assert_eq!(byte_index_to_column("e\u{301}x", 3), 1);
+        }
+    }
+
+    mod test_grapheme_width {
+        use super::*;
+
+        #[test]
+        fn test_grapheme_width() {
+            assert_eq!(grapheme_width("a"), 1);
+            assert_eq!(grapheme_width("é"), 1);
+            assert_eq!(grapheme_width("e\u{301}"), 1);
+            assert_eq!(grapheme_width("中"), 2);
+            assert_eq!(grapheme_width("🙂"), 2);
+            assert_eq!(grapheme_width("\x01"), 1);
+        }
+
+        #[test]
+        fn test_grapheme_count() {
+            assert_eq!(grapheme_count(""), 0);
+            assert_eq!(grapheme_count("héllo"), 5);
+            assert_eq!(grapheme_count("e\u{301}👍🏽"), 2);
+        }
+    }
+
+    mod test_truncate_at_char_boundary {
+        use super::*;
+
+        #[test]
+        fn test_truncate_at_char_boundary() {
+            let mut string = String::from("café");
+            truncate_at_char_boundary(&mut string, 4);
+            assert_eq!(string, "caf");
+
+            let mut string = String::from("abc");
+            truncate_at_char_boundary(&mut string, 10);
+            assert_eq!(string, "abc");
         }
     }
 